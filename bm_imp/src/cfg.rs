@@ -6,6 +6,7 @@ use serde::{Deserialize, Serialize};
 use tokio::sync::broadcast::{Sender as BSender};
 use tokio::sync::mpsc::{Sender as MSender};
 
+use cffc_base::api::recg_cluster::{RecgCluster, RecgClusterOptions};
 
 use crate::error::{AppError, AppResult};
 use std::sync::Mutex;
@@ -23,13 +24,30 @@ pub struct AppCtx {
     pub exit_tx: BSender<i64>,
 
     pub stat_tx: MSender<StageEvent>,
+
+    /// helper 节点，提取特征
+    pub detect_api: RecgCluster,
+    /// recog.url 节点，创建人员
+    pub recg_api: RecgCluster,
 }
 
 impl AppCtx {
     pub fn new(cfg: AppCfg, conn: rusqlite::Connection,
                exit_tx: BSender<i64>,
                stat_tx: MSender<StageEvent>) -> Self {
+        let recg_urls = vec![cfg.recog.url.clone()];
+        // 没有配置 helper 时，使用 recog.url
+        let helper_urls = if cfg.recog.helper.is_empty() {
+            &recg_urls
+        } else {
+            &cfg.recog.helper
+        };
+        let detect_api = RecgCluster::new(helper_urls, cfg.recog.cluster.clone());
+        let recg_api = RecgCluster::new(&recg_urls, cfg.recog.cluster.clone());
+
         AppCtx {
+            detect_api,
+            recg_api,
             cfg,
             dao: Mutex::new(AppDao {
                 conn: RefCell::new(conn),
@@ -54,6 +72,9 @@ pub struct AppCfgRecog {
     pub url: String,
     pub db_sid: String,
     pub helper: Vec<String>,
+    /// 节点选择及熔断
+    #[serde(default)]
+    pub cluster: RecgClusterOptions,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
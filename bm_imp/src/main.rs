@@ -158,18 +158,11 @@ async fn main() {
     // 启动多个detect service
     let input_files = Arc::new(dir_filter.targets);
     let detect_num = app_ctx.cfg.detect_worker as usize;
-    let mut url_index = 0_usize;
     for i in 0..detect_num {
-        // helper url 轮换
-        let api_url = app_ctx.cfg.recog.helper.get(url_index).unwrap();
+        // helper 节点由 detect_api 轮换，故障节点熔断
         let detect_service = DetectService::new(app_ctx.clone(), input_files.clone(),
-                                                api_url, fea_queue.clone(), i);
+                                                fea_queue.clone(), i);
         svc_repo.start_service(detect_service);
-
-        url_index += 1;
-        if url_index == app_ctx.cfg.recog.helper.len() {
-            url_index = 0;
-        }
     }
     info!("start {} detect serivces", detect_num);
 
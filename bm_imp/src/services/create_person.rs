@@ -8,7 +8,7 @@ use log::{debug, error};
 use tokio::sync::broadcast::Receiver;
 use tokio::task::JoinHandle;
 
-use cffc_base::api::bm_api::{ApiFeatureQuality, RecognitionApi};
+use cffc_base::api::bm_api::ApiFeatureQuality;
 use crate::cfg::{AppCtx, CreateItem, FeaItem, TaskStat, StageEvent};
 
 use crate::util;
@@ -21,8 +21,6 @@ pub struct CreatePersonService {
     pub queue: Arc<Queue<FeaItem>>,
     pub out: Arc<Queue<CreateItem>>,
 
-    pub api: RecognitionApi,
-
     pub stage_id: usize,
     /// workder id
     pub stage_wid: usize,
@@ -35,15 +33,12 @@ pub struct CreatePersonService {
 impl CreatePersonService {
     pub fn new(ctx: Arc<AppCtx>, queue: Arc<Queue<FeaItem>>,
                out: Arc<Queue<CreateItem>>, stage_wid: usize) -> Self {
-        let api = RecognitionApi::new(ctx.cfg.recog.url.as_str());
-
         CreatePersonService {
             stat_sender: ctx.stat_tx.clone(),
             ctx,
             stat: Default::default(),
             queue,
             out,
-            api,
             stage_id: 1,
             stage_wid,
         }
@@ -118,7 +113,7 @@ impl CreatePersonService {
         }).collect();
 
 
-        let res = match self.api.create_persons(db, ids.clone(), features_list).await {
+        let res = match self.ctx.recg_api.create_persons(db, ids.clone(), features_list).await {
             Ok(v) => v,
             Err(e) => {
                 error!("error, create_persons: [{:?}], {:?}", ids, e);
@@ -8,7 +8,6 @@ use tokio::sync::mpsc::Sender as MSender;
 use tokio::task::JoinHandle;
 use uuid::Uuid;
 
-use cffc_base::api::bm_api::RecognitionApi;
 use crate::cfg::{AppCtx, FeaItem, StageEvent, TaskStat};
 use crate::dir_filter::FileItem;
 use crate::util;
@@ -23,7 +22,6 @@ pub struct DetectService {
     pub files: Arc<Queue<FileItem>>,
     pub stat: TaskStat,
     pub out: Arc<Queue<FeaItem>>,
-    pub api: RecognitionApi,
 
     pub stage_id: usize,
     /// workder id
@@ -34,16 +32,13 @@ pub struct DetectService {
 
 impl DetectService {
     pub fn new(ctx: Arc<AppCtx>, files: Arc<Queue<FileItem>>,
-               url: &str, out: Arc<Queue<FeaItem>>,
+               out: Arc<Queue<FeaItem>>,
                stage_wid: usize) -> Self {
-        let api = RecognitionApi::new(url);
-
         DetectService {
             stat_sender: ctx.stat_tx.clone(),
             ctx,
             files,
             stat: Default::default(),
-            api,
             out,
             stage_id: 0,
             stage_wid,
@@ -84,7 +79,7 @@ impl DetectService {
 
         // detect
         let time_1 = Local::now();
-        let res = match self.api.detect(content, true, false).await {
+        let res = match self.ctx.detect_api.detect(content, true, false).await {
             Ok(v) => v,
             Err(e) => {
                 error!("error, detect: [{}]{:?}, {:?}", item.index, item.file_name, e);
@@ -193,6 +193,9 @@ impl ImpSrcCmd {
             rtcp_utc: 0,
             lane_desc: None,
             lane_count: 0,
+            speed_line1: 0,
+            speed_line2: 0,
+            speed_distance: 0_f64,
             memo: None,
             gmt_create: now,
             gmt_modified: now,
@@ -2,11 +2,13 @@ use clap::{App, Arg, SubCommand};
 
 use crate::reset_cmd::ResetCmd;
 use crate::imp_src_cmd::ImpSrcCmd;
+use crate::replay_cmd::ReplayCmd;
 
 mod reset_cmd;
 mod dao;
 mod error;
 mod imp_src_cmd;
+mod replay_cmd;
 
 const APP_NAME: &str = "bm_tool";
 const APP_VER_NUM: &str = "0.1.0";
@@ -67,6 +69,27 @@ async fn main() {
                     .default_value("create_src.json")
                     .required(true)
                     .help("config json"))
+        )
+        .subcommand(
+            SubCommand::with_name("replay")
+                .about("replay captured notifications")
+                .arg(Arg::with_name("url")
+                    .short("u")
+                    .long("url")
+                    .default_value("http://localhost:8090")
+                    .required(true)
+                    .help("worker url"))
+                .arg(Arg::with_name("dir")
+                    .short("d")
+                    .long("dir")
+                    .takes_value(true)
+                    .required(true)
+                    .help("capture dir"))
+                .arg(Arg::with_name("speed")
+                    .short("s")
+                    .long("speed")
+                    .default_value("1")
+                    .help("1: original speed, 2: 2x, max: no wait"))
         );
 
 
@@ -95,6 +118,21 @@ async fn main() {
                 println!("error, {:?}", e);
             }
         }
+        ("replay", Some(sub_matches)) => {
+            let url = sub_matches.value_of("url").unwrap();
+            let dir = sub_matches.value_of("dir").unwrap();
+            let speed = sub_matches.value_of("speed").unwrap();
+
+            let cmd = ReplayCmd::new(url, dir, speed);
+            if let Err(e) = cmd {
+                println!("error, {:?}", e);
+                return;
+            }
+            let mut cmd = cmd.unwrap();
+            if let Err(e) = cmd.run_cmd().await {
+                println!("error, {:?}", e);
+            }
+        }
         _ => {
             println!("{}", cli_matches.usage());
         }
@@ -0,0 +1,114 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use bm_worker::notify_recorder;
+use cffc_base::model::returndata;
+
+use crate::error::{AppError, AppResult};
+
+/// replay
+// 1) 读取 bm_worker 录制模式保存的请求 (按时间排序)
+// 2) 按原始速度、倍速或最快速度，重新 POST 到 worker
+// 3) 录制时的 hmac 签名已过期，worker 需关闭 upload_auth
+pub struct ReplayCmd {
+    /// worker 地址，如 http://localhost:8090
+    pub url: String,
+
+    /// 录制目录
+    pub dir: PathBuf,
+
+    /// 倍速，None 表示不等待
+    pub speed: Option<f64>,
+
+    pub client: reqwest::Client,
+}
+
+impl ReplayCmd {
+    pub fn new(url: &str, dir: &str, speed: &str) -> AppResult<Self> {
+        let speed = match speed {
+            "max" => None,
+            v => {
+                let v: f64 = v.parse().map_err(|_| AppError::new(&format!("invalid speed: {}", v)))?;
+                if v > 0.0 { Some(v) } else { None }
+            }
+        };
+
+        Ok(ReplayCmd {
+            url: url.trim_end_matches('/').to_string(),
+            dir: PathBuf::from(dir),
+            speed,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    pub async fn run_cmd(&mut self) -> AppResult<()> {
+        let files = notify_recorder::list_captures(&self.dir).map_err(AppError::from_debug)?;
+        if files.is_empty() {
+            println!("no capture in {:?}", self.dir);
+            return Ok(());
+        }
+        println!("find {} captures, replay to {}, speed: {:?}", files.len(), self.url, self.speed);
+
+        let start = Instant::now();
+        let mut first_ts: Option<i64> = None;
+        let mut ok_count = 0;
+        let mut fail_count = 0;
+
+        for (i, path) in files.iter().enumerate() {
+            let (meta, body) = match notify_recorder::load_capture(path) {
+                Ok(v) => v,
+                Err(e) => {
+                    println!("load {:?}, fail, err:{:?}", path, e);
+                    fail_count += 1;
+                    continue;
+                }
+            };
+
+            // 按录制时的间隔等待
+            let first = *first_ts.get_or_insert(meta.ts);
+            if let Some(speed) = self.speed {
+                let offset = ((meta.ts - first).max(0) as f64 / speed) as u64;
+                let due = start + Duration::from_millis(offset);
+                let now = Instant::now();
+                if due > now {
+                    tokio::time::delay_for(due - now).await;
+                }
+            }
+
+            let url = format!("{}{}", self.url, meta.path);
+            let rst = self.client.post(&url)
+                .header(reqwest::header::CONTENT_TYPE, meta.content_type.as_str())
+                .body(body)
+                .send().await;
+
+            match rst {
+                Ok(res) => {
+                    let status = res.status();
+                    let text = res.text().await.unwrap_or_default();
+                    println!("{}/{}, {}, {}, {}, {}", i + 1, files.len(), meta.kind, meta.source, status, text);
+                    // worker 总是返回 http 200, 按 returndata 的 status 判断
+                    if status.is_success() && is_returndata_ok(&text) {
+                        ok_count += 1;
+                    } else {
+                        fail_count += 1;
+                    }
+                }
+                Err(e) => {
+                    println!("{}/{}, post {}, fail, err:{:?}", i + 1, files.len(), url, e);
+                    fail_count += 1;
+                }
+            }
+        }
+
+        println!("replay end, ok: {}, fail: {}, use: {} ms", ok_count, fail_count, start.elapsed().as_millis());
+        Ok(())
+    }
+}
+
+fn is_returndata_ok(text: &str) -> bool {
+    let v: serde_json::Value = match serde_json::from_str(text) {
+        Ok(v) => v,
+        Err(_) => return false,
+    };
+    v.get("status").and_then(|x| x.as_i64()) == Some(returndata::STATUS_OK as i64)
+}
@@ -176,7 +176,7 @@ impl ResetCmd {
     async fn clean_sqlite(&self) -> AppResult<()> {
         // cf_dfsource / cf_facetrack / cf_poi / cf_delpoi / cf_coi / cf_cartrack
 
-        let tables = vec!["cf_dfsource", "cf_facetrack", "cf_poi", "cf_delpoi", "cf_cartrack", "cf_coi"];
+        let tables = vec!["cf_dfsource", "cf_facetrack", "cf_facetrack_feature", "cf_poi", "cf_poi_feature", "cf_retro_job", "cf_retro_alarm", "cf_stranger", "cf_stranger_track", "cf_stranger_alarm", "cf_delpoi", "cf_cartrack", "cf_lane_stat", "cf_parking_visit", "cf_coi"];
 
         for table in tables {
             match self.dao.delete_table(table) {
@@ -0,0 +1,375 @@
+use std::sync::Arc;
+
+use chrono::prelude::*;
+use log::error;
+
+use crate::app_ctx::AppCtx;
+use crate::dao::model::CfAlarmRule;
+use crate::queue_item::{CtQI, FtQI, TRACK_TYPE_CAR, TRACK_TYPE_FACE};
+
+/// 名单匹配方式
+pub const MATCH_MODE_ANY: i32 = 0;
+pub const MATCH_MODE_HIT: i32 = 1;
+pub const MATCH_MODE_MISS: i32 = 2;
+
+/// 报警级别
+pub const SEVERITY_MIN: i32 = 1;
+pub const SEVERITY_MAX: i32 = 3;
+
+/// 规则判断用到的 track 信息
+pub struct AlarmFacts<'a> {
+    pub track_type: i32,
+    pub source: &'a str,
+    pub ts: DateTime<Local>,
+    /// 命中的名单 (db_sid/group_sid, bw_flag)
+    pub hit: Option<(&'a str, i32)>,
+    /// 人脸：比对分值；车辆：车牌置信度
+    pub score: f64,
+    pub gender: i64,
+    pub color: &'a str,
+    /// 车道编号，0 表示未知
+    pub lane: i32,
+    /// 车速 km/h, 0 表示未知
+    pub speed: f64,
+}
+
+impl<'a> AlarmFacts<'a> {
+    /// 超过阈值(judged)才算命中，未命中时分值为 0
+    pub fn from_face(item: &'a FtQI) -> Self {
+        let (hit, score) = match item.match_poi {
+            Some(ref v) if item.face.judged => (Some((v.db_sid.as_str(), v.bw_flag as i32)), v.score as f64),
+            _ => (None, 0_f64),
+        };
+
+        AlarmFacts {
+            track_type: TRACK_TYPE_FACE,
+            source: item.face.source.as_str(),
+            ts: item.face.ts,
+            hit,
+            score,
+            gender: item.face.props.as_ref().map_or(0, |x| x.gender),
+            color: "",
+            lane: 0,
+            speed: 0_f64,
+        }
+    }
+
+    pub fn from_car(item: &'a CtQI) -> Self {
+        AlarmFacts {
+            track_type: TRACK_TYPE_CAR,
+            source: item.car.source.as_str(),
+            ts: item.car.ts,
+            hit: item.match_coi.as_ref().map(|x| (x.group_sid.as_str(), x.bw_flag as i32)),
+            score: item.car.plate.as_ref().map_or(0_f64, |x| x.confidence),
+            gender: 0,
+            color: item.car.props.as_ref().map_or("", |x| x.color.as_str()),
+            lane: item.car.lane_num,
+            speed: item.car.speed,
+        }
+    }
+}
+
+/// 逗号分隔的列表，空表示全部
+fn in_list(list: &Option<String>, value: &str) -> bool {
+    let list = match list {
+        Some(v) if !v.trim().is_empty() => v,
+        _ => return true,
+    };
+    list.split(',').any(|x| x.trim() == value)
+}
+
+pub fn parse_hm(s: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(s.trim(), "%H:%M").ok()
+}
+
+/// 车道限速，车道号:限速 逗号分隔，车道号 0 表示其它车道
+pub fn parse_speed_limits(s: &str) -> Option<Vec<(i32, f64)>> {
+    s.split(',').filter(|x| !x.trim().is_empty()).map(|x| {
+        let mut it = x.splitn(2, ':');
+        let lane = it.next()?.trim().parse::<i32>().ok().filter(|v| *v >= 0)?;
+        let limit = it.next()?.trim().parse::<f64>().ok().filter(|v| *v > 0_f64)?;
+        Some((lane, limit))
+    }).collect()
+}
+
+/// 车速超过所在车道的限速，没有对应车道且没有默认限速时不算超速
+fn is_speeding(limits: &str, facts: &AlarmFacts) -> bool {
+    if facts.speed <= 0_f64 {
+        return false;
+    }
+    let limits = parse_speed_limits(limits).unwrap_or_default();
+    let limit = limits.iter().find(|x| x.0 != 0 && x.0 == facts.lane)
+        .or_else(|| limits.iter().find(|x| x.0 == 0));
+    matches!(limit, Some(v) if facts.speed > v.1)
+}
+
+fn in_schedule(rule: &CfAlarmRule, ts: DateTime<Local>) -> bool {
+    let weekday = ts.weekday().number_from_monday().to_string();
+    if !in_list(&rule.week_days, &weekday) {
+        return false;
+    }
+
+    let start = rule.time_start.as_deref().and_then(parse_hm);
+    let end = rule.time_end.as_deref().and_then(parse_hm);
+    let (start, end) = match (start, end) {
+        (Some(s), Some(e)) => (s, e),
+        _ => return true,
+    };
+
+    let now = ts.time();
+    if start <= end {
+        start <= now && now < end
+    } else {
+        // 跨天
+        now >= start || now < end
+    }
+}
+
+pub fn is_match(rule: &CfAlarmRule, facts: &AlarmFacts) -> bool {
+    if rule.enabled != 1 || rule.track_type != facts.track_type {
+        return false;
+    }
+    if !in_list(&rule.src_sids, facts.source) || !in_schedule(rule, facts.ts) {
+        return false;
+    }
+
+    // 名单，分组和黑白名单都满足才算命中
+    let hit = match facts.hit {
+        Some((group, bw_flag)) => {
+            in_list(&rule.group_sids, group) && (rule.bw_flag == 0 || rule.bw_flag == bw_flag)
+        }
+        None => false,
+    };
+    match rule.match_mode {
+        MATCH_MODE_HIT if !hit => return false,
+        MATCH_MODE_MISS if hit => return false,
+        _ => {}
+    }
+
+    if rule.min_score > 0_f64 && facts.score < rule.min_score {
+        return false;
+    }
+    if rule.gender != 0 && facts.gender != rule.gender as i64 {
+        return false;
+    }
+    if let Some(ref color) = rule.car_color {
+        if !color.is_empty() && color != facts.color {
+            return false;
+        }
+    }
+    if let Some(ref limits) = rule.speed_limits {
+        if !limits.trim().is_empty() && !is_speeding(limits, facts) {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// 是否配置了该类型的规则，没有时使用 wl_alarm 的黑白名单模式
+pub fn has_rules(rules: &[CfAlarmRule], track_type: i32) -> bool {
+    rules.iter().any(|x| x.enabled == 1 && x.track_type == track_type)
+}
+
+/// 第一个满足的规则，rules 已按 severity 降序
+pub fn find_rule<'r>(rules: &'r [CfAlarmRule], facts: &AlarmFacts) -> Option<&'r CfAlarmRule> {
+    rules.iter().find(|x| is_match(x, facts))
+}
+
+/// 规则有修改时，重新加载
+pub async fn get_rules(ctx: &Arc<AppCtx>) -> Arc<Vec<CfAlarmRule>> {
+    if !ctx.registry.rules.is_stale(Local::now()) {
+        return ctx.registry.rules.current();
+    }
+
+    let ctx_cl = ctx.clone();
+    match tokio::task::spawn_blocking(move || ctx_cl.registry.get_rules(&ctx_cl.dao)).await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("error, alarm_rule, get_rules, {:?}", e);
+            ctx.registry.rules.current()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_rule(match_mode: i32) -> CfAlarmRule {
+        CfAlarmRule {
+            id: 1,
+            name: "r1".to_string(),
+            track_type: TRACK_TYPE_CAR,
+            enabled: 1,
+            severity: SEVERITY_MIN,
+            src_sids: None,
+            group_sids: None,
+            match_mode,
+            bw_flag: 0,
+            week_days: None,
+            time_start: None,
+            time_end: None,
+            min_score: 0_f64,
+            gender: 0,
+            car_color: None,
+            speed_limits: None,
+            memo: None,
+            gmt_create: Local::now(),
+            gmt_modified: Local::now(),
+        }
+    }
+
+    fn new_facts(ts: DateTime<Local>, hit: Option<(&str, i32)>) -> AlarmFacts<'_> {
+        AlarmFacts {
+            track_type: TRACK_TYPE_CAR,
+            source: "src1",
+            ts,
+            hit,
+            score: 0.9,
+            gender: 0,
+            color: "white",
+            lane: 1,
+            speed: 0_f64,
+        }
+    }
+
+    /// 2024-01-01 是周一
+    fn monday(h: u32, m: u32) -> DateTime<Local> {
+        Local.ymd(2024, 1, 1).and_hms(h, m, 0)
+    }
+
+    #[test]
+    fn schedule() {
+        let mut rule = new_rule(MATCH_MODE_ANY);
+        rule.time_start = Some("08:00".to_string());
+        rule.time_end = Some("18:00".to_string());
+        assert!(in_schedule(&rule, monday(8, 0)));
+        assert!(!in_schedule(&rule, monday(18, 0)));
+        assert!(!in_schedule(&rule, monday(7, 59)));
+
+        rule.week_days = Some("2,3".to_string());
+        assert!(!in_schedule(&rule, monday(9, 0)));
+        assert!(in_schedule(&rule, monday(9, 0) + chrono::Duration::days(1)));
+    }
+
+    #[test]
+    fn schedule_cross_midnight() {
+        let mut rule = new_rule(MATCH_MODE_ANY);
+        rule.time_start = Some("22:00".to_string());
+        rule.time_end = Some("06:00".to_string());
+        assert!(in_schedule(&rule, monday(23, 30)));
+        assert!(in_schedule(&rule, monday(0, 0)));
+        assert!(in_schedule(&rule, monday(5, 59)));
+        assert!(!in_schedule(&rule, monday(6, 0)));
+        assert!(!in_schedule(&rule, monday(12, 0)));
+
+        // 时间格式不对时不限
+        rule.time_end = Some("6点".to_string());
+        assert!(in_schedule(&rule, monday(12, 0)));
+    }
+
+    #[test]
+    fn match_mode() {
+        let ts = monday(12, 0);
+        let mut rule = new_rule(MATCH_MODE_HIT);
+        assert!(!is_match(&rule, &new_facts(ts, None)));
+        assert!(is_match(&rule, &new_facts(ts, Some(("g1", 1)))));
+
+        rule.group_sids = Some("g2, g3".to_string());
+        assert!(!is_match(&rule, &new_facts(ts, Some(("g1", 1)))));
+        assert!(is_match(&rule, &new_facts(ts, Some(("g3", 1)))));
+
+        rule.bw_flag = 2;
+        assert!(!is_match(&rule, &new_facts(ts, Some(("g3", 1)))));
+
+        let rule = new_rule(MATCH_MODE_MISS);
+        assert!(is_match(&rule, &new_facts(ts, None)));
+        assert!(!is_match(&rule, &new_facts(ts, Some(("g1", 1)))));
+    }
+
+    #[test]
+    fn match_filters() {
+        let ts = monday(12, 0);
+        let facts = new_facts(ts, None);
+
+        let mut rule = new_rule(MATCH_MODE_ANY);
+        assert!(is_match(&rule, &facts));
+
+        rule.enabled = 0;
+        assert!(!is_match(&rule, &facts));
+
+        let mut rule = new_rule(MATCH_MODE_ANY);
+        rule.track_type = TRACK_TYPE_FACE;
+        assert!(!is_match(&rule, &facts));
+
+        let mut rule = new_rule(MATCH_MODE_ANY);
+        rule.src_sids = Some("src2".to_string());
+        assert!(!is_match(&rule, &facts));
+
+        let mut rule = new_rule(MATCH_MODE_ANY);
+        rule.min_score = 0.95;
+        assert!(!is_match(&rule, &facts));
+
+        let mut rule = new_rule(MATCH_MODE_ANY);
+        rule.car_color = Some("black".to_string());
+        assert!(!is_match(&rule, &facts));
+        rule.car_color = Some("white".to_string());
+        assert!(is_match(&rule, &facts));
+    }
+
+    #[test]
+    fn find_first_rule() {
+        let facts = new_facts(monday(12, 0), None);
+        let mut r1 = new_rule(MATCH_MODE_HIT);
+        r1.severity = SEVERITY_MAX;
+        let r2 = new_rule(MATCH_MODE_ANY);
+        let rules = vec![r1, r2];
+        assert!(has_rules(&rules, TRACK_TYPE_CAR));
+        assert!(!has_rules(&rules, TRACK_TYPE_FACE));
+        assert_eq!(find_rule(&rules, &facts).map(|x| x.severity), Some(SEVERITY_MIN));
+    }
+
+    #[test]
+    fn speed_limits() {
+        assert_eq!(parse_speed_limits("1:60, 2:80,0:100"), Some(vec![(1, 60_f64), (2, 80_f64), (0, 100_f64)]));
+        assert_eq!(parse_speed_limits("1:60,,"), Some(vec![(1, 60_f64)]));
+        assert_eq!(parse_speed_limits(""), Some(vec![]));
+    }
+
+    #[test]
+    fn speed_limits_invalid() {
+        assert_eq!(parse_speed_limits("a:60"), None);
+        assert_eq!(parse_speed_limits("-1:60"), None);
+        assert_eq!(parse_speed_limits("1:0"), None);
+        assert_eq!(parse_speed_limits("1"), None);
+        assert_eq!(parse_speed_limits("1:60,2:x"), None);
+    }
+
+    #[test]
+    fn match_speeding() {
+        let mut rule = new_rule(MATCH_MODE_ANY);
+        rule.speed_limits = Some("1:60,0:100".to_string());
+
+        let mut facts = new_facts(monday(12, 0), None);
+        facts.speed = 60_f64;
+        assert!(!is_match(&rule, &facts));
+        facts.speed = 61_f64;
+        assert!(is_match(&rule, &facts));
+
+        // 其它车道按默认限速
+        facts.lane = 2;
+        assert!(!is_match(&rule, &facts));
+        facts.speed = 101_f64;
+        assert!(is_match(&rule, &facts));
+
+        // 车速未知
+        facts.speed = 0_f64;
+        assert!(!is_match(&rule, &facts));
+
+        // 没有对应车道和默认限速
+        rule.speed_limits = Some("1:60".to_string());
+        facts.speed = 200_f64;
+        assert!(!is_match(&rule, &facts));
+    }
+}
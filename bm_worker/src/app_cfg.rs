@@ -2,6 +2,8 @@ use std::{fs::File};
 
 use serde::{Deserialize, Serialize};
 
+use cffc_base::api::recg_cluster::RecgClusterOptions;
+
 use crate::error::AppResult;
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -45,6 +47,9 @@ pub struct AppCfgNotifyProcFt {
     pub clear_delay: u64,
     /// millisecond
     pub ready_delay: u64,
+    /// notify 中没有口罩/帽子/胡子属性时，保存后用质量最高的人脸异步调用 get_features 补全
+    #[serde(default)]
+    pub attr_by_api: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -107,6 +112,459 @@ pub struct AppCfgDiskClean {
     pub interval_minute: usize,
 }
 
+/// 各队列容量, 0 表示不限制
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AppCfgQueue {
+    /// /trackupload 接收队列 (face/car)
+    pub ingest_cap: usize,
+    /// 比对队列 (face/car)
+    pub judge_cap: usize,
+    /// 汇总队列
+    pub general_cap: usize,
+    /// EntBusSvc 各订阅者队列
+    pub bus_cap: usize,
+}
+
+impl Default for AppCfgQueue {
+    fn default() -> Self {
+        AppCfgQueue {
+            ingest_cap: 1000,
+            judge_cap: 1000,
+            general_cap: 2000,
+            bus_cap: 2000,
+        }
+    }
+}
+
+/// 通知写前日志目录
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AppCfgSpool {
+    pub enable: bool,
+    pub path: String,
+}
+
+impl Default for AppCfgSpool {
+    fn default() -> Self {
+        AppCfgSpool {
+            enable: false,
+            path: "../spool".to_string(),
+        }
+    }
+}
+
+/// 通知去重
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AppCfgDedup {
+    pub enable: bool,
+    /// 时间窗口, second
+    pub window_sec: u64,
+}
+
+impl Default for AppCfgDedup {
+    fn default() -> Self {
+        AppCfgDedup {
+            enable: true,
+            window_sec: 600,
+        }
+    }
+}
+
+/// /trackupload 认证, 密钥为 cf_dfnode.auth_key
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AppCfgUploadAuth {
+    pub enable: bool,
+    /// 凭证中的 ts 允许的时间偏差, second
+    pub max_skew_sec: i64,
+}
+
+impl Default for AppCfgUploadAuth {
+    fn default() -> Self {
+        AppCfgUploadAuth {
+            enable: false,
+            max_skew_sec: 300,
+        }
+    }
+}
+
+/// multipart 上传
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AppCfgUpload {
+    /// 文件超过该大小时写入临时文件, KB, 0 表示不写
+    pub spill_kb: usize,
+    /// 临时文件目录, 启动时清空
+    pub tmp_path: String,
+    /// 单个请求上限, MB, 0 表示不限制
+    pub max_request_mb: usize,
+    /// 所有请求上传中的字节数上限, MB, 0 表示不限制
+    pub max_inflight_mb: usize,
+}
+
+impl Default for AppCfgUpload {
+    fn default() -> Self {
+        AppCfgUpload {
+            spill_kb: 256,
+            tmp_path: "../tmp/upload".to_string(),
+            max_request_mb: 10,
+            max_inflight_mb: 128,
+        }
+    }
+}
+
+/// 录制模式, 保存 /trackupload 原始请求
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AppCfgRecord {
+    pub enable: bool,
+    pub path: String,
+    /// 录制的摄像头 src_sid, 为空时录制全部
+    pub sources: Vec<String>,
+}
+
+impl Default for AppCfgRecord {
+    fn default() -> Self {
+        AppCfgRecord {
+            enable: false,
+            path: "../capture".to_string(),
+            sources: vec![],
+        }
+    }
+}
+
+/// 越线事件
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AppCfgTrip {
+    pub enable: bool,
+    /// 计数的时间段, minute
+    pub bucket_min: i64,
+    /// TripSvc 队列容量
+    pub queue_cap: usize,
+}
+
+impl Default for AppCfgTrip {
+    fn default() -> Self {
+        AppCfgTrip {
+            enable: true,
+            bucket_min: 15,
+            queue_cap: 1000,
+        }
+    }
+}
+
+/// 车道流量统计
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AppCfgLaneStat {
+    pub enable: bool,
+    /// 累加到数据库的间隔, second
+    pub flush_sec: u64,
+    /// 1 分钟统计保留天数
+    pub minute_keep_days: i64,
+    /// 15 分钟统计保留天数, 小时和天的统计不删除
+    pub quarter_keep_days: i64,
+}
+
+impl Default for AppCfgLaneStat {
+    fn default() -> Self {
+        AppCfgLaneStat {
+            enable: true,
+            flush_sec: 10,
+            minute_keep_days: 3,
+            quarter_keep_days: 31,
+        }
+    }
+}
+
+/// 停车场进出配对及计费
+/// 摄像头的 io_flag 标记进口/出口
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct AppCfgParking {
+    pub enable: bool,
+    /// 免费时长, minute
+    pub free_min: i64,
+    /// 每小时费用，不足一小时按一小时
+    pub hourly_rate: f64,
+    /// 每 24 小时封顶费用，0 表示不封顶
+    pub daily_cap: f64,
+    /// 免费的车辆分组 sid
+    pub free_groups: Vec<String>,
+    /// 停放超过 n 小时报警，0 表示不检查
+    pub overstay_hours: i64,
+    /// 同一车牌 n 秒内重复抓拍时忽略
+    pub dedup_sec: i64,
+    /// 检查超时停放的间隔, second
+    pub check_sec: u64,
+}
+
+impl Default for AppCfgParking {
+    fn default() -> Self {
+        AppCfgParking {
+            enable: false,
+            free_min: 15,
+            hourly_rate: 5_f64,
+            daily_cap: 50_f64,
+            free_groups: vec![],
+            overstay_hours: 72,
+            dedup_sec: 60,
+            check_sec: 60,
+        }
+    }
+}
+
+/// 人脸比对 top-N 候选
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AppCfgCandidate {
+    /// 保留的候选数量, 1 表示只取 top1
+    pub top_n: i64,
+    /// 候选的最低分值
+    pub min_score: i64,
+}
+
+impl Default for AppCfgCandidate {
+    fn default() -> Self {
+        AppCfgCandidate {
+            top_n: 3,
+            min_score: 0,
+        }
+    }
+}
+
+/// 比对失败的重试
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AppCfgSearchRetry {
+    pub enable: bool,
+    pub queue_cap: usize,
+    /// 第一次重试的等待时间, 之后每次翻倍, millisecond
+    pub base_ms: u64,
+    /// 最长等待时间, millisecond
+    pub max_backoff_ms: u64,
+    /// 超过该时间不再重试, second
+    pub max_age_sec: i64,
+}
+
+impl Default for AppCfgSearchRetry {
+    fn default() -> Self {
+        AppCfgSearchRetry {
+            enable: true,
+            queue_cap: 1000,
+            base_ms: 1000,
+            max_backoff_ms: 60000,
+            max_age_sec: 600,
+        }
+    }
+}
+
+/// 人脸库/车辆分组缓存
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AppCfgRegistry {
+    /// 定时重新加载, 用于感知 worker 之外的修改, second, 0 表示只在修改时重新加载
+    pub reload_sec: i64,
+}
+
+impl Default for AppCfgRegistry {
+    fn default() -> Self {
+        AppCfgRegistry {
+            reload_sec: 60,
+        }
+    }
+}
+
+/// 识别节点集群
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct AppCfgRecgCluster {
+    /// 第一个为主节点，写操作只发往主节点
+    /// 为空时使用 web.server_node.url 和 api.recg_url
+    pub nodes: Vec<String>,
+    /// 健康探测间隔, second, 0 不探测
+    pub probe_sec: u64,
+    #[serde(flatten)]
+    pub opts: RecgClusterOptions,
+}
+
+impl Default for AppCfgRecgCluster {
+    fn default() -> Self {
+        AppCfgRecgCluster {
+            nodes: vec![],
+            probe_sec: 10,
+            opts: RecgClusterOptions::default(),
+        }
+    }
+}
+
+impl AppCfgRecgCluster {
+    pub fn get_nodes(&self, server_url: &str, recg_url: &str) -> Vec<String> {
+        if !self.nodes.is_empty() {
+            return self.nodes.clone();
+        }
+
+        let mut list = vec![server_url.to_string()];
+        if recg_url != server_url {
+            list.push(recg_url.to_string());
+        }
+        list
+    }
+}
+
+/// 本地特征索引
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct AppCfgFeatureIndex {
+    /// 识别节点全部不可用时，使用本地索引比对
+    pub enable: bool,
+    /// 通过 get_person_info 补全本地特征值的间隔, second
+    pub sync_sec: u64,
+    /// 每次补全的 poi 数量
+    pub sync_batch: i64,
+    /// 余弦相似度到识别模块分值的分段线性映射 [cos, score]，按识别模块的实际分值标定
+    pub score_points: Vec<(f32, f32)>,
+}
+
+impl Default for AppCfgFeatureIndex {
+    fn default() -> Self {
+        AppCfgFeatureIndex {
+            enable: true,
+            sync_sec: 300,
+            sync_batch: 100,
+            score_points: vec![(0.0, 0.0), (0.3, 60.0), (0.5, 80.0), (0.7, 95.0), (1.0, 100.0)],
+        }
+    }
+}
+
+/// 保存 facetrack 的特征值，用于以图搜图、历史回溯
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AppCfgTrackFeature {
+    pub enable: bool,
+    /// 每个 facetrack 保存质量分最高的 n 个
+    pub keep_n: usize,
+}
+
+impl Default for AppCfgTrackFeature {
+    fn default() -> Self {
+        AppCfgTrackFeature {
+            enable: true,
+            keep_n: 3,
+        }
+    }
+}
+
+/// 新增 poi 后回溯历史抓拍
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AppCfgRetroScan {
+    pub enable: bool,
+    /// 回溯最近 n 天
+    pub window_days: i64,
+    /// 每次比对的特征值数量
+    pub batch: i64,
+    /// 检查新任务的间隔, second
+    pub poll_sec: u64,
+    /// 命中阈值，0 表示使用 poi 的阈值
+    pub threshold: i32,
+    /// 使用识别模块的 compare_n，否则本地计算
+    pub use_api: bool,
+}
+
+impl Default for AppCfgRetroScan {
+    fn default() -> Self {
+        AppCfgRetroScan {
+            enable: true,
+            window_days: 7,
+            batch: 500,
+            poll_sec: 2,
+            threshold: 0,
+            use_api: true,
+        }
+    }
+}
+
+/// 以图搜图，与保存的 facetrack 特征值比对
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct AppCfgImageSearch {
+    /// 默认阈值
+    pub threshold: i64,
+    /// 最多返回的 facetrack 数量
+    pub top: i64,
+    /// 时间范围最多 n 天，未指定时搜索最近 n 天
+    pub max_days: i64,
+    /// 每次比对的特征值数量
+    pub batch: i64,
+    /// 每次搜索最多比对的特征值数量，超过时只返回已比对部分的结果
+    pub max_rows: i64,
+    /// 使用识别模块的 compare_n，否则本地计算
+    pub use_api: bool,
+}
+
+impl Default for AppCfgImageSearch {
+    fn default() -> Self {
+        AppCfgImageSearch {
+            threshold: 60,
+            top: 100,
+            max_days: 7,
+            batch: 1000,
+            max_rows: 100000,
+            use_api: true,
+        }
+    }
+}
+
+/// 未识别人脸的聚类，陌生人多次出现时报警
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AppCfgStranger {
+    pub enable: bool,
+    /// 参与的摄像头，为空时全部
+    pub src_sids: Vec<String>,
+    /// 归入同一聚类的最低分值, 0-100
+    pub threshold: i64,
+    /// 每个聚类保留的代表特征值数量
+    pub keep_features: usize,
+    /// 时间窗口, minute
+    pub window_min: i64,
+    /// 时间窗口内出现次数达到 n 时报警
+    pub alarm_count: usize,
+    /// 超过 n 天未出现的聚类不再参与
+    pub keep_days: i64,
+}
+
+impl Default for AppCfgStranger {
+    fn default() -> Self {
+        AppCfgStranger {
+            enable: false,
+            src_sids: vec![],
+            threshold: 75,
+            keep_features: 5,
+            window_min: 1440,
+            alarm_count: 10,
+            keep_days: 7,
+        }
+    }
+}
+
+/// 车牌精确查找 coi 未命中时，模糊匹配
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct AppCfgPlateMatch {
+    /// 默认关闭，只做完全匹配
+    pub enable: bool,
+    /// 最低分值, 0-100
+    pub min_score: f64,
+    /// 最大编辑代价，一个普通字符的替换/增删代价为 1
+    pub max_cost: f64,
+    /// 部分车牌的最少字符数
+    pub partial_min_len: usize,
+    /// 置信度低于此值的字符才允许替换为普通字符或删除
+    pub low_conf: f64,
+}
+
+impl Default for AppCfgPlateMatch {
+    fn default() -> Self {
+        AppCfgPlateMatch {
+            enable: false,
+            min_score: 80_f64,
+            max_cost: 1_f64,
+            partial_min_len: 5,
+            low_conf: 0.8,
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AppCfg {
@@ -126,6 +584,48 @@ pub struct AppCfg {
     pub web: AppCfgWeb,
     pub disk_clean: AppCfgDiskClean,
 
+    #[serde(default)]
+    pub queue: AppCfgQueue,
+
+    #[serde(default)]
+    pub spool: AppCfgSpool,
+
+    #[serde(default)]
+    pub dedup: AppCfgDedup,
+
+    #[serde(default)]
+    pub upload_auth: AppCfgUploadAuth,
+    #[serde(default)]
+    pub upload: AppCfgUpload,
+    #[serde(default)]
+    pub record: AppCfgRecord,
+    #[serde(default)]
+    pub trip: AppCfgTrip,
+    #[serde(default)]
+    pub lane_stat: AppCfgLaneStat,
+    #[serde(default)]
+    pub parking: AppCfgParking,
+    #[serde(default)]
+    pub registry: AppCfgRegistry,
+    #[serde(default)]
+    pub candidate: AppCfgCandidate,
+    #[serde(default)]
+    pub search_retry: AppCfgSearchRetry,
+    #[serde(default)]
+    pub recg_cluster: AppCfgRecgCluster,
+    #[serde(default)]
+    pub feature_index: AppCfgFeatureIndex,
+    #[serde(default)]
+    pub track_feature: AppCfgTrackFeature,
+    #[serde(default)]
+    pub retro_scan: AppCfgRetroScan,
+    #[serde(default)]
+    pub image_search: AppCfgImageSearch,
+    #[serde(default)]
+    pub stranger: AppCfgStranger,
+    #[serde(default)]
+    pub plate_match: AppCfgPlateMatch,
+
     #[serde(default)]
     pub local_ip: String,
 }
@@ -2,12 +2,19 @@ use std::sync::Arc;
 
 use tokio::sync::watch::Receiver;
 
-use cffc_base::api::bm_api::{AnalysisApi, RecognitionApi};
+use cffc_base::api::bm_api::AnalysisApi;
+use cffc_base::api::recg_cluster::RecgCluster;
 use cffc_base::db::SqliteClient;
+use cffc_base::util::bounded_queue::QueueStatRepo;
 
 use crate::app_cfg::AppCfg;
 use crate::dao::AppDao;
 use crate::dao::web_dao::WebDao;
+use crate::feature_index::FeatureIndex;
+use crate::judge_registry::JudgeRegistry;
+use crate::notify_dedup::NotifyDedup;
+use crate::notify_recorder::NotifyRecorder;
+use crate::notify_spool::NotifySpool;
 
 pub struct AppCtx {
     pub cfg: AppCfg,
@@ -17,19 +24,45 @@ pub struct AppCtx {
 
     // add
     pub ana_api: AnalysisApi,
-    pub recg_api: RecognitionApi,
+    /// 识别节点, 比对和人员管理共用
+    pub recg_api: RecgCluster,
+
+    /// 各队列状态
+    pub queue_stat: QueueStatRepo,
+
+    /// 通知写前日志
+    pub spool: NotifySpool,
+
+    /// 通知去重
+    pub dedup: NotifyDedup,
+
+    /// 录制模式
+    pub recorder: NotifyRecorder,
+
+    /// 人脸库/车辆分组缓存
+    pub registry: JudgeRegistry,
+
+    /// 黑白名单特征值的本地副本
+    pub feature_index: FeatureIndex,
 }
 
 impl AppCtx {
     pub fn new(cfg: AppCfg, conn: rusqlite::Connection, rx: Receiver<i64>) -> Self {
         let sqlite_client = Arc::new(SqliteClient::new(conn));
+        let recg_nodes = cfg.recg_cluster.get_nodes(&cfg.web.server_node.url, &cfg.api.recg_url);
 
         AppCtx {
             dao: AppDao::new(sqlite_client.clone()),
             web_dao: WebDao::new(sqlite_client),
             exit_rx: rx,
             ana_api: AnalysisApi::new(cfg.web.client_node.url.as_str()),
-            recg_api: RecognitionApi::new(cfg.web.server_node.url.as_str()),
+            recg_api: RecgCluster::new(&recg_nodes, cfg.recg_cluster.opts.clone()),
+            queue_stat: QueueStatRepo::new(),
+            spool: NotifySpool::new(&cfg.spool),
+            dedup: NotifyDedup::new(&cfg.dedup),
+            recorder: NotifyRecorder::new(&cfg.record),
+            registry: JudgeRegistry::new(&cfg.registry),
+            feature_index: FeatureIndex::new(&cfg.feature_index.score_points),
             cfg,
         }
     }
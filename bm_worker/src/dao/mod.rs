@@ -1,16 +1,53 @@
+use std::collections::HashMap;
 use std::sync::{Arc};
 
+use chrono::prelude::*;
 use rusqlite::{OptionalExtension, params, NO_PARAMS};
 
 use cffc_base::db::{
     SqliteClient,
     dbop::{DbOp, Result}};
 
-use crate::dao::model::{CfCartrack, CfDfsource, CfFacetrack, CfPoi, CfCoi};
+use crate::dao::model::{CfAlarmRule, CfCartrack, CfDfsource, CfFacetrack, CfFacetrackCandidate, CfFacetrackFeature, CfPoi, CfPoiFeature, CfCoi, CfRetroAlarm, CfRetroJob, CfStranger, CfStrangerAlarm, CfStrangerTrack, CfTripEvent, CfTripStat, CfLaneStat, CfParkingVisit};
+use crate::queue_item::{TRACK_TYPE_CAR, TRACK_TYPE_FACE};
 
 pub mod model;
 pub mod web_dao;
 
+/// 旧版本数据库升级时需补充的列: (表, 列, 列定义)
+/// 与 doc/data/sqlite3_init.sql 保持一致
+const MIGRATE_COLUMNS: &[(&str, &str, &str)] = &[
+    ("cf_dfnode", "auth_key", "varchar(100)"),
+    ("cf_dfsource", "speed_line1", "integer not null default 0"),
+    ("cf_dfsource", "speed_line2", "integer not null default 0"),
+    ("cf_dfsource", "speed_distance", "double not null default 0"),
+    ("cf_facetrack", "alarm_rule", "integer not null default 0"),
+    ("cf_facetrack", "alarm_level", "smallint not null default 0"),
+    ("cf_cartrack", "alarm_rule", "integer not null default 0"),
+    ("cf_cartrack", "alarm_level", "smallint not null default 0"),
+    ("cf_cartrack", "speed", "double not null default 0"),
+    ("cf_cartrack", "review_state", "smallint not null default 0"),
+    ("cf_coi_group", "min_confidence", "double not null default 0"),
+    ("cf_facetrack", "start_real_time", "integer not null default 0"),
+    ("cf_facetrack", "end_real_time", "integer not null default 0"),
+];
+
+/// 旧版本数据库缺少的表和索引，都是 if not exists, 在补充列之后执行
+const MIGRATE_SQL: &str = include_str!("../../../doc/data/sqlite3_migrate.sql");
+
+/// 替换 ft_sid 已有的特征值，在调用者的事务中执行
+fn replace_facetrack_features(con: &rusqlite::Connection, ft_sid: &str, list: &[CfFacetrackFeature]) -> Result<()> {
+    let sql = "delete from cf_facetrack_feature where ft_sid = ?";
+    con.execute(sql, params![ft_sid])?;
+
+    let sql = "insert into cf_facetrack_feature(ft_sid,src_sid,img_idx,feature,quality,capture_time,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?)";
+    let mut stmt = con.prepare(sql)?;
+    for po in list.iter() {
+        stmt.execute(params![po.ft_sid,po.src_sid,po.img_idx,po.feature,po.quality,po.capture_time,po.gmt_create,po.gmt_modified])?;
+    }
+    Ok(())
+}
+
 pub struct AppDao {
     pub client: Arc<SqliteClient>,
     // pub conn: Mutex<rusqlite::Connection>,
@@ -23,17 +60,94 @@ impl AppDao {
         }
     }
 
-    pub fn save_facetrack(&self, po: &CfFacetrack) -> Result<i64> {
-        let mut guard = self.client.lock().unwrap();
-        po.insert(&mut guard)
+    /// 补充旧版本数据库缺少的列，返回新增的列数
+    /// 表不存在时跳过
+    pub fn migrate_columns(&self) -> Result<usize> {
+        let con = self.client.lock().unwrap();
+
+        let mut affect = 0;
+        for (table, column, ddl) in MIGRATE_COLUMNS.iter() {
+            let sql = format!("pragma table_info({})", table);
+            let mut stmt = con.prepare(&sql)?;
+            let columns = stmt.query_map(NO_PARAMS, |row| row.get::<_, String>("name"))?
+                .collect::<rusqlite::Result<Vec<String>>>()?;
+            if columns.is_empty() || columns.iter().any(|x| x.eq(column)) {
+                continue;
+            }
+
+            let sql = format!("alter table {} add column {} {}", table, column, ddl);
+            con.execute(&sql, NO_PARAMS)?;
+            affect += 1;
+        }
+        Ok(affect)
     }
 
-    pub fn upate_facetrack_for_append(&self, po: &CfFacetrack) -> Result<usize> {
+    /// 创建旧版本数据库缺少的表和索引
+    pub fn migrate_tables(&self) -> Result<()> {
         let con = self.client.lock().unwrap();
+        con.execute_batch(MIGRATE_SQL)?;
+        Ok(())
+    }
 
-        let sql = "update cf_facetrack set img_ids = ?, gender = ?, age = ?, glasses = ?, direction = ?, gmt_modified = ? where ft_sid = ?";
-        let mut stmt = con.prepare(sql)?;
-        let affect = stmt.execute(params![po.img_ids,po.gender,po.age,po.glasses,po.direction,po.gmt_modified,po.ft_sid])?;
+    /// 在一个事务中保存 facetrack 及其特征值
+    pub fn save_facetrack(&self, po: &CfFacetrack, features: &[CfFacetrackFeature]) -> Result<i64> {
+        let mut con = self.client.lock().unwrap();
+        let tx = con.transaction()?;
+
+        let sql = "insert into cf_facetrack(ft_sid,src_sid,img_ids,matched,judged,alarmed,alarm_rule,alarm_level,most_person,most_score,gender,age,glasses,direction,plane_score,mask,moustache,hat,tag,flag,db_flag,db_sid,feature_ids,obj_id,submit_id,submit_time,start_real_time,end_real_time,capture_time,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?)";
+        tx.execute(sql, params![po.ft_sid,po.src_sid,po.img_ids,po.matched,po.judged,po.alarmed,po.alarm_rule,po.alarm_level,po.most_person,po.most_score,po.gender,po.age,po.glasses,po.direction,po.plane_score,po.mask,po.moustache,po.hat,po.tag,po.flag,po.db_flag,po.db_sid,po.feature_ids,po.obj_id,po.submit_id,po.submit_time,po.start_real_time,po.end_real_time,po.capture_time,po.gmt_create,po.gmt_modified])?;
+        let id = tx.last_insert_rowid();
+
+        if !features.is_empty() {
+            replace_facetrack_features(&tx, &po.ft_sid, features)?;
+        }
+        tx.commit()?;
+        Ok(id)
+    }
+
+    /// 已保存的 facetrack 的 img_ids，不存在时返回 None
+    pub fn load_facetrack_img_ids(&self, ft_sid: &str) -> Result<Option<String>> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select img_ids from cf_facetrack where ft_sid = ?";
+        let v = con.query_row(sql, params![ft_sid], |row| row.get(0)).optional()?;
+        Ok(v)
+    }
+
+    /// 在一个事务中更新 facetrack 及替换其特征值
+    pub fn upate_facetrack_for_append(&self, po: &CfFacetrack, features: &[CfFacetrackFeature]) -> Result<usize> {
+        let mut con = self.client.lock().unwrap();
+        let tx = con.transaction()?;
+
+        // 属性为空时保留原值
+        let sql = "update cf_facetrack set img_ids = ?, gender = coalesce(?, gender), age = coalesce(?, age), \
+            glasses = coalesce(?, glasses), direction = coalesce(?, direction), \
+            plane_score = coalesce(?, plane_score), mask = coalesce(?, mask), moustache = coalesce(?, moustache), hat = coalesce(?, hat), \
+            feature_ids = ?, end_real_time = max(end_real_time, ?), gmt_modified = ? where ft_sid = ?";
+        let affect = tx.execute(sql, params![po.img_ids,po.gender,po.age,po.glasses,po.direction,
+            po.plane_score,po.mask,po.moustache,po.hat,po.feature_ids,po.end_real_time,po.gmt_modified,po.ft_sid])?;
+
+        if affect == 1 && !features.is_empty() {
+            replace_facetrack_features(&tx, &po.ft_sid, features)?;
+        }
+        tx.commit()?;
+        Ok(affect)
+    }
+
+    /// get_features 补全的属性
+    /// props 为 (gender, age, glasses, direction), 为空时不修改；口罩/胡子/帽子只更新为空的
+    pub fn update_facetrack_attrs(&self, ft_sid: &str, props: Option<(i32, i32, i32, i32)>,
+                                  mask: Option<i32>, moustache: Option<i32>, hat: Option<i32>) -> Result<usize> {
+        let con = self.client.lock().unwrap();
+
+        let (gender, age, glasses, direction) = match props {
+            Some(v) => (Some(v.0), Some(v.1), Some(v.2), Some(v.3)),
+            None => (None, None, None, None),
+        };
+        let sql = "update cf_facetrack set gender = coalesce(?, gender), age = coalesce(?, age), \
+            glasses = coalesce(?, glasses), direction = coalesce(?, direction), \
+            mask = coalesce(mask, ?), moustache = coalesce(moustache, ?), hat = coalesce(hat, ?), gmt_modified = ? where ft_sid = ?";
+        let affect = con.execute(sql, params![gender, age, glasses, direction, mask, moustache, hat, Local::now(), ft_sid])?;
         Ok(affect)
     }
 
@@ -62,12 +176,31 @@ impl AppDao {
         Ok(ids)
     }
 
-    /// 删除 <= id 的facetrack记录
+    /// 删除 <= id 的facetrack记录，以及关联的越线记录
     pub fn delete_eldest_ft(&self, id: i64) -> Result<usize> {
         let con = self.client.lock().unwrap();
 
+        let sql = "delete from cf_facetrack_candidate where ft_sid in (select ft_sid from cf_facetrack where id <= ?)";
+        con.execute(sql, params![id])?;
+
+        let sql = "delete from cf_facetrack_feature where ft_sid in (select ft_sid from cf_facetrack where id <= ?)";
+        con.execute(sql, params![id])?;
+
+        let sql = "delete from cf_retro_alarm where ft_sid in (select ft_sid from cf_facetrack where id <= ?)";
+        con.execute(sql, params![id])?;
+
+        let sql = "delete from cf_stranger_track where ft_sid in (select ft_sid from cf_facetrack where id <= ?)";
+        con.execute(sql, params![id])?;
+
+        let sql = "delete from cf_trip_event where track_type = ? and track_sid in (select ft_sid from cf_facetrack where id <= ?)";
+        con.execute(sql, params![TRACK_TYPE_FACE, id])?;
+
         let sql = "delete from cf_facetrack where id <= ?";
         let affect = con.execute(sql, params![id])?;
+
+        // 越线统计保留到最旧的 facetrack
+        let sql = "delete from cf_trip_stat where track_type = ? and bucket_time < (select min(capture_time) from cf_facetrack)";
+        con.execute(sql, params![TRACK_TYPE_FACE])?;
         Ok(affect)
     }
 
@@ -89,12 +222,19 @@ impl AppDao {
         Ok(ids)
     }
 
-    /// 删除 <= id 的cartrack记录
+    /// 删除 <= id 的cartrack记录，以及关联的越线记录
     pub fn delete_eldest_ct(&self, id: i64) -> Result<usize> {
         let con = self.client.lock().unwrap();
 
+        let sql = "delete from cf_trip_event where track_type = ? and track_sid in (select sid from cf_cartrack where id <= ?)";
+        con.execute(sql, params![TRACK_TYPE_CAR, id])?;
+
         let sql = "delete from cf_cartrack where id <= ?";
         let affect = con.execute(sql, params![id])?;
+
+        // 越线统计保留到最旧的 cartrack
+        let sql = "delete from cf_trip_stat where track_type = ? and bucket_time < (select min(capture_time) from cf_cartrack)";
+        con.execute(sql, params![TRACK_TYPE_CAR])?;
         Ok(affect)
     }
 
@@ -128,12 +268,64 @@ impl AppDao {
     pub fn upate_facetrack_for_judge(&self, po: &CfFacetrack) -> Result<usize> {
         let con = self.client.lock().unwrap();
 
-        let sql = "update cf_facetrack set matched = ?, judged = ?, alarmed = ?, most_person = ?, most_score = ?, gmt_modified = ? where ft_sid = ?";
+        let sql = "update cf_facetrack set matched = ?, judged = ?, alarmed = ?, alarm_rule = ?, alarm_level = ?, most_person = ?, most_score = ?, gmt_modified = ? where ft_sid = ?";
         let mut stmt = con.prepare(sql)?;
-        let affect = stmt.execute(params![po.matched,po.judged,po.alarmed,po.most_person,po.most_score,po.gmt_modified,po.ft_sid])?;
+        let affect = stmt.execute(params![po.matched,po.judged,po.alarmed,po.alarm_rule,po.alarm_level,po.most_person,po.most_score,po.gmt_modified,po.ft_sid])?;
         Ok(affect)
     }
 
+    /// 先删除 ft_sid 已有的候选
+    /// 在一个事务中替换 ft_sid 已有的候选人
+    pub fn save_facetrack_candidates(&self, ft_sid: &str, list: &[CfFacetrackCandidate]) -> Result<usize> {
+        let mut con = self.client.lock().unwrap();
+        let tx = con.transaction()?;
+
+        let sql = "delete from cf_facetrack_candidate where ft_sid = ?";
+        tx.execute(sql, params![ft_sid])?;
+
+        let sql = "insert into cf_facetrack_candidate(ft_sid,rank_num,poi_sid,poi_name,db_sid,score,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?)";
+        {
+            let mut stmt = tx.prepare(sql)?;
+            for po in list.iter() {
+                stmt.execute(params![po.ft_sid,po.rank_num,po.poi_sid,po.poi_name,po.db_sid,po.score,po.gmt_create,po.gmt_modified])?;
+            }
+        }
+        tx.commit()?;
+        Ok(list.len())
+    }
+
+    pub fn load_facetrack_features(&self, ft_sid: &str) -> Result<Vec<CfFacetrackFeature>> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select * from cf_facetrack_feature where ft_sid = ? order by quality desc";
+        let mut stmt = con.prepare(sql)?;
+        let mut rows = stmt.query(params![ft_sid])?;
+
+        let mut list = Vec::new();
+        while let Some(row) = rows.next()? {
+            list.push(CfFacetrackFeature::scan(row)?);
+        }
+        Ok(list)
+    }
+
+    /// 批量加载候选人，按 ft_sid 分组
+    pub fn load_facetrack_candidates_by_sids(&self, ft_sids: &[String]) -> Result<HashMap<String, Vec<CfFacetrackCandidate>>> {
+        let con = self.client.lock().unwrap();
+
+        let mut map: HashMap<String, Vec<CfFacetrackCandidate>> = HashMap::new();
+        // sqlite 参数数量有限制，分批查询
+        for chunk in ft_sids.chunks(500) {
+            let sql = format!("select * from cf_facetrack_candidate where ft_sid in ({}) order by ft_sid, rank_num",
+                              vec!["?"; chunk.len()].join(","));
+            let mut stmt = con.prepare(&sql)?;
+            let mut rows = stmt.query(chunk)?;
+            while let Some(row) = rows.next()? {
+                let po = CfFacetrackCandidate::scan(row)?;
+                map.entry(po.ft_sid.clone()).or_insert_with(Vec::new).push(po);
+            }
+        }
+        Ok(map)
+    }
 
     // ----------------------------
 
@@ -142,12 +334,21 @@ impl AppDao {
         po.insert(&mut guard)
     }
 
+    /// 已保存的 cartrack 的 img_ids，不存在时返回 None
+    pub fn load_cartrack_img_ids(&self, sid: &str) -> Result<Option<String>> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select img_ids from cf_cartrack where sid = ?";
+        let v = con.query_row(sql, params![sid], |row| row.get(0)).optional()?;
+        Ok(v)
+    }
+
     pub fn upate_cartrack_for_append(&self, po: &CfCartrack) -> Result<usize> {
         let con = self.client.lock().unwrap();
 
-        let sql = "update cf_cartrack set img_ids = ?, plate_judged = ?, vehicle_judged = ?, move_direct = ?, car_direct = ?, plate_content = ?, plate_confidence = ?, plate_type = ?, car_color = ?, car_brand = ?, car_top_series = ?, car_series = ?, car_top_type = ?, car_mid_type = ?, lane_num = ?, gmt_modified = ? where sid = ?";
+        let sql = "update cf_cartrack set img_ids = ?, plate_judged = ?, vehicle_judged = ?, move_direct = ?, car_direct = ?, plate_content = ?, plate_confidence = ?, plate_type = ?, car_color = ?, car_brand = ?, car_top_series = ?, car_series = ?, car_top_type = ?, car_mid_type = ?, lane_num = ?, speed = ?, gmt_modified = ? where sid = ?";
         let mut stmt = con.prepare(sql)?;
-        let affect = stmt.execute(params![po.img_ids,po.plate_judged,po.vehicle_judged,po.move_direct,po.car_direct,po.plate_content, po.plate_confidence, po.plate_type,po.car_color,po.car_brand,po.car_top_series,po.car_series,po.car_top_type,po.car_mid_type,po.lane_num,po.gmt_modified,po.sid])?;
+        let affect = stmt.execute(params![po.img_ids,po.plate_judged,po.vehicle_judged,po.move_direct,po.car_direct,po.plate_content, po.plate_confidence, po.plate_type,po.car_color,po.car_brand,po.car_top_series,po.car_series,po.car_top_type,po.car_mid_type,po.lane_num,po.speed,po.gmt_modified,po.sid])?;
         Ok(affect)
     }
 
@@ -159,10 +360,36 @@ impl AppDao {
         Ok(v)
     }
 
-    pub fn load_coi_groups(&self) -> Result<Vec<(String, String, i32)>> {
+    pub fn load_coi_by_sid(&self, sid: &str) -> Result<Option<CfCoi>> {
         let con = self.client.lock().unwrap();
 
-        let sql = "select sid,name,bw_flag from cf_coi_group";
+        let sql = "select * from cf_coi where sid = ?";
+        let v = con.query_row(sql, params![sid], CfCoi::scan).optional()?;
+        Ok(v)
+    }
+
+    /// (sid, plate_content)
+    /// 模糊匹配用的车牌，不含白名单
+    pub fn load_coi_plates(&self) -> Result<Vec<(String, String)>> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select c.sid,c.plate_content from cf_coi c join cf_coi_group g on g.sid = c.group_sid where g.bw_flag != 2";
+        let mut stmt = con.prepare(sql)?;
+        let mut rows = stmt.query(NO_PARAMS)?;
+
+        let mut list = Vec::new();
+        while let Some(row) = rows.next()? {
+            let sid: String = row.get("sid")?;
+            let plate: String = row.get("plate_content")?;
+            list.push((sid, plate));
+        }
+        Ok(list)
+    }
+
+    pub fn load_coi_groups(&self) -> Result<Vec<(String, String, i32, f64)>> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select sid,name,bw_flag,min_confidence from cf_coi_group";
         let mut stmt = con.prepare(sql)?;
         let mut rows = stmt.query(NO_PARAMS)?;
 
@@ -171,7 +398,8 @@ impl AppDao {
             let sid: String = row.get("sid")?;
             let name: String = row.get("name")?;
             let bw_flag: i32 = row.get("bw_flag")?;
-            ids.push((sid, name, bw_flag));
+            let min_confidence: f64 = row.get("min_confidence")?;
+            ids.push((sid, name, bw_flag, min_confidence));
         }
 
         Ok(ids)
@@ -180,12 +408,37 @@ impl AppDao {
     pub fn upate_cartrack_for_judge(&self, po: &CfCartrack) -> Result<usize> {
         let con = self.client.lock().unwrap();
 
-        let sql = "update cf_cartrack set alarmed = ?, most_coi = ?, gmt_modified = ? where sid = ?";
+        let sql = "update cf_cartrack set alarmed = ?, alarm_rule = ?, alarm_level = ?, most_coi = ?, review_state = ?, gmt_modified = ? where sid = ?";
         let mut stmt = con.prepare(sql)?;
-        let affect = stmt.execute(params![po.alarmed,po.most_coi,po.gmt_modified,po.sid])?;
+        let affect = stmt.execute(params![po.alarmed,po.alarm_rule,po.alarm_level,po.most_coi,po.review_state,po.gmt_modified,po.sid])?;
         Ok(affect)
     }
 
+    /// 重新判断报警，只更新尚未报警且无需复核的记录
+    pub fn upate_cartrack_for_rejudge(&self, po: &CfCartrack) -> Result<usize> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "update cf_cartrack set alarmed = ?, alarm_rule = ?, alarm_level = ?, most_coi = ?, review_state = ?, gmt_modified = ? where sid = ? and alarmed = 0 and review_state = 0";
+        let mut stmt = con.prepare(sql)?;
+        let affect = stmt.execute(params![po.alarmed,po.alarm_rule,po.alarm_level,po.most_coi,po.review_state,po.gmt_modified,po.sid])?;
+        Ok(affect)
+    }
+
+    /// 已启用的报警规则，按级别从高到低
+    pub fn load_enabled_alarm_rules(&self) -> Result<Vec<CfAlarmRule>> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select * from cf_alarm_rule where enabled = 1 order by severity desc, id";
+        let mut stmt = con.prepare(sql)?;
+        let mut rows = stmt.query(NO_PARAMS)?;
+
+        let mut list = Vec::new();
+        while let Some(row) = rows.next()? {
+            list.push(CfAlarmRule::scan(row)?);
+        }
+        Ok(list)
+    }
+
     pub fn get_facetrack_count(&self) -> Result<Option<i64>> {
         let sql = "select count(*) from cf_facetrack";
         let con = self.client.lock().unwrap();
@@ -233,6 +486,53 @@ impl AppDao {
         Ok(list)
     }
 
+    /// 同一个 track 只保存一次越线事件, 返回影响行数
+    pub fn save_trip_event(&self, po: &CfTripEvent) -> Result<usize> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "insert or ignore into cf_trip_event(track_sid,track_type,src_sid,direction,trip_line,rect_x,rect_y,rect_w,rect_h,trip_ts,trip_pts,capture_time,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?,?,?,?,?,?,?)";
+        let mut stmt = con.prepare(sql)?;
+        let affect = stmt.execute(params![po.track_sid,po.track_type,po.src_sid,po.direction,po.trip_line,po.rect_x,po.rect_y,po.rect_w,po.rect_h,po.trip_ts,po.trip_pts,po.capture_time,po.gmt_create,po.gmt_modified])?;
+        Ok(affect)
+    }
+
+    /// 越线计数, 累加到对应时间段
+    pub fn incr_trip_stat(&self, po: &CfTripStat) -> Result<usize> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "insert into cf_trip_stat(src_sid,track_type,bucket_time,up_count,down_count,total_count,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?) \
+            on conflict(src_sid,track_type,bucket_time) do update set up_count = up_count + excluded.up_count, \
+            down_count = down_count + excluded.down_count, total_count = total_count + excluded.total_count, gmt_modified = excluded.gmt_modified";
+        let mut stmt = con.prepare(sql)?;
+        let affect = stmt.execute(params![po.src_sid,po.track_type,po.bucket_time,po.up_count,po.down_count,po.total_count,po.gmt_create,po.gmt_modified])?;
+        Ok(affect)
+    }
+
+    /// 车道流量计数, 在一个事务中累加到对应周期
+    pub fn incr_lane_stats(&self, list: &[CfLaneStat]) -> Result<usize> {
+        let mut con = self.client.lock().unwrap();
+        let tx = con.transaction()?;
+
+        let sql = "insert into cf_lane_stat(src_sid,period,bucket_time,lane_num,move_direct,car_type,count,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?,?) \
+            on conflict(src_sid,period,bucket_time,lane_num,move_direct,car_type) do update set count = count + excluded.count, gmt_modified = excluded.gmt_modified";
+        {
+            let mut stmt = tx.prepare(sql)?;
+            for po in list.iter() {
+                stmt.execute(params![po.src_sid,po.period,po.bucket_time,po.lane_num,po.move_direct,po.car_type,po.count,po.gmt_create,po.gmt_modified])?;
+            }
+        }
+        tx.commit()?;
+        Ok(list.len())
+    }
+
+    pub fn delete_lane_stats_before(&self, period: i64, before: &DateTime<Local>) -> Result<usize> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "delete from cf_lane_stat where period = ? and bucket_time < ?";
+        let affect = con.execute(sql, params![period, before])?;
+        Ok(affect)
+    }
+
     pub fn load_latest_cartrack_list(&self, limit: i64) -> Result<Vec<CfCartrack>> {
         let con = self.client.lock().unwrap();
 
@@ -247,4 +547,289 @@ impl AppDao {
         }
         Ok(list)
     }
+
+    // ----------------------------
+
+    pub fn load_poi_features(&self) -> Result<Vec<CfPoiFeature>> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select * from cf_poi_feature";
+        let mut stmt = con.prepare(sql)?;
+        let mut rows = stmt.query(NO_PARAMS)?;
+
+        let mut list = Vec::new();
+        while let Some(row) = rows.next()? {
+            list.push(CfPoiFeature::scan(row)?);
+        }
+        Ok(list)
+    }
+
+    /// 同一个 face 的特征值覆盖
+    pub fn save_poi_features(&self, list: &[CfPoiFeature]) -> Result<usize> {
+        let mut con = self.client.lock().unwrap();
+        let tx = con.transaction()?;
+
+        let sql = "insert or replace into cf_poi_feature(poi_sid,db_sid,face_id,feature,quality,gmt_create,gmt_modified) values(?,?,?,?,?,?,?)";
+        {
+            let mut stmt = tx.prepare(sql)?;
+            for po in list.iter() {
+                stmt.execute(params![po.poi_sid,po.db_sid,po.face_id,po.feature,po.quality,po.gmt_create,po.gmt_modified])?;
+            }
+        }
+        tx.commit()?;
+        Ok(list.len())
+    }
+
+    pub fn delete_poi_features(&self, poi_sid: &str) -> Result<usize> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "delete from cf_poi_feature where poi_sid = ?";
+        let affect = con.execute(sql, params![poi_sid])?;
+        Ok(affect)
+    }
+
+    pub fn delete_poi_face_feature(&self, poi_sid: &str, face_id: i64) -> Result<usize> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "delete from cf_poi_feature where poi_sid = ? and face_id = ?";
+        let affect = con.execute(sql, params![poi_sid, face_id])?;
+        Ok(affect)
+    }
+
+    /// 本地没有特征值的 poi, (poi_sid, db_sid)
+    pub fn load_pois_without_feature(&self, limit: i64) -> Result<Vec<(String, String)>> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select poi_sid,db_sid from cf_poi where poi_sid not in (select poi_sid from cf_poi_feature) order by id limit ?";
+        let mut stmt = con.prepare(sql)?;
+        let mut rows = stmt.query(params![limit])?;
+
+        let mut list = Vec::new();
+        while let Some(row) = rows.next()? {
+            list.push((row.get("poi_sid")?, row.get("db_sid")?));
+        }
+        Ok(list)
+    }
+
+    /// 删除 poi 已不存在的特征值
+    pub fn delete_orphan_poi_features(&self) -> Result<usize> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "delete from cf_poi_feature where poi_sid not in (select poi_sid from cf_poi)";
+        let affect = con.execute(sql, NO_PARAMS)?;
+        Ok(affect)
+    }
+
+    pub fn load_poi_features_by_sid(&self, poi_sid: &str) -> Result<Vec<CfPoiFeature>> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select * from cf_poi_feature where poi_sid = ?";
+        let mut stmt = con.prepare(sql)?;
+        let mut rows = stmt.query(params![poi_sid])?;
+
+        let mut list = Vec::new();
+        while let Some(row) = rows.next()? {
+            list.push(CfPoiFeature::scan(row)?);
+        }
+        Ok(list)
+    }
+
+    // ----------------------------
+
+    /// 最早的等待中的回溯任务
+    pub fn load_pending_retro_job(&self) -> Result<Option<CfRetroJob>> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select * from cf_retro_job where state = 0 order by id limit 1";
+        let v = con.query_row(sql, NO_PARAMS, CfRetroJob::scan).optional()?;
+        Ok(v)
+    }
+
+    pub fn update_retro_job(&self, po: &CfRetroJob) -> Result<usize> {
+        let mut guard = self.client.lock().unwrap();
+        po.update(&mut guard)
+    }
+
+    /// 启动时，中断的任务重新执行
+    pub fn reset_running_retro_jobs(&self) -> Result<usize> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "update cf_retro_job set state = 0 where state = 1";
+        let affect = con.execute(sql, NO_PARAMS)?;
+        Ok(affect)
+    }
+
+    pub fn count_facetrack_features(&self, start: &DateTime<Local>, end: &DateTime<Local>) -> Result<i64> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select count(*) from cf_facetrack_feature where capture_time >= ? and capture_time < ?";
+        let v = con.query_row(sql, params![start, end], |row| row.get(0))?;
+        Ok(v)
+    }
+
+    /// 按 id 分页
+    /// src_sid 为空时不限摄像头
+    pub fn load_facetrack_features_page(&self, src_sid: Option<&str>, start: &DateTime<Local>, end: &DateTime<Local>,
+                                        after_id: i64, limit: i64) -> Result<Vec<CfFacetrackFeature>> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select * from cf_facetrack_feature where (? is null or src_sid = ?) and capture_time >= ? and capture_time < ? and id > ? order by id limit ?";
+        let mut stmt = con.prepare(sql)?;
+        let mut rows = stmt.query(params![src_sid, src_sid, start, end, after_id, limit])?;
+
+        let mut list = Vec::new();
+        while let Some(row) = rows.next()? {
+            list.push(CfFacetrackFeature::scan(row)?);
+        }
+        Ok(list)
+    }
+
+    /// 同一任务同一 facetrack 只保留一条，取最高分
+    pub fn save_retro_alarms(&self, list: &[CfRetroAlarm]) -> Result<usize> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "insert into cf_retro_alarm(job_id,poi_sid,ft_sid,src_sid,img_idx,score,capture_time,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?,?) \
+            on conflict(job_id,ft_sid) do update set img_idx = excluded.img_idx, score = excluded.score, gmt_modified = excluded.gmt_modified \
+            where excluded.score > cf_retro_alarm.score";
+        let mut stmt = con.prepare(sql)?;
+        for po in list.iter() {
+            stmt.execute(params![po.job_id,po.poi_sid,po.ft_sid,po.src_sid,po.img_idx,po.score,po.capture_time,po.gmt_create,po.gmt_modified])?;
+        }
+        Ok(list.len())
+    }
+
+    pub fn count_retro_alarms(&self, job_id: i64) -> Result<i64> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select count(*) from cf_retro_alarm where job_id = ?";
+        let v = con.query_row(sql, params![job_id], |row| row.get(0))?;
+        Ok(v)
+    }
+
+    /// 最近活跃的陌生人聚类
+    pub fn load_recent_strangers(&self, since: &DateTime<Local>) -> Result<Vec<CfStranger>> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select * from cf_stranger where last_time >= ?";
+        let mut stmt = con.prepare(sql)?;
+        let mut rows = stmt.query(params![since])?;
+
+        let mut list = Vec::new();
+        while let Some(row) = rows.next()? {
+            list.push(CfStranger::scan(row)?);
+        }
+        Ok(list)
+    }
+
+    pub fn load_stranger_tracks_since(&self, since: &DateTime<Local>) -> Result<Vec<CfStrangerTrack>> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select * from cf_stranger_track where capture_time >= ? order by capture_time";
+        let mut stmt = con.prepare(sql)?;
+        let mut rows = stmt.query(params![since])?;
+
+        let mut list = Vec::new();
+        while let Some(row) = rows.next()? {
+            list.push(CfStrangerTrack::scan(row)?);
+        }
+        Ok(list)
+    }
+
+    /// 保存聚类及本次出现，同一 facetrack 只记录一次
+    /// 返回聚类的 id，None 表示 facetrack 已经记录过
+    pub fn save_stranger_track(&self, stranger: &CfStranger, po: &CfStrangerTrack) -> Result<Option<i64>> {
+        let mut guard = self.client.lock().unwrap();
+
+        let sql = "insert or ignore into cf_stranger_track(cluster_sid,ft_sid,src_sid,score,capture_time,gmt_create,gmt_modified) values(?,?,?,?,?,?,?)";
+        let affect = guard.execute(sql, params![po.cluster_sid,po.ft_sid,po.src_sid,po.score,po.capture_time,po.gmt_create,po.gmt_modified])?;
+        if affect == 0 {
+            return Ok(None);
+        }
+
+        if stranger.id == 0 {
+            return Ok(Some(stranger.insert(&mut guard)?));
+        }
+        stranger.update(&mut guard)?;
+        Ok(Some(stranger.id))
+    }
+
+    /// 在一个事务中保存报警和聚类的报警时间
+    pub fn save_stranger_alarm(&self, stranger: &CfStranger, po: &CfStrangerAlarm) -> Result<i64> {
+        let mut con = self.client.lock().unwrap();
+        let tx = con.transaction()?;
+
+        let sql = "update cf_stranger set alarm_time = ?, gmt_modified = ? where id = ?";
+        tx.execute(sql, params![stranger.alarm_time, stranger.gmt_modified, stranger.id])?;
+
+        let sql = "insert into cf_stranger_alarm(cluster_sid,ft_sid,src_sid,appear_count,window_start,capture_time,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?)";
+        tx.execute(sql, params![po.cluster_sid,po.ft_sid,po.src_sid,po.appear_count,po.window_start,po.capture_time,po.gmt_create,po.gmt_modified])?;
+        let id = tx.last_insert_rowid();
+
+        tx.commit()?;
+        Ok(id)
+    }
+
+    // ---------------- parking ----------------
+
+    /// 停车进出配对，在一个事务中读取、关闭和保存
+    /// f 的参数为车牌最近的在场记录和最近的出场记录，返回 None 时不修改
+    /// 返回值中的 close_state 不为 None 时，先把之前未出场的记录改为该状态
+    /// 返回保存的记录和关闭的记录数
+    pub fn pair_parking_visit<F>(&self, plate: &str, f: F) -> Result<Option<(CfParkingVisit, usize)>>
+        where F: FnOnce(Option<CfParkingVisit>, Option<CfParkingVisit>) -> Option<(Option<i32>, CfParkingVisit)> {
+        let mut con = self.client.lock().unwrap();
+        let tx = con.transaction()?;
+
+        let sql = "select * from cf_parking_visit where plate_content = ? and state = 0 order by entry_time desc, id desc limit 1";
+        let parked = tx.query_row(sql, params![plate], CfParkingVisit::scan).optional()?;
+        let sql = "select * from cf_parking_visit where plate_content = ? and exit_time is not null order by exit_time desc, id desc limit 1";
+        let exited = tx.query_row(sql, params![plate], CfParkingVisit::scan).optional()?;
+
+        let (close_state, mut po) = match f(parked, exited) {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+
+        let mut closed = 0;
+        if let Some(state) = close_state {
+            let sql = "update cf_parking_visit set state = ?, gmt_modified = ? where plate_content = ? and state = 0";
+            closed = tx.execute(sql, params![state, Local::now(), plate])?;
+        }
+
+        if po.id == 0 {
+            let sql = "insert into cf_parking_visit(plate_content,entry_ct_sid,entry_src_sid,entry_time,exit_ct_sid,exit_src_sid,exit_time,state,dwell_min,fee,fee_free,group_sid,overstay,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?,?,?,?,?,?,?,?)";
+            tx.execute(sql, params![po.plate_content,po.entry_ct_sid,po.entry_src_sid,po.entry_time,po.exit_ct_sid,po.exit_src_sid,po.exit_time,po.state,po.dwell_min,po.fee,po.fee_free,po.group_sid,po.overstay,po.gmt_create,po.gmt_modified])?;
+            po.id = tx.last_insert_rowid();
+        } else {
+            let sql = "update cf_parking_visit set exit_ct_sid = ?, exit_src_sid = ?, exit_time = ?, state = ?, dwell_min = ?, fee = ?, fee_free = ?, group_sid = ?, gmt_modified = ? where id = ?";
+            tx.execute(sql, params![po.exit_ct_sid,po.exit_src_sid,po.exit_time,po.state,po.dwell_min,po.fee,po.fee_free,po.group_sid,po.gmt_modified,po.id])?;
+        }
+        tx.commit()?;
+        Ok(Some((po, closed)))
+    }
+
+    /// 入场时间早于 before 且未报警的在场记录，标记为超时停放
+    pub fn mark_parking_overstay(&self, before: &DateTime<Local>) -> Result<Vec<CfParkingVisit>> {
+        let mut guard = self.client.lock().unwrap();
+        let tx = guard.transaction()?;
+
+        let mut list = Vec::new();
+        {
+            let sql = "select * from cf_parking_visit where state = 0 and overstay = 0 and entry_time < ? order by entry_time";
+            let mut stmt = tx.prepare(sql)?;
+            let mut rows = stmt.query(params![before])?;
+            while let Some(row) = rows.next()? {
+                list.push(CfParkingVisit::scan(row)?);
+            }
+        }
+
+        let now = Local::now();
+        for v in list.iter_mut() {
+            v.overstay = 1;
+            v.gmt_modified = now;
+            tx.execute("update cf_parking_visit set overstay = 1, gmt_modified = ? where id = ?", params![now, v.id])?;
+        }
+        tx.commit()?;
+        Ok(list)
+    }
 }
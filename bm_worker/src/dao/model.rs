@@ -88,6 +88,7 @@ pub struct CfDfnode {
     pub url: String,
     pub node_type: i32,
     pub sort_num: Option<i32>,
+    pub auth_key: Option<String>,
     pub gmt_create: DateTime<Local>,
     pub gmt_modified: DateTime<Local>,
 }
@@ -102,6 +103,7 @@ impl CfDfnode {
             url: row.get("url")?,
             node_type: row.get("node_type")?,
             sort_num: row.get("sort_num")?,
+            auth_key: row.get("auth_key")?,
             gmt_create: row.get("gmt_create")?,
             gmt_modified: row.get("gmt_modified")?,
         })
@@ -112,18 +114,18 @@ impl DbOp<CfDfnode> for CfDfnode {
     type Conn = Connection;
 
     fn insert(&self, con: &mut Self::Conn) -> Result<i64, dbop::Error> {
-        let sql = "insert into cf_dfnode(node_sid,name,ip,url,node_type,sort_num,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?)";
+        let sql = "insert into cf_dfnode(node_sid,name,ip,url,node_type,sort_num,auth_key,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?,?)";
         let mut stmt = con.prepare(sql)?;
-        let _affect = stmt.execute(params![self.node_sid,self.name,self.ip,self.url,self.node_type,self.sort_num,self.gmt_create,self.gmt_modified])?;
+        let _affect = stmt.execute(params![self.node_sid,self.name,self.ip,self.url,self.node_type,self.sort_num,self.auth_key,self.gmt_create,self.gmt_modified])?;
 
         let id = con.last_insert_rowid();
         Ok(id)
     }
 
     fn update(&self, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
-        let sql = "update cf_dfnode set node_sid = ?, name = ?, ip = ?, url = ?, node_type = ?, sort_num = ?, gmt_create = ?, gmt_modified = ? where id = ?";
+        let sql = "update cf_dfnode set node_sid = ?, name = ?, ip = ?, url = ?, node_type = ?, sort_num = ?, auth_key = ?, gmt_create = ?, gmt_modified = ? where id = ?";
         let mut stmt = con.prepare(sql)?;
-        let affect = stmt.execute(params![self.node_sid,self.name,self.ip,self.url,self.node_type,self.sort_num,self.gmt_create,self.gmt_modified,self.id])?;
+        let affect = stmt.execute(params![self.node_sid,self.name,self.ip,self.url,self.node_type,self.sort_num,self.auth_key,self.gmt_create,self.gmt_modified,self.id])?;
         Ok(affect)
     }
 
@@ -231,6 +233,9 @@ pub struct CfDfsource {
     pub rtcp_utc: i32,
     pub lane_desc: Option<String>,
     pub lane_count: i32,
+    pub speed_line1: i64,
+    pub speed_line2: i64,
+    pub speed_distance: f64,
     pub memo: Option<String>,
     pub gmt_create: DateTime<Local>,
     pub gmt_modified: DateTime<Local>,
@@ -261,6 +266,9 @@ impl CfDfsource {
             rtcp_utc: row.get("rtcp_utc")?,
             lane_desc: row.get("lane_desc")?,
             lane_count: row.get("lane_count")?,
+            speed_line1: row.get("speed_line1")?,
+            speed_line2: row.get("speed_line2")?,
+            speed_distance: row.get("speed_distance")?,
             memo: row.get("memo")?,
             gmt_create: row.get("gmt_create")?,
             gmt_modified: row.get("gmt_modified")?,
@@ -272,18 +280,18 @@ impl DbOp<CfDfsource> for CfDfsource {
     type Conn = Connection;
 
     fn insert(&self, con: &mut Self::Conn) -> Result<i64, dbop::Error> {
-        let sql = "insert into cf_dfsource(src_sid,name,node_sid,src_url,push_url,ip,src_state,src_config,grab_type,io_flag,direction,tp_id,upload_flag,location_name,resolution_ratio,coordinate,sort_num,trip_line,rtcp_utc,lane_desc,lane_count,memo,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?)";
+        let sql = "insert into cf_dfsource(src_sid,name,node_sid,src_url,push_url,ip,src_state,src_config,grab_type,io_flag,direction,tp_id,upload_flag,location_name,resolution_ratio,coordinate,sort_num,trip_line,rtcp_utc,lane_desc,lane_count,speed_line1,speed_line2,speed_distance,memo,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?)";
         let mut stmt = con.prepare(sql)?;
-        let _affect = stmt.execute(params![self.src_sid,self.name,self.node_sid,self.src_url,self.push_url,self.ip,self.src_state,self.src_config,self.grab_type,self.io_flag,self.direction,self.tp_id,self.upload_flag,self.location_name,self.resolution_ratio,self.coordinate,self.sort_num,self.trip_line,self.rtcp_utc,self.lane_desc,self.lane_count,self.memo,self.gmt_create,self.gmt_modified])?;
+        let _affect = stmt.execute(params![self.src_sid,self.name,self.node_sid,self.src_url,self.push_url,self.ip,self.src_state,self.src_config,self.grab_type,self.io_flag,self.direction,self.tp_id,self.upload_flag,self.location_name,self.resolution_ratio,self.coordinate,self.sort_num,self.trip_line,self.rtcp_utc,self.lane_desc,self.lane_count,self.speed_line1,self.speed_line2,self.speed_distance,self.memo,self.gmt_create,self.gmt_modified])?;
 
         let id = con.last_insert_rowid();
         Ok(id)
     }
 
     fn update(&self, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
-        let sql = "update cf_dfsource set src_sid = ?, name = ?, node_sid = ?, src_url = ?, push_url = ?, ip = ?, src_state = ?, src_config = ?, grab_type = ?, io_flag = ?, direction = ?, tp_id = ?, upload_flag = ?, location_name = ?, resolution_ratio = ?, coordinate = ?, sort_num = ?, trip_line = ?, rtcp_utc = ?, lane_desc = ?, lane_count = ?, memo = ?, gmt_create = ?, gmt_modified = ? where id = ?";
+        let sql = "update cf_dfsource set src_sid = ?, name = ?, node_sid = ?, src_url = ?, push_url = ?, ip = ?, src_state = ?, src_config = ?, grab_type = ?, io_flag = ?, direction = ?, tp_id = ?, upload_flag = ?, location_name = ?, resolution_ratio = ?, coordinate = ?, sort_num = ?, trip_line = ?, rtcp_utc = ?, lane_desc = ?, lane_count = ?, speed_line1 = ?, speed_line2 = ?, speed_distance = ?, memo = ?, gmt_create = ?, gmt_modified = ? where id = ?";
         let mut stmt = con.prepare(sql)?;
-        let affect = stmt.execute(params![self.src_sid,self.name,self.node_sid,self.src_url,self.push_url,self.ip,self.src_state,self.src_config,self.grab_type,self.io_flag,self.direction,self.tp_id,self.upload_flag,self.location_name,self.resolution_ratio,self.coordinate,self.sort_num,self.trip_line,self.rtcp_utc,self.lane_desc,self.lane_count,self.memo,self.gmt_create,self.gmt_modified,self.id])?;
+        let affect = stmt.execute(params![self.src_sid,self.name,self.node_sid,self.src_url,self.push_url,self.ip,self.src_state,self.src_config,self.grab_type,self.io_flag,self.direction,self.tp_id,self.upload_flag,self.location_name,self.resolution_ratio,self.coordinate,self.sort_num,self.trip_line,self.rtcp_utc,self.lane_desc,self.lane_count,self.speed_line1,self.speed_line2,self.speed_distance,self.memo,self.gmt_create,self.gmt_modified,self.id])?;
         Ok(affect)
     }
 
@@ -386,6 +394,8 @@ pub struct CfFacetrack {
     pub matched: Option<i32>,
     pub judged: Option<i32>,
     pub alarmed: Option<i32>,
+    pub alarm_rule: i64,
+    pub alarm_level: i32,
     pub most_person: Option<String>,
     pub most_score: Option<f64>,
     pub gender: Option<i32>,
@@ -404,6 +414,8 @@ pub struct CfFacetrack {
     pub obj_id: Option<String>,
     pub submit_id: Option<String>,
     pub submit_time: Option<DateTime<Local>>,
+    pub start_real_time: i64,
+    pub end_real_time: i64,
     pub capture_time: DateTime<Local>,
     pub gmt_create: DateTime<Local>,
     pub gmt_modified: DateTime<Local>,
@@ -419,6 +431,8 @@ impl CfFacetrack {
             matched: row.get("matched")?,
             judged: row.get("judged")?,
             alarmed: row.get("alarmed")?,
+            alarm_rule: row.get("alarm_rule")?,
+            alarm_level: row.get("alarm_level")?,
             most_person: row.get("most_person")?,
             most_score: row.get("most_score")?,
             gender: row.get("gender")?,
@@ -437,6 +451,8 @@ impl CfFacetrack {
             obj_id: row.get("obj_id")?,
             submit_id: row.get("submit_id")?,
             submit_time: row.get("submit_time")?,
+            start_real_time: row.get("start_real_time")?,
+            end_real_time: row.get("end_real_time")?,
             capture_time: row.get("capture_time")?,
             gmt_create: row.get("gmt_create")?,
             gmt_modified: row.get("gmt_modified")?,
@@ -448,18 +464,18 @@ impl DbOp<CfFacetrack> for CfFacetrack {
     type Conn = Connection;
 
     fn insert(&self, con: &mut Self::Conn) -> Result<i64, dbop::Error> {
-        let sql = "insert into cf_facetrack(ft_sid,src_sid,img_ids,matched,judged,alarmed,most_person,most_score,gender,age,glasses,direction,plane_score,mask,moustache,hat,tag,flag,db_flag,db_sid,feature_ids,obj_id,submit_id,submit_time,capture_time,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?)";
+        let sql = "insert into cf_facetrack(ft_sid,src_sid,img_ids,matched,judged,alarmed,alarm_rule,alarm_level,most_person,most_score,gender,age,glasses,direction,plane_score,mask,moustache,hat,tag,flag,db_flag,db_sid,feature_ids,obj_id,submit_id,submit_time,start_real_time,end_real_time,capture_time,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?)";
         let mut stmt = con.prepare(sql)?;
-        let _affect = stmt.execute(params![self.ft_sid,self.src_sid,self.img_ids,self.matched,self.judged,self.alarmed,self.most_person,self.most_score,self.gender,self.age,self.glasses,self.direction,self.plane_score,self.mask,self.moustache,self.hat,self.tag,self.flag,self.db_flag,self.db_sid,self.feature_ids,self.obj_id,self.submit_id,self.submit_time,self.capture_time,self.gmt_create,self.gmt_modified])?;
+        let _affect = stmt.execute(params![self.ft_sid,self.src_sid,self.img_ids,self.matched,self.judged,self.alarmed,self.alarm_rule,self.alarm_level,self.most_person,self.most_score,self.gender,self.age,self.glasses,self.direction,self.plane_score,self.mask,self.moustache,self.hat,self.tag,self.flag,self.db_flag,self.db_sid,self.feature_ids,self.obj_id,self.submit_id,self.submit_time,self.start_real_time,self.end_real_time,self.capture_time,self.gmt_create,self.gmt_modified])?;
 
         let id = con.last_insert_rowid();
         Ok(id)
     }
 
     fn update(&self, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
-        let sql = "update cf_facetrack set ft_sid = ?, src_sid = ?, img_ids = ?, matched = ?, judged = ?, alarmed = ?, most_person = ?, most_score = ?, gender = ?, age = ?, glasses = ?, direction = ?, plane_score = ?, mask = ?, moustache = ?, hat = ?, tag = ?, flag = ?, db_flag = ?, db_sid = ?, feature_ids = ?, obj_id = ?, submit_id = ?, submit_time = ?, capture_time = ?, gmt_create = ?, gmt_modified = ? where id = ?";
+        let sql = "update cf_facetrack set ft_sid = ?, src_sid = ?, img_ids = ?, matched = ?, judged = ?, alarmed = ?, alarm_rule = ?, alarm_level = ?, most_person = ?, most_score = ?, gender = ?, age = ?, glasses = ?, direction = ?, plane_score = ?, mask = ?, moustache = ?, hat = ?, tag = ?, flag = ?, db_flag = ?, db_sid = ?, feature_ids = ?, obj_id = ?, submit_id = ?, submit_time = ?, start_real_time = ?, end_real_time = ?, capture_time = ?, gmt_create = ?, gmt_modified = ? where id = ?";
         let mut stmt = con.prepare(sql)?;
-        let affect = stmt.execute(params![self.ft_sid,self.src_sid,self.img_ids,self.matched,self.judged,self.alarmed,self.most_person,self.most_score,self.gender,self.age,self.glasses,self.direction,self.plane_score,self.mask,self.moustache,self.hat,self.tag,self.flag,self.db_flag,self.db_sid,self.feature_ids,self.obj_id,self.submit_id,self.submit_time,self.capture_time,self.gmt_create,self.gmt_modified,self.id])?;
+        let affect = stmt.execute(params![self.ft_sid,self.src_sid,self.img_ids,self.matched,self.judged,self.alarmed,self.alarm_rule,self.alarm_level,self.most_person,self.most_score,self.gender,self.age,self.glasses,self.direction,self.plane_score,self.mask,self.moustache,self.hat,self.tag,self.flag,self.db_flag,self.db_sid,self.feature_ids,self.obj_id,self.submit_id,self.submit_time,self.start_real_time,self.end_real_time,self.capture_time,self.gmt_create,self.gmt_modified,self.id])?;
         Ok(affect)
     }
 
@@ -608,6 +624,8 @@ pub struct CfCartrack {
     pub src_sid: String,
     pub img_ids: String,
     pub alarmed: i32,
+    pub alarm_rule: i64,
+    pub alarm_level: i32,
     pub most_coi: Option<String>,
     pub plate_judged: i32,
     pub vehicle_judged: i32,
@@ -632,6 +650,8 @@ pub struct CfCartrack {
     pub capture_ts: i64,
     pub capture_pts: i64,
     pub lane_num: i32,
+    pub review_state: i32,
+    pub speed: f64,
     pub gmt_create: DateTime<Local>,
     pub gmt_modified: DateTime<Local>,
 }
@@ -644,6 +664,8 @@ impl CfCartrack {
             src_sid: row.get("src_sid")?,
             img_ids: row.get("img_ids")?,
             alarmed: row.get("alarmed")?,
+            alarm_rule: row.get("alarm_rule")?,
+            alarm_level: row.get("alarm_level")?,
             most_coi: row.get("most_coi")?,
             plate_judged: row.get("plate_judged")?,
             vehicle_judged: row.get("vehicle_judged")?,
@@ -668,6 +690,8 @@ impl CfCartrack {
             capture_ts: row.get("capture_ts")?,
             capture_pts: row.get("capture_pts")?,
             lane_num: row.get("lane_num")?,
+            review_state: row.get("review_state")?,
+            speed: row.get("speed")?,
             gmt_create: row.get("gmt_create")?,
             gmt_modified: row.get("gmt_modified")?,
         })
@@ -678,18 +702,18 @@ impl DbOp<CfCartrack> for CfCartrack {
     type Conn = Connection;
 
     fn insert(&self, con: &mut Self::Conn) -> Result<i64, dbop::Error> {
-        let sql = "insert into cf_cartrack(sid,src_sid,img_ids,alarmed,most_coi,plate_judged,vehicle_judged,move_direct,car_direct,plate_content,plate_confidence,plate_type,car_color,car_brand,car_top_series,car_series,car_top_type,car_mid_type,tag,flag,obj_id,submit_id,submit_time,is_realtime,capture_time,capture_ts,capture_pts,lane_num,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?)";
+        let sql = "insert into cf_cartrack(sid,src_sid,img_ids,alarmed,alarm_rule,alarm_level,most_coi,plate_judged,vehicle_judged,move_direct,car_direct,plate_content,plate_confidence,plate_type,car_color,car_brand,car_top_series,car_series,car_top_type,car_mid_type,tag,flag,obj_id,submit_id,submit_time,is_realtime,capture_time,capture_ts,capture_pts,lane_num,review_state,speed,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?)";
         let mut stmt = con.prepare(sql)?;
-        let _affect = stmt.execute(params![self.sid,self.src_sid,self.img_ids,self.alarmed,self.most_coi,self.plate_judged,self.vehicle_judged,self.move_direct,self.car_direct,self.plate_content,self.plate_confidence,self.plate_type,self.car_color,self.car_brand,self.car_top_series,self.car_series,self.car_top_type,self.car_mid_type,self.tag,self.flag,self.obj_id,self.submit_id,self.submit_time,self.is_realtime,self.capture_time,self.capture_ts,self.capture_pts,self.lane_num,self.gmt_create,self.gmt_modified])?;
+        let _affect = stmt.execute(params![self.sid,self.src_sid,self.img_ids,self.alarmed,self.alarm_rule,self.alarm_level,self.most_coi,self.plate_judged,self.vehicle_judged,self.move_direct,self.car_direct,self.plate_content,self.plate_confidence,self.plate_type,self.car_color,self.car_brand,self.car_top_series,self.car_series,self.car_top_type,self.car_mid_type,self.tag,self.flag,self.obj_id,self.submit_id,self.submit_time,self.is_realtime,self.capture_time,self.capture_ts,self.capture_pts,self.lane_num,self.review_state,self.speed,self.gmt_create,self.gmt_modified])?;
 
         let id = con.last_insert_rowid();
         Ok(id)
     }
 
     fn update(&self, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
-        let sql = "update cf_cartrack set sid = ?, src_sid = ?, img_ids = ?, alarmed = ?, most_coi = ?, plate_judged = ?, vehicle_judged = ?, move_direct = ?, car_direct = ?, plate_content = ?, plate_confidence = ?, plate_type = ?, car_color = ?, car_brand = ?, car_top_series = ?, car_series = ?, car_top_type = ?, car_mid_type = ?, tag = ?, flag = ?, obj_id = ?, submit_id = ?, submit_time = ?, is_realtime = ?, capture_time = ?, capture_ts = ?, capture_pts = ?, lane_num = ?, gmt_create = ?, gmt_modified = ? where id = ?";
+        let sql = "update cf_cartrack set sid = ?, src_sid = ?, img_ids = ?, alarmed = ?, alarm_rule = ?, alarm_level = ?, most_coi = ?, plate_judged = ?, vehicle_judged = ?, move_direct = ?, car_direct = ?, plate_content = ?, plate_confidence = ?, plate_type = ?, car_color = ?, car_brand = ?, car_top_series = ?, car_series = ?, car_top_type = ?, car_mid_type = ?, tag = ?, flag = ?, obj_id = ?, submit_id = ?, submit_time = ?, is_realtime = ?, capture_time = ?, capture_ts = ?, capture_pts = ?, lane_num = ?, review_state = ?, speed = ?, gmt_create = ?, gmt_modified = ? where id = ?";
         let mut stmt = con.prepare(sql)?;
-        let affect = stmt.execute(params![self.sid,self.src_sid,self.img_ids,self.alarmed,self.most_coi,self.plate_judged,self.vehicle_judged,self.move_direct,self.car_direct,self.plate_content,self.plate_confidence,self.plate_type,self.car_color,self.car_brand,self.car_top_series,self.car_series,self.car_top_type,self.car_mid_type,self.tag,self.flag,self.obj_id,self.submit_id,self.submit_time,self.is_realtime,self.capture_time,self.capture_ts,self.capture_pts,self.lane_num,self.gmt_create,self.gmt_modified,self.id])?;
+        let affect = stmt.execute(params![self.sid,self.src_sid,self.img_ids,self.alarmed,self.alarm_rule,self.alarm_level,self.most_coi,self.plate_judged,self.vehicle_judged,self.move_direct,self.car_direct,self.plate_content,self.plate_confidence,self.plate_type,self.car_color,self.car_brand,self.car_top_series,self.car_series,self.car_top_type,self.car_mid_type,self.tag,self.flag,self.obj_id,self.submit_id,self.submit_time,self.is_realtime,self.capture_time,self.capture_ts,self.capture_pts,self.lane_num,self.review_state,self.speed,self.gmt_create,self.gmt_modified,self.id])?;
         Ok(affect)
     }
 
@@ -795,6 +819,7 @@ pub struct CfCoiGroup {
     pub sid: String,
     pub name: String,
     pub bw_flag: i32,
+    pub min_confidence: f64,
     pub memo: Option<String>,
     pub gmt_create: DateTime<Local>,
     pub gmt_modified: DateTime<Local>,
@@ -807,6 +832,7 @@ impl CfCoiGroup {
             sid: row.get("sid")?,
             name: row.get("name")?,
             bw_flag: row.get("bw_flag")?,
+            min_confidence: row.get("min_confidence")?,
             memo: row.get("memo")?,
             gmt_create: row.get("gmt_create")?,
             gmt_modified: row.get("gmt_modified")?,
@@ -818,18 +844,18 @@ impl DbOp<CfCoiGroup> for CfCoiGroup {
     type Conn = Connection;
 
     fn insert(&self, con: &mut Self::Conn) -> Result<i64, dbop::Error> {
-        let sql = "insert into cf_coi_group(sid,name,bw_flag,memo,gmt_create,gmt_modified) values(?,?,?,?,?,?)";
+        let sql = "insert into cf_coi_group(sid,name,bw_flag,min_confidence,memo,gmt_create,gmt_modified) values(?,?,?,?,?,?,?)";
         let mut stmt = con.prepare(sql)?;
-        let _affect = stmt.execute(params![self.sid,self.name,self.bw_flag,self.memo,self.gmt_create,self.gmt_modified])?;
+        let _affect = stmt.execute(params![self.sid,self.name,self.bw_flag,self.min_confidence,self.memo,self.gmt_create,self.gmt_modified])?;
 
         let id = con.last_insert_rowid();
         Ok(id)
     }
 
     fn update(&self, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
-        let sql = "update cf_coi_group set sid = ?, name = ?, bw_flag = ?, memo = ?, gmt_create = ?, gmt_modified = ? where id = ?";
+        let sql = "update cf_coi_group set sid = ?, name = ?, bw_flag = ?, min_confidence = ?, memo = ?, gmt_create = ?, gmt_modified = ? where id = ?";
         let mut stmt = con.prepare(sql)?;
-        let affect = stmt.execute(params![self.sid,self.name,self.bw_flag,self.memo,self.gmt_create,self.gmt_modified,self.id])?;
+        let affect = stmt.execute(params![self.sid,self.name,self.bw_flag,self.min_confidence,self.memo,self.gmt_create,self.gmt_modified,self.id])?;
         Ok(affect)
     }
 
@@ -980,3 +1006,869 @@ impl DbOp<CfGatehistory> for CfGatehistory {
     }
 }
 
+
+//---------------------- CfTripEvent ----------------------
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CfTripEvent {
+    pub id: i64,
+    pub track_sid: String,
+    pub track_type: i32,
+    pub src_sid: String,
+    pub direction: i32,
+    pub trip_line: i64,
+    pub rect_x: i64,
+    pub rect_y: i64,
+    pub rect_w: i64,
+    pub rect_h: i64,
+    pub trip_ts: i64,
+    pub trip_pts: i64,
+    pub capture_time: DateTime<Local>,
+    pub gmt_create: DateTime<Local>,
+    pub gmt_modified: DateTime<Local>,
+}
+
+impl CfTripEvent {
+    pub fn scan(row: &rusqlite::Row<'_>) -> rusqlite::Result<CfTripEvent> {
+        Ok(CfTripEvent {
+            id: row.get("id")?,
+            track_sid: row.get("track_sid")?,
+            track_type: row.get("track_type")?,
+            src_sid: row.get("src_sid")?,
+            direction: row.get("direction")?,
+            trip_line: row.get("trip_line")?,
+            rect_x: row.get("rect_x")?,
+            rect_y: row.get("rect_y")?,
+            rect_w: row.get("rect_w")?,
+            rect_h: row.get("rect_h")?,
+            trip_ts: row.get("trip_ts")?,
+            trip_pts: row.get("trip_pts")?,
+            capture_time: row.get("capture_time")?,
+            gmt_create: row.get("gmt_create")?,
+            gmt_modified: row.get("gmt_modified")?,
+        })
+    }
+}
+
+impl DbOp<CfTripEvent> for CfTripEvent {
+    type Conn = Connection;
+
+    fn insert(&self, con: &mut Self::Conn) -> Result<i64, dbop::Error> {
+        let sql = "insert into cf_trip_event(track_sid,track_type,src_sid,direction,trip_line,rect_x,rect_y,rect_w,rect_h,trip_ts,trip_pts,capture_time,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?,?,?,?,?,?,?)";
+        let mut stmt = con.prepare(sql)?;
+        let _affect = stmt.execute(params![self.track_sid,self.track_type,self.src_sid,self.direction,self.trip_line,self.rect_x,self.rect_y,self.rect_w,self.rect_h,self.trip_ts,self.trip_pts,self.capture_time,self.gmt_create,self.gmt_modified])?;
+
+        let id = con.last_insert_rowid();
+        Ok(id)
+    }
+
+    fn update(&self, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
+        let sql = "update cf_trip_event set track_sid = ?, track_type = ?, src_sid = ?, direction = ?, trip_line = ?, rect_x = ?, rect_y = ?, rect_w = ?, rect_h = ?, trip_ts = ?, trip_pts = ?, capture_time = ?, gmt_create = ?, gmt_modified = ? where id = ?";
+        let mut stmt = con.prepare(sql)?;
+        let affect = stmt.execute(params![self.track_sid,self.track_type,self.src_sid,self.direction,self.trip_line,self.rect_x,self.rect_y,self.rect_w,self.rect_h,self.trip_ts,self.trip_pts,self.capture_time,self.gmt_create,self.gmt_modified,self.id])?;
+        Ok(affect)
+    }
+
+    fn delete(id: i64, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
+        let sql = "delete from cf_trip_event where id = ?";
+        let affect = con.execute(sql, params![id])?;
+        Ok(affect)
+    }
+
+    fn load(id: i64, con: &mut Self::Conn) -> Result<Option<CfTripEvent>, dbop::Error> {
+        let sql = "select * from cf_trip_event where id = ?";
+        let v = con.query_row(sql, params![id], CfTripEvent::scan).optional()?;
+        Ok(v)
+    }
+}
+
+//---------------------- CfTripStat ----------------------
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CfTripStat {
+    pub id: i64,
+    pub src_sid: String,
+    pub track_type: i32,
+    pub bucket_time: DateTime<Local>,
+    pub up_count: i64,
+    pub down_count: i64,
+    pub total_count: i64,
+    pub gmt_create: DateTime<Local>,
+    pub gmt_modified: DateTime<Local>,
+}
+
+impl CfTripStat {
+    pub fn scan(row: &rusqlite::Row<'_>) -> rusqlite::Result<CfTripStat> {
+        Ok(CfTripStat {
+            id: row.get("id")?,
+            src_sid: row.get("src_sid")?,
+            track_type: row.get("track_type")?,
+            bucket_time: row.get("bucket_time")?,
+            up_count: row.get("up_count")?,
+            down_count: row.get("down_count")?,
+            total_count: row.get("total_count")?,
+            gmt_create: row.get("gmt_create")?,
+            gmt_modified: row.get("gmt_modified")?,
+        })
+    }
+}
+
+impl DbOp<CfTripStat> for CfTripStat {
+    type Conn = Connection;
+
+    fn insert(&self, con: &mut Self::Conn) -> Result<i64, dbop::Error> {
+        let sql = "insert into cf_trip_stat(src_sid,track_type,bucket_time,up_count,down_count,total_count,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?)";
+        let mut stmt = con.prepare(sql)?;
+        let _affect = stmt.execute(params![self.src_sid,self.track_type,self.bucket_time,self.up_count,self.down_count,self.total_count,self.gmt_create,self.gmt_modified])?;
+
+        let id = con.last_insert_rowid();
+        Ok(id)
+    }
+
+    fn update(&self, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
+        let sql = "update cf_trip_stat set src_sid = ?, track_type = ?, bucket_time = ?, up_count = ?, down_count = ?, total_count = ?, gmt_create = ?, gmt_modified = ? where id = ?";
+        let mut stmt = con.prepare(sql)?;
+        let affect = stmt.execute(params![self.src_sid,self.track_type,self.bucket_time,self.up_count,self.down_count,self.total_count,self.gmt_create,self.gmt_modified,self.id])?;
+        Ok(affect)
+    }
+
+    fn delete(id: i64, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
+        let sql = "delete from cf_trip_stat where id = ?";
+        let affect = con.execute(sql, params![id])?;
+        Ok(affect)
+    }
+
+    fn load(id: i64, con: &mut Self::Conn) -> Result<Option<CfTripStat>, dbop::Error> {
+        let sql = "select * from cf_trip_stat where id = ?";
+        let v = con.query_row(sql, params![id], CfTripStat::scan).optional()?;
+        Ok(v)
+    }
+}
+
+//---------------------- CfFacetrackFeature ----------------------
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CfFacetrackFeature {
+    pub id: i64,
+    pub ft_sid: String,
+    pub src_sid: String,
+    pub img_idx: i32,
+    pub feature: String,
+    pub quality: f64,
+    pub capture_time: DateTime<Local>,
+    pub gmt_create: DateTime<Local>,
+    pub gmt_modified: DateTime<Local>,
+}
+
+impl CfFacetrackFeature {
+    pub fn scan(row: &rusqlite::Row<'_>) -> rusqlite::Result<CfFacetrackFeature> {
+        Ok(CfFacetrackFeature {
+            id: row.get("id")?,
+            ft_sid: row.get("ft_sid")?,
+            src_sid: row.get("src_sid")?,
+            img_idx: row.get("img_idx")?,
+            feature: row.get("feature")?,
+            quality: row.get("quality")?,
+            capture_time: row.get("capture_time")?,
+            gmt_create: row.get("gmt_create")?,
+            gmt_modified: row.get("gmt_modified")?,
+        })
+    }
+}
+
+impl DbOp<CfFacetrackFeature> for CfFacetrackFeature {
+    type Conn = Connection;
+
+    fn insert(&self, con: &mut Self::Conn) -> Result<i64, dbop::Error> {
+        let sql = "insert into cf_facetrack_feature(ft_sid,src_sid,img_idx,feature,quality,capture_time,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?)";
+        let mut stmt = con.prepare(sql)?;
+        let _affect = stmt.execute(params![self.ft_sid,self.src_sid,self.img_idx,self.feature,self.quality,self.capture_time,self.gmt_create,self.gmt_modified])?;
+
+        let id = con.last_insert_rowid();
+        Ok(id)
+    }
+
+    fn update(&self, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
+        let sql = "update cf_facetrack_feature set ft_sid = ?, src_sid = ?, img_idx = ?, feature = ?, quality = ?, capture_time = ?, gmt_create = ?, gmt_modified = ? where id = ?";
+        let mut stmt = con.prepare(sql)?;
+        let affect = stmt.execute(params![self.ft_sid,self.src_sid,self.img_idx,self.feature,self.quality,self.capture_time,self.gmt_create,self.gmt_modified,self.id])?;
+        Ok(affect)
+    }
+
+    fn delete(id: i64, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
+        let sql = "delete from cf_facetrack_feature where id = ?";
+        let affect = con.execute(sql, params![id])?;
+        Ok(affect)
+    }
+
+    fn load(id: i64, con: &mut Self::Conn) -> Result<Option<CfFacetrackFeature>, dbop::Error> {
+        let sql = "select * from cf_facetrack_feature where id = ?";
+        let v = con.query_row(sql, params![id], CfFacetrackFeature::scan).optional()?;
+        Ok(v)
+    }
+}
+
+//---------------------- CfFacetrackCandidate ----------------------
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CfFacetrackCandidate {
+    pub id: i64,
+    pub ft_sid: String,
+    pub rank_num: i32,
+    pub poi_sid: String,
+    pub poi_name: Option<String>,
+    pub db_sid: String,
+    pub score: f64,
+    pub gmt_create: DateTime<Local>,
+    pub gmt_modified: DateTime<Local>,
+}
+
+impl CfFacetrackCandidate {
+    pub fn scan(row: &rusqlite::Row<'_>) -> rusqlite::Result<CfFacetrackCandidate> {
+        Ok(CfFacetrackCandidate {
+            id: row.get("id")?,
+            ft_sid: row.get("ft_sid")?,
+            rank_num: row.get("rank_num")?,
+            poi_sid: row.get("poi_sid")?,
+            poi_name: row.get("poi_name")?,
+            db_sid: row.get("db_sid")?,
+            score: row.get("score")?,
+            gmt_create: row.get("gmt_create")?,
+            gmt_modified: row.get("gmt_modified")?,
+        })
+    }
+}
+
+impl DbOp<CfFacetrackCandidate> for CfFacetrackCandidate {
+    type Conn = Connection;
+
+    fn insert(&self, con: &mut Self::Conn) -> Result<i64, dbop::Error> {
+        let sql = "insert into cf_facetrack_candidate(ft_sid,rank_num,poi_sid,poi_name,db_sid,score,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?)";
+        let mut stmt = con.prepare(sql)?;
+        let _affect = stmt.execute(params![self.ft_sid,self.rank_num,self.poi_sid,self.poi_name,self.db_sid,self.score,self.gmt_create,self.gmt_modified])?;
+
+        let id = con.last_insert_rowid();
+        Ok(id)
+    }
+
+    fn update(&self, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
+        let sql = "update cf_facetrack_candidate set ft_sid = ?, rank_num = ?, poi_sid = ?, poi_name = ?, db_sid = ?, score = ?, gmt_create = ?, gmt_modified = ? where id = ?";
+        let mut stmt = con.prepare(sql)?;
+        let affect = stmt.execute(params![self.ft_sid,self.rank_num,self.poi_sid,self.poi_name,self.db_sid,self.score,self.gmt_create,self.gmt_modified,self.id])?;
+        Ok(affect)
+    }
+
+    fn delete(id: i64, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
+        let sql = "delete from cf_facetrack_candidate where id = ?";
+        let affect = con.execute(sql, params![id])?;
+        Ok(affect)
+    }
+
+    fn load(id: i64, con: &mut Self::Conn) -> Result<Option<CfFacetrackCandidate>, dbop::Error> {
+        let sql = "select * from cf_facetrack_candidate where id = ?";
+        let v = con.query_row(sql, params![id], CfFacetrackCandidate::scan).optional()?;
+        Ok(v)
+    }
+}
+
+//---------------------- CfAlarmRule ----------------------
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CfAlarmRule {
+    pub id: i64,
+    pub name: String,
+    pub track_type: i32,
+    pub enabled: i32,
+    pub severity: i32,
+    pub src_sids: Option<String>,
+    pub group_sids: Option<String>,
+    pub match_mode: i32,
+    pub bw_flag: i32,
+    pub week_days: Option<String>,
+    pub time_start: Option<String>,
+    pub time_end: Option<String>,
+    pub min_score: f64,
+    pub gender: i32,
+    pub car_color: Option<String>,
+    pub speed_limits: Option<String>,
+    pub memo: Option<String>,
+    pub gmt_create: DateTime<Local>,
+    pub gmt_modified: DateTime<Local>,
+}
+
+impl CfAlarmRule {
+    pub fn scan(row: &rusqlite::Row<'_>) -> rusqlite::Result<CfAlarmRule> {
+        Ok(CfAlarmRule {
+            id: row.get("id")?,
+            name: row.get("name")?,
+            track_type: row.get("track_type")?,
+            enabled: row.get("enabled")?,
+            severity: row.get("severity")?,
+            src_sids: row.get("src_sids")?,
+            group_sids: row.get("group_sids")?,
+            match_mode: row.get("match_mode")?,
+            bw_flag: row.get("bw_flag")?,
+            week_days: row.get("week_days")?,
+            time_start: row.get("time_start")?,
+            time_end: row.get("time_end")?,
+            min_score: row.get("min_score")?,
+            gender: row.get("gender")?,
+            car_color: row.get("car_color")?,
+            speed_limits: row.get("speed_limits")?,
+            memo: row.get("memo")?,
+            gmt_create: row.get("gmt_create")?,
+            gmt_modified: row.get("gmt_modified")?,
+        })
+    }
+}
+
+impl DbOp<CfAlarmRule> for CfAlarmRule {
+    type Conn = Connection;
+
+    fn insert(&self, con: &mut Self::Conn) -> Result<i64, dbop::Error> {
+        let sql = "insert into cf_alarm_rule(name,track_type,enabled,severity,src_sids,group_sids,match_mode,bw_flag,week_days,time_start,time_end,min_score,gender,car_color,speed_limits,memo,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?)";
+        let mut stmt = con.prepare(sql)?;
+        let _affect = stmt.execute(params![self.name,self.track_type,self.enabled,self.severity,self.src_sids,self.group_sids,self.match_mode,self.bw_flag,self.week_days,self.time_start,self.time_end,self.min_score,self.gender,self.car_color,self.speed_limits,self.memo,self.gmt_create,self.gmt_modified])?;
+
+        let id = con.last_insert_rowid();
+        Ok(id)
+    }
+
+    fn update(&self, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
+        let sql = "update cf_alarm_rule set name = ?, track_type = ?, enabled = ?, severity = ?, src_sids = ?, group_sids = ?, match_mode = ?, bw_flag = ?, week_days = ?, time_start = ?, time_end = ?, min_score = ?, gender = ?, car_color = ?, speed_limits = ?, memo = ?, gmt_create = ?, gmt_modified = ? where id = ?";
+        let mut stmt = con.prepare(sql)?;
+        let affect = stmt.execute(params![self.name,self.track_type,self.enabled,self.severity,self.src_sids,self.group_sids,self.match_mode,self.bw_flag,self.week_days,self.time_start,self.time_end,self.min_score,self.gender,self.car_color,self.speed_limits,self.memo,self.gmt_create,self.gmt_modified,self.id])?;
+        Ok(affect)
+    }
+
+    fn delete(id: i64, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
+        let sql = "delete from cf_alarm_rule where id = ?";
+        let affect = con.execute(sql, params![id])?;
+        Ok(affect)
+    }
+
+    fn load(id: i64, con: &mut Self::Conn) -> Result<Option<CfAlarmRule>, dbop::Error> {
+        let sql = "select * from cf_alarm_rule where id = ?";
+        let v = con.query_row(sql, params![id], CfAlarmRule::scan).optional()?;
+        Ok(v)
+    }
+}
+
+//---------------------- CfPoiFeature ----------------------
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CfPoiFeature {
+    pub id: i64,
+    pub poi_sid: String,
+    pub db_sid: String,
+    pub face_id: i64,
+    pub feature: String,
+    pub quality: f64,
+    pub gmt_create: DateTime<Local>,
+    pub gmt_modified: DateTime<Local>,
+}
+
+impl CfPoiFeature {
+    pub fn scan(row: &rusqlite::Row<'_>) -> rusqlite::Result<CfPoiFeature> {
+        Ok(CfPoiFeature {
+            id: row.get("id")?,
+            poi_sid: row.get("poi_sid")?,
+            db_sid: row.get("db_sid")?,
+            face_id: row.get("face_id")?,
+            feature: row.get("feature")?,
+            quality: row.get("quality")?,
+            gmt_create: row.get("gmt_create")?,
+            gmt_modified: row.get("gmt_modified")?,
+        })
+    }
+}
+
+impl DbOp<CfPoiFeature> for CfPoiFeature {
+    type Conn = Connection;
+
+    fn insert(&self, con: &mut Self::Conn) -> Result<i64, dbop::Error> {
+        let sql = "insert into cf_poi_feature(poi_sid,db_sid,face_id,feature,quality,gmt_create,gmt_modified) values(?,?,?,?,?,?,?)";
+        let mut stmt = con.prepare(sql)?;
+        let _affect = stmt.execute(params![self.poi_sid,self.db_sid,self.face_id,self.feature,self.quality,self.gmt_create,self.gmt_modified])?;
+
+        let id = con.last_insert_rowid();
+        Ok(id)
+    }
+
+    fn update(&self, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
+        let sql = "update cf_poi_feature set poi_sid = ?, db_sid = ?, face_id = ?, feature = ?, quality = ?, gmt_create = ?, gmt_modified = ? where id = ?";
+        let mut stmt = con.prepare(sql)?;
+        let affect = stmt.execute(params![self.poi_sid,self.db_sid,self.face_id,self.feature,self.quality,self.gmt_create,self.gmt_modified,self.id])?;
+        Ok(affect)
+    }
+
+    fn delete(id: i64, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
+        let sql = "delete from cf_poi_feature where id = ?";
+        let affect = con.execute(sql, params![id])?;
+        Ok(affect)
+    }
+
+    fn load(id: i64, con: &mut Self::Conn) -> Result<Option<CfPoiFeature>, dbop::Error> {
+        let sql = "select * from cf_poi_feature where id = ?";
+        let v = con.query_row(sql, params![id], CfPoiFeature::scan).optional()?;
+        Ok(v)
+    }
+}
+
+//---------------------- CfRetroJob ----------------------
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CfRetroJob {
+    pub id: i64,
+    pub poi_sid: String,
+    pub db_sid: String,
+    pub poi_name: Option<String>,
+    pub threshold: i32,
+    pub time_start: DateTime<Local>,
+    pub time_end: DateTime<Local>,
+    pub state: i32,
+    pub total: i64,
+    pub scanned: i64,
+    pub hits: i64,
+    pub msg: Option<String>,
+    pub gmt_create: DateTime<Local>,
+    pub gmt_modified: DateTime<Local>,
+}
+
+impl CfRetroJob {
+    pub fn scan(row: &rusqlite::Row<'_>) -> rusqlite::Result<CfRetroJob> {
+        Ok(CfRetroJob {
+            id: row.get("id")?,
+            poi_sid: row.get("poi_sid")?,
+            db_sid: row.get("db_sid")?,
+            poi_name: row.get("poi_name")?,
+            threshold: row.get("threshold")?,
+            time_start: row.get("time_start")?,
+            time_end: row.get("time_end")?,
+            state: row.get("state")?,
+            total: row.get("total")?,
+            scanned: row.get("scanned")?,
+            hits: row.get("hits")?,
+            msg: row.get("msg")?,
+            gmt_create: row.get("gmt_create")?,
+            gmt_modified: row.get("gmt_modified")?,
+        })
+    }
+}
+
+impl DbOp<CfRetroJob> for CfRetroJob {
+    type Conn = Connection;
+
+    fn insert(&self, con: &mut Self::Conn) -> Result<i64, dbop::Error> {
+        let sql = "insert into cf_retro_job(poi_sid,db_sid,poi_name,threshold,time_start,time_end,state,total,scanned,hits,msg,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?,?,?,?,?,?)";
+        let mut stmt = con.prepare(sql)?;
+        let _affect = stmt.execute(params![self.poi_sid,self.db_sid,self.poi_name,self.threshold,self.time_start,self.time_end,self.state,self.total,self.scanned,self.hits,self.msg,self.gmt_create,self.gmt_modified])?;
+
+        let id = con.last_insert_rowid();
+        Ok(id)
+    }
+
+    fn update(&self, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
+        let sql = "update cf_retro_job set poi_sid = ?, db_sid = ?, poi_name = ?, threshold = ?, time_start = ?, time_end = ?, state = ?, total = ?, scanned = ?, hits = ?, msg = ?, gmt_create = ?, gmt_modified = ? where id = ?";
+        let mut stmt = con.prepare(sql)?;
+        let affect = stmt.execute(params![self.poi_sid,self.db_sid,self.poi_name,self.threshold,self.time_start,self.time_end,self.state,self.total,self.scanned,self.hits,self.msg,self.gmt_create,self.gmt_modified,self.id])?;
+        Ok(affect)
+    }
+
+    fn delete(id: i64, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
+        let sql = "delete from cf_retro_job where id = ?";
+        let affect = con.execute(sql, params![id])?;
+        Ok(affect)
+    }
+
+    fn load(id: i64, con: &mut Self::Conn) -> Result<Option<CfRetroJob>, dbop::Error> {
+        let sql = "select * from cf_retro_job where id = ?";
+        let v = con.query_row(sql, params![id], CfRetroJob::scan).optional()?;
+        Ok(v)
+    }
+}
+
+//---------------------- CfRetroAlarm ----------------------
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CfRetroAlarm {
+    pub id: i64,
+    pub job_id: i64,
+    pub poi_sid: String,
+    pub ft_sid: String,
+    pub src_sid: String,
+    pub img_idx: i32,
+    pub score: f64,
+    pub capture_time: DateTime<Local>,
+    pub gmt_create: DateTime<Local>,
+    pub gmt_modified: DateTime<Local>,
+}
+
+impl CfRetroAlarm {
+    pub fn scan(row: &rusqlite::Row<'_>) -> rusqlite::Result<CfRetroAlarm> {
+        Ok(CfRetroAlarm {
+            id: row.get("id")?,
+            job_id: row.get("job_id")?,
+            poi_sid: row.get("poi_sid")?,
+            ft_sid: row.get("ft_sid")?,
+            src_sid: row.get("src_sid")?,
+            img_idx: row.get("img_idx")?,
+            score: row.get("score")?,
+            capture_time: row.get("capture_time")?,
+            gmt_create: row.get("gmt_create")?,
+            gmt_modified: row.get("gmt_modified")?,
+        })
+    }
+}
+
+impl DbOp<CfRetroAlarm> for CfRetroAlarm {
+    type Conn = Connection;
+
+    fn insert(&self, con: &mut Self::Conn) -> Result<i64, dbop::Error> {
+        let sql = "insert into cf_retro_alarm(job_id,poi_sid,ft_sid,src_sid,img_idx,score,capture_time,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?,?)";
+        let mut stmt = con.prepare(sql)?;
+        let _affect = stmt.execute(params![self.job_id,self.poi_sid,self.ft_sid,self.src_sid,self.img_idx,self.score,self.capture_time,self.gmt_create,self.gmt_modified])?;
+
+        let id = con.last_insert_rowid();
+        Ok(id)
+    }
+
+    fn update(&self, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
+        let sql = "update cf_retro_alarm set job_id = ?, poi_sid = ?, ft_sid = ?, src_sid = ?, img_idx = ?, score = ?, capture_time = ?, gmt_create = ?, gmt_modified = ? where id = ?";
+        let mut stmt = con.prepare(sql)?;
+        let affect = stmt.execute(params![self.job_id,self.poi_sid,self.ft_sid,self.src_sid,self.img_idx,self.score,self.capture_time,self.gmt_create,self.gmt_modified,self.id])?;
+        Ok(affect)
+    }
+
+    fn delete(id: i64, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
+        let sql = "delete from cf_retro_alarm where id = ?";
+        let affect = con.execute(sql, params![id])?;
+        Ok(affect)
+    }
+
+    fn load(id: i64, con: &mut Self::Conn) -> Result<Option<CfRetroAlarm>, dbop::Error> {
+        let sql = "select * from cf_retro_alarm where id = ?";
+        let v = con.query_row(sql, params![id], CfRetroAlarm::scan).optional()?;
+        Ok(v)
+    }
+}
+
+//---------------------- CfStranger ----------------------
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CfStranger {
+    pub id: i64,
+    pub cluster_sid: String,
+    pub features: String,
+    pub appear_count: i64,
+    pub first_time: DateTime<Local>,
+    pub last_time: DateTime<Local>,
+    pub last_ft_sid: String,
+    pub last_src_sid: String,
+    pub alarm_time: Option<DateTime<Local>>,
+    pub gmt_create: DateTime<Local>,
+    pub gmt_modified: DateTime<Local>,
+}
+
+impl CfStranger {
+    pub fn scan(row: &rusqlite::Row<'_>) -> rusqlite::Result<CfStranger> {
+        Ok(CfStranger {
+            id: row.get("id")?,
+            cluster_sid: row.get("cluster_sid")?,
+            features: row.get("features")?,
+            appear_count: row.get("appear_count")?,
+            first_time: row.get("first_time")?,
+            last_time: row.get("last_time")?,
+            last_ft_sid: row.get("last_ft_sid")?,
+            last_src_sid: row.get("last_src_sid")?,
+            alarm_time: row.get("alarm_time")?,
+            gmt_create: row.get("gmt_create")?,
+            gmt_modified: row.get("gmt_modified")?,
+        })
+    }
+}
+
+impl DbOp<CfStranger> for CfStranger {
+    type Conn = Connection;
+
+    fn insert(&self, con: &mut Self::Conn) -> Result<i64, dbop::Error> {
+        let sql = "insert into cf_stranger(cluster_sid,features,appear_count,first_time,last_time,last_ft_sid,last_src_sid,alarm_time,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?,?,?)";
+        let mut stmt = con.prepare(sql)?;
+        let _affect = stmt.execute(params![self.cluster_sid,self.features,self.appear_count,self.first_time,self.last_time,self.last_ft_sid,self.last_src_sid,self.alarm_time,self.gmt_create,self.gmt_modified])?;
+
+        let id = con.last_insert_rowid();
+        Ok(id)
+    }
+
+    fn update(&self, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
+        let sql = "update cf_stranger set cluster_sid = ?, features = ?, appear_count = ?, first_time = ?, last_time = ?, last_ft_sid = ?, last_src_sid = ?, alarm_time = ?, gmt_create = ?, gmt_modified = ? where id = ?";
+        let mut stmt = con.prepare(sql)?;
+        let affect = stmt.execute(params![self.cluster_sid,self.features,self.appear_count,self.first_time,self.last_time,self.last_ft_sid,self.last_src_sid,self.alarm_time,self.gmt_create,self.gmt_modified,self.id])?;
+        Ok(affect)
+    }
+
+    fn delete(id: i64, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
+        let sql = "delete from cf_stranger where id = ?";
+        let affect = con.execute(sql, params![id])?;
+        Ok(affect)
+    }
+
+    fn load(id: i64, con: &mut Self::Conn) -> Result<Option<CfStranger>, dbop::Error> {
+        let sql = "select * from cf_stranger where id = ?";
+        let v = con.query_row(sql, params![id], CfStranger::scan).optional()?;
+        Ok(v)
+    }
+}
+
+//---------------------- CfStrangerTrack ----------------------
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CfStrangerTrack {
+    pub id: i64,
+    pub cluster_sid: String,
+    pub ft_sid: String,
+    pub src_sid: String,
+    pub score: f64,
+    pub capture_time: DateTime<Local>,
+    pub gmt_create: DateTime<Local>,
+    pub gmt_modified: DateTime<Local>,
+}
+
+impl CfStrangerTrack {
+    pub fn scan(row: &rusqlite::Row<'_>) -> rusqlite::Result<CfStrangerTrack> {
+        Ok(CfStrangerTrack {
+            id: row.get("id")?,
+            cluster_sid: row.get("cluster_sid")?,
+            ft_sid: row.get("ft_sid")?,
+            src_sid: row.get("src_sid")?,
+            score: row.get("score")?,
+            capture_time: row.get("capture_time")?,
+            gmt_create: row.get("gmt_create")?,
+            gmt_modified: row.get("gmt_modified")?,
+        })
+    }
+}
+
+impl DbOp<CfStrangerTrack> for CfStrangerTrack {
+    type Conn = Connection;
+
+    fn insert(&self, con: &mut Self::Conn) -> Result<i64, dbop::Error> {
+        let sql = "insert into cf_stranger_track(cluster_sid,ft_sid,src_sid,score,capture_time,gmt_create,gmt_modified) values(?,?,?,?,?,?,?)";
+        let mut stmt = con.prepare(sql)?;
+        let _affect = stmt.execute(params![self.cluster_sid,self.ft_sid,self.src_sid,self.score,self.capture_time,self.gmt_create,self.gmt_modified])?;
+
+        let id = con.last_insert_rowid();
+        Ok(id)
+    }
+
+    fn update(&self, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
+        let sql = "update cf_stranger_track set cluster_sid = ?, ft_sid = ?, src_sid = ?, score = ?, capture_time = ?, gmt_create = ?, gmt_modified = ? where id = ?";
+        let mut stmt = con.prepare(sql)?;
+        let affect = stmt.execute(params![self.cluster_sid,self.ft_sid,self.src_sid,self.score,self.capture_time,self.gmt_create,self.gmt_modified,self.id])?;
+        Ok(affect)
+    }
+
+    fn delete(id: i64, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
+        let sql = "delete from cf_stranger_track where id = ?";
+        let affect = con.execute(sql, params![id])?;
+        Ok(affect)
+    }
+
+    fn load(id: i64, con: &mut Self::Conn) -> Result<Option<CfStrangerTrack>, dbop::Error> {
+        let sql = "select * from cf_stranger_track where id = ?";
+        let v = con.query_row(sql, params![id], CfStrangerTrack::scan).optional()?;
+        Ok(v)
+    }
+}
+
+//---------------------- CfStrangerAlarm ----------------------
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CfStrangerAlarm {
+    pub id: i64,
+    pub cluster_sid: String,
+    pub ft_sid: String,
+    pub src_sid: String,
+    pub appear_count: i64,
+    pub window_start: DateTime<Local>,
+    pub capture_time: DateTime<Local>,
+    pub gmt_create: DateTime<Local>,
+    pub gmt_modified: DateTime<Local>,
+}
+
+impl CfStrangerAlarm {
+    pub fn scan(row: &rusqlite::Row<'_>) -> rusqlite::Result<CfStrangerAlarm> {
+        Ok(CfStrangerAlarm {
+            id: row.get("id")?,
+            cluster_sid: row.get("cluster_sid")?,
+            ft_sid: row.get("ft_sid")?,
+            src_sid: row.get("src_sid")?,
+            appear_count: row.get("appear_count")?,
+            window_start: row.get("window_start")?,
+            capture_time: row.get("capture_time")?,
+            gmt_create: row.get("gmt_create")?,
+            gmt_modified: row.get("gmt_modified")?,
+        })
+    }
+}
+
+impl DbOp<CfStrangerAlarm> for CfStrangerAlarm {
+    type Conn = Connection;
+
+    fn insert(&self, con: &mut Self::Conn) -> Result<i64, dbop::Error> {
+        let sql = "insert into cf_stranger_alarm(cluster_sid,ft_sid,src_sid,appear_count,window_start,capture_time,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?)";
+        let mut stmt = con.prepare(sql)?;
+        let _affect = stmt.execute(params![self.cluster_sid,self.ft_sid,self.src_sid,self.appear_count,self.window_start,self.capture_time,self.gmt_create,self.gmt_modified])?;
+
+        let id = con.last_insert_rowid();
+        Ok(id)
+    }
+
+    fn update(&self, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
+        let sql = "update cf_stranger_alarm set cluster_sid = ?, ft_sid = ?, src_sid = ?, appear_count = ?, window_start = ?, capture_time = ?, gmt_create = ?, gmt_modified = ? where id = ?";
+        let mut stmt = con.prepare(sql)?;
+        let affect = stmt.execute(params![self.cluster_sid,self.ft_sid,self.src_sid,self.appear_count,self.window_start,self.capture_time,self.gmt_create,self.gmt_modified,self.id])?;
+        Ok(affect)
+    }
+
+    fn delete(id: i64, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
+        let sql = "delete from cf_stranger_alarm where id = ?";
+        let affect = con.execute(sql, params![id])?;
+        Ok(affect)
+    }
+
+    fn load(id: i64, con: &mut Self::Conn) -> Result<Option<CfStrangerAlarm>, dbop::Error> {
+        let sql = "select * from cf_stranger_alarm where id = ?";
+        let v = con.query_row(sql, params![id], CfStrangerAlarm::scan).optional()?;
+        Ok(v)
+    }
+}
+
+//---------------------- CfLaneStat ----------------------
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CfLaneStat {
+    pub id: i64,
+    pub src_sid: String,
+    pub period: i64,
+    pub bucket_time: DateTime<Local>,
+    pub lane_num: i32,
+    pub move_direct: i32,
+    pub car_type: String,
+    pub count: i64,
+    pub gmt_create: DateTime<Local>,
+    pub gmt_modified: DateTime<Local>,
+}
+
+impl CfLaneStat {
+    pub fn scan(row: &rusqlite::Row<'_>) -> rusqlite::Result<CfLaneStat> {
+        Ok(CfLaneStat {
+            id: row.get("id")?,
+            src_sid: row.get("src_sid")?,
+            period: row.get("period")?,
+            bucket_time: row.get("bucket_time")?,
+            lane_num: row.get("lane_num")?,
+            move_direct: row.get("move_direct")?,
+            car_type: row.get("car_type")?,
+            count: row.get("count")?,
+            gmt_create: row.get("gmt_create")?,
+            gmt_modified: row.get("gmt_modified")?,
+        })
+    }
+}
+
+impl DbOp<CfLaneStat> for CfLaneStat {
+    type Conn = Connection;
+
+    fn insert(&self, con: &mut Self::Conn) -> Result<i64, dbop::Error> {
+        let sql = "insert into cf_lane_stat(src_sid,period,bucket_time,lane_num,move_direct,car_type,count,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?,?)";
+        let mut stmt = con.prepare(sql)?;
+        let _affect = stmt.execute(params![self.src_sid,self.period,self.bucket_time,self.lane_num,self.move_direct,self.car_type,self.count,self.gmt_create,self.gmt_modified])?;
+
+        let id = con.last_insert_rowid();
+        Ok(id)
+    }
+
+    fn update(&self, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
+        let sql = "update cf_lane_stat set src_sid = ?, period = ?, bucket_time = ?, lane_num = ?, move_direct = ?, car_type = ?, count = ?, gmt_create = ?, gmt_modified = ? where id = ?";
+        let mut stmt = con.prepare(sql)?;
+        let affect = stmt.execute(params![self.src_sid,self.period,self.bucket_time,self.lane_num,self.move_direct,self.car_type,self.count,self.gmt_create,self.gmt_modified,self.id])?;
+        Ok(affect)
+    }
+
+    fn delete(id: i64, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
+        let sql = "delete from cf_lane_stat where id = ?";
+        let affect = con.execute(sql, params![id])?;
+        Ok(affect)
+    }
+
+    fn load(id: i64, con: &mut Self::Conn) -> Result<Option<CfLaneStat>, dbop::Error> {
+        let sql = "select * from cf_lane_stat where id = ?";
+        let v = con.query_row(sql, params![id], CfLaneStat::scan).optional()?;
+        Ok(v)
+    }
+}
+
+//---------------------- CfParkingVisit ----------------------
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CfParkingVisit {
+    pub id: i64,
+    pub plate_content: String,
+    pub entry_ct_sid: Option<String>,
+    pub entry_src_sid: Option<String>,
+    pub entry_time: Option<DateTime<Local>>,
+    pub exit_ct_sid: Option<String>,
+    pub exit_src_sid: Option<String>,
+    pub exit_time: Option<DateTime<Local>>,
+    pub state: i32,
+    pub dwell_min: i64,
+    pub fee: f64,
+    pub fee_free: i32,
+    pub group_sid: Option<String>,
+    pub overstay: i32,
+    pub gmt_create: DateTime<Local>,
+    pub gmt_modified: DateTime<Local>,
+}
+
+impl CfParkingVisit {
+    pub fn scan(row: &rusqlite::Row<'_>) -> rusqlite::Result<CfParkingVisit> {
+        Ok(CfParkingVisit {
+            id: row.get("id")?,
+            plate_content: row.get("plate_content")?,
+            entry_ct_sid: row.get("entry_ct_sid")?,
+            entry_src_sid: row.get("entry_src_sid")?,
+            entry_time: row.get("entry_time")?,
+            exit_ct_sid: row.get("exit_ct_sid")?,
+            exit_src_sid: row.get("exit_src_sid")?,
+            exit_time: row.get("exit_time")?,
+            state: row.get("state")?,
+            dwell_min: row.get("dwell_min")?,
+            fee: row.get("fee")?,
+            fee_free: row.get("fee_free")?,
+            group_sid: row.get("group_sid")?,
+            overstay: row.get("overstay")?,
+            gmt_create: row.get("gmt_create")?,
+            gmt_modified: row.get("gmt_modified")?,
+        })
+    }
+}
+
+impl DbOp<CfParkingVisit> for CfParkingVisit {
+    type Conn = Connection;
+
+    fn insert(&self, con: &mut Self::Conn) -> Result<i64, dbop::Error> {
+        let sql = "insert into cf_parking_visit(plate_content,entry_ct_sid,entry_src_sid,entry_time,exit_ct_sid,exit_src_sid,exit_time,state,dwell_min,fee,fee_free,group_sid,overstay,gmt_create,gmt_modified) values(?,?,?,?,?,?,?,?,?,?,?,?,?,?,?)";
+        let mut stmt = con.prepare(sql)?;
+        let _affect = stmt.execute(params![self.plate_content,self.entry_ct_sid,self.entry_src_sid,self.entry_time,self.exit_ct_sid,self.exit_src_sid,self.exit_time,self.state,self.dwell_min,self.fee,self.fee_free,self.group_sid,self.overstay,self.gmt_create,self.gmt_modified])?;
+
+        let id = con.last_insert_rowid();
+        Ok(id)
+    }
+
+    fn update(&self, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
+        let sql = "update cf_parking_visit set plate_content = ?, entry_ct_sid = ?, entry_src_sid = ?, entry_time = ?, exit_ct_sid = ?, exit_src_sid = ?, exit_time = ?, state = ?, dwell_min = ?, fee = ?, fee_free = ?, group_sid = ?, overstay = ?, gmt_create = ?, gmt_modified = ? where id = ?";
+        let mut stmt = con.prepare(sql)?;
+        let affect = stmt.execute(params![self.plate_content,self.entry_ct_sid,self.entry_src_sid,self.entry_time,self.exit_ct_sid,self.exit_src_sid,self.exit_time,self.state,self.dwell_min,self.fee,self.fee_free,self.group_sid,self.overstay,self.gmt_create,self.gmt_modified,self.id])?;
+        Ok(affect)
+    }
+
+    fn delete(id: i64, con: &mut Self::Conn) -> Result<usize, dbop::Error> {
+        let sql = "delete from cf_parking_visit where id = ?";
+        let affect = con.execute(sql, params![id])?;
+        Ok(affect)
+    }
+
+    fn load(id: i64, con: &mut Self::Conn) -> Result<Option<CfParkingVisit>, dbop::Error> {
+        let sql = "select * from cf_parking_visit where id = ?";
+        let v = con.query_row(sql, params![id], CfParkingVisit::scan).optional()?;
+        Ok(v)
+    }
+}
@@ -1,5 +1,7 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
+use chrono::prelude::*;
 use log::debug;
 use rusqlite::{NO_PARAMS, OptionalExtension, params};
 
@@ -9,6 +11,53 @@ use cffc_base::util::utils;
 use cffc_base::util::utils::DateRange;
 
 use crate::dao::model::*;
+use crate::queue_item::{REVIEW_STATE_CONFIRMED, REVIEW_STATE_PENDING, REVIEW_STATE_REJECTED};
+
+/// facetrack 的属性过滤条件
+#[derive(Debug, Clone, Default)]
+pub struct FtAttrFilter {
+    pub gender: Option<i64>,
+    pub age_min: Option<i64>,
+    pub age_max: Option<i64>,
+    pub glasses: Option<i64>,
+    pub mask: Option<i64>,
+    pub hat: Option<i64>,
+}
+
+impl FtAttrFilter {
+    fn push_sql<'a>(&'a self, sql: &mut String, vals: &mut Vec<&'a dyn rusqlite::ToSql>) {
+        let conds = [
+            (&self.gender, " and t.gender = ? "),
+            (&self.age_min, " and t.age >= ? "),
+            (&self.age_max, " and t.age <= ? "),
+            (&self.glasses, " and t.glasses = ? "),
+            // 未知的为 null, 按 0 查询
+            (&self.mask, " and coalesce(t.mask, 0) = ? "),
+            (&self.hat, " and coalesce(t.hat, 0) = ? "),
+        ];
+        for (v, cond) in conds.iter() {
+            if let Some(v) = v {
+                *sql += cond;
+                vals.push(v);
+            }
+        }
+    }
+}
+
+/// cf_lane_stat 的查询条件
+/// group_xx 为 false 时该维度求和，返回 0 或空字符串
+#[derive(Debug, Clone)]
+pub struct LaneStatFilter {
+    pub camera: Option<String>,
+    pub period: i64,
+    pub date_range: DateRange,
+    pub lane: Option<i64>,
+    pub direct: Option<i64>,
+    pub car_type: Option<String>,
+    pub group_lane: bool,
+    pub group_direct: bool,
+    pub group_type: bool,
+}
 
 pub struct WebDao {
     pub client: Arc<SqliteClient>,
@@ -29,6 +78,15 @@ impl WebDao {
         Ok(v)
     }
 
+    /// 摄像头所属的节点
+    pub fn load_dfnode_by_src_sid(&self, src_sid: &str) -> Result<Option<CfDfnode>> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select a.* from cf_dfnode a join cf_dfsource b on a.node_sid = b.node_sid where b.src_sid = ?";
+        let v = con.query_row(sql, params![src_sid], CfDfnode::scan).optional()?;
+        Ok(v)
+    }
+
     pub fn load_beuser_by_loginname(&self, login_name: &str) -> Result<Option<BeUser>> {
         let con = self.client.lock().unwrap();
 
@@ -117,6 +175,15 @@ impl WebDao {
         Ok(affect)
     }
 
+    pub fn update_dfsource_for_speed_calib(&self, po: &CfDfsource) -> Result<usize> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "update cf_dfsource set speed_line1 = ?, speed_line2 = ?, speed_distance = ?, gmt_modified = ? where src_sid = ?";
+        let mut stmt = con.prepare(sql)?;
+        let affect = stmt.execute(params![po.speed_line1, po.speed_line2, po.speed_distance, po.gmt_modified, po.src_sid])?;
+        Ok(affect)
+    }
+
     pub fn update_dfsource_for_setstate(&self, po: &CfDfsource) -> Result<usize> {
         let con = self.client.lock().unwrap();
 
@@ -151,9 +218,9 @@ impl WebDao {
     pub fn update_dfsource_for_modify(&self, po: &CfDfsource) -> Result<usize> {
         let con = self.client.lock().unwrap();
 
-        let sql = "update cf_dfsource set name = ?, src_url = ?, ip = ?, src_state = ?, src_config = ?, grab_type = ?, gmt_modified = ? where src_sid = ? ";
+        let sql = "update cf_dfsource set name = ?, src_url = ?, push_url = ?, ip = ?, src_state = ?, src_config = ?, grab_type = ?, io_flag = ?, gmt_modified = ? where src_sid = ? ";
         let mut stmt = con.prepare(sql)?;
-        let affect = stmt.execute(params![po.name,po.src_url,po.ip,po.src_state,po.src_config, po.grab_type, po.gmt_modified,po.src_sid])?;
+        let affect = stmt.execute(params![po.name,po.src_url,po.push_url,po.ip,po.src_state,po.src_config, po.grab_type, po.io_flag, po.gmt_modified,po.src_sid])?;
         Ok(affect)
     }
 
@@ -172,6 +239,15 @@ impl WebDao {
         Ok(list)
     }
 
+    pub fn update_dfdb_for_modify(&self, po: &CfDfdb) -> Result<usize> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "update cf_dfdb set name = ?, auto_match = ?, bw_flag = ?, gmt_modified = ? where db_sid = ?";
+        let mut stmt = con.prepare(sql)?;
+        let affect = stmt.execute(params![po.name,po.auto_match,po.bw_flag,po.gmt_modified,po.db_sid])?;
+        Ok(affect)
+    }
+
     pub fn load_cfpoi_by_sid(&self, sid: &str) -> Result<Option<CfPoi>> {
         let con = self.client.lock().unwrap();
 
@@ -323,7 +399,7 @@ impl WebDao {
 
     pub fn get_facetrack_total(&self, camera: Option<String>, date_range: Option<utils::DateRange>, alarm: Option<i64>,
                                name: Option<String>, identity_card: Option<String>,
-                               gender: Option<i64>) -> Result<Option<i64>> {
+                               attr: FtAttrFilter) -> Result<Option<i64>> {
 
         // select count(*) from cf_facetrack t where 1=1 and t.src_sid= ? and t.capture_time >= ? and t.capture_time < ?
         // and t.alarmed = ? and t.gender = ?
@@ -339,7 +415,6 @@ impl WebDao {
         let has_alarm = alarm.is_some();
         let has_name = name.is_some();
         let has_identity = identity_card.is_some();
-        let has_gender = gender.is_some();
         let has_join = has_name || has_identity;
 
         let mut vals: Vec<&dyn rusqlite::ToSql> = Vec::new();
@@ -382,10 +457,7 @@ impl WebDao {
             vals.push(&alarm);
         }
 
-        if has_gender {
-            sql += " and t.gender = ? ";
-            vals.push(&gender);
-        }
+        attr.push_sql(&mut sql, &mut vals);
         debug!("sql: {}", sql);
 
         let con = self.client.lock().unwrap();
@@ -396,7 +468,7 @@ impl WebDao {
 
     pub fn get_facetrack_datapage(&self, camera: Option<String>, date_range: Option<utils::DateRange>,
                                   alarm: Option<i64>, name: Option<String>,
-                                  identity_card: Option<String>, gender: Option<i64>,
+                                  identity_card: Option<String>, attr: FtAttrFilter,
                                   page_size: i64, start_index: i64) -> Result<Vec<CfFacetrack>> {
 
         //q := "select a.* from cf_poi a join () b on a.id = b.id order by a.id desc"
@@ -406,7 +478,6 @@ impl WebDao {
         let has_alarm = alarm.is_some();
         let has_name = name.is_some();
         let has_identity = identity_card.is_some();
-        let has_gender = gender.is_some();
         let has_join = has_name || has_identity;
 
         let mut vals: Vec<&dyn rusqlite::ToSql> = Vec::new();
@@ -449,10 +520,7 @@ impl WebDao {
             vals.push(&alarm);
         }
 
-        if has_gender {
-            sql += " and t.gender = ? ";
-            vals.push(&gender);
-        }
+        attr.push_sql(&mut sql, &mut vals);
 
         sql += " order by t.id desc limit ?, ? ";
         vals.push(&start_index);
@@ -473,6 +541,82 @@ impl WebDao {
         Ok(list)
     }
 
+    /// 识别为 poi 的最近 limit 条 facetrack，按 capture_time 升序
+    pub fn get_poi_facetracks(&self, poi_sid: &str, date_range: Option<DateRange>, limit: i64) -> Result<Vec<CfFacetrack>> {
+        let mut vals: Vec<&dyn rusqlite::ToSql> = vec![&poi_sid];
+        let mut sql = String::from("select * from cf_facetrack t where t.most_person = ? and t.judged = 1 ");
+
+        let date_range_cl: DateRange;
+        if let Some(v) = date_range {
+            sql += " and t.capture_time >= ? and t.capture_time < ? ";
+            date_range_cl = v;
+            vals.push(&date_range_cl.begin);
+            vals.push(&date_range_cl.end);
+        }
+
+        // 取最近的 limit 条
+        sql += " order by t.capture_time desc limit ? ";
+        vals.push(&limit);
+        debug!("sql: {}", sql);
+
+        let con = self.client.lock().unwrap();
+        let mut stmt = con.prepare(sql.as_str())?;
+        let mut rows = stmt.query(vals)?;
+
+        let mut list = Vec::new();
+        while let Some(row) = rows.next()? {
+            list.push(CfFacetrack::scan(row)?);
+        }
+        // 按时间升序返回
+        list.reverse();
+        Ok(list)
+    }
+
+    /// 以图搜图，按 id 降序分页，先返回最新的抓拍
+    /// src_sid 为空时不限摄像头
+    pub fn load_facetrack_features_page(&self, src_sid: Option<&str>, start: &DateTime<Local>, end: &DateTime<Local>,
+                                        before_id: i64, limit: i64) -> Result<Vec<CfFacetrackFeature>> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select * from cf_facetrack_feature where (? is null or src_sid = ?) and capture_time >= ? and capture_time < ? and id < ? order by id desc limit ?";
+        let mut stmt = con.prepare(sql)?;
+        let mut rows = stmt.query(params![src_sid, src_sid, start, end, before_id, limit])?;
+
+        let mut list = Vec::new();
+        while let Some(row) = rows.next()? {
+            list.push(CfFacetrackFeature::scan(row)?);
+        }
+        Ok(list)
+    }
+
+    /// 批量加载候选人，按 ft_sid 分组
+    pub fn load_facetrack_candidates_by_sids(&self, ft_sids: &[String]) -> Result<HashMap<String, Vec<CfFacetrackCandidate>>> {
+        let con = self.client.lock().unwrap();
+
+        let mut map: HashMap<String, Vec<CfFacetrackCandidate>> = HashMap::new();
+        // sqlite 参数数量有限制，分批查询
+        for chunk in ft_sids.chunks(500) {
+            let sql = format!("select * from cf_facetrack_candidate where ft_sid in ({}) order by ft_sid, rank_num",
+                              vec!["?"; chunk.len()].join(","));
+            let mut stmt = con.prepare(&sql)?;
+            let mut rows = stmt.query(chunk)?;
+            while let Some(row) = rows.next()? {
+                let po = CfFacetrackCandidate::scan(row)?;
+                map.entry(po.ft_sid.clone()).or_insert_with(Vec::new).push(po);
+            }
+        }
+        Ok(map)
+    }
+
+    pub fn load_facetrack_by_sid(&self, ft_sid: &str) -> Result<Option<CfFacetrack>> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select * from cf_facetrack where ft_sid = ?";
+        let mut stmt = con.prepare(sql)?;
+        let po = stmt.query_row(params![ft_sid], CfFacetrack::scan).optional()?;
+        Ok(po)
+    }
+
     pub fn get_coigroup_list(&self) -> Result<Vec<CfCoiGroup>> {
         let con = self.client.lock().unwrap();
 
@@ -488,6 +632,15 @@ impl WebDao {
         Ok(list)
     }
 
+    pub fn update_coigroup_for_modify(&self, po: &CfCoiGroup) -> Result<usize> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "update cf_coi_group set name = ?, bw_flag = ?, min_confidence = ?, memo = ?, gmt_modified = ? where sid = ?";
+        let mut stmt = con.prepare(sql)?;
+        let affect = stmt.execute(params![po.name,po.bw_flag,po.min_confidence,po.memo,po.gmt_modified,po.sid])?;
+        Ok(affect)
+    }
+
 
     pub fn get_cartrack_total(&self, camera: Option<String>, date_range: Option<utils::DateRange>, alarm: Option<i64>,
                               plate_content: Option<String>, plate_type: Option<String>,
@@ -818,4 +971,292 @@ impl WebDao {
         }
         Ok(list)
     }
-}
\ No newline at end of file
+
+    /// 待复核的 cartrack
+    pub fn get_cartrack_review_list(&self, camera: Option<String>, limit: i64) -> Result<Vec<CfCartrack>> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select * from cf_cartrack where review_state = ? and (? is null or src_sid = ?) order by id desc limit ?";
+        let mut stmt = con.prepare(sql)?;
+        let mut rows = stmt.query(params![REVIEW_STATE_PENDING, camera, camera, limit])?;
+
+        let mut list = Vec::new();
+        while let Some(row) = rows.next()? {
+            let po = CfCartrack::scan(row)?;
+            list.push(po);
+        }
+        Ok(list)
+    }
+
+    pub fn load_cartrack_by_sid(&self, sid: &str) -> Result<Option<CfCartrack>> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select * from cf_cartrack where sid = ?";
+        let mut stmt = con.prepare(sql)?;
+        let mut rows = stmt.query(params![sid])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(CfCartrack::scan(row)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// 确认: 报警；驳回: 不报警，车牌视为未识别
+    /// 只更新待复核的记录
+    pub fn update_cartrack_for_review(&self, sid: &str, confirm: bool) -> Result<usize> {
+        let con = self.client.lock().unwrap();
+
+        let (alarmed, plate_judged, review_state) = match confirm {
+            true => (1, 1, REVIEW_STATE_CONFIRMED),
+            false => (0, 0, REVIEW_STATE_REJECTED),
+        };
+        let sql = "update cf_cartrack set alarmed = ?, plate_judged = ?, review_state = ?, gmt_modified = ? where sid = ? and review_state = ?";
+        let mut stmt = con.prepare(sql)?;
+        let affect = stmt.execute(params![alarmed,plate_judged,review_state,Local::now(),sid,REVIEW_STATE_PENDING])?;
+        Ok(affect)
+    }
+
+    // ---------------- alarm rule ----------------
+
+    pub fn get_alarm_rule_list(&self) -> Result<Vec<CfAlarmRule>> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select * from cf_alarm_rule order by track_type, severity desc, id";
+        let mut stmt = con.prepare(sql)?;
+        let mut rows = stmt.query(NO_PARAMS)?;
+
+        let mut list = Vec::new();
+        while let Some(row) = rows.next()? {
+            list.push(CfAlarmRule::scan(row)?);
+        }
+        Ok(list)
+    }
+
+    pub fn load_alarm_rule(&self, id: i64) -> Result<Option<CfAlarmRule>> {
+        let mut guard = self.client.lock().unwrap();
+        CfAlarmRule::load(id, &mut guard)
+    }
+
+    pub fn save_alarm_rule(&self, po: &CfAlarmRule) -> Result<i64> {
+        let mut guard = self.client.lock().unwrap();
+        po.insert(&mut guard)
+    }
+
+    pub fn update_alarm_rule(&self, po: &CfAlarmRule) -> Result<usize> {
+        let mut guard = self.client.lock().unwrap();
+        po.update(&mut guard)
+    }
+
+    pub fn delete_alarm_rule(&self, id: i64) -> Result<usize> {
+        let mut guard = self.client.lock().unwrap();
+        CfAlarmRule::delete(id, &mut guard)
+    }
+
+    // ----------------------------
+
+    pub fn save_retro_job(&self, po: &CfRetroJob) -> Result<i64> {
+        let mut guard = self.client.lock().unwrap();
+        po.insert(&mut guard)
+    }
+
+    pub fn load_retro_job(&self, id: i64) -> Result<Option<CfRetroJob>> {
+        let mut guard = self.client.lock().unwrap();
+        CfRetroJob::load(id, &mut guard)
+    }
+
+    /// 最新的 limit 个任务, poi_sid 为空时查询全部
+    pub fn get_retro_job_list(&self, poi_sid: Option<String>, limit: i64) -> Result<Vec<CfRetroJob>> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select * from cf_retro_job where ? is null or poi_sid = ? order by id desc limit ?";
+        let mut stmt = con.prepare(sql)?;
+        let mut rows = stmt.query(params![poi_sid, poi_sid, limit])?;
+
+        let mut list = Vec::new();
+        while let Some(row) = rows.next()? {
+            list.push(CfRetroJob::scan(row)?);
+        }
+        Ok(list)
+    }
+
+    pub fn get_retro_alarm_list(&self, job_id: i64) -> Result<Vec<CfRetroAlarm>> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select * from cf_retro_alarm where job_id = ? order by score desc";
+        let mut stmt = con.prepare(sql)?;
+        let mut rows = stmt.query(params![job_id])?;
+
+        let mut list = Vec::new();
+        while let Some(row) = rows.next()? {
+            list.push(CfRetroAlarm::scan(row)?);
+        }
+        Ok(list)
+    }
+
+    pub fn get_stranger_list(&self, limit: i64) -> Result<Vec<CfStranger>> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select * from cf_stranger order by last_time desc limit ?";
+        let mut stmt = con.prepare(sql)?;
+        let mut rows = stmt.query(params![limit])?;
+
+        let mut list = Vec::new();
+        while let Some(row) = rows.next()? {
+            list.push(CfStranger::scan(row)?);
+        }
+        Ok(list)
+    }
+
+    pub fn get_stranger_tracks(&self, cluster_sid: &str, limit: i64) -> Result<Vec<CfStrangerTrack>> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select * from cf_stranger_track where cluster_sid = ? order by capture_time desc limit ?";
+        let mut stmt = con.prepare(sql)?;
+        let mut rows = stmt.query(params![cluster_sid, limit])?;
+
+        let mut list = Vec::new();
+        while let Some(row) = rows.next()? {
+            list.push(CfStrangerTrack::scan(row)?);
+        }
+        Ok(list)
+    }
+
+    pub fn get_stranger_alarm_list(&self, cluster_sid: Option<String>, limit: i64) -> Result<Vec<CfStrangerAlarm>> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select * from cf_stranger_alarm where ? is null or cluster_sid = ? order by id desc limit ?";
+        let mut stmt = con.prepare(sql)?;
+        let mut rows = stmt.query(params![cluster_sid, cluster_sid, limit])?;
+
+        let mut list = Vec::new();
+        while let Some(row) = rows.next()? {
+            list.push(CfStrangerAlarm::scan(row)?);
+        }
+        Ok(list)
+    }
+
+    // ---------------- lane stat ----------------
+
+    pub fn get_lane_stat_list(&self, filter: &LaneStatFilter, limit: i64) -> Result<Vec<CfLaneStat>> {
+        let con = self.client.lock().unwrap();
+
+        let mut groups = vec!["t.src_sid", "t.period", "t.bucket_time"];
+        let dims = [
+            (filter.group_lane, "t.lane_num", "0 as lane_num"),
+            (filter.group_direct, "t.move_direct", "0 as move_direct"),
+            (filter.group_type, "t.car_type", "'' as car_type"),
+        ];
+        let mut cols = Vec::new();
+        for (keep, col, empty) in dims.iter() {
+            if *keep {
+                groups.push(col);
+                cols.push(*col);
+            } else {
+                cols.push(*empty);
+            }
+        }
+
+        let mut vals: Vec<&dyn rusqlite::ToSql> = Vec::new();
+        let mut sql = format!("select min(t.id) as id, t.src_sid, t.period, t.bucket_time, {}, sum(t.count) as count, \
+            min(t.gmt_create) as gmt_create, max(t.gmt_modified) as gmt_modified from cf_lane_stat t \
+            where t.period = ? and t.bucket_time >= ? and t.bucket_time < ? ", cols.join(", "));
+        vals.push(&filter.period);
+        vals.push(&filter.date_range.begin);
+        vals.push(&filter.date_range.end);
+
+        if let Some(ref v) = filter.camera {
+            sql += " and t.src_sid = ? ";
+            vals.push(v);
+        }
+        if let Some(ref v) = filter.lane {
+            sql += " and t.lane_num = ? ";
+            vals.push(v);
+        }
+        if let Some(ref v) = filter.direct {
+            sql += " and t.move_direct = ? ";
+            vals.push(v);
+        }
+        if let Some(ref v) = filter.car_type {
+            sql += " and t.car_type = ? ";
+            vals.push(v);
+        }
+
+        let mut orders = vec!["t.bucket_time", "t.src_sid"];
+        orders.extend_from_slice(&groups[3..]);
+        sql += &format!(" group by {} order by {} limit ?", groups.join(", "), orders.join(", "));
+        vals.push(&limit);
+        debug!("get_lane_stat_list, sql: {}", sql);
+
+        let mut stmt = con.prepare(sql.as_str())?;
+        let mut rows = stmt.query(vals)?;
+
+        let mut list = Vec::new();
+        while let Some(row) = rows.next()? {
+            list.push(CfLaneStat::scan(row)?);
+        }
+        Ok(list)
+    }
+
+    // ---------------- parking ----------------
+
+    pub fn get_parking_visit_list(&self, plate: Option<String>, state: Option<i64>, date_range: Option<DateRange>, limit: i64) -> Result<Vec<CfParkingVisit>> {
+        let con = self.client.lock().unwrap();
+
+        let date_range_cl: DateRange;
+        let mut vals: Vec<&dyn rusqlite::ToSql> = Vec::new();
+        let mut sql = String::from("select * from cf_parking_visit t where 1=1 ");
+
+        if let Some(ref v) = plate {
+            sql += " and t.plate_content = ? ";
+            vals.push(v);
+        }
+        if let Some(ref v) = state {
+            sql += " and t.state = ? ";
+            vals.push(v);
+        }
+        // 按入场或出场时间
+        if let Some(v) = date_range {
+            date_range_cl = v;
+            sql += " and ((t.entry_time >= ? and t.entry_time < ?) or (t.exit_time >= ? and t.exit_time < ?)) ";
+            vals.push(&date_range_cl.begin);
+            vals.push(&date_range_cl.end);
+            vals.push(&date_range_cl.begin);
+            vals.push(&date_range_cl.end);
+        }
+        sql += " order by t.id desc limit ? ";
+        vals.push(&limit);
+        debug!("get_parking_visit_list, sql: {}", sql);
+
+        let mut stmt = con.prepare(sql.as_str())?;
+        let mut rows = stmt.query(vals)?;
+
+        let mut list = Vec::new();
+        while let Some(row) = rows.next()? {
+            list.push(CfParkingVisit::scan(row)?);
+        }
+        Ok(list)
+    }
+
+    /// 在场车辆数，其中超时停放的数量
+    pub fn get_parking_occupancy(&self) -> Result<(i64, i64)> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select count(*), ifnull(sum(overstay), 0) from cf_parking_visit where state = 0";
+        let v = con.query_row(sql, NO_PARAMS, |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(v)
+    }
+
+    /// 超时停放且仍在场的记录，按入场时间升序
+    pub fn get_parking_overstay_list(&self, limit: i64) -> Result<Vec<CfParkingVisit>> {
+        let con = self.client.lock().unwrap();
+
+        let sql = "select * from cf_parking_visit where state = 0 and overstay = 1 order by entry_time limit ?";
+        let mut stmt = con.prepare(sql)?;
+        let mut rows = stmt.query(params![limit])?;
+
+        let mut list = Vec::new();
+        while let Some(row) = rows.next()? {
+            list.push(CfParkingVisit::scan(row)?);
+        }
+        Ok(list)
+    }
+}
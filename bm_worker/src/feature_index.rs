@@ -0,0 +1,276 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+
+use log::{debug, info};
+
+use cffc_base::api::bm_api::{ApiFeatureQuality, SearchResPerson};
+
+use crate::dao::AppDao;
+use crate::dao::model::CfPoiFeature;
+use crate::error::AppResult;
+
+struct IndexEntry {
+    poi_sid: String,
+    face_id: i64,
+    /// 已归一化
+    vec: Vec<f32>,
+}
+
+/// base64 的 little-endian f32 数组，归一化后返回
+pub fn decode_feature(feature: &str) -> Option<Vec<f32>> {
+    let buf = base64::decode(feature.trim()).ok()?;
+    if buf.is_empty() || buf.len() % 4 != 0 {
+        return None;
+    }
+
+    let mut vec: Vec<f32> = buf.chunks_exact(4)
+        .map(|x| f32::from_le_bytes([x[0], x[1], x[2], x[3]]))
+        .collect();
+
+    let norm = dot(&vec, &vec).sqrt();
+    if !norm.is_normal() {
+        return None;
+    }
+    vec.iter_mut().for_each(|x| *x /= norm);
+    Some(vec)
+}
+
+/// 按 8 路累加，便于编译器向量化
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    let (ca, cb) = (a.chunks_exact(8), b.chunks_exact(8));
+    let tail: f32 = ca.remainder().iter().zip(cb.remainder()).map(|(x, y)| x * y).sum();
+
+    let mut acc = [0_f32; 8];
+    for (x, y) in ca.zip(cb) {
+        for ((s, x), y) in acc.iter_mut().zip(x).zip(y) {
+            *s += x * y;
+        }
+    }
+    acc.iter().sum::<f32>() + tail
+}
+
+/// 两组已归一化特征值之间的最高余弦相似度，维度不一致的不比较
+pub fn best_cos(queries: &[Vec<f32>], targets: &[Vec<f32>]) -> Option<f32> {
+    queries.iter().flat_map(|q| {
+        targets.iter().filter(move |t| t.len() == q.len()).map(move |t| dot(q, t))
+    }).fold(None, |acc: Option<f32>, x| Some(acc.map_or(x, |v| v.max(x))))
+}
+
+/// 两组已归一化特征值之间的最高分，余弦相似度 * 100
+/// 只用于本地特征值之间的比较，与识别模块的分值不在同一尺度
+pub fn best_score(queries: &[Vec<f32>], targets: &[Vec<f32>]) -> Option<i64> {
+    best_cos(queries, targets).map(|x| ((x * 100_f32).round() as i64).max(0).min(100))
+}
+
+/// 余弦相似度到识别模块分值 (0-100) 的分段线性映射
+/// 用于和 poi 的 threshold 等按识别模块分值设置的阈值比较
+pub struct ScoreMap {
+    /// (cos, score), 按 cos 升序
+    points: Vec<(f32, f32)>,
+}
+
+impl ScoreMap {
+    /// points 为空时按 cos * 100
+    pub fn new(points: &[(f32, f32)]) -> Self {
+        let mut points = points.to_vec();
+        if points.is_empty() {
+            points = vec![(0_f32, 0_f32), (1_f32, 100_f32)];
+        }
+        points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        ScoreMap {
+            points,
+        }
+    }
+
+    pub fn to_score(&self, cos: f32) -> i64 {
+        let first = self.points[0];
+        let last = self.points[self.points.len() - 1];
+        let score = if cos <= first.0 {
+            first.1
+        } else if cos >= last.0 {
+            last.1
+        } else {
+            self.points.windows(2).find(|w| cos <= w[1].0).map_or(last.1, |w| {
+                let (a, b) = (w[0], w[1]);
+                match b.0 > a.0 {
+                    true => a.1 + (cos - a.0) / (b.0 - a.0) * (b.1 - a.1),
+                    false => b.1,
+                }
+            })
+        };
+        (score.round() as i64).max(0).min(100)
+    }
+}
+
+/// 黑白名单特征值的本地副本 (按 db_sid 分组)
+/// 识别节点全部不可用时，用于 1:N 比对
+pub struct FeatureIndex {
+    entries: RwLock<HashMap<String, Vec<IndexEntry>>>,
+    score_map: ScoreMap,
+}
+
+impl FeatureIndex {
+    pub fn new(score_points: &[(f32, f32)]) -> Self {
+        FeatureIndex {
+            entries: RwLock::new(HashMap::new()),
+            score_map: ScoreMap::new(score_points),
+        }
+    }
+
+    /// 两组已归一化特征值之间的最高分，按识别模块的分值尺度
+    pub fn api_score(&self, queries: &[Vec<f32>], targets: &[Vec<f32>]) -> Option<i64> {
+        best_cos(queries, targets).map(|x| self.score_map.to_score(x))
+    }
+
+    fn insert(map: &mut HashMap<String, Vec<IndexEntry>>, po: &CfPoiFeature) {
+        let vec = match decode_feature(&po.feature) {
+            Some(v) => v,
+            None => {
+                debug!("FeatureIndex, poi:{}, face:{}, invalid feature", po.poi_sid, po.face_id);
+                return;
+            }
+        };
+
+        map.entry(po.db_sid.clone()).or_insert_with(Vec::new).push(IndexEntry {
+            poi_sid: po.poi_sid.clone(),
+            face_id: po.face_id,
+            vec,
+        });
+    }
+
+    /// 阻塞调用，从数据库重新加载
+    pub fn reload(&self, dao: &AppDao) -> AppResult<usize> {
+        let list = dao.load_poi_features()?;
+
+        let mut map = HashMap::new();
+        list.iter().for_each(|x| Self::insert(&mut map, x));
+        let count = map.values().map(|x| x.len()).sum();
+
+        *self.entries.write().unwrap() = map;
+        info!("FeatureIndex, reload, features: {}", count);
+        Ok(count)
+    }
+
+    /// 阻塞调用，保存到数据库并加入索引
+    pub fn save(&self, dao: &AppDao, list: &[CfPoiFeature]) -> AppResult<usize> {
+        let count = dao.save_poi_features(list)?;
+
+        // 同一个 face 只保留最新的
+        let faces: HashSet<(&str, i64)> = list.iter().map(|x| (x.poi_sid.as_str(), x.face_id)).collect();
+        let mut map = self.entries.write().unwrap();
+        map.values_mut().for_each(|x| x.retain(|e| !faces.contains(&(e.poi_sid.as_str(), e.face_id))));
+        list.iter().for_each(|x| Self::insert(&mut map, x));
+        Ok(count)
+    }
+
+    /// 阻塞调用
+    pub fn delete_person(&self, dao: &AppDao, poi_sid: &str) -> AppResult<usize> {
+        let affect = dao.delete_poi_features(poi_sid)?;
+
+        let mut map = self.entries.write().unwrap();
+        map.values_mut().for_each(|x| x.retain(|e| e.poi_sid != poi_sid));
+        Ok(affect)
+    }
+
+    /// 阻塞调用
+    pub fn delete_face(&self, dao: &AppDao, poi_sid: &str, face_id: i64) -> AppResult<usize> {
+        let affect = dao.delete_poi_face_feature(poi_sid, face_id)?;
+
+        let mut map = self.entries.write().unwrap();
+        map.values_mut().for_each(|x| x.retain(|e| e.poi_sid != poi_sid || e.face_id != face_id));
+        Ok(affect)
+    }
+
+    /// dbs 中的特征值数量
+    pub fn count(&self, dbs: &[String]) -> usize {
+        let map = self.entries.read().unwrap();
+        dbs.iter().filter_map(|x| map.get(x)).map(|x| x.len()).sum()
+    }
+
+    /// 与 RecognitionApi::search 相同的返回结构
+    /// 每个 person 取各特征值中最高的分值，按分值降序取 top 个
+    pub fn search(&self, dbs: &[String], top: usize, threshold: i64, features: &[Vec<ApiFeatureQuality>]) -> Vec<Vec<SearchResPerson>> {
+        let map = self.entries.read().unwrap();
+
+        features.iter().map(|feas| {
+            let queries: Vec<Vec<f32>> = feas.iter().filter_map(|x| decode_feature(&x.feature)).collect();
+
+            // (db, poi) -> score
+            let mut best: HashMap<(&str, &str), i64> = HashMap::new();
+            for db in dbs.iter() {
+                let entries = match map.get(db) {
+                    Some(v) => v,
+                    None => continue,
+                };
+                for entry in entries.iter() {
+                    let score = match self.api_score(&queries, std::slice::from_ref(&entry.vec)) {
+                        Some(v) if v >= threshold => v,
+                        _ => continue,
+                    };
+
+                    let v = best.entry((db.as_str(), entry.poi_sid.as_str())).or_insert(score);
+                    *v = (*v).max(score);
+                }
+            }
+
+            let mut persons: Vec<SearchResPerson> = best.into_iter().map(|((db, poi), score)| SearchResPerson {
+                id: poi.to_string(),
+                score,
+                db: db.to_string(),
+            }).collect();
+            persons.sort_by(|a, b| b.score.cmp(&a.score));
+            persons.truncate(top);
+            persons
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn score_map_default() {
+        let map = ScoreMap::new(&[]);
+        assert_eq!(map.to_score(-0.5), 0);
+        assert_eq!(map.to_score(0.734), 73);
+        assert_eq!(map.to_score(1.2), 100);
+    }
+
+    #[test]
+    fn score_map_points() {
+        // 乱序的点
+        let map = ScoreMap::new(&[(0.8, 90_f32), (0.3, 0_f32), (0.5, 60_f32)]);
+        assert_eq!(map.to_score(0.2), 0);
+        assert_eq!(map.to_score(0.4), 30);
+        assert_eq!(map.to_score(0.5), 60);
+        assert_eq!(map.to_score(0.65), 75);
+        assert_eq!(map.to_score(0.9), 90);
+
+        // 分值限制在 0-100
+        let map = ScoreMap::new(&[(0_f32, -10_f32), (1_f32, 120_f32)]);
+        assert_eq!(map.to_score(0_f32), 0);
+        assert_eq!(map.to_score(1_f32), 100);
+    }
+
+    #[test]
+    fn score_map_duplicate_cos() {
+        let map = ScoreMap::new(&[(0.5, 40_f32), (0.5, 60_f32), (1_f32, 100_f32)]);
+        assert_eq!(map.to_score(0.5), 40);
+        assert_eq!(map.to_score(0.75), 80);
+    }
+
+    #[test]
+    fn feature_decode_and_score() {
+        let buf: Vec<u8> = [3_f32, 4_f32].iter().flat_map(|x| x.to_le_bytes().to_vec()).collect();
+        let vec = decode_feature(&base64::encode(buf)).unwrap();
+        assert!((vec[0] - 0.6).abs() < 1e-6 && (vec[1] - 0.8).abs() < 1e-6);
+
+        assert!(decode_feature("").is_none());
+        assert!(decode_feature(&base64::encode([0_u8; 8])).is_none());
+
+        let other = vec![1_f32, 0_f32];
+        assert_eq!(best_score(&[vec.clone()], &[other, vec![1_f32]]), Some(60));
+        assert_eq!(best_score(&[vec], &[vec![1_f32]]), None);
+    }
+}
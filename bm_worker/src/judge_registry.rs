@@ -0,0 +1,174 @@
+use std::sync::{Arc, Mutex};
+
+use chrono::prelude::*;
+use chrono::Duration;
+use log::{error, info};
+
+use crate::app_cfg::AppCfgRegistry;
+use crate::dao::AppDao;
+use crate::dao::model::CfAlarmRule;
+use crate::error::AppResult;
+
+/// sid,name,bw_flag
+pub type RegItem = (String, String, i32);
+
+/// sid,name,bw_flag,min_confidence
+pub type GroupItem = (String, String, i32, f64);
+
+/// coi sid,plate_content
+pub type PlateItem = (String, String);
+
+struct RegState<T> {
+    items: Arc<Vec<T>>,
+    /// 每次失效 +1
+    version: u64,
+    /// 最后加载的 version 和时间
+    loaded: Option<(u64, DateTime<Local>)>,
+}
+
+/// 可失效的列表缓存
+/// 失效或超过 reload 时间后，下次读取时重新加载
+pub struct RegCache<T> {
+    name: &'static str,
+    reload: Option<Duration>,
+    state: Mutex<RegState<T>>,
+}
+
+impl<T> RegCache<T> {
+    fn new(name: &'static str, reload: Option<Duration>) -> Self {
+        RegCache {
+            name,
+            reload,
+            state: Mutex::new(RegState {
+                items: Arc::new(vec![]),
+                version: 0,
+                loaded: None,
+            }),
+        }
+    }
+
+    pub fn invalidate(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.version += 1;
+        info!("RegCache[{}], invalidate, version: {}", self.name, state.version);
+    }
+
+    pub fn is_stale(&self, now: DateTime<Local>) -> bool {
+        let state = self.state.lock().unwrap();
+        match state.loaded {
+            None => true,
+            Some((version, ts)) => {
+                if version != state.version {
+                    return true;
+                }
+                match self.reload {
+                    Some(v) => now.signed_duration_since(ts) >= v,
+                    None => false,
+                }
+            }
+        }
+    }
+
+    pub fn current(&self) -> Arc<Vec<T>> {
+        self.state.lock().unwrap().items.clone()
+    }
+
+    /// 重新加载，加载期间发生的失效会在下次读取时再加载
+    pub fn reload<F>(&self, load: F) -> AppResult<Arc<Vec<T>>>
+        where F: FnOnce() -> cffc_base::db::dbop::Result<Vec<T>> {
+        let version = self.state.lock().unwrap().version;
+        let items = Arc::new(load()?);
+
+        let mut state = self.state.lock().unwrap();
+        state.items = items.clone();
+        state.loaded = Some((version, Local::now()));
+        info!("RegCache[{}], reload, version: {}, items: {}", self.name, version, items.len());
+        Ok(items)
+    }
+
+    /// 需要时重新加载，加载失败继续使用旧的列表
+    pub fn get<F>(&self, load: F) -> Arc<Vec<T>>
+        where F: FnOnce() -> cffc_base::db::dbop::Result<Vec<T>> {
+        if !self.is_stale(Local::now()) {
+            return self.current();
+        }
+
+        match self.reload(load) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("error, RegCache[{}], reload, {:?}", self.name, e);
+                self.current()
+            }
+        }
+    }
+}
+
+/// 自动比对的人脸库、车辆分组、报警规则 和 车牌列表
+/// web 修改后调用 invalidate_xxx, 比对/判断时每批次读取
+pub struct JudgeRegistry {
+    pub dbs: RegCache<RegItem>,
+    pub groups: RegCache<GroupItem>,
+    pub rules: RegCache<CfAlarmRule>,
+    pub plates: RegCache<PlateItem>,
+}
+
+impl JudgeRegistry {
+    pub fn new(cfg: &AppCfgRegistry) -> Self {
+        let reload = if cfg.reload_sec > 0 {
+            Some(Duration::seconds(cfg.reload_sec))
+        } else {
+            None
+        };
+
+        JudgeRegistry {
+            dbs: RegCache::new("dbs", reload),
+            groups: RegCache::new("groups", reload),
+            rules: RegCache::new("rules", reload),
+            plates: RegCache::new("plates", reload),
+        }
+    }
+
+    pub fn invalidate_dbs(&self) {
+        self.dbs.invalidate();
+    }
+
+    pub fn invalidate_groups(&self) {
+        self.groups.invalidate();
+    }
+
+    pub fn invalidate_rules(&self) {
+        self.rules.invalidate();
+    }
+
+    pub fn invalidate_plates(&self) {
+        self.plates.invalidate();
+    }
+
+    pub fn load_dbs(&self, dao: &AppDao) -> AppResult<Arc<Vec<RegItem>>> {
+        self.dbs.reload(|| dao.load_automatch_dbs())
+    }
+
+    pub fn load_groups(&self, dao: &AppDao) -> AppResult<Arc<Vec<GroupItem>>> {
+        self.groups.reload(|| dao.load_coi_groups())
+    }
+
+    /// 阻塞调用
+    pub fn get_dbs(&self, dao: &AppDao) -> Arc<Vec<RegItem>> {
+        self.dbs.get(|| dao.load_automatch_dbs())
+    }
+
+    /// 阻塞调用
+    pub fn get_groups(&self, dao: &AppDao) -> Arc<Vec<GroupItem>> {
+        self.groups.get(|| dao.load_coi_groups())
+    }
+
+    /// 阻塞调用, 已启用的规则
+    pub fn get_rules(&self, dao: &AppDao) -> Arc<Vec<CfAlarmRule>> {
+        self.rules.get(|| dao.load_enabled_alarm_rules())
+    }
+
+    /// 阻塞调用, 用于车牌模糊匹配
+    pub fn get_plates(&self, dao: &AppDao) -> Arc<Vec<PlateItem>> {
+        self.plates.get(|| dao.load_coi_plates())
+    }
+}
@@ -2,6 +2,12 @@ pub mod error;
 pub mod app_cfg;
 pub mod app_ctx;
 pub mod queue_item;
+pub mod notify_spool;
+pub mod notify_dedup;
+pub mod notify_recorder;
+pub mod judge_registry;
+pub mod alarm_rule;
+pub mod feature_index;
 
 pub mod services;
 pub mod web;
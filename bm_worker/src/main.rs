@@ -1,14 +1,16 @@
 use std::fs::File;
+use std::path::Path;
 use std::sync::Arc;
 
 use clap::{App, Arg};
-use deadqueue::unlimited::Queue;
 use log::{debug, info};
 use tokio::sync::watch;
 
 use bm_worker::app_cfg::AppCfg;
 use bm_worker::app_ctx::AppCtx;
 use bm_worker::error::AppResult;
+use bm_worker::notify_dedup::{DEDUP_CAR, DEDUP_FACE};
+use bm_worker::notify_spool;
 use bm_worker::services::{car::car_notify::CarNotifyProcSvc,
                           face::face_notify::FaceNotifyProcSvc,
                           ServiceRepo,
@@ -17,10 +19,19 @@ use bm_worker::services::{car::car_notify::CarNotifyProcSvc,
 };
 use bm_worker::services::car::car_judge::CarJudgeSvc;
 use bm_worker::services::ent_bus::EntBusSvc;
+use bm_worker::services::trip::TripSvc;
 use bm_worker::services::face::face_judge::FaceJudgeSvc;
+use bm_worker::services::face::search_retry::SearchRetrySvc;
+use bm_worker::services::face::feature_sync::FeatureSyncSvc;
+use bm_worker::services::face::retro_scan::RetroScanSvc;
+use bm_worker::services::face::stranger::StrangerSvc;
+use bm_worker::services::car::lane_stat::LaneStatSvc;
+use bm_worker::services::car::parking::ParkingSvc;
+use bm_worker::services::recg_probe::RecgProbeSvc;
 use bm_worker::web::server::WebServer;
 use cffc_base::api::bm_api::{self, CreateSourceReqConfig};
 use cffc_base::util::{self, logger, utils};
+use cffc_base::util::bounded_queue::BoundedQueue;
 
 // use actix_service::ServiceFactory;
 
@@ -104,22 +115,75 @@ async fn main() {
 
     let app_ctx = Arc::new(AppCtx::new(cfg, sql_conn, rx));
 
-    let face_queue = Arc::new(Queue::new());
-    let face_judge_queue = Arc::new(Queue::new());
+    // 升级数据库，补充缺少的列，再创建缺少的表和索引
+    let migrated = app_ctx.dao.migrate_columns().unwrap();
+    info!("migrate columns: {}", migrated);
+    app_ctx.dao.migrate_tables().unwrap();
 
-    let car_queue = Arc::new(Queue::new());
-    let car_judge_queue = Arc::new(Queue::new());
-    let general_queue = Arc::new(Queue::new());
+    let queue_cfg = &app_ctx.cfg.queue;
+    let face_queue = Arc::new(BoundedQueue::new("face", queue_cfg.ingest_cap));
+    let face_judge_queue = Arc::new(BoundedQueue::new("face_judge", queue_cfg.judge_cap));
 
-    let face_notify_proc_svc = FaceNotifyProcSvc::new(app_ctx.clone(), face_queue.clone(), face_judge_queue.clone());
-    let face_judge_svc = FaceJudgeSvc::new(app_ctx.clone(), face_judge_queue, general_queue.clone());
-    let car_notify_proc_svc = CarNotifyProcSvc::new(app_ctx.clone(), car_queue.clone(), car_judge_queue.clone());
+    let car_queue = Arc::new(BoundedQueue::new("car", queue_cfg.ingest_cap));
+    let car_judge_queue = Arc::new(BoundedQueue::new("car_judge", queue_cfg.judge_cap));
+    let general_queue = Arc::new(BoundedQueue::new("general", queue_cfg.general_cap));
+
+    app_ctx.queue_stat.register(face_queue.clone());
+    app_ctx.queue_stat.register(face_judge_queue.clone());
+    app_ctx.queue_stat.register(car_queue.clone());
+    app_ctx.queue_stat.register(car_judge_queue.clone());
+    app_ctx.queue_stat.register(general_queue.clone());
+
+    let trip_queue = if app_ctx.cfg.trip.enable {
+        let queue = Arc::new(BoundedQueue::new("trip", app_ctx.cfg.trip.queue_cap));
+        app_ctx.queue_stat.register(queue.clone());
+        Some(queue)
+    } else {
+        None
+    };
+
+    let search_retry_queue = if app_ctx.cfg.search_retry.enable {
+        let queue = Arc::new(BoundedQueue::new("search_retry", app_ctx.cfg.search_retry.queue_cap));
+        app_ctx.queue_stat.register(queue.clone());
+        Some(queue)
+    } else {
+        None
+    };
+
+    // 重放 spool 中未保存的通知
+    for x in notify_spool::load_face_items(&app_ctx.spool) {
+        app_ctx.dedup.check_insert(DEDUP_FACE, &x.notify.id, x.notify.index, x.ts);
+        face_queue.force_push(x);
+    }
+    for x in notify_spool::load_car_items(&app_ctx.spool) {
+        app_ctx.dedup.check_insert(DEDUP_CAR, &x.notify.id, x.notify.index, x.ts);
+        car_queue.force_push(x);
+    }
+
+    let face_notify_proc_svc = FaceNotifyProcSvc::new(app_ctx.clone(), face_queue.clone(), face_judge_queue.clone(), trip_queue.clone());
+    let face_judge_svc = FaceJudgeSvc::new(app_ctx.clone(), face_judge_queue.clone(), general_queue.clone(), search_retry_queue.clone());
+    let car_notify_proc_svc = CarNotifyProcSvc::new(app_ctx.clone(), car_queue.clone(), car_judge_queue.clone(), trip_queue.clone());
     let car_judge_svc = CarJudgeSvc::new(app_ctx.clone(), car_judge_queue, general_queue.clone());
 
     let ent_bus_svc = EntBusSvc::new(app_ctx.clone(), general_queue.clone());
     let signal_proc_svc = SignalProcSvc::new(tx);
     let ws_queue = ent_bus_svc.get_queue("ws");
-    let web_server = WebServer::new(app_ctx.clone(), face_queue, car_queue, ws_queue);
+    let lane_stat_svc = if app_ctx.cfg.lane_stat.enable {
+        Some(LaneStatSvc::new(app_ctx.clone(), ent_bus_svc.get_queue("lane_stat")))
+    } else {
+        None
+    };
+    let parking_svc = if app_ctx.cfg.parking.enable {
+        Some(ParkingSvc::new(app_ctx.clone(), ent_bus_svc.get_queue("parking"), general_queue.clone()))
+    } else {
+        None
+    };
+    let stranger_svc = if app_ctx.cfg.stranger.enable {
+        Some(StrangerSvc::new(app_ctx.clone(), ent_bus_svc.get_queue("stranger"), general_queue.clone()))
+    } else {
+        None
+    };
+    let web_server = WebServer::new(app_ctx.clone(), face_queue, car_queue, ws_queue, general_queue.clone());
 
 
     // 启动各个模块
@@ -131,6 +195,43 @@ async fn main() {
     svc_repo.start_service(car_judge_svc);
     svc_repo.start_service(ent_bus_svc);
 
+    if let Some(queue) = search_retry_queue {
+        let search_retry_svc = SearchRetrySvc::new(app_ctx.clone(), queue, face_judge_queue);
+        svc_repo.start_service(search_retry_svc);
+    }
+
+    if let Some(queue) = trip_queue {
+        let trip_svc = TripSvc::new(app_ctx.clone(), queue, general_queue.clone());
+        svc_repo.start_service(trip_svc);
+    }
+
+    if app_ctx.cfg.recg_cluster.probe_sec > 0 {
+        let recg_probe_svc = RecgProbeSvc::new(app_ctx.clone());
+        svc_repo.start_service(recg_probe_svc);
+    }
+
+    if app_ctx.cfg.feature_index.enable {
+        let feature_sync_svc = FeatureSyncSvc::new(app_ctx.clone());
+        svc_repo.start_service(feature_sync_svc);
+    }
+
+    if let Some(svc) = stranger_svc {
+        svc_repo.start_service(svc);
+    }
+
+    if let Some(svc) = lane_stat_svc {
+        svc_repo.start_service(svc);
+    }
+
+    if let Some(svc) = parking_svc {
+        svc_repo.start_service(svc);
+    }
+
+    if app_ctx.cfg.retro_scan.enable {
+        let retro_scan_svc = RetroScanSvc::new(app_ctx.clone());
+        svc_repo.start_service(retro_scan_svc);
+    }
+
     info!("start web_server");
     svc_repo.start_service(web_server);
 
@@ -151,5 +252,13 @@ pub fn load_src_cfg(path: &str) -> AppResult<CreateSourceReqConfig> {
 
 async fn prepare_dirs(cfg: &AppCfg) -> AppResult<()> {
     let _ = utils::prepare_dir(&cfg.web.upload_path).await?;
+    notify_spool::NotifySpool::new(&cfg.spool).prepare()?;
+
+    // 上次未清除的上传临时文件
+    let upload_tmp = Path::new(&cfg.upload.tmp_path);
+    if upload_tmp.exists() {
+        utils::remove_dir(upload_tmp).await?;
+    }
+    utils::prepare_dir(upload_tmp).await?;
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,144 @@
+use std::sync::Mutex;
+
+use chrono::prelude::*;
+use chrono::Duration;
+use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
+use log::debug;
+
+use crate::app_cfg::AppCfgDedup;
+
+pub const DEDUP_FACE: &str = "ft";
+pub const DEDUP_CAR: &str = "ct";
+
+/// 通知去重，(id, index) 在时间窗口内只接收一次
+/// 采集端重传时，重复的通知直接返回成功
+pub struct NotifyDedup {
+    enable: bool,
+    window: Duration,
+    map: DashMap<String, DateTime<Local>>,
+    last_clean: Mutex<DateTime<Local>>,
+}
+
+impl NotifyDedup {
+    pub fn new(cfg: &AppCfgDedup) -> Self {
+        NotifyDedup {
+            enable: cfg.enable,
+            window: Duration::seconds(cfg.window_sec as i64),
+            map: DashMap::new(),
+            last_clean: Mutex::new(Local::now()),
+        }
+    }
+
+    fn gen_key(kind: &str, id: &str, index: i64) -> String {
+        format!("{}:{}:{}", kind, id, index)
+    }
+
+    /// 新的通知返回 true, 并记录；重复的返回 false
+    pub fn check_insert(&self, kind: &str, id: &str, index: i64, now: DateTime<Local>) -> bool {
+        if !self.enable {
+            return true;
+        }
+
+        self.clean_expired(now);
+
+        match self.map.entry(NotifyDedup::gen_key(kind, id, index)) {
+            Entry::Occupied(mut o) => {
+                if now.signed_duration_since(*o.get()) > self.window {
+                    // 已过期
+                    o.insert(now);
+                    true
+                } else {
+                    false
+                }
+            }
+            Entry::Vacant(v) => {
+                v.insert(now);
+                true
+            }
+        }
+    }
+
+    /// 未能接收(如队列满)，撤销记录，以便重传
+    pub fn remove(&self, kind: &str, id: &str, index: i64) {
+        if !self.enable {
+            return;
+        }
+        self.map.remove(&NotifyDedup::gen_key(kind, id, index));
+    }
+
+    /// 每个窗口期清理一次
+    fn clean_expired(&self, now: DateTime<Local>) {
+        {
+            let mut last = self.last_clean.lock().unwrap();
+            if now.signed_duration_since(*last) < self.window {
+                return;
+            }
+            *last = now;
+        }
+
+        let window = self.window;
+        self.map.retain(|_, v| now.signed_duration_since(*v) <= window);
+        debug!("NotifyDedup, clean expired, left: {}", self.map.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_dedup(window_sec: u64) -> NotifyDedup {
+        NotifyDedup::new(&AppCfgDedup {
+            enable: true,
+            window_sec,
+        })
+    }
+
+    #[test]
+    fn duplicate_in_window() {
+        let dedup = new_dedup(600);
+        let now = Local::now();
+        assert!(dedup.check_insert(DEDUP_FACE, "a", 0, now));
+        assert!(!dedup.check_insert(DEDUP_FACE, "a", 0, now + Duration::seconds(600)));
+
+        // index, 类型不同的不算重复
+        assert!(dedup.check_insert(DEDUP_FACE, "a", 1, now));
+        assert!(dedup.check_insert(DEDUP_CAR, "a", 0, now));
+    }
+
+    #[test]
+    fn expired_after_window() {
+        let dedup = new_dedup(600);
+        let now = Local::now();
+        assert!(dedup.check_insert(DEDUP_FACE, "a", 0, now));
+        let later = now + Duration::seconds(601);
+        assert!(dedup.check_insert(DEDUP_FACE, "a", 0, later));
+        // 重新记录的时间
+        assert!(!dedup.check_insert(DEDUP_FACE, "a", 0, later + Duration::seconds(600)));
+    }
+
+    #[test]
+    fn clean_expired_entries() {
+        let dedup = new_dedup(600);
+        let now = Local::now();
+        dedup.check_insert(DEDUP_FACE, "a", 0, now);
+        dedup.check_insert(DEDUP_FACE, "b", 0, now + Duration::seconds(601));
+        assert_eq!(dedup.map.len(), 1);
+    }
+
+    #[test]
+    fn remove_and_disable() {
+        let dedup = new_dedup(600);
+        let now = Local::now();
+        assert!(dedup.check_insert(DEDUP_CAR, "a", 0, now));
+        dedup.remove(DEDUP_CAR, "a", 0);
+        assert!(dedup.check_insert(DEDUP_CAR, "a", 0, now));
+
+        let dedup = NotifyDedup::new(&AppCfgDedup {
+            enable: false,
+            window_sec: 600,
+        });
+        assert!(dedup.check_insert(DEDUP_CAR, "a", 0, now));
+        assert!(dedup.check_insert(DEDUP_CAR, "a", 0, now));
+    }
+}
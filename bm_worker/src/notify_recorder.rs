@@ -0,0 +1,135 @@
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use bytes::Bytes;
+use chrono::prelude::*;
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+
+use crate::app_cfg::AppCfgRecord;
+use crate::error::{AppError, AppResult};
+
+pub const CAPTURE_EXT: &str = "req";
+const TEMP_EXT: &str = "part";
+
+/// 录制的 body, 在内存中或在接收时写入的临时文件中
+pub enum CaptureBody<'a> {
+    Memory(&'a [u8]),
+    File(&'a Path),
+}
+
+/// 录制请求的头信息
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CaptureMeta {
+    /// 接收时间, 毫秒
+    pub ts: i64,
+    /// 请求路径, 带 query
+    pub path: String,
+    pub content_type: String,
+    pub source: String,
+    /// facetrack / vehicletrack
+    pub kind: String,
+}
+
+/// 录制模式，保存指定摄像头的 /trackupload 原始请求，用于 bm_tool replay 重放
+/// 每次启动一个目录: {path}/{yyyyMMdd_HHmmss}/
+/// 每个请求一个文件: {ts毫秒}_{seq}.req, 第一行为 CaptureMeta json，之后为原始 body
+pub struct NotifyRecorder {
+    enable: bool,
+    dir: PathBuf,
+    /// 为空时录制全部摄像头
+    sources: Vec<String>,
+    seq: AtomicU64,
+}
+
+impl NotifyRecorder {
+    pub fn new(cfg: &AppCfgRecord) -> Self {
+        let session = Local::now().format("%Y%m%d_%H%M%S").to_string();
+        NotifyRecorder {
+            enable: cfg.enable,
+            dir: Path::new(&cfg.path).join(session),
+            sources: cfg.sources.clone(),
+            seq: AtomicU64::new(0),
+        }
+    }
+
+    pub fn is_enable(&self) -> bool {
+        self.enable
+    }
+
+    pub fn is_selected(&self, source: &str) -> bool {
+        self.enable && (self.sources.is_empty() || self.sources.iter().any(|x| x == source))
+    }
+
+    /// 接收 body 时写入的临时文件, 在录制目录下
+    pub fn temp_path(&self) -> AppResult<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        Ok(self.dir.join(format!("{}_{:06}.{}", Local::now().timestamp_millis(), seq, TEMP_EXT)))
+    }
+
+    pub fn save(&self, meta: &CaptureMeta, body: CaptureBody) -> AppResult<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        let path = self.dir.join(format!("{}_{:06}.{}", meta.ts, seq, CAPTURE_EXT));
+
+        let mut buf = serde_json::to_vec(meta)?;
+        buf.push(b'\n');
+        let mut f = fs::File::create(&path)?;
+        f.write_all(&buf)?;
+        match body {
+            CaptureBody::Memory(v) => f.write_all(v)?,
+            CaptureBody::File(v) => {
+                io::copy(&mut fs::File::open(v)?, &mut f)?;
+            }
+        }
+        Ok(path)
+    }
+
+    /// 录制失败不影响接收
+    pub fn record(&self, meta: CaptureMeta, body: CaptureBody) {
+        if !self.is_selected(&meta.source) {
+            return;
+        }
+
+        match self.save(&meta, body) {
+            Ok(v) => debug!("NotifyRecorder, save {:?}", v),
+            Err(e) => error!("error, NotifyRecorder, save {}, {:?}", meta.source, e),
+        }
+    }
+}
+
+/// 读取录制的请求
+pub fn load_capture(path: &Path) -> AppResult<(CaptureMeta, Bytes)> {
+    let mut reader = BufReader::new(fs::File::open(path)?);
+
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let meta: CaptureMeta = serde_json::from_str(line.trim_end())?;
+
+    let mut body = Vec::new();
+    reader.read_to_end(&mut body)?;
+    Ok((meta, Bytes::from(body)))
+}
+
+/// 目录下的录制文件，按文件名(时间)排序
+pub fn list_captures(dir: &Path) -> AppResult<Vec<PathBuf>> {
+    if !dir.is_dir() {
+        return Err(AppError::new(&format!("{:?} is not a dir", dir)));
+    }
+
+    let mut list = Vec::new();
+    for x in fs::read_dir(dir)? {
+        let path = x?.path();
+        if path.extension().map_or(false, |x| x == CAPTURE_EXT) {
+            list.push(path);
+        }
+    }
+    list.sort();
+    info!("list_captures, {:?}, {} files", dir, list.len());
+    Ok(list)
+}
@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use bytes::Bytes;
+use chrono::prelude::*;
+use log::{debug, error, info};
+
+use cffc_base::api::bm_api::{CarNotifyParams, FaceNotifyParams, NotifyBackground};
+
+use crate::app_cfg::AppCfgSpool;
+use crate::error::{AppError, AppResult};
+use crate::queue_item::{NotifyCarQueueItem, NotifyFaceQueueItem};
+
+pub const SPOOL_FACE: &str = "face";
+pub const SPOOL_CAR: &str = "car";
+
+const SPOOL_TMP: &str = "tmp";
+const NOTIFY_FILE: &str = "notify.json";
+
+/// spool 中的一条通知
+/// key: {ts毫秒}_{id}_{index}
+pub struct SpoolEntry {
+    pub key: String,
+    pub ts: DateTime<Local>,
+    pub json: String,
+    pub blobs: HashMap<String, Bytes>,
+}
+
+/// 写入 spool 的图片，大图在临时文件中时直接复制
+pub enum SpoolBlob {
+    Memory(Bytes),
+    File(PathBuf),
+}
+
+/// 已接收，但还未保存的通知，先写到磁盘上 (json + 图片)
+/// 保存完成后删除，启动时重放
+/// 目录结构: {path}/{face|car}/{key}/notify.json, 图片文件
+pub struct NotifySpool {
+    enable: bool,
+    path: PathBuf,
+}
+
+impl NotifySpool {
+    pub fn new(cfg: &AppCfgSpool) -> Self {
+        NotifySpool {
+            enable: cfg.enable,
+            path: PathBuf::from(&cfg.path),
+        }
+    }
+
+    pub fn is_enable(&self) -> bool {
+        self.enable
+    }
+
+    pub fn prepare(&self) -> AppResult<()> {
+        if !self.enable {
+            return Ok(());
+        }
+
+        // 上次未完成的临时目录，直接清除
+        let tmp = self.path.join(SPOOL_TMP);
+        if tmp.exists() {
+            fs::remove_dir_all(&tmp)?;
+        }
+        fs::create_dir_all(&tmp)?;
+        fs::create_dir_all(self.path.join(SPOOL_FACE))?;
+        fs::create_dir_all(self.path.join(SPOOL_CAR))?;
+        Ok(())
+    }
+
+    pub fn gen_key(ts: DateTime<Local>, id: &str, index: i64) -> String {
+        let id: String = id.chars().map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        }).collect();
+        format!("{}_{}_{}", ts.timestamp_millis(), id, index)
+    }
+
+    /// 先写到 tmp 目录，再 rename 到 kind 目录下
+    pub fn save(&self, kind: &str, key: &str, json: &str, blobs: &[(String, SpoolBlob)]) -> AppResult<()> {
+        let tmp_dir = self.path.join(SPOOL_TMP).join(key);
+        fs::create_dir_all(&tmp_dir)?;
+
+        for (name, blob) in blobs.iter() {
+            match blob {
+                SpoolBlob::Memory(buf) => write_sync(&tmp_dir.join(name), buf)?,
+                SpoolBlob::File(src) => copy_sync(src, &tmp_dir.join(name))?,
+            }
+        }
+        write_sync(&tmp_dir.join(NOTIFY_FILE), json.as_bytes())?;
+
+        let dir = self.path.join(kind).join(key);
+        fs::rename(&tmp_dir, dir)?;
+        Ok(())
+    }
+
+    pub fn remove(&self, kind: &str, key: &str) -> AppResult<()> {
+        let dir = self.path.join(kind).join(key);
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        Ok(())
+    }
+
+    /// 按 key 排序(接收时间)
+    pub fn load_all(&self, kind: &str) -> AppResult<Vec<SpoolEntry>> {
+        let mut entries = Vec::new();
+        if !self.enable {
+            return Ok(entries);
+        }
+
+        let mut keys = Vec::new();
+        for x in fs::read_dir(self.path.join(kind))? {
+            let x = x?;
+            if x.file_type()?.is_dir() {
+                keys.push(x.file_name().to_string_lossy().to_string());
+            }
+        }
+        keys.sort();
+
+        for key in keys {
+            let dir = self.path.join(kind).join(&key);
+            match load_entry(&dir, &key) {
+                Ok(v) => entries.push(v),
+                Err(e) => {
+                    error!("error, NotifySpool, load {:?}, {:?}, remove it", dir, e);
+                    let _ = fs::remove_dir_all(&dir);
+                }
+            }
+        }
+        Ok(entries)
+    }
+}
+
+fn write_sync(path: &Path, buf: &[u8]) -> AppResult<()> {
+    let mut f = File::create(path)?;
+    f.write_all(buf)?;
+    f.sync_all()?;
+    Ok(())
+}
+
+fn copy_sync(src: &Path, path: &Path) -> AppResult<()> {
+    fs::copy(src, path)?;
+    File::open(path)?.sync_all()?;
+    Ok(())
+}
+
+fn load_entry(dir: &Path, key: &str) -> AppResult<SpoolEntry> {
+    let ts_millis: i64 = key.split('_').next()
+        .and_then(|x| x.parse().ok())
+        .ok_or_else(|| AppError::new(&format!("invalid key: {}", key)))?;
+    let ts = Local.timestamp_millis(ts_millis);
+
+    let mut json = String::new();
+    let mut blobs = HashMap::new();
+    for x in fs::read_dir(dir)? {
+        let x = x?;
+        let name = x.file_name().to_string_lossy().to_string();
+        let buf = fs::read(x.path())?;
+        if name == NOTIFY_FILE {
+            json = String::from_utf8(buf).map_err(AppError::from_debug)?;
+        } else {
+            blobs.insert(name, Bytes::from(buf));
+        }
+    }
+
+    if json.is_empty() {
+        return Err(AppError::new(&format!("{} not found", NOTIFY_FILE)));
+    }
+
+    Ok(SpoolEntry {
+        key: key.to_string(),
+        ts,
+        json,
+        blobs,
+    })
+}
+
+/// img_ids 中的图片数量, index:quality 逗号分隔
+pub fn count_img_ids(img_ids: &str) -> usize {
+    img_ids.split(',').filter(|x| !x.trim().is_empty()).count()
+}
+
+fn take_blob(entry: &mut SpoolEntry, name: &str) -> AppResult<Bytes> {
+    entry.blobs.remove(name)
+        .ok_or_else(|| AppError::new(&format!("{}, blob: {} not found", entry.key, name)))
+}
+
+// ------------------- face -------------------
+
+fn bg_blob(bg: &NotifyBackground) -> (String, SpoolBlob) {
+    let blob = match bg.image_spill {
+        Some(ref v) => SpoolBlob::File(v.path.clone()),
+        None => SpoolBlob::Memory(bg.image_buf.clone()),
+    };
+    ("bg".to_string(), blob)
+}
+
+pub fn face_blobs(notify: &FaceNotifyParams) -> Vec<(String, SpoolBlob)> {
+    let mut blobs = vec![bg_blob(&notify.background)];
+    for (i, x) in notify.faces.iter().enumerate() {
+        blobs.push((format!("f{}_aligned", i), SpoolBlob::Memory(x.aligned_buf.clone())));
+        blobs.push((format!("f{}_display", i), SpoolBlob::Memory(x.display_buf.clone())));
+        if let Some(ref v) = x.feature_buf {
+            blobs.push((format!("f{}_fea", i), SpoolBlob::Memory(v.clone())));
+        }
+    }
+    blobs
+}
+
+pub fn restore_face(mut entry: SpoolEntry) -> AppResult<NotifyFaceQueueItem> {
+    let mut notify: FaceNotifyParams = serde_json::from_str(&entry.json)?;
+
+    notify.background.image_buf = take_blob(&mut entry, "bg")?;
+    for (i, x) in notify.faces.iter_mut().enumerate() {
+        x.aligned_buf = take_blob(&mut entry, &format!("f{}_aligned", i))?;
+        x.display_buf = take_blob(&mut entry, &format!("f{}_display", i))?;
+        x.feature_buf = entry.blobs.remove(&format!("f{}_fea", i));
+    }
+
+    Ok(NotifyFaceQueueItem {
+        uuid: notify.id.clone(),
+        notify,
+        ts: entry.ts,
+        spool_key: Some(entry.key),
+    })
+}
+
+// ------------------- car -------------------
+
+pub fn car_blobs(notify: &CarNotifyParams) -> Vec<(String, SpoolBlob)> {
+    let mut blobs = vec![bg_blob(&notify.background)];
+    for (i, x) in notify.vehicles.iter().enumerate() {
+        blobs.push((format!("v{}", i), SpoolBlob::Memory(x.img_buf.clone())));
+    }
+    if let Some(ref x) = notify.plate_info {
+        if !x.img_buf.is_empty() {
+            blobs.push(("plate".to_string(), SpoolBlob::Memory(x.img_buf.clone())));
+        }
+        if !x.binary_buf.is_empty() {
+            blobs.push(("plate_bin".to_string(), SpoolBlob::Memory(x.binary_buf.clone())));
+        }
+    }
+    blobs
+}
+
+pub fn restore_car(mut entry: SpoolEntry) -> AppResult<NotifyCarQueueItem> {
+    let mut notify: CarNotifyParams = serde_json::from_str(&entry.json)?;
+
+    notify.background.image_buf = take_blob(&mut entry, "bg")?;
+    for (i, x) in notify.vehicles.iter_mut().enumerate() {
+        x.img_buf = take_blob(&mut entry, &format!("v{}", i))?;
+    }
+    if let Some(x) = notify.plate_info.as_mut() {
+        x.img_buf = entry.blobs.remove("plate").unwrap_or_default();
+        x.binary_buf = entry.blobs.remove("plate_bin").unwrap_or_default();
+    }
+
+    Ok(NotifyCarQueueItem {
+        uuid: notify.id.clone(),
+        notify,
+        ts: entry.ts,
+        spool_key: Some(entry.key),
+    })
+}
+
+// ------------------- replay -------------------
+
+/// 启动时，读取 spool 中未完成的通知
+pub fn load_face_items(spool: &NotifySpool) -> Vec<NotifyFaceQueueItem> {
+    let mut items = Vec::new();
+    let entries = match spool.load_all(SPOOL_FACE) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("error, NotifySpool, load face, {:?}", e);
+            return items;
+        }
+    };
+
+    for entry in entries {
+        let key = entry.key.clone();
+        match restore_face(entry) {
+            Ok(v) => {
+                debug!("NotifySpool, restore face, {}", key);
+                items.push(v);
+            }
+            Err(e) => {
+                error!("error, NotifySpool, restore face, {}, {:?}", key, e);
+                let _ = spool.remove(SPOOL_FACE, &key);
+            }
+        }
+    }
+    info!("NotifySpool, load {} face items", items.len());
+    items
+}
+
+pub fn load_car_items(spool: &NotifySpool) -> Vec<NotifyCarQueueItem> {
+    let mut items = Vec::new();
+    let entries = match spool.load_all(SPOOL_CAR) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("error, NotifySpool, load car, {:?}", e);
+            return items;
+        }
+    };
+
+    for entry in entries {
+        let key = entry.key.clone();
+        match restore_car(entry) {
+            Ok(v) => {
+                debug!("NotifySpool, restore car, {}", key);
+                items.push(v);
+            }
+            Err(e) => {
+                error!("error, NotifySpool, restore car, {}, {:?}", key, e);
+                let _ = spool.remove(SPOOL_CAR, &key);
+            }
+        }
+    }
+    info!("NotifySpool, load {} car items", items.len());
+    items
+}
@@ -2,10 +2,11 @@ use bytes::Buf;
 use chrono::prelude::*;
 use serde::{Deserialize, Serialize};
 
-use cffc_base::api::bm_api::{CarNotifyParams, FaceNotifyParams};
+use cffc_base::api::bm_api::{ApiCarPlateBit, CarNotifyParams, FaceNotifyParams};
 use cffc_base::model::img_file;
+use cffc_base::util::plate_match::PlateMatchType;
 
-use crate::dao::model::{CfCartrack, CfCoi, CfCoiGroup, CfDfdb, CfDfsource, CfFacetrack, CfPoi};
+use crate::dao::model::{CfCartrack, CfCoi, CfCoiGroup, CfDfdb, CfDfsource, CfFacetrack, CfFacetrackCandidate, CfParkingVisit, CfPoi, CfTripEvent};
 use crate::error::{AppError, AppResult};
 
 // ------------------- queue structs (face) -------------------
@@ -15,6 +16,8 @@ pub struct NotifyFaceQueueItem {
     pub uuid: String,
     pub notify: FaceNotifyParams,
     pub ts: DateTime<Local>,
+    /// spool 中的key, 保存完成后删除
+    pub spool_key: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -56,6 +59,17 @@ pub struct FtQIFaces {
     pub matched: bool,
     pub judged: bool,
     pub alarmed: bool,
+    /// 触发的报警规则 id, 0 表示无
+    #[serde(default)]
+    pub alarm_rule: i64,
+    #[serde(default)]
+    pub alarm_level: i32,
+    /// 比对失败后，重试比对得到的结果
+    #[serde(default)]
+    pub delayed: bool,
+    /// 识别节点不可用时，由本地特征索引比对得到的结果
+    #[serde(default)]
+    pub fallback: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -75,12 +89,38 @@ pub struct FtQIPerson {
     pub bw_flag: i64,
 }
 
+/// 比对失败后的重试信息
+#[derive(Debug, Clone)]
+pub struct SearchRetry {
+    /// 已重试次数
+    pub attempts: u32,
+    /// 第一次比对失败的时间
+    pub first_ts: DateTime<Local>,
+}
+
+/// 比对的 top-N 候选, rank 从1开始
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FtQICandidate {
+    pub rank: i64,
+    pub sid: String,
+    pub name: String,
+    pub cover_url: String,
+    pub score: i64,
+    pub db_sid: String,
+    pub db_name: String,
+    pub bw_flag: i64,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct FtQI {
     pub sid: String,
     pub face: FtQIFaces,
     pub camera: Option<CameraQI>,
     pub match_poi: Option<FtQIPerson>,
+    #[serde(default)]
+    pub candidates: Vec<FtQICandidate>,
+    #[serde(skip)]
+    pub retry: Option<SearchRetry>,
 }
 
 impl FtQIFaces {
@@ -117,6 +157,10 @@ impl FtQIFaces {
             matched: po.matched.map_or(false, |x| x == 1),
             judged: po.judged.map_or(false, |x| x == 1),
             alarmed: po.alarmed.map_or(false, |x| x == 1),
+            alarm_rule: po.alarm_rule,
+            alarm_level: po.alarm_level,
+            delayed: false,
+            fallback: false,
         })
     }
 }
@@ -147,6 +191,8 @@ impl CtQIPerson {
             group_sid: group.sid.clone(),
             group_name: group.name.clone(),
             bw_flag: group.bw_flag as i64,
+            match_type: PlateMatchType::Exact,
+            match_score: 100_f64,
         }
     }
 }
@@ -183,6 +229,37 @@ impl FtQIPerson {
 }
 
 
+impl FtQICandidate {
+    fn from_po(po: &CfFacetrackCandidate, db_list: &[CfDfdb]) -> Self {
+        let db = db_list.iter().find(|x| x.db_sid.eq(&po.db_sid));
+
+        FtQICandidate {
+            rank: po.rank_num as i64,
+            sid: po.poi_sid.clone(),
+            name: po.poi_name.clone().unwrap_or_default(),
+            cover_url: "".to_string(),
+            score: po.score as i64,
+            db_sid: po.db_sid.clone(),
+            db_name: db.map_or("".to_string(), |x| x.name.clone()),
+            bw_flag: db.map_or(0, |x| x.bw_flag as i64),
+        }
+    }
+
+    pub fn to_po(&self, ft_sid: &str, now: DateTime<Local>) -> CfFacetrackCandidate {
+        CfFacetrackCandidate {
+            id: 0,
+            ft_sid: ft_sid.to_string(),
+            rank_num: self.rank as i32,
+            poi_sid: self.sid.clone(),
+            poi_name: Some(self.name.clone()),
+            db_sid: self.db_sid.clone(),
+            score: self.score as f64,
+            gmt_create: now,
+            gmt_modified: now,
+        }
+    }
+}
+
 impl FtQI {
     pub fn from_notify(url_prefix: &str, ts: DateTime<Local>, notify: &FaceNotifyParams, source_po: &Option<CfDfsource>) -> Self {
         let camera = source_po.as_ref().map(|x| CameraQI::from_po(x));
@@ -232,13 +309,20 @@ impl FtQI {
                 matched: false,
                 judged: false,
                 alarmed: false,
+                alarm_rule: 0,
+                alarm_level: 0,
+                delayed: false,
+                fallback: false,
             },
             camera,
             match_poi: None,
+            candidates: vec![],
+            retry: None,
         }
     }
 
-    pub fn from_po(url_prefix: &str, po: &CfFacetrack, camera: Option<&CfDfsource>, db_list: &Vec<CfDfdb>, match_poi: Option<CfPoi>) -> AppResult<Self> {
+    pub fn from_po(url_prefix: &str, po: &CfFacetrack, camera: Option<&CfDfsource>, db_list: &Vec<CfDfdb>, match_poi: Option<CfPoi>,
+                   candidates: &[CfFacetrackCandidate]) -> AppResult<Self> {
         let qi_camera = match camera {
             Some(v) => {
                 Some(CameraQI::from_po(v))
@@ -269,6 +353,8 @@ impl FtQI {
             face: qi_faces,
             camera: qi_camera,
             match_poi: qi_match,
+            candidates: candidates.iter().map(|x| FtQICandidate::from_po(x, db_list)).collect(),
+            retry: None,
         })
     }
 }
@@ -280,6 +366,8 @@ pub struct NotifyCarQueueItem {
     pub uuid: String,
     pub notify: CarNotifyParams,
     pub ts: DateTime<Local>,
+    /// spool 中的key, 保存完成后删除
+    pub spool_key: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -288,6 +376,12 @@ pub struct CtQICarPlate {
     pub plate_type: String,
     // pub type_score: f64,
     pub img_url: String,
+    /// 车牌置信度
+    #[serde(default)]
+    pub confidence: f64,
+    /// 每个字符的候选及置信度，用于模糊匹配
+    #[serde(default, skip_serializing)]
+    pub bits: Vec<Vec<ApiCarPlateBit>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -330,6 +424,26 @@ pub struct CtQICar {
     pub bg_url: String,
     pub ts: DateTime<Local>,
     pub alarmed: bool,
+    /// 触发的报警规则 id, 0 表示无
+    #[serde(default)]
+    pub alarm_rule: i64,
+    #[serde(default)]
+    pub alarm_level: i32,
+    /// 复核状态，车牌置信度低于分组阈值时待复核，不报警
+    #[serde(default)]
+    pub review_state: i32,
+    /// 车道编号，0 表示车道之外或未标注
+    #[serde(default)]
+    pub lane_num: i32,
+    /// 摄像头进出口标志 0:未知 1:进口 2:出口 3:进口/出口
+    #[serde(default)]
+    pub io_flag: i32,
+    /// 估算车速 km/h, 0 表示未知
+    #[serde(default)]
+    pub speed: f64,
+    /// 首次判断后才估算出车速，重新判断报警，不重复统计
+    #[serde(default)]
+    pub rejudge: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -344,6 +458,11 @@ pub struct CtQIPerson {
     pub group_sid: String,
     pub group_name: String,
     pub bw_flag: i64,
+    /// 车牌匹配方式及分值 (0-100)
+    #[serde(default)]
+    pub match_type: PlateMatchType,
+    #[serde(default)]
+    pub match_score: f64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -370,6 +489,8 @@ impl CtQICar {
                 content: po.plate_content.as_ref().map_or("".to_string(), |x| x.clone()),
                 plate_type: po.plate_type.as_ref().map_or("".to_string(), |x| x.clone()),
                 img_url: img_file::get_cartrack_plate_url(url_prefix, &po.sid),
+                confidence: po.plate_confidence.unwrap_or_default(),
+                bits: vec![],
             });
         }
 
@@ -396,6 +517,13 @@ impl CtQICar {
             bg_url: img_file::get_cartrack_bgimg_url(url_prefix, &po.sid),
             ts: po.capture_time,
             alarmed: po.alarmed == 1,
+            alarm_rule: po.alarm_rule,
+            alarm_level: po.alarm_level,
+            review_state: po.review_state,
+            lane_num: po.lane_num,
+            io_flag: 0,
+            speed: po.speed,
+            rejudge: false,
         })
     }
 }
@@ -434,6 +562,8 @@ impl CtQI {
                 content: plate_content.unwrap_or_default(),
                 plate_type: plate_type.unwrap_or_default(),
                 img_url: img_file::get_cartrack_plate_url(url_prefix, &notify.id),
+                confidence: notify.get_plate_confidence().unwrap_or_default(),
+                bits: notify.get_plate_bits(),
             });
         }
 
@@ -448,6 +578,13 @@ impl CtQI {
                 bg_url: img_file::get_cartrack_bgimg_url(url_prefix, &notify.id),
                 ts,
                 alarmed: false,
+                alarm_rule: 0,
+                alarm_level: 0,
+                review_state: REVIEW_STATE_NONE,
+                lane_num: 0,
+                io_flag: source_po.as_ref().map_or(0, |x| x.io_flag),
+                speed: 0_f64,
+                rejudge: false,
             },
             camera,
             match_coi: None,
@@ -478,7 +615,8 @@ impl CtQI {
             qi_match = Some(CtQIPerson::from_po(coi, db))
         }
 
-        let qi_car = CtQICar::from_po(url_prefix, po)?;
+        let mut qi_car = CtQICar::from_po(url_prefix, po)?;
+        qi_car.io_flag = camera.map_or(0, |x| x.io_flag);
 
         Ok(CtQI {
             sid: po.sid.clone(),
@@ -489,6 +627,175 @@ impl CtQI {
     }
 }
 
+// ------------------- queue structs (trip) -------------------
+pub const TRACK_TYPE_FACE: i32 = 0;
+pub const TRACK_TYPE_CAR: i32 = 1;
+
+/// cartrack 复核状态
+pub const REVIEW_STATE_NONE: i32 = 0;
+pub const REVIEW_STATE_PENDING: i32 = 1;
+pub const REVIEW_STATE_CONFIRMED: i32 = 2;
+pub const REVIEW_STATE_REJECTED: i32 = 3;
+
+/// 运动方向，0 未知；1 向上；2 向下
+pub const TRIP_DIRECT_UNKNOWN: i32 = 0;
+pub const TRIP_DIRECT_UP: i32 = 1;
+pub const TRIP_DIRECT_DOWN: i32 = 2;
+
+/// 带 trip 信息的通知，交给 TripSvc 判断是否越线
+#[derive(Debug, Clone)]
+pub struct TripQueueItem {
+    pub track_sid: String,
+    pub track_type: i32,
+    pub source: String,
+
+    /// 越线时目标位置
+    pub x: i64,
+    pub y: i64,
+    pub w: i64,
+    pub h: i64,
+    pub real_time: i64,
+    pub pts: i64,
+
+    /// 背景图中目标位置的中心 y, 用于推算方向
+    pub ref_cy: i64,
+    /// 通知中的运动方向
+    pub move_direction: i64,
+    pub ts: DateTime<Local>,
+}
+
+impl TripQueueItem {
+    pub fn from_face(ts: DateTime<Local>, notify: &FaceNotifyParams) -> Option<Self> {
+        if !notify.has_trip_info() {
+            return None;
+        }
+        let trip = notify.trip.as_ref()?;
+        let rect = &notify.background.rect;
+
+        Some(TripQueueItem {
+            track_sid: notify.id.clone(),
+            track_type: TRACK_TYPE_FACE,
+            source: notify.source.clone(),
+            x: trip.x.unwrap_or(0),
+            y: trip.y.unwrap_or(0),
+            w: trip.w.unwrap_or(0),
+            h: trip.h.unwrap_or(0),
+            real_time: trip.real_time.unwrap_or(0),
+            pts: trip.pts.unwrap_or(0),
+            ref_cy: rect.y + rect.h / 2,
+            move_direction: notify.props.as_ref().map_or(0, |x| x.move_direction),
+            ts,
+        })
+    }
+
+    pub fn from_car(ts: DateTime<Local>, notify: &CarNotifyParams) -> Option<Self> {
+        if !notify.has_trip_info() {
+            return None;
+        }
+        let trip = notify.trip.as_ref()?;
+        let rect = &notify.background.rect;
+
+        Some(TripQueueItem {
+            track_sid: notify.id.clone(),
+            track_type: TRACK_TYPE_CAR,
+            source: notify.source.clone(),
+            x: trip.x.unwrap_or(0),
+            y: trip.y.unwrap_or(0),
+            w: trip.w.unwrap_or(0),
+            h: trip.h.unwrap_or(0),
+            real_time: trip.real_time.unwrap_or(0),
+            pts: trip.pts.unwrap_or(0),
+            ref_cy: rect.y + rect.h / 2,
+            move_direction: notify.props.as_ref().and_then(|x| x.move_direction).unwrap_or(0),
+            ts,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TripQI {
+    pub sid: String,
+    /// 0:人脸 1:车辆
+    pub track_type: i64,
+    pub source: String,
+    /// 0 未知；1 向上；2 向下
+    pub direction: i64,
+    pub trip_line: i64,
+    pub x: i64,
+    pub y: i64,
+    pub w: i64,
+    pub h: i64,
+    pub ts: DateTime<Local>,
+    pub camera: Option<CameraQI>,
+}
+
+impl TripQI {
+    pub fn from_po(po: &CfTripEvent, camera: Option<&CfDfsource>) -> Self {
+        TripQI {
+            sid: po.track_sid.clone(),
+            track_type: po.track_type as i64,
+            source: po.src_sid.clone(),
+            direction: po.direction as i64,
+            trip_line: po.trip_line,
+            x: po.rect_x,
+            y: po.rect_y,
+            w: po.rect_w,
+            h: po.rect_h,
+            ts: po.capture_time,
+            camera: camera.map(CameraQI::from_po),
+        }
+    }
+}
+
+/// 陌生人在时间窗口内多次出现
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StrangerQI {
+    /// 触发报警的 facetrack
+    pub sid: String,
+    pub cluster_sid: String,
+    pub source: String,
+    /// 时间窗口内出现的次数
+    pub appear_count: i64,
+    pub window_start: DateTime<Local>,
+    pub first_time: DateTime<Local>,
+    pub img_url: String,
+    pub ts: DateTime<Local>,
+    pub camera: Option<CameraQI>,
+}
+
+/// 超时停放
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ParkingQI {
+    /// 入场的 cartrack
+    pub sid: String,
+    pub visit_id: i64,
+    pub plate_content: String,
+    /// 入场摄像头
+    pub source: String,
+    pub entry_time: Option<DateTime<Local>>,
+    /// 已停放时长, minute
+    pub dwell_min: i64,
+    pub group_sid: Option<String>,
+    pub ts: DateTime<Local>,
+    pub camera: Option<CameraQI>,
+}
+
+impl ParkingQI {
+    pub fn from_po(po: &CfParkingVisit, camera: Option<&CfDfsource>, ts: DateTime<Local>) -> Self {
+        ParkingQI {
+            sid: po.entry_ct_sid.clone().unwrap_or_default(),
+            visit_id: po.id,
+            plate_content: po.plate_content.clone(),
+            source: po.entry_src_sid.clone().unwrap_or_default(),
+            entry_time: po.entry_time,
+            dwell_min: po.entry_time.map_or(0, |x| (ts - x).num_minutes().max(0)),
+            group_sid: po.group_sid.clone(),
+            ts,
+            camera: camera.map(CameraQI::from_po),
+        }
+    }
+}
+
 // ------------------- queue structs (general) -------------------
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum QI {
@@ -497,6 +804,18 @@ pub enum QI {
 
     #[serde(rename = "ct")]
     CT(Box<CtQI>),
+
+    /// 越线事件
+    #[serde(rename = "trip")]
+    TRIP(TripQI),
+
+    /// 陌生人多次出现
+    #[serde(rename = "stranger")]
+    STRANGER(StrangerQI),
+
+    /// 超时停放
+    #[serde(rename = "parking")]
+    PARKING(ParkingQI),
 }
 
 impl QI {
@@ -504,6 +823,9 @@ impl QI {
         match self {
             QI::FT(v) => v.sid.clone(),
             QI::CT(v) => v.sid.clone(),
+            QI::TRIP(v) => v.sid.clone(),
+            QI::STRANGER(v) => v.sid.clone(),
+            QI::PARKING(v) => v.sid.clone(),
         }
     }
 
@@ -511,6 +833,14 @@ impl QI {
         match self {
             QI::FT(_) => 0,
             QI::CT(_) => 1,
+            QI::TRIP(_) => 2,
+            QI::STRANGER(_) => 3,
+            QI::PARKING(_) => 4,
         }
     }
+
+    /// 人脸/车辆 track
+    pub fn is_track(&self) -> bool {
+        matches!(self, QI::FT(_) | QI::CT(_))
+    }
 }
@@ -1,56 +1,117 @@
 use std::sync::Arc;
 
 use chrono::Local;
-use deadqueue::unlimited::Queue;
 use log::{debug, error, info};
 use tokio::stream::StreamExt;
 use tokio::sync::watch::Receiver;
 use tokio::task::JoinHandle as TkJoinHandle;
 
+use cffc_base::util::bounded_queue::BoundedQueue;
+use cffc_base::util::plate_match::{PlateMatch, PlateMatchOpts, PlateMatchType, PlateMatcher, PlateText};
+
+use crate::alarm_rule::{self, AlarmFacts};
 use crate::app_ctx::AppCtx;
 use crate::dao::model::{CfCartrack, CfCoi};
 use crate::error::AppResult;
-use crate::queue_item::{CtQI, CtQIPerson, QI};
+use crate::judge_registry::GroupItem;
+use crate::queue_item::{CtQI, CtQICarPlate, CtQIPerson, QI, REVIEW_STATE_PENDING, TRACK_TYPE_CAR};
 use crate::services::Service;
 
 pub struct CarJudgeSvc {
     ctx: Arc<AppCtx>,
-    queue: Arc<Queue<CtQI>>,
-    out: Arc<Queue<QI>>,
-    groups: Vec<(String, String, i32)>,
+    queue: Arc<BoundedQueue<CtQI>>,
+    out: Arc<BoundedQueue<QI>>,
+    /// sid,name,bw_flag,min_confidence, 每次从 registry 刷新
+    groups: Arc<Vec<GroupItem>>,
+    matcher: Arc<PlateMatcher>,
 }
 
 impl CarJudgeSvc {
-    pub fn new(ctx: Arc<AppCtx>, queue: Arc<Queue<CtQI>>, out: Arc<Queue<QI>>) -> Self {
+    pub fn new(ctx: Arc<AppCtx>, queue: Arc<BoundedQueue<CtQI>>, out: Arc<BoundedQueue<QI>>) -> Self {
+        let cfg = &ctx.cfg.plate_match;
+        let matcher = PlateMatcher::new(PlateMatchOpts {
+            min_score: cfg.min_score,
+            max_cost: cfg.max_cost,
+            partial_min_len: cfg.partial_min_len,
+            low_conf: cfg.low_conf,
+        });
         CarJudgeSvc {
             ctx,
             queue,
             out,
-            groups: vec![],
+            groups: Arc::new(vec![]),
+            matcher: Arc::new(matcher),
+        }
+    }
+
+    /// 分组有修改时，重新加载
+    async fn refresh_groups(&mut self) {
+        if !self.ctx.registry.groups.is_stale(Local::now()) {
+            self.groups = self.ctx.registry.groups.current();
+            return;
+        }
+
+        let ctx = self.ctx.clone();
+        match tokio::task::spawn_blocking(move || ctx.registry.get_groups(&ctx.dao)).await {
+            Ok(v) => self.groups = v,
+            Err(e) => error!("error, CarJudgeSvc, refresh_groups, {:?}", e),
         }
     }
 
-    fn find_group(&self, sid: &str) -> Option<(String, String, i32)> {
+    fn find_group(&self, sid: &str) -> Option<GroupItem> {
         self.groups.iter().find(|&x| { x.0.eq(sid) }).map(|x| {
-            (x.0.clone(), x.1.clone(), x.2)
+            (x.0.clone(), x.1.clone(), x.2, x.3)
         })
     }
 
 
-    // 查询数据库中的db列表
-    fn load_groups(&self) -> AppResult<Vec<(String, String, i32)>> {
-        let groups = self.ctx.dao.load_coi_groups()?;
-        Ok(groups)
+    // 查询数据库中的分组列表
+    fn load_groups(&self) -> AppResult<Arc<Vec<GroupItem>>> {
+        self.ctx.registry.load_groups(&self.ctx.dao)
+    }
+
+    /// 先按车牌精确查找，未命中时模糊匹配
+    /// 阻塞调用
+    fn find_coi(ctx: &AppCtx, matcher: &PlateMatcher, plate: &CtQICarPlate) -> AppResult<Option<(CfCoi, PlateMatch)>> {
+        if let Some(po) = ctx.dao.load_coi_by_plate(plate.content.as_str())? {
+            return Ok(Some((po, PlateMatch {
+                match_type: PlateMatchType::Exact,
+                score: 100_f64,
+            })));
+        }
+        if !ctx.cfg.plate_match.enable {
+            return Ok(None);
+        }
+
+        let text = PlateText::new(plate.content.as_str(), &plate.bits);
+        if text.is_empty() {
+            return Ok(None);
+        }
+        let plates = ctx.registry.get_plates(&ctx.dao);
+        let (idx, m) = match matcher.best_match(&text, plates.iter().map(|x| x.1.as_str())) {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+
+        let po = ctx.dao.load_coi_by_sid(plates[idx].0.as_str())?;
+        debug!("CarJudgeSvc, plate:{} -> {}, {:?}", plate.content, plates[idx].1, m);
+        Ok(po.map(|x| (x, m)))
     }
 
-    fn fill_qi_person(&self, qi: &mut CtQI, po: CfCoi) {
+    fn fill_qi_person(&self, qi: &mut CtQI, po: CfCoi, m: PlateMatch) {
         let group = match self.find_group(po.group_sid.as_str()) {
             Some(v) => v,
             None => {
                 error!("error, CarJudgeSvc, can't find group:{} in cache", po.group_sid);
+                self.ctx.registry.invalidate_groups();
                 return;
             }
         };
+        // 白名单只认完全匹配，不能因模糊匹配不报警
+        if group.2 == 2 && m.match_type != PlateMatchType::Exact {
+            debug!("CarJudgeSvc, skip white list plate:{}, {:?}", po.plate_content, m);
+            return;
+        }
 
         let person = CtQIPerson {
             id: po.id,
@@ -63,6 +124,8 @@ impl CarJudgeSvc {
             group_sid: group.0,
             group_name: group.1,
             bw_flag: group.2 as i64,
+            match_type: m.match_type,
+            match_score: m.score,
         };
 
         qi.match_coi = Some(person);
@@ -75,13 +138,15 @@ impl CarJudgeSvc {
     /// 放入后续队列中
     async fn process_item(&mut self, mut item: CtQI) {
         debug!("CarJudgeSvc, recv item:{:?}", item.sid);
+        self.refresh_groups().await;
 
         if let Some(ref plate) = item.car.plate {
             let ctx = self.ctx.clone();
-            let plate_content = plate.content.clone();
+            let matcher = self.matcher.clone();
+            let plate = plate.clone();
 
             let po = tokio::task::spawn_blocking(move || {
-                ctx.dao.load_coi_by_plate(plate_content.as_str())
+                Self::find_coi(&ctx, &matcher, &plate)
             }).await;
 
             if let Err(e) = po {
@@ -94,15 +159,24 @@ impl CarJudgeSvc {
                     let po = po.unwrap();
                     if po.is_some() {
                         //
-                        let po = po.unwrap();
-                        self.fill_qi_person(&mut item, po);
+                        let (po, m) = po.unwrap();
+                        self.fill_qi_person(&mut item, po, m);
                     }
                 }
             }
         }
 
-        // 判断报警
-        if self.ctx.cfg.notify_proc.cartrack.wl_alarm {
+        // 判断报警，list_hit: 是否因命中车牌名单报警
+        let list_hit;
+        let rules = alarm_rule::get_rules(&self.ctx).await;
+        if alarm_rule::has_rules(&rules, TRACK_TYPE_CAR) {
+            let fired = alarm_rule::find_rule(&rules, &AlarmFacts::from_car(&item))
+                .map(|x| (x.id, x.severity, x.match_mode));
+            item.car.alarmed = fired.is_some();
+            item.car.alarm_rule = fired.map_or(0, |x| x.0);
+            item.car.alarm_level = fired.map_or(0, |x| x.1);
+            list_hit = matches!(fired, Some(x) if x.2 == alarm_rule::MATCH_MODE_HIT);
+        } else if self.ctx.cfg.notify_proc.cartrack.wl_alarm {
             // 白名单报警模式
             item.car.alarmed = true;
             if let Some(ref mut person) = item.match_coi {
@@ -111,6 +185,7 @@ impl CarJudgeSvc {
                     item.car.alarmed = false;
                 }
             }
+            list_hit = matches!(item.match_coi, Some(ref x) if x.bw_flag == 1);
         } else {
             // 黑名单报警模式
             item.car.alarmed = false;
@@ -120,6 +195,26 @@ impl CarJudgeSvc {
                     item.car.alarmed = true;
                 }
             }
+            list_hit = item.car.alarmed;
+        }
+
+        // 命中车牌名单的报警，车牌置信度低于分组阈值时改为待复核
+        if item.car.alarmed && list_hit {
+            if let Some(ref person) = item.match_coi {
+                let min_confidence = self.find_group(person.group_sid.as_str()).map_or(0_f64, |x| x.3);
+                let confidence = item.car.plate.as_ref().map_or(0_f64, |x| x.confidence);
+                if min_confidence > 0_f64 && confidence < min_confidence {
+                    debug!("CarJudgeSvc, {} need review, confidence:{}, min:{}", item.sid, confidence, min_confidence);
+                    item.car.alarmed = false;
+                    item.car.review_state = REVIEW_STATE_PENDING;
+                }
+            }
+        }
+
+        // 重新判断时，只有新触发报警才更新和推送
+        if item.car.rejudge && !item.car.alarmed {
+            debug!("CarJudgeSvc, rejudge, {} not alarmed, speed:{}", item.sid, item.car.speed);
+            return;
         }
 
         // 更新db数据
@@ -135,6 +230,8 @@ impl CarJudgeSvc {
                 true => 1,
                 false => 0,
             },
+            alarm_rule: item.car.alarm_rule,
+            alarm_level: item.car.alarm_level,
             most_coi: match item.match_coi {
                 Some(ref v) => Some(v.sid.clone()),
                 None => None,
@@ -162,13 +259,19 @@ impl CarJudgeSvc {
             capture_ts: 0,
             capture_pts: 0,
             lane_num: 0,
+            review_state: item.car.review_state,
+            speed: item.car.speed,
             gmt_create: now,
             gmt_modified: now,
         };
 
         let ctx = self.ctx.clone();
+        let rejudge = item.car.rejudge;
         let affect = tokio::task::spawn_blocking(move || {
-            ctx.dao.upate_cartrack_for_judge(&cartrack)
+            match rejudge {
+                true => ctx.dao.upate_cartrack_for_rejudge(&cartrack),
+                false => ctx.dao.upate_cartrack_for_judge(&cartrack),
+            }
         }).await;
 
         if let Err(e) = affect {
@@ -181,6 +284,10 @@ impl CarJudgeSvc {
                 let affect = affect.unwrap();
                 if affect == 1 {
                     debug!("CarJudgeSvc, update cartrack, ok, {}", item.sid);
+                } else if rejudge {
+                    // 已报警或待复核，不重复推送
+                    debug!("CarJudgeSvc, rejudge, {} already judged", item.sid);
+                    return;
                 } else {
                     debug!("error, CarJudgeSvc, update cartrack, affect:{}, {}", affect, item.sid);
                 }
@@ -189,7 +296,9 @@ impl CarJudgeSvc {
 
         // 放入后续队列中
         debug!("CarJudgeSvc, put ot next, {}", item.sid);
-        self.out.push(QI::CT(Box::new(item)));
+        if let Err(qi) = self.out.push(QI::CT(Box::new(item))) {
+            error!("error, CarJudgeSvc, queue: {} is full, drop {}", self.out.name(), qi.get_sid());
+        }
     }
 }
 
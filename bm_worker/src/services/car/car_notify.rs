@@ -4,7 +4,6 @@ use std::time::Duration;
 use bytes::Buf;
 use chrono::prelude::*;
 use dashmap::DashMap;
-use deadqueue::unlimited::Queue;
 use log::{debug, error, info};
 use tokio::stream::StreamExt;
 use tokio::sync::mpsc::{Receiver as TkReceiver, Sender as TkSender};
@@ -15,14 +14,17 @@ use tokio::time::{delay_queue::{DelayQueue, Expired}, Error as TkTimeError};
 
 use cffc_base::api::bm_api::CarNotifyParams;
 use cffc_base::api::lane;
+use cffc_base::api::speed::{self, SpeedCalib, SpeedSample};
 use cffc_base::model::img_file;
 use cffc_base::util::delay_queue::DelayQueueChan;
 use cffc_base::util::utils;
+use cffc_base::util::bounded_queue::BoundedQueue;
 
 use crate::app_ctx::AppCtx;
 use crate::dao::model::{CfCartrack, CfDfsource};
 use crate::error::{AppError, AppResult};
-use crate::queue_item::{CtQI, NotifyCarQueueItem};
+use crate::notify_spool::{self, SPOOL_CAR};
+use crate::queue_item::{CtQI, NotifyCarQueueItem, REVIEW_STATE_NONE, TripQueueItem};
 use crate::services::Service;
 
 use super::spool_async::{SerialPool, SpHolder};
@@ -38,6 +40,26 @@ pub struct Track {
     wp: usize,
 
     notify: CarNotifyParams,
+
+    /// 每次通知中背景图的车辆位置 (帧号, 车辆框底边 y 视频坐标), 用于测速
+    positions: Vec<(i64, f64)>,
+
+    /// 已交给后续队列的车速, 0 表示未知
+    speed: f64,
+
+    /// 待删除的 spool key
+    spool_keys: Vec<String>,
+
+    /// 重放时数据库中已保存的 img_ids，新图片的序号从 base + 1 开始
+    saved_ids: Option<String>,
+    base: usize,
+}
+
+/// 背景图中车辆框底边中点，换算为视频坐标
+fn get_bg_position(notify: &CarNotifyParams) -> (i64, f64) {
+    let bg = &notify.background;
+    let scale_y: f64 = bg.video_height as f64 / bg.height.max(1) as f64;
+    (bg.frame_num, (bg.rect.y + bg.rect.h) as f64 * scale_y)
 }
 
 pub enum TrackEvent {
@@ -48,12 +70,12 @@ pub enum TrackEvent {
 
 pub struct CarHandler {
     ctx: Arc<AppCtx>,
-    out: Arc<Queue<CtQI>>,
+    out: Arc<BoundedQueue<CtQI>>,
 }
 
 pub struct CarNotifyProcSvc {
     ctx: Arc<AppCtx>,
-    queue: Arc<Queue<NotifyCarQueueItem>>,
+    queue: Arc<BoundedQueue<NotifyCarQueueItem>>,
 
     spool: SerialPool,
     ready_tx: TkSender<(String, Duration)>,
@@ -63,11 +85,15 @@ pub struct CarNotifyProcSvc {
     clean_rx: TkReceiver<Result<Expired<String>, TkTimeError>>,
 
     track_map: DashMap<String, Arc<SpHolder>>,
+
+    /// 越线判断, 未开启时为 None
+    trip_queue: Option<Arc<BoundedQueue<TripQueueItem>>>,
 }
 
 // ------------------- impls -------------------
 impl CarNotifyProcSvc {
-    pub fn new(ctx: Arc<AppCtx>, queue: Arc<Queue<NotifyCarQueueItem>>, out: Arc<Queue<CtQI>>) -> Self {
+    pub fn new(ctx: Arc<AppCtx>, queue: Arc<BoundedQueue<NotifyCarQueueItem>>, out: Arc<BoundedQueue<CtQI>>,
+               trip_queue: Option<Arc<BoundedQueue<TripQueueItem>>>) -> Self {
         let handler = CarHandler {
             ctx: ctx.clone(),
             out,
@@ -92,6 +118,7 @@ impl CarNotifyProcSvc {
             clean_tx,
             clean_rx,
             track_map,
+            trip_queue,
         }
     }
 
@@ -102,6 +129,15 @@ impl CarNotifyProcSvc {
     async fn process_item(&mut self, item: NotifyCarQueueItem) {
         debug!("CarNotifyProcSvc, process_item:{:?}", item.uuid);
 
+        // 带 trip 信息的，交给 TripSvc
+        if let Some(ref queue) = self.trip_queue {
+            if let Some(trip) = TripQueueItem::from_car(item.ts, &item.notify) {
+                if queue.push(trip).is_err() {
+                    error!("error, CarNotifyProcSvc, queue: {} is full, drop trip {}", queue.name(), item.uuid);
+                }
+            }
+        }
+
         let uuid = item.uuid.clone();
         let ready = self.check_recv_mode(&item);
         let track = Track {
@@ -110,7 +146,12 @@ impl CarNotifyProcSvc {
             ts: item.ts,
             invalid: false,
             wp: 0,
+            positions: vec![get_bg_position(&item.notify)],
+            speed: 0_f64,
             notify: item.notify,
+            spool_keys: item.spool_key.into_iter().collect(),
+            saved_ids: None,
+            base: 0,
         };
 
         if let Some(holder) = self.track_map.get(uuid.as_str()) {
@@ -194,6 +235,25 @@ impl CarHandler {
     /// 更新 wp
     /// 记录各种操作耗时
     async fn save_track(&self, track: &mut Track, source_po: &Option<CfDfsource>) -> AppResult<()> {
+        // 重启后内存中的 track 丢失，重放的通知可能属于已保存的 cartrack
+        let ctx = self.ctx.clone();
+        let sid = track.uuid.clone();
+        let saved_ids = tokio::task::spawn_blocking(move || {
+            ctx.dao.load_cartrack_img_ids(&sid)
+        }).await??;
+        if let Some(ids) = saved_ids {
+            if track.notify.index == 0 {
+                // 新建时的通知，已经保存过
+                debug!("cartrack already saved, sid:{}", track.uuid);
+                track.wp = track.notify.vehicles.len();
+                return Ok(());
+            }
+            debug!("cartrack already saved, append, sid:{}", track.uuid);
+            track.base = notify_spool::count_img_ids(&ids);
+            track.saved_ids = Some(ids);
+            return self.update_track(track, source_po).await;
+        }
+
         let df_imgs = self.ctx.cfg.df_imgs.as_str();
         let track_id = track.uuid.as_str();
 
@@ -218,7 +278,7 @@ impl CarHandler {
         // 保存背景图
         let fn_bg = img_file::get_cartrack_full_bgpath(df_imgs, track_id);
         // debug!("save vehicle bg img file: {:?}", fn_bg);
-        utils::write_jpg_file(fn_bg, track.notify.background.image_buf.bytes()).await?;
+        track.notify.background.write_image(fn_bg).await?;
 
 
         // 保存车牌图，（如果有车牌）
@@ -268,6 +328,8 @@ impl CarHandler {
             src_sid: track.notify.source.clone(),
             img_ids,
             alarmed: 0,
+            alarm_rule: 0,
+            alarm_level: 0,
             most_coi: None,
             plate_judged: plate_judeged,
             vehicle_judged: vehicle_judeged,
@@ -292,6 +354,8 @@ impl CarHandler {
             capture_ts: 0,
             capture_pts: 0,
             lane_num: self.get_lane(&track.notify, source_po) as i32,
+            review_state: REVIEW_STATE_NONE,
+            speed: self.get_speed(track, source_po),
             gmt_create: now,
             gmt_modified: now,
         };
@@ -318,10 +382,14 @@ impl CarHandler {
         let df_imgs = self.ctx.cfg.df_imgs.as_str();
         let track_id = track.uuid.as_str();
 
-        let mut img_ids = String::new();
-        let mut img_num = 0;
+        // 重放时接在已保存的图片之后
+        let mut img_ids = match track.saved_ids {
+            Some(ref v) if !v.is_empty() => format!("{},", v),
+            _ => String::new(),
+        };
+        let mut img_num = track.base;
         let now = Local::now();
-        let wp_old = track.wp;
+        let wp_old = track.base + track.wp;
 
         // 准备目录
         let dir = img_file::get_cartrack_imgdir(df_imgs, track_id);
@@ -345,7 +413,7 @@ impl CarHandler {
         // 保存背景图, 覆盖
         let fn_bg = img_file::get_cartrack_full_bgpath(df_imgs, track_id);
         // debug!("save bg img file: {:?}", fn_bg);
-        utils::write_jpg_file(fn_bg, track.notify.background.image_buf.bytes()).await?;
+        track.notify.background.write_image(fn_bg).await?;
 
 
         // 保存车牌图，（如果有车牌）
@@ -396,6 +464,8 @@ impl CarHandler {
             src_sid: track.notify.source.clone(),
             img_ids,
             alarmed: 0,
+            alarm_rule: 0,
+            alarm_level: 0,
             most_coi: None,
             plate_judged: plate_judeged,
             vehicle_judged: vehicle_judeged,
@@ -420,6 +490,8 @@ impl CarHandler {
             capture_ts: 0,
             capture_pts: 0,
             lane_num: self.get_lane(&track.notify, source_po) as i32,
+            review_state: REVIEW_STATE_NONE,
+            speed: self.get_speed(track, source_po),
             gmt_create: now,
             gmt_modified: now,
         };
@@ -444,13 +516,59 @@ impl CarHandler {
 
     /// 查询 source
     /// 生成 CtQI 放入后续队列中
-    async fn put_to_next(&self, track: &Track, source_po: &Option<CfDfsource>) -> AppResult<()> {
-        let qi = CtQI::from_notify(&self.ctx.cfg.dfimg_url, track.ts, &track.notify, source_po);
-        self.out.push(qi);
+    async fn put_to_next(&self, track: &mut Track, source_po: &Option<CfDfsource>, rejudge: bool) -> AppResult<()> {
+        let mut qi = CtQI::from_notify(&self.ctx.cfg.dfimg_url, track.ts, &track.notify, source_po);
+        qi.car.lane_num = self.get_lane(&track.notify, source_po) as i32;
+        qi.car.speed = self.get_speed(track, source_po);
+        qi.car.rejudge = rejudge;
+        let speed = qi.car.speed;
+        if let Err(qi) = self.out.push(qi) {
+            return Err(AppError::new(&format!("queue: {} is full, drop {}", self.out.name(), qi.sid)));
+        }
+        track.speed = speed;
         Ok(())
     }
 
     pub async fn process(&self, holder_data: Arc<Mutex<Track>>, events: Vec<TrackEvent>) {
+        // 本次涉及的 spool key
+        let mut spool_keys = {
+            let mut data = holder_data.lock().await;
+            data.spool_keys.drain(..).collect::<Vec<String>>()
+        };
+        for event in events.iter() {
+            if let TrackEvent::APPEND(track) = event {
+                spool_keys.extend(track.spool_keys.iter().cloned());
+            }
+        }
+
+        // 数据库中已保存，删除 spool; 保存失败时保留，下次启动时重放
+        if self.process_events(holder_data, events).await {
+            self.remove_spool(spool_keys).await;
+        } else if !spool_keys.is_empty() {
+            error!("error, CarHandler process fail, keep spool: {:?}", spool_keys);
+        }
+    }
+
+    async fn remove_spool(&self, keys: Vec<String>) {
+        if keys.is_empty() {
+            return;
+        }
+
+        let ctx = self.ctx.clone();
+        let rst = tokio::task::spawn_blocking(move || {
+            for key in keys.iter() {
+                if let Err(e) = ctx.spool.remove(SPOOL_CAR, key) {
+                    error!("error, CarHandler remove spool, {}, {:?}", key, e);
+                }
+            }
+        }).await;
+        if let Err(e) = rst {
+            error!("error, CarHandler remove spool, {:?}", e);
+        }
+    }
+
+    /// 保存/更新成功时返回 true，放入后续队列失败只记录
+    async fn process_events(&self, holder_data: Arc<Mutex<Track>>, events: Vec<TrackEvent>) -> bool {
         let mut newed = false;
         let mut appended = false;
         let mut delayed = false;
@@ -468,6 +586,12 @@ impl CarHandler {
                 TrackEvent::APPEND(mut track) => {
                     appended = true;
                     // 替换背景图，增加图片，车牌图，属性
+                    data.positions.append(&mut track.positions);
+                    if track.notify.position.end_frame > data.notify.position.end_frame {
+                        data.notify.position.end = track.notify.position.end;
+                        data.notify.position.end_frame = track.notify.position.end_frame;
+                        data.notify.position.end_real_time = track.notify.position.end_real_time;
+                    }
                     data.notify.background = track.notify.background;
                     data.notify.vehicles.append(&mut track.notify.vehicles);
                     if track.notify.plate_info.is_some() {
@@ -491,12 +615,12 @@ impl CarHandler {
         }).await;
         if let Err(e) = source_po {
             error!("error, CarHandler load_source_by_sid, {}, {:?}", data.notify.source, e);
-            return;
+            return false;
         }
         let source_po = source_po.unwrap();
         if let Err(e) = source_po {
             error!("error, CarHandler load_source_by_sid, {}, {:?}", data.notify.source, e);
-            return;
+            return false;
         }
         let source_po = source_po.unwrap();
 
@@ -506,7 +630,7 @@ impl CarHandler {
             if let Err(e) = self.save_track(&mut data, &source_po).await {
                 error!("error, CarHandler save_track, {}, {:?}", data.uuid, e);
                 data.invalid = true; //保存失败
-                return;
+                return false;
             } else {
                 debug!("CarHandler save_track ok, {}", data.uuid);
             }
@@ -516,7 +640,7 @@ impl CarHandler {
         if !newed && appended {
             if let Err(e) = self.update_track(&mut data, &source_po).await {
                 error!("error, CarHandler update_track, {}, {:?}", data.uuid, e);
-                return;
+                return false;
             } else {
                 debug!("CarHandler update_track ok, {}", data.uuid);
             }
@@ -532,16 +656,25 @@ impl CarHandler {
                 error!("error, cartrack:{} is ready, but invalid, skip it", data.uuid);
             } else {
                 // 交给后续队列处理
-                if let Err(e) = self.put_to_next(&data, &source_po).await {
+                if let Err(e) = self.put_to_next(&mut data, &source_po, false).await {
                     error!("error, CarHandler put_to_next, {}, {:?}", data.uuid, e);
                 } else {
                     debug!("CarHandler put_to_next ok, {}", data.uuid);
                 }
             }
+        } else if ready_old && !newed && appended && !data.invalid && data.speed <= 0_f64
+            && self.get_speed(&data, &source_po) > 0_f64 {
+            // 已判断过报警，后续通知才估算出车速，重新判断超速规则
+            if let Err(e) = self.put_to_next(&mut data, &source_po, true).await {
+                error!("error, CarHandler put_to_next rejudge, {}, {:?}", data.uuid, e);
+            } else {
+                debug!("CarHandler put_to_next rejudge ok, {}, speed:{}", data.uuid, data.speed);
+            }
         }
 
         // 清除图片
         data.notify.clear_blob();
+        true
     }
 
     fn get_lane(&self, notify: &CarNotifyParams, source_po: &Option<CfDfsource>) -> usize {
@@ -593,4 +726,32 @@ impl CarHandler {
 
         lane_num.unwrap()
     }
+
+    /// 车辆经过摄像头两条测速线的车速, km/h
+    /// 未标定或无法估算时返回 0
+    fn get_speed(&self, track: &Track, source_po: &Option<CfDfsource>) -> f64 {
+        let calib = match source_po {
+            Some(v) => SpeedCalib::new(v.speed_line1, v.speed_line2, v.speed_distance),
+            None => None,
+        };
+        let calib = match calib {
+            Some(v) => v,
+            None => return 0_f64,
+        };
+
+        let position = &track.notify.position;
+        let samples: Vec<SpeedSample> = track.positions.iter()
+            .filter_map(|(frame, y)| speed::frame_to_time(position, *frame).map(|time| SpeedSample {
+                time,
+                y: *y,
+            }))
+            .collect();
+        match calib.estimate(&samples) {
+            Some(v) => v,
+            None => {
+                debug!("CarHandler, {} can't estimate speed, samples:{}", track.uuid, samples.len());
+                0_f64
+            }
+        }
+    }
 }
\ No newline at end of file
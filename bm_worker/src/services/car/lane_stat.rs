@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+
+use chrono::prelude::*;
+use chrono::Duration;
+use log::{debug, error, info};
+use tokio::stream::StreamExt;
+use tokio::sync::watch::Receiver;
+use tokio::task::JoinHandle as TkJoinHandle;
+use tokio::time;
+
+use cffc_base::util::bounded_queue::BoundedQueue;
+
+use crate::app_ctx::AppCtx;
+use crate::dao::model::CfLaneStat;
+use crate::error::AppResult;
+use crate::queue_item::{CtQI, QI};
+use crate::services::Service;
+
+/// 统计周期, minute
+pub const LANE_STAT_PERIODS: [i64; 4] = [1, 15, 60, 1440];
+
+/// src_sid, lane_num, move_direct, car_type, bucket_time
+type LaneKey = (String, i32, i32, String, DateTime<Local>);
+
+/// 周期的开始时间，按本地时间对齐
+pub fn get_bucket_time(ts: DateTime<Local>, period: i64) -> DateTime<Local> {
+    let bucket = period.max(1) * 60;
+    let offset = ts.offset().local_minus_utc() as i64;
+    let secs = ts.timestamp() + offset;
+    Local.timestamp(secs - secs.rem_euclid(bucket) - offset, 0)
+}
+
+/// 车道流量统计
+/// 订阅 EntBusSvc 的 cartrack, 按 摄像头/车道/运动方向/车粗分类别 计数
+/// 内存中按分钟累加，定时累加到 cf_lane_stat 的各个周期
+pub struct LaneStatSvc {
+    ctx: Arc<AppCtx>,
+    queue: Arc<BoundedQueue<QI>>,
+    /// 1 分钟周期的计数，写入数据库后清空
+    pending: HashMap<LaneKey, i64>,
+}
+
+impl LaneStatSvc {
+    pub fn new(ctx: Arc<AppCtx>, queue: Arc<BoundedQueue<QI>>) -> Self {
+        LaneStatSvc {
+            ctx,
+            queue,
+            pending: HashMap::new(),
+        }
+    }
+
+    fn process_item(&mut self, item: QI) {
+        let item: Box<CtQI> = match item {
+            QI::CT(v) => v,
+            _ => return,
+        };
+        // 重新判断报警的记录已统计过
+        if item.car.rejudge {
+            return;
+        }
+
+        let (move_direct, car_type) = match item.car.props {
+            Some(ref v) => (v.move_direct as i32, v.top_type.clone()),
+            None => (0, String::new()),
+        };
+        let key = (item.car.source.clone(), item.car.lane_num, move_direct, car_type, get_bucket_time(item.car.ts, 1));
+        *self.pending.entry(key).or_insert(0) += 1;
+    }
+
+    /// 展开到各个周期，相同的 key 合并
+    fn to_stats(&self) -> Vec<CfLaneStat> {
+        let mut map: HashMap<(i64, LaneKey), i64> = HashMap::new();
+        for (key, count) in self.pending.iter() {
+            for period in LANE_STAT_PERIODS.iter() {
+                let mut k = key.clone();
+                k.4 = get_bucket_time(key.4, *period);
+                *map.entry((*period, k)).or_insert(0) += *count;
+            }
+        }
+
+        let now = Local::now();
+        map.into_iter().map(|((period, key), count)| CfLaneStat {
+            id: 0,
+            src_sid: key.0,
+            period,
+            bucket_time: key.4,
+            lane_num: key.1,
+            move_direct: key.2,
+            car_type: key.3,
+            count,
+            gmt_create: now,
+            gmt_modified: now,
+        }).collect()
+    }
+
+    /// 在一个事务中写入，失败时保留计数，下次重试
+    async fn flush(&mut self) -> AppResult<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let list = self.to_stats();
+        let ctx = self.ctx.clone();
+        let count = tokio::task::spawn_blocking(move || {
+            ctx.dao.incr_lane_stats(&list)
+        }).await??;
+        debug!("LaneStatSvc, flush, keys:{}, stats:{}", self.pending.len(), count);
+        self.pending.clear();
+        Ok(())
+    }
+
+    /// 删除过期的 1 分钟 和 15 分钟统计
+    async fn prune(&self) -> AppResult<()> {
+        let cfg = &self.ctx.cfg.lane_stat;
+        let now = Local::now();
+        let list = vec![
+            (LANE_STAT_PERIODS[0], now - Duration::days(cfg.minute_keep_days.max(1))),
+            (LANE_STAT_PERIODS[1], now - Duration::days(cfg.quarter_keep_days.max(1))),
+        ];
+
+        let ctx = self.ctx.clone();
+        let affect = tokio::task::spawn_blocking(move || -> AppResult<usize> {
+            let mut affect = 0;
+            for (period, before) in list.iter() {
+                affect += ctx.dao.delete_lane_stats_before(*period, before)?;
+            }
+            Ok(affect)
+        }).await??;
+        if affect > 0 {
+            info!("LaneStatSvc, prune stats: {}", affect);
+        }
+        Ok(())
+    }
+}
+
+impl Service for LaneStatSvc {
+    fn run(self, rx: Receiver<i64>) -> TkJoinHandle<()> {
+        let mut svc = self;
+        let mut exit_rx = rx;
+
+        tokio::spawn(async move {
+            let mut flush_interval = time::interval(StdDuration::from_secs(svc.ctx.cfg.lane_stat.flush_sec.max(1)));
+            let mut prune_interval = time::interval(StdDuration::from_secs(3600));
+            loop {
+                tokio::select! {
+                    quit = exit_rx.next() => {
+                        if let Some(100) = quit {
+                            info!("LaneStatSvc recv exit");
+                            break;
+                        }
+                    }
+                    item = svc.queue.pop() => {
+                        svc.process_item(item);
+                    }
+                    _ = flush_interval.tick() => {
+                        if let Err(e) = svc.flush().await {
+                            error!("error, LaneStatSvc, flush, {:?}", e);
+                        }
+                    }
+                    _ = prune_interval.tick() => {
+                        if let Err(e) = svc.prune().await {
+                            error!("error, LaneStatSvc, prune, {:?}", e);
+                        }
+                    }
+                }
+            }
+
+            // 退出前写入未保存的计数
+            if let Err(e) = svc.flush().await {
+                error!("error, LaneStatSvc, flush, {:?}", e);
+            }
+            info!("LaneStatSvc exit");
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_time() {
+        let ts = Local.ymd(2024, 1, 1).and_hms(10, 17, 5);
+        assert_eq!(get_bucket_time(ts, 1), Local.ymd(2024, 1, 1).and_hms(10, 17, 0));
+        assert_eq!(get_bucket_time(ts, 15), Local.ymd(2024, 1, 1).and_hms(10, 15, 0));
+        assert_eq!(get_bucket_time(ts, 60), Local.ymd(2024, 1, 1).and_hms(10, 0, 0));
+        // 按本地时间的 0 点对齐
+        assert_eq!(get_bucket_time(ts, 1440), Local.ymd(2024, 1, 1).and_hms(0, 0, 0));
+        // 不足 1 分钟按 1 分钟
+        assert_eq!(get_bucket_time(ts, 0), Local.ymd(2024, 1, 1).and_hms(10, 17, 0));
+
+        let ts = Local.ymd(2024, 1, 1).and_hms(10, 15, 0);
+        assert_eq!(get_bucket_time(ts, 15), ts);
+    }
+}
@@ -1,3 +1,5 @@
 pub mod car_notify;
 pub mod spool_async;
-pub mod car_judge;
\ No newline at end of file
+pub mod car_judge;
+pub mod lane_stat;
+pub mod parking;
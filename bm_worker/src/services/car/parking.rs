@@ -0,0 +1,417 @@
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+
+use chrono::prelude::*;
+use chrono::Duration;
+use log::{debug, error, info, warn};
+use tokio::stream::StreamExt;
+use tokio::sync::watch::Receiver;
+use tokio::task::JoinHandle as TkJoinHandle;
+use tokio::time;
+
+use cffc_base::util::bounded_queue::BoundedQueue;
+
+use crate::app_cfg::AppCfgParking;
+use crate::app_ctx::AppCtx;
+use crate::dao::model::CfParkingVisit;
+use crate::error::AppResult;
+use crate::queue_item::{CtQI, ParkingQI, QI};
+use crate::services::Service;
+
+/// 摄像头进出口标志
+pub const IO_FLAG_ENTRY: i32 = 1;
+pub const IO_FLAG_EXIT: i32 = 2;
+pub const IO_FLAG_BOTH: i32 = 3;
+
+/// 停车记录状态
+pub const VISIT_STATE_PARKED: i32 = 0;
+pub const VISIT_STATE_EXITED: i32 = 1;
+/// 出场时没有找到入场记录
+pub const VISIT_STATE_EXIT_ONLY: i32 = 2;
+/// 再次入场时仍未出场，视为漏拍出场
+pub const VISIT_STATE_LOST: i32 = 3;
+
+/// 停车费，超过免费时长时按全部时长计费
+/// 不足一小时按一小时，每 24 小时不超过封顶费用
+pub fn calc_fee(cfg: &AppCfgParking, dwell_min: i64) -> f64 {
+    if dwell_min <= cfg.free_min {
+        return 0_f64;
+    }
+
+    let hours = (dwell_min + 59) / 60;
+    let day_fee = |h: i64| {
+        let fee = h as f64 * cfg.hourly_rate;
+        match cfg.daily_cap > 0_f64 {
+            true => fee.min(cfg.daily_cap),
+            false => fee,
+        }
+    };
+    let fee = (hours / 24) as f64 * day_fee(24) + day_fee(hours % 24);
+    (fee * 100_f64).round() / 100_f64
+}
+
+struct ParkingEvent {
+    ct_sid: String,
+    src_sid: String,
+    plate: String,
+    io_flag: i32,
+    group_sid: Option<String>,
+    ts: DateTime<Local>,
+}
+
+/// 停车场进出配对
+/// 订阅 EntBusSvc 的 cartrack, 进口摄像头的记录为入场，出口摄像头的记录与同一车牌最近的未出场记录配对
+/// 出场时计算停留时长和费用，保存到 cf_parking_visit；定时检查超时停放
+pub struct ParkingSvc {
+    ctx: Arc<AppCtx>,
+    queue: Arc<BoundedQueue<QI>>,
+    /// 超时停放报警
+    out: Arc<BoundedQueue<QI>>,
+}
+
+impl ParkingSvc {
+    pub fn new(ctx: Arc<AppCtx>, queue: Arc<BoundedQueue<QI>>, out: Arc<BoundedQueue<QI>>) -> Self {
+        ParkingSvc {
+            ctx,
+            queue,
+            out,
+        }
+    }
+
+    fn to_event(item: &CtQI) -> Option<ParkingEvent> {
+        if item.car.rejudge || !matches!(item.car.io_flag, IO_FLAG_ENTRY | IO_FLAG_EXIT | IO_FLAG_BOTH) {
+            return None;
+        }
+        let plate = item.car.plate.as_ref().map(|x| x.content.trim())?;
+        if plate.is_empty() {
+            return None;
+        }
+
+        Some(ParkingEvent {
+            ct_sid: item.sid.clone(),
+            src_sid: item.car.source.clone(),
+            plate: plate.to_string(),
+            io_flag: item.car.io_flag,
+            group_sid: item.match_coi.as_ref().map(|x| x.group_sid.clone()),
+            ts: item.car.ts,
+        })
+    }
+
+    async fn process_item(&self, item: QI) {
+        let event = match item {
+            QI::CT(ref v) => Self::to_event(v),
+            _ => None,
+        };
+        let event = match event {
+            Some(v) => v,
+            None => return,
+        };
+
+        let ctx = self.ctx.clone();
+        let ct_sid = event.ct_sid.clone();
+        let visit = tokio::task::spawn_blocking(move || {
+            Self::do_process(&ctx, event)
+        }).await;
+        match visit {
+            Ok(Ok(Some(v))) => debug!("ParkingSvc, {}, plate:{}, state:{}, dwell:{}, fee:{}", ct_sid, v.plate_content, v.state, v.dwell_min, v.fee),
+            Ok(Ok(None)) => debug!("ParkingSvc, {}, ignore duplicate", ct_sid),
+            Ok(Err(e)) => error!("error, ParkingSvc, {}, {:?}", ct_sid, e),
+            Err(e) => error!("error, ParkingSvc, {}, {:?}", ct_sid, e),
+        }
+    }
+
+    /// 返回保存的记录，重复抓拍时返回 None
+    fn do_process(ctx: &AppCtx, event: ParkingEvent) -> AppResult<Option<CfParkingVisit>> {
+        let cfg = &ctx.cfg.parking;
+        let plate = event.plate.clone();
+        let v = ctx.dao.pair_parking_visit(&plate, |parked, exited| Self::pair(cfg, event, parked, exited))?;
+        let (po, closed) = match v {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        if closed > 0 {
+            warn!("warn, ParkingSvc, {} entry again, close {} visits", plate, closed);
+        }
+        Ok(Some(po))
+    }
+
+    /// 进出配对，返回之前在场记录的新状态和要保存的记录，重复抓拍时返回 None
+    fn pair(cfg: &AppCfgParking, event: ParkingEvent,
+            parked: Option<CfParkingVisit>, exited: Option<CfParkingVisit>) -> Option<(Option<i32>, CfParkingVisit)> {
+        let dedup_since = event.ts - Duration::seconds(cfg.dedup_sec.max(0));
+
+        // 刚入场的重复抓拍，共用摄像头时不能当作出场
+        let dup_entry = matches!(parked, Some(ref v) if matches!(v.entry_time, Some(t) if t >= dedup_since));
+        if dup_entry && event.io_flag != IO_FLAG_EXIT {
+            return None;
+        }
+
+        // 进口/出口共用的摄像头，有在场记录时为出场
+        let is_entry = match event.io_flag {
+            IO_FLAG_ENTRY => true,
+            IO_FLAG_EXIT => false,
+            _ => parked.is_none(),
+        };
+
+        let now = Local::now();
+        if is_entry {
+            let close_state = parked.as_ref().map(|_| VISIT_STATE_LOST);
+            let po = CfParkingVisit {
+                id: 0,
+                plate_content: event.plate,
+                entry_ct_sid: Some(event.ct_sid),
+                entry_src_sid: Some(event.src_sid),
+                entry_time: Some(event.ts),
+                exit_ct_sid: None,
+                exit_src_sid: None,
+                exit_time: None,
+                state: VISIT_STATE_PARKED,
+                dwell_min: 0,
+                fee: 0_f64,
+                fee_free: 0,
+                group_sid: event.group_sid,
+                overstay: 0,
+                gmt_create: now,
+                gmt_modified: now,
+            };
+            return Some((close_state, po));
+        }
+
+        if matches!(exited, Some(ref v) if matches!(v.exit_time, Some(t) if t >= dedup_since)) {
+            return None;
+        }
+
+        let mut po = match parked {
+            Some(v) => v,
+            None => CfParkingVisit {
+                id: 0,
+                plate_content: event.plate,
+                entry_ct_sid: None,
+                entry_src_sid: None,
+                entry_time: None,
+                exit_ct_sid: None,
+                exit_src_sid: None,
+                exit_time: None,
+                state: VISIT_STATE_EXIT_ONLY,
+                dwell_min: 0,
+                fee: 0_f64,
+                fee_free: 0,
+                group_sid: None,
+                overstay: 0,
+                gmt_create: now,
+                gmt_modified: now,
+            },
+        };
+        po.exit_ct_sid = Some(event.ct_sid);
+        po.exit_src_sid = Some(event.src_sid);
+        po.exit_time = Some(event.ts);
+        po.gmt_modified = now;
+        if event.group_sid.is_some() {
+            po.group_sid = event.group_sid;
+        }
+
+        if let Some(entry_time) = po.entry_time {
+            po.state = VISIT_STATE_EXITED;
+            po.dwell_min = (event.ts - entry_time).num_minutes().max(0);
+            let free = matches!(po.group_sid, Some(ref g) if cfg.free_groups.iter().any(|x| x.eq(g)));
+            po.fee_free = free as i32;
+            po.fee = match free {
+                true => 0_f64,
+                false => calc_fee(cfg, po.dwell_min),
+            };
+        }
+        Some((None, po))
+    }
+
+    /// 超时停放报警，每条记录只报一次，发送到 EntBusSvc
+    async fn check_overstay(&self) -> AppResult<()> {
+        let hours = self.ctx.cfg.parking.overstay_hours;
+        if hours <= 0 {
+            return Ok(());
+        }
+
+        let ctx = self.ctx.clone();
+        let now = Local::now();
+        let before = now - Duration::hours(hours);
+        let list = tokio::task::spawn_blocking(move || -> AppResult<Vec<ParkingQI>> {
+            let list = ctx.dao.mark_parking_overstay(&before)?;
+            let mut qi_list = Vec::with_capacity(list.len());
+            for v in list.iter() {
+                let camera = match v.entry_src_sid {
+                    Some(ref sid) => ctx.dao.load_source_by_sid(sid)?,
+                    None => None,
+                };
+                qi_list.push(ParkingQI::from_po(v, camera.as_ref(), now));
+            }
+            Ok(qi_list)
+        }).await??;
+
+        for qi in list.into_iter() {
+            info!("ParkingSvc, overstay, plate:{}, entry_time:{:?}", qi.plate_content, qi.entry_time);
+            if let Err(qi) = self.out.push(QI::PARKING(qi)) {
+                error!("error, ParkingSvc, queue: {} is full, drop {}", self.out.name(), qi.get_sid());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Service for ParkingSvc {
+    fn run(self, rx: Receiver<i64>) -> TkJoinHandle<()> {
+        let svc = self;
+        let mut exit_rx = rx;
+
+        tokio::spawn(async move {
+            let mut interval = time::interval(StdDuration::from_secs(svc.ctx.cfg.parking.check_sec.max(1)));
+            loop {
+                tokio::select! {
+                    quit = exit_rx.next() => {
+                        if let Some(100) = quit {
+                            info!("ParkingSvc recv exit");
+                            break;
+                        }
+                    }
+                    item = svc.queue.pop() => {
+                        svc.process_item(item).await;
+                    }
+                    _ = interval.tick() => {
+                        if let Err(e) = svc.check_overstay().await {
+                            error!("error, ParkingSvc, check_overstay, {:?}", e);
+                        }
+                    }
+                }
+            }
+            info!("ParkingSvc exit");
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fee_free_min() {
+        let cfg = AppCfgParking::default();
+        assert_eq!(calc_fee(&cfg, 0), 0_f64);
+        assert_eq!(calc_fee(&cfg, 15), 0_f64);
+        // 超过免费时长，按全部时长计费
+        assert_eq!(calc_fee(&cfg, 16), 5_f64);
+    }
+
+    #[test]
+    fn fee_round_up_hours() {
+        let cfg = AppCfgParking::default();
+        assert_eq!(calc_fee(&cfg, 60), 5_f64);
+        assert_eq!(calc_fee(&cfg, 61), 10_f64);
+
+        let cfg = AppCfgParking {
+            hourly_rate: 2.5,
+            ..Default::default()
+        };
+        assert_eq!(calc_fee(&cfg, 150), 7.5);
+    }
+
+    #[test]
+    fn fee_daily_cap() {
+        let cfg = AppCfgParking::default();
+        assert_eq!(calc_fee(&cfg, 10 * 60), 50_f64);
+        assert_eq!(calc_fee(&cfg, 11 * 60), 50_f64);
+        // 每 24 小时封顶
+        assert_eq!(calc_fee(&cfg, 24 * 60 + 1), 55_f64);
+        assert_eq!(calc_fee(&cfg, 48 * 60), 100_f64);
+
+        let cfg = AppCfgParking {
+            daily_cap: 0_f64,
+            ..Default::default()
+        };
+        assert_eq!(calc_fee(&cfg, 11 * 60), 55_f64);
+    }
+
+    fn new_event(io_flag: i32, ts: DateTime<Local>) -> ParkingEvent {
+        ParkingEvent {
+            ct_sid: format!("ct{}", ts.timestamp()),
+            src_sid: "src1".to_string(),
+            plate: "京A12345".to_string(),
+            io_flag,
+            group_sid: None,
+            ts,
+        }
+    }
+
+    /// 入场记录
+    fn parked_at(cfg: &AppCfgParking, ts: DateTime<Local>) -> CfParkingVisit {
+        ParkingSvc::pair(cfg, new_event(IO_FLAG_ENTRY, ts), None, None).unwrap().1
+    }
+
+    #[test]
+    fn pair_entry_exit() {
+        let cfg = AppCfgParking::default();
+        let t0 = Local.ymd(2024, 1, 1).and_hms(8, 0, 0);
+
+        let (close, po) = ParkingSvc::pair(&cfg, new_event(IO_FLAG_ENTRY, t0), None, None).unwrap();
+        assert_eq!(close, None);
+        assert_eq!(po.state, VISIT_STATE_PARKED);
+        assert_eq!(po.entry_time, Some(t0));
+
+        let t1 = t0 + Duration::minutes(90);
+        let (close, po) = ParkingSvc::pair(&cfg, new_event(IO_FLAG_EXIT, t1), Some(po), None).unwrap();
+        assert_eq!(close, None);
+        assert_eq!(po.state, VISIT_STATE_EXITED);
+        assert_eq!(po.exit_time, Some(t1));
+        assert_eq!(po.dwell_min, 90);
+        assert_eq!(po.fee, 10_f64);
+    }
+
+    #[test]
+    fn pair_shared_camera() {
+        let cfg = AppCfgParking::default();
+        let t0 = Local.ymd(2024, 1, 1).and_hms(8, 0, 0);
+
+        let (_, po) = ParkingSvc::pair(&cfg, new_event(IO_FLAG_BOTH, t0), None, None).unwrap();
+        assert_eq!(po.state, VISIT_STATE_PARKED);
+
+        // dedup_sec 内的重复抓拍
+        assert!(ParkingSvc::pair(&cfg, new_event(IO_FLAG_BOTH, t0 + Duration::seconds(30)), Some(parked_at(&cfg, t0)), None).is_none());
+
+        let (_, po) = ParkingSvc::pair(&cfg, new_event(IO_FLAG_BOTH, t0 + Duration::minutes(10)), Some(parked_at(&cfg, t0)), None).unwrap();
+        assert_eq!(po.state, VISIT_STATE_EXITED);
+        assert_eq!(po.fee, 0_f64);
+    }
+
+    #[test]
+    fn pair_exit_only_and_lost() {
+        let cfg = AppCfgParking::default();
+        let t0 = Local.ymd(2024, 1, 1).and_hms(8, 0, 0);
+
+        let (_, po) = ParkingSvc::pair(&cfg, new_event(IO_FLAG_EXIT, t0), None, None).unwrap();
+        assert_eq!(po.state, VISIT_STATE_EXIT_ONLY);
+        assert_eq!(po.entry_time, None);
+
+        // 刚出场的重复抓拍
+        assert!(ParkingSvc::pair(&cfg, new_event(IO_FLAG_EXIT, t0 + Duration::seconds(30)), None, Some(po)).is_none());
+
+        // 未出场又入场，之前的记录为漏拍出场
+        let t1 = t0 + Duration::hours(2);
+        let (close, po) = ParkingSvc::pair(&cfg, new_event(IO_FLAG_ENTRY, t1), Some(parked_at(&cfg, t0)), None).unwrap();
+        assert_eq!(close, Some(VISIT_STATE_LOST));
+        assert_eq!(po.state, VISIT_STATE_PARKED);
+        assert_eq!(po.entry_time, Some(t1));
+    }
+
+    #[test]
+    fn pair_free_group() {
+        let cfg = AppCfgParking {
+            free_groups: vec!["g1".to_string()],
+            ..Default::default()
+        };
+        let t0 = Local.ymd(2024, 1, 1).and_hms(8, 0, 0);
+        let mut event = new_event(IO_FLAG_EXIT, t0 + Duration::hours(3));
+        event.group_sid = Some("g1".to_string());
+
+        let (_, po) = ParkingSvc::pair(&cfg, event, Some(parked_at(&cfg, t0)), None).unwrap();
+        assert_eq!(po.dwell_min, 180);
+        assert_eq!(po.fee_free, 1);
+        assert_eq!(po.fee, 0_f64);
+    }
+}
@@ -1,13 +1,14 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::sync::RwLock;
-use deadqueue::unlimited::Queue;
-use log::{debug, info};
+use log::{debug, error, info};
 use tokio::stream::StreamExt;
 use tokio::sync::watch::Receiver;
 use tokio::task::JoinHandle as TkJoinHandle;
 
 
+use cffc_base::util::bounded_queue::BoundedQueue;
+
 use crate::app_ctx::AppCtx;
 
 use crate::queue_item::QI;
@@ -15,23 +16,28 @@ use crate::queue_item::QI;
 use super::Service;
 
 pub struct EntBusSvc {
-    _ctx: Arc<AppCtx>,
-    queue: Arc<Queue<QI>>,
-    out_queues: RwLock<HashMap<String, Arc<Queue<QI>>>>,
+    ctx: Arc<AppCtx>,
+    queue: Arc<BoundedQueue<QI>>,
+    out_queues: RwLock<HashMap<String, Arc<BoundedQueue<QI>>>>,
 }
 
 impl EntBusSvc {
-    pub fn new(_ctx: Arc<AppCtx>, queue: Arc<Queue<QI>>) -> Self {
+    pub fn new(ctx: Arc<AppCtx>, queue: Arc<BoundedQueue<QI>>) -> Self {
         EntBusSvc {
-            _ctx,
+            ctx,
             queue,
             out_queues: RwLock::default(),
         }
     }
 
-    pub fn get_queue(&self, name: &str) -> Arc<Queue<QI>> {
+    /// 订阅者队列, 新建时登记到 queue_stat
+    pub fn get_queue(&self, name: &str) -> Arc<BoundedQueue<QI>> {
         let mut lock = self.out_queues.write().unwrap();
-        let queue = lock.entry(name.to_string()).or_insert_with(|| Arc::new(Queue::new()));
+        let queue = lock.entry(name.to_string()).or_insert_with(|| {
+            let queue = Arc::new(BoundedQueue::new(&format!("bus_{}", name), self.ctx.cfg.queue.bus_cap));
+            self.ctx.queue_stat.register(queue.clone());
+            queue
+        });
         queue.clone()
     }
 
@@ -40,8 +46,10 @@ impl EntBusSvc {
 
         let lock = self.out_queues.read().unwrap();
 
-        for (_k, v) in lock.iter() {
-            v.push(item.clone());
+        for (k, v) in lock.iter() {
+            if v.push(item.clone()).is_err() {
+                error!("error, EntBusSvc, queue: {} is full, drop {}", k, item.get_sid());
+            }
         }
     }
 }
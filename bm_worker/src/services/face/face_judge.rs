@@ -1,7 +1,6 @@
 use std::sync::Arc;
 
 use chrono::Local;
-use deadqueue::unlimited::Queue;
 use log::{debug, error, info};
 use tokio::stream::StreamExt;
 use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
@@ -9,11 +8,14 @@ use tokio::sync::watch::Receiver;
 use tokio::task::JoinHandle as TkJoinHandle;
 
 use cffc_base::model::img_file;
+use cffc_base::util::bounded_queue::BoundedQueue;
 
+use crate::alarm_rule::{self, AlarmFacts};
 use crate::app_ctx::AppCtx;
-use crate::dao::model::{CfFacetrack, CfPoi};
+use crate::dao::model::{CfFacetrack, CfFacetrackCandidate, CfPoi};
 use crate::error::AppResult;
-use crate::queue_item::{FtQI, FtQIPerson, QI};
+use crate::judge_registry::RegItem;
+use crate::queue_item::{FtQI, FtQICandidate, FtQIPerson, QI, TRACK_TYPE_FACE};
 use crate::services::face::face_search::FaceSearchWorker;
 use crate::services::Service;
 
@@ -23,15 +25,19 @@ use crate::services::Service;
 
 pub struct FaceJudgeSvc {
     ctx: Arc<AppCtx>,
-    queue: Arc<Queue<FtQI>>,
-    out: Arc<Queue<QI>>,
+    queue: Arc<BoundedQueue<FtQI>>,
+    out: Arc<BoundedQueue<QI>>,
 
     tx: UnboundedSender<FtQI>,
     rx: UnboundedReceiver<FtQI>,
+
+    /// 比对失败的重试队列
+    retry: Option<Arc<BoundedQueue<FtQI>>>,
 }
 
 impl FaceJudgeSvc {
-    pub fn new(ctx: Arc<AppCtx>, queue: Arc<Queue<FtQI>>, out: Arc<Queue<QI>>) -> Self {
+    pub fn new(ctx: Arc<AppCtx>, queue: Arc<BoundedQueue<FtQI>>, out: Arc<BoundedQueue<QI>>,
+               retry: Option<Arc<BoundedQueue<FtQI>>>) -> Self {
         let (tx, rx) = mpsc::unbounded_channel::<FtQI>();
 
         FaceJudgeSvc {
@@ -40,13 +46,13 @@ impl FaceJudgeSvc {
             tx,
             rx,
             out,
+            retry,
         }
     }
 
     // 查询数据库中的db列表
-    fn load_dbs(&self) -> AppResult<Vec<(String, String, i32)>> {
-        let dbs = self.ctx.dao.load_automatch_dbs()?;
-        Ok(dbs)
+    fn load_dbs(&self) -> AppResult<Arc<Vec<RegItem>>> {
+        self.ctx.registry.load_dbs(&self.ctx.dao)
     }
 
     fn fill_qi_person(&self, qi: &mut FtQIPerson, po: CfPoi) {
@@ -74,6 +80,40 @@ impl FaceJudgeSvc {
     }
 
 
+    /// 查询候选人的姓名、封面，保存到 cf_facetrack_candidate
+    async fn fill_candidates(&self, item: &mut FtQI) {
+        if item.candidates.is_empty() {
+            return;
+        }
+
+        let ctx = self.ctx.clone();
+        let ft_sid = item.sid.clone();
+        let mut candidates = std::mem::take(&mut item.candidates);
+        let prefix = self.ctx.cfg.dfimg_url.clone();
+
+        let rst = tokio::task::spawn_blocking(move || -> AppResult<Vec<FtQICandidate>> {
+            for x in candidates.iter_mut() {
+                if let Some(po) = ctx.dao.load_poi_by_sid(&x.sid)? {
+                    x.name = po.name;
+                    if po.cover.is_some() {
+                        x.cover_url = img_file::get_person_cover_url(&prefix, &po.poi_sid);
+                    }
+                }
+            }
+
+            let now = Local::now();
+            let list: Vec<CfFacetrackCandidate> = candidates.iter().map(|x| x.to_po(&ft_sid, now)).collect();
+            ctx.dao.save_facetrack_candidates(&ft_sid, &list)?;
+            Ok(candidates)
+        }).await;
+
+        match rst {
+            Ok(Ok(v)) => item.candidates = v,
+            Ok(Err(e)) => error!("error, FaceJudgeSvc, fill_candidates:{}, {:?}", item.sid, e),
+            Err(e) => error!("error, FaceJudgeSvc, fill_candidates:{}, {:?}", item.sid, e),
+        }
+    }
+
     /// 对比对的结果，查询数据库对应的person信息
     /// 判断报警情况
     /// 更新 facetrack表
@@ -111,8 +151,18 @@ impl FaceJudgeSvc {
             }
         }
 
+        // 候选人信息
+        self.fill_candidates(&mut item).await;
+
         // 判断报警
-        if self.ctx.cfg.notify_proc.facetrack.wl_alarm {
+        let rules = alarm_rule::get_rules(&self.ctx).await;
+        if alarm_rule::has_rules(&rules, TRACK_TYPE_FACE) {
+            let fired = alarm_rule::find_rule(&rules, &AlarmFacts::from_face(&item))
+                .map(|x| (x.id, x.severity));
+            item.face.alarmed = fired.is_some();
+            item.face.alarm_rule = fired.map_or(0, |x| x.0);
+            item.face.alarm_level = fired.map_or(0, |x| x.1);
+        } else if self.ctx.cfg.notify_proc.facetrack.wl_alarm {
             // 白名单报警模式
             item.face.alarmed = true;
             if let Some(ref mut person) = item.match_poi {
@@ -153,6 +203,8 @@ impl FaceJudgeSvc {
                 true => Some(1),
                 false => Some(0),
             },
+            alarm_rule: item.face.alarm_rule,
+            alarm_level: item.face.alarm_level,
             most_person: match item.match_poi {
                 Some(ref v) => Some(v.sid.clone()),
                 None => None,
@@ -177,6 +229,8 @@ impl FaceJudgeSvc {
             obj_id: None,
             submit_id: None,
             submit_time: None,
+            start_real_time: 0,
+            end_real_time: 0,
             capture_time: now,
             gmt_create: now,
             gmt_modified: now,
@@ -204,7 +258,9 @@ impl FaceJudgeSvc {
 
         // 放入后续队列中
         debug!("FaceJudgeSvc, put ot next, {}", item.sid);
-        self.out.push(QI::FT(item));
+        if let Err(qi) = self.out.push(QI::FT(item)) {
+            error!("error, FaceJudgeSvc, queue: {} is full, drop {}", self.out.name(), qi.get_sid());
+        }
     }
 }
 
@@ -228,7 +284,7 @@ impl Service for FaceJudgeSvc {
         let count = svc.ctx.cfg.notify_proc.search_worker as i64;
         for i in 0..count {
             let worker = FaceSearchWorker::new(i + 1, svc.ctx.clone(),
-                                               svc.queue.clone(), dbs.clone(), svc.tx.clone());
+                                               svc.queue.clone(), dbs.clone(), svc.tx.clone(), svc.retry.clone());
 
             let rx = svc.ctx.exit_rx.clone();
             services.push(worker.run(rx));
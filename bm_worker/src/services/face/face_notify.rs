@@ -4,7 +4,6 @@ use std::time::Duration;
 use bytes::Buf;
 use chrono::prelude::*;
 use dashmap::DashMap;
-use deadqueue::unlimited::Queue;
 use log::{debug, error, info};
 use tokio::stream::StreamExt;
 use tokio::sync::mpsc::{Receiver as TkReceiver, Sender as TkSender};
@@ -17,11 +16,13 @@ use cffc_base::api::bm_api::FaceNotifyParams;
 use cffc_base::model::img_file;
 use cffc_base::util::delay_queue::DelayQueueChan;
 use cffc_base::util::utils;
+use cffc_base::util::bounded_queue::BoundedQueue;
 
 use crate::app_ctx::AppCtx;
-use crate::dao::model::CfFacetrack;
+use crate::dao::model::{CfFacetrack, CfFacetrackFeature};
 use crate::error::{AppError, AppResult};
-use crate::queue_item::{FtQI, NotifyFaceQueueItem};
+use crate::notify_spool::{self, SPOOL_FACE};
+use crate::queue_item::{FtQI, NotifyFaceQueueItem, TripQueueItem};
 use crate::services::Service;
 
 use super::spool_async::{SerialPool, SpHolder};
@@ -37,6 +38,55 @@ pub struct Track {
     wp: usize,
 
     notify: FaceNotifyParams,
+
+    /// 待删除的 spool key
+    spool_keys: Vec<String>,
+
+    /// 重放时数据库中已保存的 img_ids，新图片的序号从 base + 1 开始
+    saved_ids: Option<String>,
+    base: usize,
+}
+
+/// 人脸属性，来自 notify 的 props 或 get_features
+#[derive(Default)]
+struct FaceAttrs {
+    /// 0 不确定; 1 男性; 2 ⼥性
+    gender: i64,
+    age: i64,
+    /// 0 不戴眼镜; 1 墨镜; 2 普通眼镜
+    glasses: i64,
+    /// 0 未知；1 向上；2 向下
+    direction: i64,
+    /// 0 未知; 1 有; 2 没有
+    mask: Option<i64>,
+    moustache: Option<i64>,
+    hat: Option<i64>,
+    plane_score: Option<f64>,
+    /// props 中有基本属性
+    has_props: bool,
+}
+
+impl FaceAttrs {
+    fn from_props(track: &Track) -> Self {
+        match track.notify.props {
+            Some(ref v) => FaceAttrs {
+                gender: v.gender,
+                age: v.age,
+                glasses: v.glasses,
+                direction: v.move_direction,
+                mask: v.mask,
+                moustache: v.moustache,
+                hat: v.hat,
+                plane_score: v.plane_score,
+                has_props: true,
+            },
+            None => FaceAttrs::default(),
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.has_props && self.mask.is_some() && self.moustache.is_some() && self.hat.is_some()
+    }
 }
 
 pub enum TrackEvent {
@@ -47,12 +97,12 @@ pub enum TrackEvent {
 
 pub struct FaceHandler {
     ctx: Arc<AppCtx>,
-    out: Arc<Queue<FtQI>>,
+    out: Arc<BoundedQueue<FtQI>>,
 }
 
 pub struct FaceNotifyProcSvc {
     ctx: Arc<AppCtx>,
-    queue: Arc<Queue<NotifyFaceQueueItem>>,
+    queue: Arc<BoundedQueue<NotifyFaceQueueItem>>,
 
     spool: SerialPool,
     ready_tx: TkSender<(String, Duration)>,
@@ -62,11 +112,15 @@ pub struct FaceNotifyProcSvc {
     clean_rx: TkReceiver<Result<Expired<String>, TkTimeError>>,
 
     track_map: DashMap<String, Arc<SpHolder>>,
+
+    /// 越线判断, 未开启时为 None
+    trip_queue: Option<Arc<BoundedQueue<TripQueueItem>>>,
 }
 
 // ------------------- impls -------------------
 impl FaceNotifyProcSvc {
-    pub fn new(ctx: Arc<AppCtx>, queue: Arc<Queue<NotifyFaceQueueItem>>, out: Arc<Queue<FtQI>>) -> Self {
+    pub fn new(ctx: Arc<AppCtx>, queue: Arc<BoundedQueue<NotifyFaceQueueItem>>, out: Arc<BoundedQueue<FtQI>>,
+               trip_queue: Option<Arc<BoundedQueue<TripQueueItem>>>) -> Self {
         let handler = FaceHandler {
             ctx: ctx.clone(),
             out,
@@ -91,6 +145,7 @@ impl FaceNotifyProcSvc {
             clean_tx,
             clean_rx,
             track_map,
+            trip_queue,
         }
     }
 
@@ -118,6 +173,15 @@ impl FaceNotifyProcSvc {
     async fn process_item(&mut self, item: NotifyFaceQueueItem) {
         debug!("FaceNotifyProcSvc, process_item:{:?}", item.uuid);
 
+        // 带 trip 信息的，交给 TripSvc
+        if let Some(ref queue) = self.trip_queue {
+            if let Some(trip) = TripQueueItem::from_face(item.ts, &item.notify) {
+                if queue.push(trip).is_err() {
+                    error!("error, FaceNotifyProcSvc, queue: {} is full, drop trip {}", queue.name(), item.uuid);
+                }
+            }
+        }
+
         let uuid = item.uuid.clone();
         let ready = self.check_recv_mode(&item);
         let track = Track {
@@ -127,6 +191,9 @@ impl FaceNotifyProcSvc {
             invalid: false,
             wp: 0,
             notify: item.notify,
+            spool_keys: item.spool_key.into_iter().collect(),
+            saved_ids: None,
+            base: 0,
         };
 
         if let Some(holder) = self.track_map.get(uuid.as_str()) {
@@ -203,13 +270,124 @@ impl Service for FaceNotifyProcSvc {
     }
 }
 
+/// 按质量分降序，保留 keep_n 个
+fn sort_features(list: &mut Vec<CfFacetrackFeature>, keep_n: usize) {
+    list.sort_by(|a, b| b.quality.partial_cmp(&a.quality).unwrap_or(std::cmp::Ordering::Equal));
+    list.truncate(keep_n);
+}
+
+/// 特征值对应的 feature_ids (index:quality)
+fn join_feature_ids(list: &[CfFacetrackFeature]) -> Option<String> {
+    if list.is_empty() {
+        return None;
+    }
+    let ids: Vec<String> = list.iter().map(|x| format!("{}:{}", x.img_idx, x.quality)).collect();
+    Some(ids.join(","))
+}
+
 // ------------------- impl Handler -------------------
 impl FaceHandler {
+    /// 质量分最高的 keep_n 个特征值
+    fn best_features(&self, track: &Track, now: DateTime<Local>) -> Vec<CfFacetrackFeature> {
+        let cfg = &self.ctx.cfg.track_feature;
+        if !cfg.enable {
+            return vec![];
+        }
+
+        let mut list: Vec<CfFacetrackFeature> = track.notify.faces.iter().enumerate().filter_map(|(i, face)| {
+            face.feature_buf.as_ref().map(|buf| CfFacetrackFeature {
+                id: 0,
+                ft_sid: track.uuid.clone(),
+                src_sid: track.notify.source.clone(),
+                img_idx: (track.base + i) as i32 + 1,
+                feature: base64::encode(buf.bytes()),
+                quality: face.quality,
+                capture_time: track.ts,
+                gmt_create: now,
+                gmt_modified: now,
+            })
+        }).collect();
+        sort_features(&mut list, cfg.keep_n);
+        list
+    }
+
+    /// notify 中没有的属性，用质量最高的人脸调用 get_features 补全，失败只记录
+    /// 在保存之后异步执行，只更新仍为空的属性
+    fn fill_attrs_by_api(&self, track: &Track, attrs: &FaceAttrs) {
+        if !self.ctx.cfg.notify_proc.facetrack.attr_by_api || attrs.is_complete() {
+            return;
+        }
+
+        let face = track.notify.faces.iter()
+            .max_by(|a, b| a.quality.partial_cmp(&b.quality).unwrap_or(std::cmp::Ordering::Equal));
+        let face = match face {
+            Some(v) => v,
+            None => return,
+        };
+
+        let ctx = self.ctx.clone();
+        let ft_sid = track.uuid.clone();
+        let has_props = attrs.has_props;
+        let img = base64::encode(face.aligned_buf.bytes());
+        tokio::spawn(async move {
+            let res = match ctx.recg_api.get_features(vec![img], true).await {
+                Ok(v) if v.code == 0 => v,
+                Ok(v) => {
+                    debug!("get_features, {}, code:{}, msg:{}", ft_sid, v.code, v.msg);
+                    return;
+                }
+                Err(e) => {
+                    debug!("get_features, {}, {:?}", ft_sid, e);
+                    return;
+                }
+            };
+
+            let v = match res.attrs.as_ref().and_then(|x| x.first()) {
+                Some(v) => v,
+                None => return,
+            };
+            let props = match has_props {
+                true => None,
+                false => Some((v.gender as i32, v.age as i32, v.glasses as i32, v.direction as i32)),
+            };
+            let (mask, moustache, hat) = (v.mask.map(|x| x as i32), v.moustache.map(|x| x as i32), v.hat.map(|x| x as i32));
+            let ctx_cl = ctx.clone();
+            let ft_sid_cl = ft_sid.clone();
+            let affect = tokio::task::spawn_blocking(move || {
+                ctx_cl.dao.update_facetrack_attrs(&ft_sid_cl, props, mask, moustache, hat)
+            }).await;
+            match affect {
+                Ok(Ok(v)) => debug!("update facetrack attrs, {}, affect:{}", ft_sid, v),
+                Ok(Err(e)) => error!("error, update facetrack attrs, {}, {:?}", ft_sid, e),
+                Err(e) => error!("error, update facetrack attrs, {}, {:?}", ft_sid, e),
+            }
+        });
+    }
+
     /// 保存人脸大图/小图/背景图到文件中
     /// 新建一条facetrack记录，保存到数据库中
     /// 更新 wp
     /// 记录各种操作耗时
     async fn save_track(&self, track: &mut Track) -> AppResult<()> {
+        // 重启后内存中的 track 丢失，重放的通知可能属于已保存的 facetrack
+        let ctx = self.ctx.clone();
+        let ft_sid = track.uuid.clone();
+        let saved_ids = tokio::task::spawn_blocking(move || {
+            ctx.dao.load_facetrack_img_ids(&ft_sid)
+        }).await??;
+        if let Some(ids) = saved_ids {
+            if track.notify.index == 0 {
+                // 新建时的通知，已经保存过
+                debug!("facetrack already saved, sid:{}", track.uuid);
+                track.wp = track.notify.faces.len();
+                return Ok(());
+            }
+            debug!("facetrack already saved, append, sid:{}", track.uuid);
+            track.base = notify_spool::count_img_ids(&ids);
+            track.saved_ids = Some(ids);
+            return self.update_track(track).await;
+        }
+
         let df_imgs = self.ctx.cfg.df_imgs.as_str();
         let track_id = track.uuid.as_str();
 
@@ -238,24 +416,17 @@ impl FaceHandler {
         // 保存背景图
         let fn_bg = img_file::get_facetrack_full_bgpath(df_imgs, track_id);
         // debug!("save bg img file: {:?}", fn_bg);
-        utils::write_jpg_file(fn_bg, track.notify.background.image_buf.bytes()).await?;
+        track.notify.background.write_image(fn_bg).await?;
 
 
         // 保存数据库
-        let mut gender = 0; // 0 不确定; 1 男性; 2 ⼥性
-        let mut age = 0;
-        let mut glasses = 0; // 0 不戴眼镜; 1 墨镜; 2 普通眼镜
-        let mut direction = 0; // 0 未知；1 向上；2 向下
-        if let Some(ref v) = track.notify.props {
-            gender = v.gender;
-            age = v.age;
-            glasses = v.glasses;
-            direction = v.move_direction;
-        }
+        let attrs = FaceAttrs::from_props(track);
         if !img_ids.is_empty() {
             // 去掉最后的 ","
             img_ids.truncate(img_ids.len() - 1);
         }
+        let features = self.best_features(track, now);
+        let feature_ids = join_feature_ids(&features);
 
         let po = CfFacetrack {
             id: 0,
@@ -265,24 +436,29 @@ impl FaceHandler {
             matched: Some(0),
             judged: Some(0),
             alarmed: Some(0),
+            alarm_rule: 0,
+            alarm_level: 0,
             most_person: None,
             most_score: None,
-            gender: Some(gender as i32),
-            age: Some(age as i32),
-            glasses: Some(glasses as i32),
-            direction: Some(direction as i32),
-            plane_score: None,
-            mask: None,
-            moustache: None,
-            hat: None,
+            gender: Some(attrs.gender as i32),
+            age: Some(attrs.age as i32),
+            glasses: Some(attrs.glasses as i32),
+            direction: Some(attrs.direction as i32),
+            plane_score: Some(attrs.plane_score.unwrap_or(-1_f64)),
+            // 未知的属性为 null
+            mask: attrs.mask.map(|x| x as i32),
+            moustache: attrs.moustache.map(|x| x as i32),
+            hat: attrs.hat.map(|x| x as i32),
             tag: None,
             flag: 0,
             db_flag: None,
             db_sid: None,
-            feature_ids: None,
+            feature_ids,
             obj_id: None,
             submit_id: None,
             submit_time: None,
+            start_real_time: track.notify.position.start_real_time,
+            end_real_time: track.notify.position.end_real_time,
             capture_time: track.ts,
             gmt_create: now,
             gmt_modified: now,
@@ -290,12 +466,14 @@ impl FaceHandler {
 
         let ctx = self.ctx.clone();
         let saved = tokio::task::spawn_blocking(move || {
-            ctx.dao.save_facetrack(&po)
+            ctx.dao.save_facetrack(&po, &features)
         }).await?;
 
         let new_id = saved?;
         debug!("saved new facetrack, id:{}, sid:{}", new_id, track_id);
 
+        self.fill_attrs_by_api(track, &attrs);
+
         // 更新 wp
         track.wp = track.notify.faces.len();
 
@@ -310,10 +488,14 @@ impl FaceHandler {
         let df_imgs = self.ctx.cfg.df_imgs.as_str();
         let track_id = track.uuid.as_str();
 
-        let mut img_ids = String::new();
-        let mut img_num = 0;
+        // 重放时接在已保存的图片之后
+        let mut img_ids = match track.saved_ids {
+            Some(ref v) if !v.is_empty() => format!("{},", v),
+            _ => String::new(),
+        };
+        let mut img_num = track.base;
         let now = Local::now();
-        let wp_old = track.wp;
+        let wp_old = track.base + track.wp;
 
         // 准备目录
         let dir = img_file::get_facetrack_imgdir(df_imgs, track_id);
@@ -341,24 +523,17 @@ impl FaceHandler {
         // 保存背景图, 覆盖
         let fn_bg = img_file::get_facetrack_full_bgpath(df_imgs, track_id);
         // debug!("save bg img file: {:?}", fn_bg);
-        utils::write_jpg_file(fn_bg, track.notify.background.image_buf.bytes()).await?;
+        track.notify.background.write_image(fn_bg).await?;
 
 
         // 保存数据库
-        let mut gender = 0; // 0 不确定; 1 男性; 2 ⼥性
-        let mut age = 0;
-        let mut glasses = 0; // 0 不戴眼镜; 1 墨镜; 2 普通眼镜
-        let mut direction = 0; // 0 未知；1 向上；2 向下
-        if let Some(ref v) = track.notify.props {
-            gender = v.gender;
-            age = v.age;
-            glasses = v.glasses;
-            direction = v.move_direction;
-        }
+        let attrs = FaceAttrs::from_props(track);
         if !img_ids.is_empty() {
             // 去掉最后的 ","
             img_ids.truncate(img_ids.len() - 1);
         }
+        let features = self.best_features(track, now);
+        let feature_ids = join_feature_ids(&features);
 
         let po = CfFacetrack {
             id: 0,
@@ -368,32 +543,47 @@ impl FaceHandler {
             matched: None,
             judged: None,
             alarmed: None,
+            alarm_rule: 0,
+            alarm_level: 0,
             most_person: None,
             most_score: None,
-            gender: Some(gender as i32),
-            age: Some(age as i32),
-            glasses: Some(glasses as i32),
-            direction: Some(direction as i32),
-            plane_score: None,
-            mask: None,
-            moustache: None,
-            hat: None,
+            // 没有 props 时保留新建时的属性
+            gender: attrs.has_props.then_some(attrs.gender as i32),
+            age: attrs.has_props.then_some(attrs.age as i32),
+            glasses: attrs.has_props.then_some(attrs.glasses as i32),
+            direction: attrs.has_props.then_some(attrs.direction as i32),
+            plane_score: attrs.plane_score,
+            mask: attrs.mask.map(|x| x as i32),
+            moustache: attrs.moustache.map(|x| x as i32),
+            hat: attrs.hat.map(|x| x as i32),
             tag: None,
             flag: 0,
             db_flag: None,
             db_sid: None,
-            feature_ids: None,
+            feature_ids,
             obj_id: None,
             submit_id: None,
             submit_time: None,
+            start_real_time: track.notify.position.start_real_time,
+            end_real_time: track.notify.position.end_real_time,
             capture_time: track.ts,
             gmt_create: now,
             gmt_modified: now,
         };
 
         let ctx = self.ctx.clone();
-        let affect = tokio::task::spawn_blocking(move || {
-            ctx.dao.upate_facetrack_for_append(&po)
+        let base = track.base;
+        let keep_n = self.ctx.cfg.track_feature.keep_n;
+        let affect = tokio::task::spawn_blocking(move || -> AppResult<usize> {
+            let (mut po, mut features) = (po, features);
+            if base > 0 && !features.is_empty() {
+                // 重放时，与之前保存的特征值一起取质量分最高的
+                let old = ctx.dao.load_facetrack_features(&po.ft_sid)?;
+                features.extend(old.into_iter().filter(|x| x.img_idx as usize <= base));
+                sort_features(&mut features, keep_n);
+                po.feature_ids = join_feature_ids(&features);
+            }
+            Ok(ctx.dao.upate_facetrack_for_append(&po, &features)?)
         }).await?;
 
         let affect = affect?;
@@ -420,12 +610,53 @@ impl FaceHandler {
 
         let source_po = source_po?;
         let qi = FtQI::from_notify(&self.ctx.cfg.dfimg_url, track.ts, &track.notify, &source_po);
-        self.out.push(qi);
+        if let Err(qi) = self.out.push(qi) {
+            return Err(AppError::new(&format!("queue: {} is full, drop {}", self.out.name(), qi.sid)));
+        }
 
         Ok(())
     }
 
     pub async fn process(&self, holder_data: Arc<Mutex<Track>>, events: Vec<TrackEvent>) {
+        // 本次涉及的 spool key
+        let mut spool_keys = {
+            let mut data = holder_data.lock().await;
+            data.spool_keys.drain(..).collect::<Vec<String>>()
+        };
+        for event in events.iter() {
+            if let TrackEvent::APPEND(track) = event {
+                spool_keys.extend(track.spool_keys.iter().cloned());
+            }
+        }
+
+        // 数据库中已保存，删除 spool; 保存失败时保留，下次启动时重放
+        if self.process_events(holder_data, events).await {
+            self.remove_spool(spool_keys).await;
+        } else if !spool_keys.is_empty() {
+            error!("error, FaceHandler process fail, keep spool: {:?}", spool_keys);
+        }
+    }
+
+    async fn remove_spool(&self, keys: Vec<String>) {
+        if keys.is_empty() {
+            return;
+        }
+
+        let ctx = self.ctx.clone();
+        let rst = tokio::task::spawn_blocking(move || {
+            for key in keys.iter() {
+                if let Err(e) = ctx.spool.remove(SPOOL_FACE, key) {
+                    error!("error, FaceHandler remove spool, {}, {:?}", key, e);
+                }
+            }
+        }).await;
+        if let Err(e) = rst {
+            error!("error, FaceHandler remove spool, {:?}", e);
+        }
+    }
+
+    /// 保存/更新成功时返回 true，放入后续队列失败只记录
+    async fn process_events(&self, holder_data: Arc<Mutex<Track>>, events: Vec<TrackEvent>) -> bool {
         let mut newed = false;
         let mut appended = false;
         let mut delayed = false;
@@ -458,7 +689,7 @@ impl FaceHandler {
             if let Err(e) = self.save_track(&mut data).await {
                 error!("error, FaceHandler save_track, {}, {:?}", data.uuid, e);
                 data.invalid = true; //保存失败
-                return;
+                return false;
             } else {
                 debug!("FaceHandler save_track ok, {}", data.uuid);
             }
@@ -468,7 +699,7 @@ impl FaceHandler {
         if !newed && appended {
             if let Err(e) = self.update_track(&mut data).await {
                 error!("error, FaceHandler update_track, {}, {:?}", data.uuid, e);
-                return;
+                return false;
             } else {
                 debug!("FaceHandler update_track ok, {}", data.uuid);
             }
@@ -494,5 +725,6 @@ impl FaceHandler {
 
         // 清除图片及feature内存块
         data.notify.clear_blob(ready_new);
+        true
     }
 }
\ No newline at end of file
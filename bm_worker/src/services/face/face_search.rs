@@ -1,56 +1,73 @@
 use std::sync::Arc;
 use chrono::prelude::*;
-use deadqueue::unlimited::Queue;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use tokio::stream::StreamExt;
 use tokio::sync::mpsc::{UnboundedSender};
 use tokio::sync::watch::Receiver;
 use tokio::task::JoinHandle as TkJoinHandle;
 
-use cffc_base::api::bm_api::{ApiFeatureQuality, RecognitionApi, SearchResPerson};
-use cffc_base::util::utils;
+use cffc_base::api::bm_api::{ApiFeatureQuality, SearchResPerson};
+use cffc_base::api::recg_cluster;
+use cffc_base::util::bounded_queue::BoundedQueue;
 
 use crate::app_ctx::AppCtx;
-use crate::queue_item::{FtQI, FtQIPerson};
+use crate::judge_registry::RegItem;
+use crate::queue_item::{FtQI, FtQICandidate, FtQIPerson, SearchRetry};
 use crate::services::Service;
 
 pub struct FaceSearchWorker {
     num: i64,
     ctx: Arc<AppCtx>,
-    queue: Arc<Queue<FtQI>>,
+    queue: Arc<BoundedQueue<FtQI>>,
 
     skip_search: bool,
 
-    /// sid,name,bw_flag
-    dbs: Vec<(String, String, i32)>,
+    /// sid,name,bw_flag, 每批次从 registry 刷新
+    dbs: Arc<Vec<RegItem>>,
     tx: UnboundedSender<FtQI>,
-    api: RecognitionApi,
+
+    /// 比对失败的 item 放入重试队列
+    retry: Option<Arc<BoundedQueue<FtQI>>>,
 
 }
 
 impl FaceSearchWorker {
-    pub fn new(num: i64, ctx: Arc<AppCtx>, queue: Arc<Queue<FtQI>>, dbs: Vec<(String, String, i32)>, tx: UnboundedSender<FtQI>) -> Self {
-        let api = RecognitionApi::new(ctx.cfg.api.recg_url.as_str());
+    pub fn new(num: i64, ctx: Arc<AppCtx>, queue: Arc<BoundedQueue<FtQI>>, dbs: Arc<Vec<RegItem>>, tx: UnboundedSender<FtQI>,
+               retry: Option<Arc<BoundedQueue<FtQI>>>) -> Self {
         let skip_search = ctx.cfg.notify_proc.skip_search;
 
         FaceSearchWorker {
             num,
             ctx,
-            api,
             queue,
             skip_search,
             dbs,
             tx,
+            retry,
         }
     }
 
     async fn pop_batch(&mut self) -> Vec<FtQI> {
         let max = self.ctx.cfg.notify_proc.search_batch as usize;
-        utils::pop_queue_batch(&self.queue, max).await
+        self.queue.pop_batch(max).await
     }
 
 
-    fn find_db(&self, db_sid: &str) -> Option<(String, String, i32)> {
+    /// 人脸库有修改时，重新加载
+    async fn refresh_dbs(&mut self) {
+        if !self.ctx.registry.dbs.is_stale(Local::now()) {
+            self.dbs = self.ctx.registry.dbs.current();
+            return;
+        }
+
+        let ctx = self.ctx.clone();
+        match tokio::task::spawn_blocking(move || ctx.registry.get_dbs(&ctx.dao)).await {
+            Ok(v) => self.dbs = v,
+            Err(e) => error!("error, FaceSearchWorker[{}], refresh_dbs, {:?}", self.num, e),
+        }
+    }
+
+    fn find_db(&self, db_sid: &str) -> Option<RegItem> {
         self.dbs.iter().find(|&x| { x.0.eq(db_sid) }).map(|x| {
             (x.0.clone(), x.1.clone(), x.2)
         })
@@ -77,6 +94,20 @@ impl FaceSearchWorker {
             // 设置 已经执行match
             item.face.matched = true;
 
+            // top-N 候选，姓名在 FaceJudgeSvc 中填充
+            item.candidates = persons.get(i).unwrap().iter().enumerate().filter_map(|(rank, x)| {
+                self.find_db(x.db.as_str()).map(|db| FtQICandidate {
+                    rank: rank as i64 + 1,
+                    sid: x.id.clone(),
+                    name: "".to_string(),
+                    cover_url: "".to_string(),
+                    score: x.score,
+                    db_sid: db.0,
+                    db_name: db.1,
+                    bw_flag: db.2 as i64,
+                })
+            }).collect();
+
             // 取 top 1
             let person = match persons.get(i).unwrap().get(0) {
                 Some(v) => v,
@@ -90,6 +121,7 @@ impl FaceSearchWorker {
                 Some(v) => v,
                 None => {
                     error!("error, FaceSearchWorker[{}], can't find db:{} in cache", self.num, person.db);
+                    self.ctx.registry.invalidate_dbs();
                     continue;
                 }
             };
@@ -114,41 +146,106 @@ impl FaceSearchWorker {
         }
     }
 
+    fn use_fallback(&self) -> bool {
+        self.ctx.cfg.feature_index.enable
+    }
+
+    /// 本地特征索引比对，结果标记为 fallback
+    /// 本地没有 dbs 的特征值时返回 false
+    async fn search_local(&self, items: &mut Vec<FtQI>, dbs: &[String], persons: &[Vec<ApiFeatureQuality>]) -> bool {
+        if self.ctx.feature_index.count(dbs) == 0 {
+            debug!("FaceSearchWorker[{}], feature index is empty", self.num);
+            return false;
+        }
+
+        // 暴力比对，在阻塞线程中执行
+        let ts_start = Local::now();
+        let ctx = self.ctx.clone();
+        let dbs = dbs.to_vec();
+        let persons = persons.to_vec();
+        let res = tokio::task::spawn_blocking(move || {
+            let top = ctx.cfg.candidate.top_n.max(1) as usize;
+            ctx.feature_index.search(&dbs, top, ctx.cfg.candidate.min_score, &persons)
+        }).await;
+        let res = match res {
+            Ok(v) => v,
+            Err(e) => {
+                error!("error, FaceSearchWorker[{}], search by feature index, {:?}", self.num, e);
+                return false;
+            }
+        };
+        let ts_use = Local::now().signed_duration_since(ts_start);
+        warn!("warn, FaceSearchWorker[{}], search by feature index, use: {} ms, batch size:{}", self.num, ts_use.num_milliseconds(), items.len());
+
+        self.fill_with_matchinfo(items, &Some(res));
+        items.iter_mut().for_each(|x| x.face.fallback = true);
+        true
+    }
+
+    /// 比对失败，带着特征值放入重试队列
+    fn park_retry(&self, items: &[FtQI]) {
+        let queue = match self.retry {
+            Some(ref v) => v,
+            None => return,
+        };
+
+        let now = Local::now();
+        for x in items.iter() {
+            let mut item = x.clone();
+            item.retry = Some(match x.retry {
+                Some(ref v) => SearchRetry {
+                    attempts: v.attempts + 1,
+                    first_ts: v.first_ts,
+                },
+                None => SearchRetry {
+                    attempts: 1,
+                    first_ts: now,
+                },
+            });
+
+            if let Err(v) = queue.push(item) {
+                error!("error, FaceSearchWorker[{}], queue: {} is full, drop {}", self.num, queue.name(), v.sid);
+            }
+        }
+    }
+
     /// api 比对搜索，(有特征值, 并且dbs不为空)
     /// 无论处理成功或失败，都提交到mpsc中
-    async fn process_batch(&mut self, mut items: Vec<FtQI>) {
-        let tops = vec![1_i64];
-        let thresholds = vec![0_i64];
+    /// 比对失败的放入重试队列，重试的 item 只在比中时提交(delayed)
+    async fn process_batch(&mut self, items: Vec<FtQI>) {
+        self.refresh_dbs().await;
+
+        let tops = vec![self.ctx.cfg.candidate.top_n.max(1)];
+        let thresholds = vec![self.ctx.cfg.candidate.min_score];
         let dbs: Vec<String> = self.dbs.iter().map(|x| x.0.clone()).collect();
-        let mut persons = Vec::new();
 
         debug!("FaceSearchWorker[{}], process_batch: {}", self.num, items.len());
 
-        items.iter_mut().for_each(|x| {
-            let mut feas = Vec::new();
-            x.face.faces.iter_mut().for_each(|f| {
-                if let Some(ref feature) = f.feature {
-                    feas.push(ApiFeatureQuality {
-                        feature: feature.clone(),
-                        quality: f.quality,
-                    });
-                }
-                // 清除 feature
-                f.feature.take();
-            });
-
-            if !feas.is_empty() {
-                // 不为空，才加入到 最后的比对中
-                persons.push(feas);
-            }
-        });
+        // 只比对有特征值的 item, 其余的直接提交
+        let (mut items, rest): (Vec<FtQI>, Vec<FtQI>) = items.into_iter()
+            .partition(|x| x.face.faces.iter().any(|f| f.feature.is_some()));
+        let persons: Vec<Vec<ApiFeatureQuality>> = items.iter().map(|x| {
+            x.face.faces.iter().filter_map(|f| {
+                f.feature.as_ref().map(|feature| ApiFeatureQuality {
+                    feature: feature.clone(),
+                    quality: f.quality,
+                })
+            }).collect()
+        }).collect();
+        if !rest.is_empty() {
+            debug!("FaceSearchWorker[{}], {} items has no feature", self.num, rest.len());
+        }
 
-        if self.skip_search || dbs.is_empty() || persons.len() != items.len() {
-            // dbs 为空，或者 items中存在没有特征值的item，则不进行比对
+        let mut failed = false;
+        if self.skip_search || dbs.is_empty() || items.is_empty() {
+            // dbs 为空，或者没有特征值，则不进行比对
             debug!("FaceSearchWorker[{}], skip search", self.num);
+        } else if self.use_fallback() && !self.ctx.recg_api.has_available() {
+            // 识别节点全部熔断
+            failed = !self.search_local(&mut items, &dbs, &persons).await;
         } else {
             let ts_start = Local::now();
-            let search_res = self.api.search(dbs, tops, thresholds, persons).await;
+            let search_res = self.ctx.recg_api.search(dbs.clone(), tops, thresholds, persons.clone()).await;
             let ts_use = Local::now().signed_duration_since(ts_start);
             debug!("FaceSearchWorker[{}], search api use: {} ms, batch size:{}", self.num, ts_use.num_milliseconds(), items.len());
 
@@ -159,16 +256,37 @@ impl FaceSearchWorker {
                         self.fill_with_matchinfo(&mut items, &res.persons);
                     } else {
                         error!("error, FaceSearchWorker[{}], search, code:{}, msg:{}", self.num, res.code, res.msg);
+                        failed = true;
                     }
                 }
                 Err(e) => {
                     error!("error, FaceSearchWorker[{}], search, {:?}", self.num, e);
+                    failed = !(self.use_fallback() && recg_cluster::is_node_err(&e) && self.search_local(&mut items, &dbs, &persons).await);
                 }
             }
         }
 
+        if failed {
+            self.park_retry(&items);
+        }
+
         // 放入mpsc中
-        for v in items {
+        for mut v in items.into_iter().chain(rest) {
+            // 清除 feature
+            v.face.faces.iter_mut().for_each(|f| {
+                f.feature.take();
+            });
+
+            if v.retry.is_some() {
+                // 第一次比对时已经提交过
+                if failed || v.match_poi.is_none() {
+                    debug!("FaceSearchWorker[{}], retry facetrack:{}, no match", self.num, v.sid);
+                    continue;
+                }
+                v.face.delayed = true;
+                info!("FaceSearchWorker[{}], retry facetrack:{}, delayed match", self.num, v.sid);
+            }
+
             match self.tx.send(v) {
                 Ok(_) => {}
                 Err(e) => {
@@ -0,0 +1,133 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::prelude::*;
+use log::{debug, error, info};
+use tokio::stream::StreamExt;
+use tokio::sync::watch::Receiver;
+use tokio::task::JoinHandle as TkJoinHandle;
+use tokio::time;
+
+use crate::app_ctx::AppCtx;
+use crate::dao::model::CfPoiFeature;
+use crate::error::AppResult;
+use crate::services::Service;
+
+/// 本地特征索引的维护
+/// 启动时从数据库加载；定时删除已不存在 poi 的特征值，
+/// 对本地没有特征值的 poi (bm_imp 导入等)，通过 get_person_info 补全
+pub struct FeatureSyncSvc {
+    ctx: Arc<AppCtx>,
+    dur: Duration,
+    loaded: bool,
+    /// 识别模块中没有特征值的 poi，不再重复查询
+    empty: HashSet<String>,
+}
+
+impl FeatureSyncSvc {
+    pub fn new(ctx: Arc<AppCtx>) -> Self {
+        let dur = Duration::from_secs(ctx.cfg.feature_index.sync_sec.max(1));
+        FeatureSyncSvc {
+            ctx,
+            dur,
+            loaded: false,
+            empty: HashSet::new(),
+        }
+    }
+
+    async fn do_sync(&mut self) -> AppResult<()> {
+        let ctx = self.ctx.clone();
+        let orphan = tokio::task::spawn_blocking(move || {
+            ctx.dao.delete_orphan_poi_features()
+        }).await??;
+
+        if !self.loaded || orphan > 0 {
+            debug!("FeatureSyncSvc, delete orphan features: {}", orphan);
+            let ctx = self.ctx.clone();
+            tokio::task::spawn_blocking(move || {
+                ctx.feature_index.reload(&ctx.dao)
+            }).await??;
+            self.loaded = true;
+        }
+
+        let ctx = self.ctx.clone();
+        let limit = self.ctx.cfg.feature_index.sync_batch + self.empty.len() as i64;
+        let pois = tokio::task::spawn_blocking(move || {
+            ctx.dao.load_pois_without_feature(limit)
+        }).await??;
+
+        let mut count = 0;
+        for (poi_sid, db_sid) in pois {
+            if self.empty.contains(&poi_sid) {
+                continue;
+            }
+
+            // 单个 poi 失败时跳过，下次再补全
+            let res = match self.ctx.recg_api.get_person_info(db_sid.clone(), poi_sid.clone()).await {
+                Ok(v) => v,
+                Err(e) => {
+                    error!("error, FeatureSyncSvc, get_person_info:{}, {:?}", poi_sid, e);
+                    continue;
+                }
+            };
+            if res.code != 0 {
+                error!("error, FeatureSyncSvc, get_person_info:{}, code:{}, msg:{}", poi_sid, res.code, res.msg);
+                continue;
+            }
+
+            let now = Local::now();
+            let list: Vec<CfPoiFeature> = res.faces.unwrap_or_default().into_iter().map(|x| CfPoiFeature {
+                id: 0,
+                poi_sid: poi_sid.clone(),
+                db_sid: db_sid.clone(),
+                face_id: x.id,
+                feature: x.feature,
+                quality: x.quality,
+                gmt_create: now,
+                gmt_modified: now,
+            }).collect();
+            if list.is_empty() {
+                self.empty.insert(poi_sid);
+                continue;
+            }
+
+            let ctx = self.ctx.clone();
+            count += tokio::task::spawn_blocking(move || {
+                ctx.feature_index.save(&ctx.dao, &list)
+            }).await??;
+        }
+
+        if count > 0 {
+            info!("FeatureSyncSvc, sync features: {}", count);
+        }
+        Ok(())
+    }
+}
+
+impl Service for FeatureSyncSvc {
+    fn run(self, rx: Receiver<i64>) -> TkJoinHandle<()> {
+        let mut svc = self;
+        let mut exit_rx = rx;
+
+        tokio::spawn(async move {
+            let mut interval = time::interval(svc.dur);
+            loop {
+                tokio::select! {
+                    quit = exit_rx.next() => {
+                        if let Some(100) = quit {
+                            info!("FeatureSyncSvc recv exit");
+                            break;
+                        }
+                    }
+                    _ = interval.tick() => {
+                        if let Err(e) = svc.do_sync().await {
+                            error!("error, FeatureSyncSvc, do_sync, {:?}", e);
+                        }
+                    }
+                }
+            }
+            info!("FeatureSyncSvc exit");
+        })
+    }
+}
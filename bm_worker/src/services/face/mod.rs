@@ -1,4 +1,8 @@
 pub mod face_notify;
 pub mod face_judge;
 pub mod spool_async;
-pub mod face_search;
\ No newline at end of file
+pub mod face_search;
+pub mod search_retry;
+pub mod feature_sync;
+pub mod retro_scan;
+pub mod stranger;
@@ -0,0 +1,275 @@
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+
+use chrono::prelude::*;
+use chrono::Duration;
+use log::{debug, error, info, warn};
+use tokio::stream::StreamExt;
+use tokio::sync::watch::Receiver;
+use tokio::task::JoinHandle as TkJoinHandle;
+use tokio::time;
+
+use cffc_base::api::bm_api::ApiFeatureQuality;
+
+use crate::app_ctx::AppCtx;
+use crate::dao::model::{CfFacetrackFeature, CfRetroAlarm, CfRetroJob};
+use crate::error::{AppError, AppResult};
+use crate::feature_index;
+use crate::services::Service;
+
+/// 回溯任务状态
+pub const RETRO_STATE_PENDING: i32 = 0;
+pub const RETRO_STATE_RUNNING: i32 = 1;
+pub const RETRO_STATE_DONE: i32 = 2;
+pub const RETRO_STATE_FAILED: i32 = 3;
+
+/// 新建等待中的任务，回溯最近 days 天的抓拍
+pub fn new_job(poi_sid: &str, db_sid: &str, poi_name: &str, threshold: i32, days: i64) -> CfRetroJob {
+    let now = Local::now();
+    CfRetroJob {
+        id: 0,
+        poi_sid: poi_sid.to_string(),
+        db_sid: db_sid.to_string(),
+        poi_name: Some(poi_name.to_string()),
+        threshold,
+        time_start: now - Duration::days(days),
+        time_end: now,
+        state: RETRO_STATE_PENDING,
+        total: 0,
+        scanned: 0,
+        hits: 0,
+        msg: None,
+        gmt_create: now,
+        gmt_modified: now,
+    }
+}
+
+/// 按 facetrack 分组，page 按 id 升序或降序，同一 facetrack 的特征值是连续的
+pub fn group_by_track(page: &[CfFacetrackFeature]) -> Vec<Vec<&CfFacetrackFeature>> {
+    let mut tracks: Vec<Vec<&CfFacetrackFeature>> = Vec::new();
+    for v in page.iter() {
+        match tracks.last_mut() {
+            Some(last) if last[0].ft_sid == v.ft_sid => last.push(v),
+            _ => tracks.push(vec![v]),
+        }
+    }
+    tracks
+}
+
+/// 每个 facetrack 的分值，按识别模块的分值尺度
+/// use_api 时优先使用 compare_n，识别节点不可用或失败时本地计算，本地分值经 FeatureIndex 映射
+pub async fn score_tracks(ctx: &Arc<AppCtx>, use_api: bool, feas: &[ApiFeatureQuality], tracks: &[Vec<&CfFacetrackFeature>]) -> AppResult<Vec<i64>> {
+    if use_api && ctx.recg_api.has_available() {
+        let b = tracks.iter().map(|x| x.iter().map(|f| ApiFeatureQuality {
+            feature: f.feature.clone(),
+            quality: f.quality,
+        }).collect()).collect();
+
+        match ctx.recg_api.compare_n(feas.to_vec(), b).await {
+            Ok(res) if res.code == 0 => match res.scores {
+                Some(v) if v.len() == tracks.len() => return Ok(v),
+                _ => warn!("warn, score_tracks, compare_n, scores not match"),
+            },
+            Ok(res) => warn!("warn, score_tracks, compare_n, code:{}, msg:{}", res.code, res.msg),
+            Err(e) => warn!("warn, score_tracks, compare_n, {:?}", e),
+        }
+    }
+
+    // 本地暴力比对，在阻塞线程中执行
+    let ctx = ctx.clone();
+    let queries: Vec<String> = feas.iter().map(|x| x.feature.clone()).collect();
+    let targets: Vec<Vec<String>> = tracks.iter().map(|x| x.iter().map(|f| f.feature.clone()).collect()).collect();
+    let scores = tokio::task::spawn_blocking(move || {
+        let queries: Vec<Vec<f32>> = queries.iter().filter_map(|x| feature_index::decode_feature(x)).collect();
+        targets.iter().map(|x| {
+            let targets: Vec<Vec<f32>> = x.iter().filter_map(|f| feature_index::decode_feature(f)).collect();
+            ctx.feature_index.api_score(&queries, &targets).unwrap_or(0)
+        }).collect()
+    }).await?;
+    Ok(scores)
+}
+
+/// 新增 poi 后，与保存的历史 facetrack 特征值比对
+/// 命中的记录到 cf_retro_alarm
+pub struct RetroScanSvc {
+    ctx: Arc<AppCtx>,
+    dur: StdDuration,
+}
+
+impl RetroScanSvc {
+    pub fn new(ctx: Arc<AppCtx>) -> Self {
+        let dur = StdDuration::from_secs(ctx.cfg.retro_scan.poll_sec.max(1));
+        RetroScanSvc {
+            ctx,
+            dur,
+        }
+    }
+
+    async fn save_job(&self, job: &CfRetroJob) -> AppResult<()> {
+        let ctx = self.ctx.clone();
+        let po = job.clone();
+        tokio::task::spawn_blocking(move || {
+            ctx.dao.update_retro_job(&po)
+        }).await??;
+        Ok(())
+    }
+
+    /// poi 的特征值，本地没有时从识别模块获取
+    async fn load_poi_features(&self, job: &CfRetroJob) -> AppResult<Vec<ApiFeatureQuality>> {
+        let ctx = self.ctx.clone();
+        let poi_sid = job.poi_sid.clone();
+        let list = tokio::task::spawn_blocking(move || {
+            ctx.dao.load_poi_features_by_sid(&poi_sid)
+        }).await??;
+        if !list.is_empty() {
+            return Ok(list.into_iter().map(|x| ApiFeatureQuality {
+                feature: x.feature,
+                quality: x.quality,
+            }).collect());
+        }
+
+        let res = self.ctx.recg_api.get_person_info(job.db_sid.clone(), job.poi_sid.clone()).await?;
+        if res.code != 0 {
+            return Err(AppError::new(&format!("get_person_info, code:{}, msg:{}", res.code, res.msg)));
+        }
+        Ok(res.faces.unwrap_or_default().into_iter().map(|x| ApiFeatureQuality {
+            feature: x.feature,
+            quality: x.quality,
+        }).collect())
+    }
+
+    /// 退出时中断，返回 false
+    async fn run_job(&self, job: &mut CfRetroJob) -> AppResult<bool> {
+        let ctx = self.ctx.clone();
+        let (start, end) = (job.time_start, job.time_end);
+        job.total = tokio::task::spawn_blocking(move || {
+            ctx.dao.count_facetrack_features(&start, &end)
+        }).await??;
+        job.state = RETRO_STATE_RUNNING;
+        job.scanned = 0;
+        job.hits = 0;
+        job.gmt_modified = Local::now();
+        self.save_job(job).await?;
+
+        let poi_feas = self.load_poi_features(job).await?;
+        if poi_feas.is_empty() {
+            return Err(AppError::new("poi has no feature"));
+        }
+
+        let limit = self.ctx.cfg.retro_scan.batch.max(1);
+        let mut after_id = 0;
+        loop {
+            // 每页之间检查退出
+            if *self.ctx.exit_rx.borrow() == 100 {
+                return Ok(false);
+            }
+
+            let ctx = self.ctx.clone();
+            let page = tokio::task::spawn_blocking(move || {
+                ctx.dao.load_facetrack_features_page(None, &start, &end, after_id, limit)
+            }).await??;
+            if page.is_empty() {
+                break;
+            }
+            after_id = page.last().unwrap().id;
+
+            // 跨页的 facetrack 保存时取最高分
+            let tracks = group_by_track(&page);
+            let scores = score_tracks(&self.ctx, self.ctx.cfg.retro_scan.use_api, &poi_feas, &tracks).await?;
+            let now = Local::now();
+            let hits: Vec<CfRetroAlarm> = tracks.iter().zip(scores.iter())
+                .filter(|(_, score)| **score >= job.threshold as i64)
+                .map(|(x, score)| CfRetroAlarm {
+                    id: 0,
+                    job_id: job.id,
+                    poi_sid: job.poi_sid.clone(),
+                    ft_sid: x[0].ft_sid.clone(),
+                    src_sid: x[0].src_sid.clone(),
+                    img_idx: x[0].img_idx,
+                    score: *score as f64,
+                    capture_time: x[0].capture_time,
+                    gmt_create: now,
+                    gmt_modified: now,
+                }).collect();
+
+            let ctx = self.ctx.clone();
+            let job_id = job.id;
+            job.hits = tokio::task::spawn_blocking(move || -> AppResult<i64> {
+                ctx.dao.save_retro_alarms(&hits)?;
+                Ok(ctx.dao.count_retro_alarms(job_id)?)
+            }).await??;
+            job.scanned += page.len() as i64;
+            job.gmt_modified = now;
+            self.save_job(job).await?;
+            debug!("RetroScanSvc, job:{}, scanned:{}/{}, hits:{}", job.id, job.scanned, job.total, job.hits);
+        }
+
+        Ok(true)
+    }
+
+    async fn poll(&self) -> AppResult<()> {
+        let ctx = self.ctx.clone();
+        let job = tokio::task::spawn_blocking(move || {
+            ctx.dao.load_pending_retro_job()
+        }).await??;
+        let mut job = match job {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+
+        info!("RetroScanSvc, start job:{}, poi:{}", job.id, job.poi_sid);
+        match self.run_job(&mut job).await {
+            Ok(false) => {
+                // 保持 running 状态，重启后重新执行
+                info!("RetroScanSvc, job:{} interrupted, scanned:{}/{}", job.id, job.scanned, job.total);
+                return Ok(());
+            }
+            Ok(true) => {
+                job.state = RETRO_STATE_DONE;
+                info!("RetroScanSvc, job:{} done, scanned:{}, hits:{}", job.id, job.scanned, job.hits);
+            }
+            Err(e) => {
+                error!("error, RetroScanSvc, job:{}, {:?}", job.id, e);
+                job.state = RETRO_STATE_FAILED;
+                job.msg = Some(format!("{:?}", e).chars().take(200).collect());
+            }
+        }
+        job.gmt_modified = Local::now();
+        self.save_job(&job).await
+    }
+}
+
+impl Service for RetroScanSvc {
+    fn run(self, rx: Receiver<i64>) -> TkJoinHandle<()> {
+        let svc = self;
+        let mut exit_rx = rx;
+
+        tokio::spawn(async move {
+            // 中断的任务重新执行
+            let ctx = svc.ctx.clone();
+            match tokio::task::spawn_blocking(move || ctx.dao.reset_running_retro_jobs()).await {
+                Ok(Ok(v)) if v > 0 => info!("RetroScanSvc, reset {} running jobs", v),
+                Ok(Err(e)) => error!("error, RetroScanSvc, reset_running_retro_jobs, {:?}", e),
+                _ => {}
+            }
+
+            let mut interval = time::interval(svc.dur);
+            loop {
+                tokio::select! {
+                    quit = exit_rx.next() => {
+                        if let Some(100) = quit {
+                            info!("RetroScanSvc recv exit");
+                            break;
+                        }
+                    }
+                    _ = interval.tick() => {
+                        if let Err(e) = svc.poll().await {
+                            error!("error, RetroScanSvc, poll, {:?}", e);
+                        }
+                    }
+                }
+            }
+            info!("RetroScanSvc exit");
+        })
+    }
+}
@@ -0,0 +1,150 @@
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+
+use chrono::prelude::*;
+use chrono::Duration;
+use log::{debug, info, warn};
+use tokio::stream::StreamExt;
+use tokio::sync::watch::Receiver;
+use tokio::task::JoinHandle as TkJoinHandle;
+use tokio::time;
+
+use cffc_base::util::bounded_queue::BoundedQueue;
+
+use crate::app_cfg::AppCfgSearchRetry;
+use crate::app_ctx::AppCtx;
+use crate::queue_item::FtQI;
+use crate::services::Service;
+
+/// 第 n 次失败，等待 base * 2^(n-1)
+fn get_backoff(cfg: &AppCfgSearchRetry, attempts: u32) -> Duration {
+    let shift = attempts.max(1).min(20) - 1;
+    let ms = cfg.base_ms.saturating_mul(1 << shift).min(cfg.max_backoff_ms);
+    Duration::milliseconds(ms as i64)
+}
+
+/// 到期时距第一次失败超过 max_age_sec
+fn is_too_old(cfg: &AppCfgSearchRetry, first_ts: DateTime<Local>, due: DateTime<Local>) -> bool {
+    due.signed_duration_since(first_ts) > Duration::seconds(cfg.max_age_sec)
+}
+
+/// 比对失败的重试
+/// FaceSearchWorker 比对失败的 item 放入 queue，按指数退避等待后，重新放入比对队列
+/// 超过 max_age_sec 的丢弃，等待的 item 超过 queue_cap 时丢弃最早的
+pub struct SearchRetrySvc {
+    ctx: Arc<AppCtx>,
+    queue: Arc<BoundedQueue<FtQI>>,
+    out: Arc<BoundedQueue<FtQI>>,
+
+    /// (到期时间, item), 按放入的先后
+    waiting: Vec<(DateTime<Local>, FtQI)>,
+    /// waiting 满时丢弃的数量
+    dropped: u64,
+}
+
+impl SearchRetrySvc {
+    pub fn new(ctx: Arc<AppCtx>, queue: Arc<BoundedQueue<FtQI>>, out: Arc<BoundedQueue<FtQI>>) -> Self {
+        SearchRetrySvc {
+            ctx,
+            queue,
+            out,
+            waiting: Vec::new(),
+            dropped: 0,
+        }
+    }
+
+    fn park(&mut self, item: FtQI) {
+        let now = Local::now();
+        let (attempts, first_ts) = item.retry.as_ref().map_or((1, now), |x| (x.attempts, x.first_ts));
+
+        let cfg = &self.ctx.cfg.search_retry;
+        let due = now + get_backoff(cfg, attempts);
+        if is_too_old(cfg, first_ts, due) {
+            warn!("warn, SearchRetrySvc, facetrack:{}, attempts:{}, too old, drop", item.sid, attempts);
+            return;
+        }
+
+        if self.waiting.len() >= cfg.queue_cap.max(1) {
+            let (_, oldest) = self.waiting.remove(0);
+            self.dropped += 1;
+            warn!("warn, SearchRetrySvc, waiting is full, drop facetrack:{}, dropped:{}", oldest.sid, self.dropped);
+        }
+
+        debug!("SearchRetrySvc, facetrack:{}, attempts:{}, retry at {}", item.sid, attempts, due);
+        self.waiting.push((due, item));
+    }
+
+    /// 到期的放回比对队列，队列满时等下一次
+    fn flush_due(&mut self) {
+        if self.waiting.is_empty() {
+            return;
+        }
+
+        let now = Local::now();
+        let waiting = std::mem::take(&mut self.waiting);
+        for (due, item) in waiting {
+            if due > now {
+                self.waiting.push((due, item));
+                continue;
+            }
+
+            if let Err(item) = self.out.push(item) {
+                debug!("SearchRetrySvc, queue: {} is full, wait", self.out.name());
+                self.waiting.push((due, item));
+            }
+        }
+    }
+}
+
+impl Service for SearchRetrySvc {
+    fn run(self, rx: Receiver<i64>) -> TkJoinHandle<()> {
+        let mut svc = self;
+        let mut exit_rx = rx;
+
+        tokio::spawn(async move {
+            let mut interval = time::interval(StdDuration::from_millis(200));
+            loop {
+                tokio::select! {
+                    quit = exit_rx.next() => {
+                        if let Some(100) = quit {
+                            info!("SearchRetrySvc recv exit");
+                            break;
+                        }
+                    }
+                    item = svc.queue.pop() => {
+                        svc.park(item);
+                    }
+                    _ = interval.tick() => {
+                        svc.flush_due();
+                    }
+                }
+            }
+            info!("SearchRetrySvc exit, {} items waiting, {} dropped", svc.waiting.len(), svc.dropped);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff() {
+        let cfg = AppCfgSearchRetry::default();
+        assert_eq!(get_backoff(&cfg, 0), Duration::milliseconds(1000));
+        assert_eq!(get_backoff(&cfg, 1), Duration::milliseconds(1000));
+        assert_eq!(get_backoff(&cfg, 2), Duration::milliseconds(2000));
+        assert_eq!(get_backoff(&cfg, 6), Duration::milliseconds(32000));
+        // 不超过 max_backoff_ms
+        assert_eq!(get_backoff(&cfg, 7), Duration::milliseconds(60000));
+        assert_eq!(get_backoff(&cfg, 100), Duration::milliseconds(60000));
+    }
+
+    #[test]
+    fn max_age() {
+        let cfg = AppCfgSearchRetry::default();
+        let first_ts = Local::now();
+        assert!(!is_too_old(&cfg, first_ts, first_ts + Duration::seconds(600)));
+        assert!(is_too_old(&cfg, first_ts, first_ts + Duration::seconds(601)));
+    }
+}
@@ -0,0 +1,312 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+
+use chrono::prelude::*;
+use chrono::Duration;
+use log::{debug, error, info, warn};
+use tokio::stream::StreamExt;
+use tokio::sync::watch::Receiver;
+use tokio::task::JoinHandle as TkJoinHandle;
+use tokio::time;
+use uuid::Uuid;
+
+use cffc_base::util::bounded_queue::BoundedQueue;
+
+use crate::app_ctx::AppCtx;
+use crate::dao::model::{CfStranger, CfStrangerAlarm, CfStrangerTrack};
+use crate::error::AppResult;
+use crate::feature_index;
+use crate::queue_item::{FtQI, QI, StrangerQI};
+use crate::services::Service;
+
+struct Cluster {
+    po: CfStranger,
+    /// 代表特征值，已归一化
+    vecs: Arc<Vec<Vec<f32>>>,
+    /// 时间窗口内出现的抓拍时间
+    recent: VecDeque<DateTime<Local>>,
+}
+
+impl Cluster {
+    fn from_po(po: CfStranger) -> Self {
+        let vecs = po.features.split(',').filter_map(feature_index::decode_feature).collect();
+        Cluster {
+            po,
+            vecs: Arc::new(vecs),
+            recent: VecDeque::new(),
+        }
+    }
+}
+
+/// 陌生人聚类
+/// 订阅 EntBusSvc，已比对但未识别的 facetrack，按特征值相似度归入匿名聚类 (cf_stranger)
+/// 时间窗口内出现次数达到阈值时，记录到 cf_stranger_alarm 并发布到 EntBusSvc
+pub struct StrangerSvc {
+    ctx: Arc<AppCtx>,
+    queue: Arc<BoundedQueue<QI>>,
+    out: Arc<BoundedQueue<QI>>,
+    clusters: Vec<Cluster>,
+}
+
+impl StrangerSvc {
+    pub fn new(ctx: Arc<AppCtx>, queue: Arc<BoundedQueue<QI>>, out: Arc<BoundedQueue<QI>>) -> Self {
+        StrangerSvc {
+            ctx,
+            queue,
+            out,
+            clusters: Vec::new(),
+        }
+    }
+
+    fn get_window(&self) -> Duration {
+        Duration::minutes(self.ctx.cfg.stranger.window_min.max(1))
+    }
+
+    /// 加载最近活跃的聚类，及时间窗口内的出现记录
+    async fn load(&mut self) -> AppResult<()> {
+        let ctx = self.ctx.clone();
+        let now = Local::now();
+        let keep_since = now - Duration::days(self.ctx.cfg.stranger.keep_days.max(1));
+        let window_since = now - self.get_window();
+        let (list, tracks) = tokio::task::spawn_blocking(move || -> AppResult<_> {
+            let list = ctx.dao.load_recent_strangers(&keep_since)?;
+            let tracks = ctx.dao.load_stranger_tracks_since(&window_since)?;
+            Ok((list, tracks))
+        }).await??;
+
+        let mut recent: HashMap<String, VecDeque<DateTime<Local>>> = HashMap::new();
+        for v in tracks {
+            recent.entry(v.cluster_sid).or_insert_with(VecDeque::new).push_back(v.capture_time);
+        }
+
+        self.clusters = list.into_iter().map(|po| {
+            let mut cluster = Cluster::from_po(po);
+            cluster.recent = recent.remove(&cluster.po.cluster_sid).unwrap_or_default();
+            cluster
+        }).collect();
+        info!("StrangerSvc, load clusters: {}", self.clusters.len());
+        Ok(())
+    }
+
+    /// 超过 keep_days 未出现的聚类不再参与
+    fn prune(&mut self) {
+        let since = Local::now() - Duration::days(self.ctx.cfg.stranger.keep_days.max(1));
+        let before = self.clusters.len();
+        self.clusters.retain(|x| x.po.last_time >= since);
+        if self.clusters.len() != before {
+            debug!("StrangerSvc, prune clusters: {} -> {}", before, self.clusters.len());
+        }
+    }
+
+    /// 已比对但未识别，且在参与的摄像头中
+    fn is_stranger(&self, item: &FtQI) -> bool {
+        let src_sids = &self.ctx.cfg.stranger.src_sids;
+        item.face.matched && !item.face.judged
+            && (src_sids.is_empty() || src_sids.iter().any(|x| x.eq_ignore_ascii_case(&item.face.source)))
+    }
+
+    async fn process_item(&mut self, item: QI) {
+        let item = match item {
+            QI::FT(v) => v,
+            _ => return,
+        };
+        if !self.is_stranger(&item) {
+            return;
+        }
+
+        if let Err(e) = self.do_process(&item).await {
+            error!("error, StrangerSvc, {}, {:?}", item.sid, e);
+        }
+    }
+
+    async fn do_process(&mut self, item: &FtQI) -> AppResult<()> {
+        let cfg = &self.ctx.cfg.stranger;
+
+        // 比对后 facetrack 上的特征值已清除，从 cf_facetrack_feature 读取 (已按质量降序)
+        // 与所有聚类的比较放在 blocking 线程中
+        let ctx = self.ctx.clone();
+        let sid = item.sid.clone();
+        let keep = cfg.keep_features.max(1);
+        let targets: Vec<Arc<Vec<Vec<f32>>>> = self.clusters.iter().map(|x| x.vecs.clone()).collect();
+        let (feas, best) = tokio::task::spawn_blocking(move || -> AppResult<_> {
+            let feas: Vec<(String, Vec<f32>)> = ctx.dao.load_facetrack_features(&sid)?.into_iter()
+                .filter_map(|x| feature_index::decode_feature(&x.feature).map(|v| (x.feature, v)))
+                .take(keep)
+                .collect();
+            let queries: Vec<Vec<f32>> = feas.iter().map(|x| x.1.clone()).collect();
+            let best = targets.iter().enumerate()
+                .filter_map(|(i, c)| feature_index::best_score(&queries, c).map(|s| (i, s)))
+                .max_by_key(|x| x.1);
+            Ok((feas, best))
+        }).await??;
+        if feas.is_empty() {
+            debug!("StrangerSvc, {} has no feature", item.sid);
+            return Ok(());
+        }
+
+        let now = Local::now();
+        let ts = item.face.ts;
+        let (idx, score) = match best {
+            Some((i, s)) if s >= cfg.threshold => (Some(i), s),
+            _ => (None, 100),
+        };
+
+        let mut po = match idx {
+            Some(i) => {
+                let mut po = self.clusters[i].po.clone();
+                po.appear_count += 1;
+                po.last_time = po.last_time.max(ts);
+                po
+            }
+            None => CfStranger {
+                id: 0,
+                cluster_sid: Uuid::new_v4().to_string(),
+                features: String::new(),
+                appear_count: 1,
+                first_time: ts,
+                last_time: ts,
+                last_ft_sid: String::new(),
+                last_src_sid: String::new(),
+                alarm_time: None,
+                gmt_create: now,
+                gmt_modified: now,
+            },
+        };
+        po.last_ft_sid = item.sid.clone();
+        po.last_src_sid = item.face.source.clone();
+        po.gmt_modified = now;
+
+        // 补充代表特征值
+        let count = idx.map_or(0, |i| self.clusters[i].vecs.len());
+        let added: Vec<(String, Vec<f32>)> = feas.into_iter().take(cfg.keep_features.saturating_sub(count)).collect();
+        if !added.is_empty() {
+            let mut list: Vec<&str> = po.features.split(',').filter(|x| !x.is_empty()).collect();
+            list.extend(added.iter().map(|x| x.0.as_str()));
+            po.features = list.join(",");
+        }
+
+        let track = CfStrangerTrack {
+            id: 0,
+            cluster_sid: po.cluster_sid.clone(),
+            ft_sid: item.sid.clone(),
+            src_sid: item.face.source.clone(),
+            score: score as f64,
+            capture_time: ts,
+            gmt_create: now,
+            gmt_modified: now,
+        };
+
+        let ctx = self.ctx.clone();
+        let po_cl = po.clone();
+        let id = tokio::task::spawn_blocking(move || {
+            ctx.dao.save_stranger_track(&po_cl, &track)
+        }).await??;
+        po.id = match id {
+            Some(v) => v,
+            None => {
+                debug!("StrangerSvc, {} already assigned", item.sid);
+                return Ok(());
+            }
+        };
+        debug!("StrangerSvc, {} -> cluster:{}, score:{}", item.sid, po.cluster_sid, score);
+
+        let idx = match idx {
+            Some(i) => {
+                self.clusters[i].po = po;
+                Arc::make_mut(&mut self.clusters[i].vecs).extend(added.into_iter().map(|x| x.1));
+                i
+            }
+            None => {
+                // from_po 从 features 解码特征值，已包含 added
+                self.clusters.push(Cluster::from_po(po));
+                self.clusters.len() - 1
+            }
+        };
+        let cluster = &mut self.clusters[idx];
+
+        // 时间窗口内的出现次数
+        let window_start = ts - Duration::minutes(cfg.window_min.max(1));
+        cluster.recent.push_back(ts);
+        cluster.recent.retain(|x| *x >= window_start);
+        if cluster.recent.len() < cfg.alarm_count.max(1) {
+            return Ok(());
+        }
+
+        // 同一窗口内只报警一次
+        if matches!(cluster.po.alarm_time, Some(v) if v >= window_start) {
+            return Ok(());
+        }
+        cluster.po.alarm_time = Some(ts);
+
+        let alarm = CfStrangerAlarm {
+            id: 0,
+            cluster_sid: cluster.po.cluster_sid.clone(),
+            ft_sid: item.sid.clone(),
+            src_sid: item.face.source.clone(),
+            appear_count: cluster.recent.len() as i64,
+            window_start,
+            capture_time: ts,
+            gmt_create: now,
+            gmt_modified: now,
+        };
+        let qi = StrangerQI {
+            sid: item.sid.clone(),
+            cluster_sid: alarm.cluster_sid.clone(),
+            source: alarm.src_sid.clone(),
+            appear_count: alarm.appear_count,
+            window_start,
+            first_time: cluster.po.first_time,
+            img_url: item.face.faces.first().map_or(String::new(), |x| x.s_img_url.clone()),
+            ts,
+            camera: item.camera.clone(),
+        };
+        info!("StrangerSvc, frequent stranger, cluster:{}, count:{}", alarm.cluster_sid, alarm.appear_count);
+
+        let ctx = self.ctx.clone();
+        let po = cluster.po.clone();
+        tokio::task::spawn_blocking(move || {
+            ctx.dao.save_stranger_alarm(&po, &alarm)
+        }).await??;
+
+        if let Err(qi) = self.out.push(QI::STRANGER(qi)) {
+            error!("error, StrangerSvc, queue: {} is full, drop {}", self.out.name(), qi.get_sid());
+        }
+        Ok(())
+    }
+}
+
+impl Service for StrangerSvc {
+    fn run(self, rx: Receiver<i64>) -> TkJoinHandle<()> {
+        let mut svc = self;
+        let mut exit_rx = rx;
+
+        tokio::spawn(async move {
+            if !svc.ctx.cfg.track_feature.enable {
+                warn!("warn, StrangerSvc, track_feature is disabled, facetracks have no saved feature");
+            }
+            if let Err(e) = svc.load().await {
+                error!("error, StrangerSvc, load, {:?}", e);
+            }
+
+            let mut interval = time::interval(StdDuration::from_secs(3600));
+            loop {
+                tokio::select! {
+                    quit = exit_rx.next() => {
+                        if let Some(100) = quit {
+                            info!("StrangerSvc recv exit");
+                            break;
+                        }
+                    }
+                    item = svc.queue.pop() => {
+                        svc.process_item(item).await;
+                    }
+                    _ = interval.tick() => {
+                        svc.prune();
+                    }
+                }
+            }
+            info!("StrangerSvc exit");
+        })
+    }
+}
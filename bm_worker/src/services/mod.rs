@@ -3,6 +3,8 @@ pub mod car;
 pub mod face;
 pub mod signal_proc;
 pub mod ent_bus;
+pub mod trip;
+pub mod recg_probe;
 pub mod ws;
 
 use crate::app_ctx::AppCtx;
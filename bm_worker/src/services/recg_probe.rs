@@ -0,0 +1,52 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::info;
+use tokio::stream::StreamExt;
+use tokio::sync::watch::Receiver;
+use tokio::task::JoinHandle as TkJoinHandle;
+use tokio::time;
+
+use crate::app_ctx::AppCtx;
+use crate::services::Service;
+
+/// 定时探测识别节点，熔断到期的节点探测通过后恢复
+pub struct RecgProbeSvc {
+    ctx: Arc<AppCtx>,
+    dur: Duration,
+}
+
+impl RecgProbeSvc {
+    pub fn new(ctx: Arc<AppCtx>) -> Self {
+        let dur = Duration::from_secs(ctx.cfg.recg_cluster.probe_sec.max(1));
+        RecgProbeSvc {
+            ctx,
+            dur,
+        }
+    }
+}
+
+impl Service for RecgProbeSvc {
+    fn run(self, rx: Receiver<i64>) -> TkJoinHandle<()> {
+        let svc = self;
+        let mut exit_rx = rx;
+
+        tokio::spawn(async move {
+            let mut interval = time::interval(svc.dur);
+            loop {
+                tokio::select! {
+                    quit = exit_rx.next() => {
+                        if let Some(100) = quit {
+                            info!("RecgProbeSvc recv exit");
+                            break;
+                        }
+                    }
+                    _ = interval.tick() => {
+                        svc.ctx.recg_api.probe().await;
+                    }
+                }
+            }
+            info!("RecgProbeSvc exit");
+        })
+    }
+}
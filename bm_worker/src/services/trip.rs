@@ -0,0 +1,238 @@
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use chrono::prelude::*;
+use log::{debug, error, info};
+use tokio::stream::StreamExt;
+use tokio::sync::watch::Receiver;
+use tokio::task::JoinHandle as TkJoinHandle;
+
+use cffc_base::util::bounded_queue::BoundedQueue;
+
+use crate::app_ctx::AppCtx;
+use crate::dao::model::{CfDfsource, CfTripEvent, CfTripStat};
+use crate::error::AppResult;
+use crate::queue_item::{QI, TRIP_DIRECT_DOWN, TRIP_DIRECT_UNKNOWN, TRIP_DIRECT_UP, TripQI, TripQueueItem};
+
+use super::Service;
+
+/// 越线事件
+/// 根据通知中的 trip 位置和摄像头的 trip_line(水平线) 判断是否越线
+/// 保存到 cf_trip_event, 按时间段计数到 cf_trip_stat, 发布到 EntBusSvc
+pub struct TripSvc {
+    ctx: Arc<AppCtx>,
+    queue: Arc<BoundedQueue<TripQueueItem>>,
+    out: Arc<BoundedQueue<QI>>,
+}
+
+/// trip 位置覆盖了触发线，或者 背景图位置 -> trip 位置 跨过了触发线
+fn is_crossed(item: &TripQueueItem, line: i64) -> bool {
+    if item.y <= line && line <= item.y + item.h {
+        return true;
+    }
+
+    let cy = item.y + item.h / 2;
+    let (min, max) = if cy < item.ref_cy { (cy, item.ref_cy) } else { (item.ref_cy, cy) };
+    min <= line && line <= max
+}
+
+/// 优先用通知中的运动方向，没有时按 背景图位置 -> trip 位置 推算
+fn get_direction(item: &TripQueueItem) -> i32 {
+    match item.move_direction as i32 {
+        TRIP_DIRECT_UP => return TRIP_DIRECT_UP,
+        TRIP_DIRECT_DOWN => return TRIP_DIRECT_DOWN,
+        _ => {}
+    }
+
+    let cy = item.y + item.h / 2;
+    match cy.cmp(&item.ref_cy) {
+        Ordering::Greater => TRIP_DIRECT_DOWN,
+        Ordering::Less => TRIP_DIRECT_UP,
+        Ordering::Equal => TRIP_DIRECT_UNKNOWN,
+    }
+}
+
+/// 时间段的开始时间
+fn get_bucket_time(ts: DateTime<Local>, bucket_min: i64) -> DateTime<Local> {
+    let bucket = bucket_min.max(1) * 60;
+    let secs = ts.timestamp();
+    Local.timestamp(secs - secs.rem_euclid(bucket), 0)
+}
+
+impl TripSvc {
+    pub fn new(ctx: Arc<AppCtx>, queue: Arc<BoundedQueue<TripQueueItem>>, out: Arc<BoundedQueue<QI>>) -> Self {
+        TripSvc {
+            ctx,
+            queue,
+            out,
+        }
+    }
+
+    async fn process_item(&self, item: TripQueueItem) {
+        debug!("TripSvc, process_item: {}, type: {}", item.track_sid, item.track_type);
+        if let Err(e) = self.do_process(item).await {
+            error!("error, TripSvc, {:?}", e);
+        }
+    }
+
+    async fn do_process(&self, item: TripQueueItem) -> AppResult<()> {
+        let ctx = self.ctx.clone();
+        let source_id = item.source.clone();
+        let source_po = tokio::task::spawn_blocking(move || {
+            ctx.dao.load_source_by_sid(&source_id)
+        }).await??;
+
+        let source_po: CfDfsource = match source_po {
+            Some(v) if v.trip_line > 0 => v,
+            _ => {
+                debug!("TripSvc, source: {} has no trip_line, skip", item.source);
+                return Ok(());
+            }
+        };
+
+        if !is_crossed(&item, source_po.trip_line) {
+            debug!("TripSvc, {} not crossed, line: {}", item.track_sid, source_po.trip_line);
+            return Ok(());
+        }
+
+        let now = Local::now();
+        let direction = get_direction(&item);
+        let event = CfTripEvent {
+            id: 0,
+            track_sid: item.track_sid.clone(),
+            track_type: item.track_type,
+            src_sid: item.source.clone(),
+            direction,
+            trip_line: source_po.trip_line,
+            rect_x: item.x,
+            rect_y: item.y,
+            rect_w: item.w,
+            rect_h: item.h,
+            trip_ts: item.real_time,
+            trip_pts: item.pts,
+            capture_time: item.ts,
+            gmt_create: now,
+            gmt_modified: now,
+        };
+        let stat = CfTripStat {
+            id: 0,
+            src_sid: item.source.clone(),
+            track_type: item.track_type,
+            bucket_time: get_bucket_time(item.ts, self.ctx.cfg.trip.bucket_min),
+            up_count: (direction == TRIP_DIRECT_UP) as i64,
+            down_count: (direction == TRIP_DIRECT_DOWN) as i64,
+            total_count: 1,
+            gmt_create: now,
+            gmt_modified: now,
+        };
+
+        // 同一个 track 只计一次
+        let ctx = self.ctx.clone();
+        let event_cl = event.clone();
+        let saved = tokio::task::spawn_blocking(move || -> AppResult<bool> {
+            if ctx.dao.save_trip_event(&event_cl)? == 0 {
+                return Ok(false);
+            }
+            ctx.dao.incr_trip_stat(&stat)?;
+            Ok(true)
+        }).await??;
+
+        if !saved {
+            debug!("TripSvc, {} already saved, skip", item.track_sid);
+            return Ok(());
+        }
+
+        let qi = TripQI::from_po(&event, Some(&source_po));
+        if let Err(qi) = self.out.push(QI::TRIP(qi)) {
+            error!("error, TripSvc, queue: {} is full, drop {}", self.out.name(), qi.get_sid());
+        }
+        Ok(())
+    }
+}
+
+impl Service for TripSvc {
+    fn run(self, rx: Receiver<i64>) -> TkJoinHandle<()> {
+        let svc = self;
+        let mut exit_rx = rx;
+
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    quit = exit_rx.next() => {
+                        if let Some(100) = quit {
+                            info!("TripSvc recv exit");
+                            break;
+                        }
+                    }
+                    item = svc.queue.pop() => {
+                        svc.process_item(item).await;
+                    }
+                }
+            }
+            info!("TripSvc exit.");
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue_item::TRACK_TYPE_CAR;
+
+    fn trip_item(y: i64, h: i64, ref_cy: i64, move_direction: i64) -> TripQueueItem {
+        TripQueueItem {
+            track_sid: "t1".to_string(),
+            track_type: TRACK_TYPE_CAR,
+            source: "src1".to_string(),
+            x: 0,
+            y,
+            w: 100,
+            h,
+            real_time: 0,
+            pts: 0,
+            ref_cy,
+            move_direction,
+            ts: Local::now(),
+        }
+    }
+
+    #[test]
+    fn crossed_by_rect() {
+        let item = trip_item(100, 50, 125, 0);
+        assert!(is_crossed(&item, 100));
+        assert!(is_crossed(&item, 150));
+        assert!(!is_crossed(&item, 99));
+        assert!(!is_crossed(&item, 151));
+    }
+
+    #[test]
+    fn crossed_by_move() {
+        // 中心 y 为 125, 背景图中为 300
+        let item = trip_item(100, 50, 300, 0);
+        assert!(is_crossed(&item, 200));
+        assert!(is_crossed(&item, 300));
+        assert!(!is_crossed(&item, 301));
+        assert_eq!(get_direction(&item), TRIP_DIRECT_UP);
+
+        let item = trip_item(400, 50, 300, 0);
+        assert!(is_crossed(&item, 350));
+        assert_eq!(get_direction(&item), TRIP_DIRECT_DOWN);
+
+        // 通知中的方向优先
+        let item = trip_item(400, 50, 300, TRIP_DIRECT_UP as i64);
+        assert_eq!(get_direction(&item), TRIP_DIRECT_UP);
+        let item = trip_item(100, 50, 125, 0);
+        assert_eq!(get_direction(&item), TRIP_DIRECT_UNKNOWN);
+    }
+
+    #[test]
+    fn bucket_time() {
+        let base = 1_700_000_000 - 1_700_000_000 % 3600;
+        let ts = Local.timestamp(base + 17 * 60 + 5, 0);
+        assert_eq!(get_bucket_time(ts, 15).timestamp(), base + 15 * 60);
+        assert_eq!(get_bucket_time(ts, 60).timestamp(), base);
+        // 不足 1 分钟按 1 分钟
+        assert_eq!(get_bucket_time(ts, 0).timestamp(), base + 17 * 60);
+        assert_eq!(get_bucket_time(Local.timestamp(base, 0), 15).timestamp(), base);
+    }
+}
@@ -2,13 +2,12 @@ use std::sync::Arc;
 use std::thread;
 
 use actix::prelude::*;
-use deadqueue::unlimited::Queue;
 use log::{debug, error, info};
 use serde::{Deserialize, Serialize};
 use tokio::runtime;
 use tokio::stream::StreamExt;
 
-use cffc_base::util::utils;
+use cffc_base::util::bounded_queue::BoundedQueue;
 
 use crate::app_ctx::AppCtx;
 use crate::error::AppResult;
@@ -34,7 +33,7 @@ pub struct WsMsg {
 
 pub struct WsWorker {
     ctx: Arc<AppCtx>,
-    queue: Arc<Queue<QI>>,
+    queue: Arc<BoundedQueue<QI>>,
     agent_addr: Option<Recipient<DeliverMessage>>,
 
     //----
@@ -52,7 +51,7 @@ impl Actor for WsWorker {
 }
 
 impl WsWorker {
-    pub fn new(ctx: Arc<AppCtx>, queue: Arc<Queue<QI>>) -> Self {
+    pub fn new(ctx: Arc<AppCtx>, queue: Arc<BoundedQueue<QI>>) -> Self {
         let batch = ctx.cfg.ws.batch;
         WsWorker {
             ctx,
@@ -155,6 +154,9 @@ impl WsWorker {
         let db_list = self.ctx.web_dao.get_dfdb_list()?;
         let car_group_list = self.ctx.web_dao.get_coigroup_list()?;
 
+        let ft_sids: Vec<String> = facetrack_list.iter().map(|x| x.ft_sid.clone()).collect();
+        let mut candidate_map = self.ctx.dao.load_facetrack_candidates_by_sids(&ft_sids)?;
+
         let mut qi_list = Vec::new();
 
         //人脸记录
@@ -174,7 +176,9 @@ impl WsWorker {
                 None => None,
             };
 
-            let qi_ft = FtQI::from_po(prefix, v, camera, &db_list, match_poi)?;
+            let candidates = candidate_map.remove(&v.ft_sid).unwrap_or_default();
+
+            let qi_ft = FtQI::from_po(prefix, v, camera, &db_list, match_poi, &candidates)?;
             qi_list.push(QI::FT(qi_ft));
         }
 
@@ -204,6 +208,9 @@ impl WsWorker {
             match x {
                 QI::FT(v) => v.face.ts,
                 QI::CT(v) => v.car.ts,
+                QI::TRIP(v) => v.ts,
+                QI::STRANGER(v) => v.ts,
+                QI::PARKING(v) => v.ts,
             }
         });
 
@@ -221,8 +228,8 @@ impl WsWorker {
 }
 
 
-async fn pop_batch(queue: &Arc<Queue<QI>>, max: usize) -> Vec<QI> {
-    utils::pop_queue_batch(queue, max).await
+async fn pop_batch(queue: &Arc<BoundedQueue<QI>>, max: usize) -> Vec<QI> {
+    queue.pop_batch(max).await
 }
 
 
@@ -250,7 +257,12 @@ impl Handler<QiMessage> for WsWorker {
     type Result = ();
 
     fn handle(&mut self, msg: QiMessage, _ctx: &mut Context<Self>) -> Self::Result {
-        let items = self.track_snap.append(msg.0);
+        // 只推送 人脸/车辆 track
+        let list: Vec<QI> = msg.0.into_iter().filter(|x| x.is_track()).collect();
+        if list.is_empty() {
+            return;
+        }
+        let items = self.track_snap.append(list);
 
         let ws_msg = WsMsg {
             stat: self.track_snap.stat.clone(),
@@ -295,11 +307,14 @@ impl TrackSnap {
                 }
             }
             QI::CT(v) => {
-                self.stat.total_car_count += 1;
+                if !v.car.rejudge {
+                    self.stat.total_car_count += 1;
+                }
                 if v.car.alarmed {
                     self.stat.total_car_alarm += 1;
                 }
             }
+            QI::TRIP(_) | QI::STRANGER(_) | QI::PARKING(_) => {}
         }
     }
 
@@ -0,0 +1,242 @@
+use actix_web::web;
+use chrono::prelude::*;
+use log::{debug, error};
+use serde::{Deserialize, Serialize};
+
+use cffc_base::model::returndata::{self, ReturnDataType};
+use cffc_base::util::utils;
+
+use crate::alarm_rule::{self, MATCH_MODE_ANY, MATCH_MODE_HIT, SEVERITY_MAX, SEVERITY_MIN};
+use crate::dao::model::CfAlarmRule;
+use crate::web::AppState;
+
+pub async fn list(app_state: web::Data<AppState>) -> ReturnDataType<Vec<CfAlarmRule>> {
+    let ctx = app_state.ctx.clone();
+    let list = web::block(move || {
+        ctx.web_dao.get_alarm_rule_list()
+    }).await;
+
+    if let Err(e) = list {
+        error!("error, alarm_rule_ctl, get_alarm_rule_list, {:?}", e);
+        return returndata::fail("query db fail");
+    }
+    let list = list.unwrap();
+    returndata::success(list)
+}
+
+//----------------- add / modify -------------------------------
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RuleFormData {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub track_type: Option<String>,
+    pub enabled: Option<String>,
+    pub severity: Option<String>,
+    pub src_sids: Option<String>,
+    pub group_sids: Option<String>,
+    pub match_mode: Option<String>,
+    pub bw_flag: Option<String>,
+    pub week_days: Option<String>,
+    pub time_start: Option<String>,
+    pub time_end: Option<String>,
+    pub min_score: Option<String>,
+    pub gender: Option<String>,
+    pub car_color: Option<String>,
+    /// 车道限速 km/h, 如 1:120,2:100,0:80
+    pub speed_limits: Option<String>,
+    pub memo: Option<String>,
+}
+
+fn check_rule_param(form: &RuleFormData) -> std::result::Result<(), String> {
+    if !utils::option_must_length(&form.name, 1, 100) {
+        return Err("invalid name".to_string());
+    }
+    if !utils::option_must_num_range(&form.track_type, 0, 1) {
+        return Err("invalid track_type".to_string());
+    }
+
+    //选填
+    if !utils::option_should_num_range(&form.enabled, 0, 1) {
+        return Err("invalid enabled".to_string());
+    }
+    if !utils::option_should_num_range(&form.severity, SEVERITY_MIN as i64, SEVERITY_MAX as i64) {
+        return Err("invalid severity".to_string());
+    }
+    if !utils::option_should_num_range(&form.match_mode, 0, 2) {
+        return Err("invalid match_mode".to_string());
+    }
+    if !utils::option_should_num_range(&form.bw_flag, 0, 2) {
+        return Err("invalid bw_flag".to_string());
+    }
+    if !utils::option_should_num_range(&form.gender, 0, 2) {
+        return Err("invalid gender".to_string());
+    }
+    if let Some(v) = utils::clean_option_string(&form.min_score) {
+        if v.parse::<f64>().is_err() {
+            return Err("invalid min_score".to_string());
+        }
+    }
+    if let Some(v) = utils::clean_option_string(&form.week_days) {
+        let ok = v.split(',').all(|x| matches!(x.trim().parse::<u32>(), Ok(1..=7)));
+        if !ok {
+            return Err("invalid week_days".to_string());
+        }
+    }
+    if let Some(v) = utils::clean_option_string(&form.speed_limits) {
+        if alarm_rule::parse_speed_limits(&v).is_none() {
+            return Err("invalid speed_limits".to_string());
+        }
+    }
+    for (v, name) in [(&form.time_start, "time_start"), (&form.time_end, "time_end")].iter() {
+        if let Some(v) = utils::clean_option_string(v) {
+            if alarm_rule::parse_hm(&v).is_none() {
+                return Err(format!("invalid {}", name));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn fill_po(form: &RuleFormData, po: &mut CfAlarmRule) {
+    po.name = utils::clean_option_string(&form.name).unwrap();
+    po.track_type = utils::get_option_num(&form.track_type).unwrap() as i32;
+    po.enabled = utils::get_option_num(&form.enabled).map_or(1, |x| x as i32);
+    po.severity = utils::get_option_num(&form.severity).map_or(SEVERITY_MIN, |x| x as i32);
+    po.src_sids = utils::clean_option_string(&form.src_sids);
+    po.group_sids = utils::clean_option_string(&form.group_sids);
+    po.speed_limits = utils::clean_option_string(&form.speed_limits);
+    // 只判断车速的规则，默认不限是否命中名单
+    let default_mode = match po.speed_limits {
+        Some(_) => MATCH_MODE_ANY,
+        None => MATCH_MODE_HIT,
+    };
+    po.match_mode = utils::get_option_num(&form.match_mode).map_or(default_mode, |x| x as i32);
+    po.bw_flag = utils::get_option_num(&form.bw_flag).map_or(0, |x| x as i32);
+    po.week_days = utils::clean_option_string(&form.week_days);
+    po.time_start = utils::clean_option_string(&form.time_start);
+    po.time_end = utils::clean_option_string(&form.time_end);
+    po.min_score = utils::clean_option_string(&form.min_score).map_or(0_f64, |x| x.parse().unwrap_or(0_f64));
+    po.gender = utils::get_option_num(&form.gender).map_or(0, |x| x as i32);
+    po.car_color = utils::clean_option_string(&form.car_color);
+    po.memo = utils::clean_option_string(&form.memo);
+    po.gmt_modified = Local::now();
+}
+
+/// 保存规则，判断服务在下一次处理时重新加载
+pub async fn add(app_state: web::Data<AppState>, form: web::Form<RuleFormData>) -> ReturnDataType<String> {
+    if let Err(e) = check_rule_param(&form) {
+        return returndata::fail(e.as_str());
+    }
+
+    let now = Local::now();
+    let mut po = CfAlarmRule {
+        id: 0,
+        name: "".to_string(),
+        track_type: 0,
+        enabled: 1,
+        severity: SEVERITY_MIN,
+        src_sids: None,
+        group_sids: None,
+        match_mode: MATCH_MODE_HIT,
+        bw_flag: 0,
+        week_days: None,
+        time_start: None,
+        time_end: None,
+        min_score: 0_f64,
+        gender: 0,
+        car_color: None,
+        speed_limits: None,
+        memo: None,
+        gmt_create: now,
+        gmt_modified: now,
+    };
+    fill_po(&form, &mut po);
+
+    let ctx = app_state.ctx.clone();
+    let rule_id = web::block(move || {
+        ctx.web_dao.save_alarm_rule(&po)
+    }).await;
+    if let Err(e) = rule_id {
+        error!("error, alarm_rule_ctl, save_alarm_rule, {:?}", e);
+        return returndata::fail(format!("{:?}", e).as_str());
+    }
+    let rule_id = rule_id.unwrap();
+    debug!("alarm_rule_ctl, add rule: {}", rule_id);
+
+    app_state.ctx.registry.invalidate_rules();
+    returndata::success(rule_id.to_string())
+}
+
+pub async fn modify(app_state: web::Data<AppState>, form: web::Form<RuleFormData>) -> ReturnDataType<String> {
+    if !utils::option_must_num(&form.id) {
+        return returndata::fail("invalid id");
+    }
+    if let Err(e) = check_rule_param(&form) {
+        return returndata::fail(e.as_str());
+    }
+    let id = utils::get_option_num(&form.id).unwrap();
+
+    let ctx = app_state.ctx.clone();
+    let po = web::block(move || {
+        ctx.web_dao.load_alarm_rule(id)
+    }).await;
+    if let Err(e) = po {
+        error!("error, alarm_rule_ctl, load_alarm_rule, {:?}", e);
+        return returndata::fail(format!("{:?}", e).as_str());
+    }
+    let po = po.unwrap();
+    if po.is_none() {
+        debug!("alarm_rule_ctl, can't find rule:{}", id);
+        return returndata::fail_msg("规则不存在", "rule not exsit");
+    }
+    let mut po = po.unwrap();
+    fill_po(&form, &mut po);
+
+    let ctx = app_state.ctx.clone();
+    let affect = web::block(move || {
+        ctx.web_dao.update_alarm_rule(&po)
+    }).await;
+    if let Err(e) = affect {
+        error!("error, alarm_rule_ctl, update_alarm_rule, {:?}", e);
+        return returndata::fail(format!("{:?}", e).as_str());
+    }
+    let affect = affect.unwrap();
+    if affect != 1 {
+        error!("error, alarm_rule_ctl, update rule, affect:{}", affect);
+        return returndata::fail("update fail");
+    }
+
+    app_state.ctx.registry.invalidate_rules();
+    returndata::success_str("succ")
+}
+
+//----------------- delete -------------------------------
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DeleteFormData {
+    pub id: Option<String>,
+}
+
+pub async fn delete(app_state: web::Data<AppState>, form: web::Form<DeleteFormData>) -> ReturnDataType<String> {
+    if !utils::option_must_num(&form.id) {
+        return returndata::fail("invalid id");
+    }
+    let id = utils::get_option_num(&form.id).unwrap();
+
+    let ctx = app_state.ctx.clone();
+    let affect = web::block(move || {
+        ctx.web_dao.delete_alarm_rule(id)
+    }).await;
+    if let Err(e) = affect {
+        error!("error, alarm_rule_ctl, delete_alarm_rule, {:?}", e);
+        return returndata::fail(format!("{:?}", e).as_str());
+    }
+    let affect = affect.unwrap();
+    if affect != 1 {
+        debug!("alarm_rule_ctl, delete rule:{}, affect:{}", id, affect);
+        return returndata::fail_msg("规则不存在", "rule not exsit");
+    }
+
+    app_state.ctx.registry.invalidate_rules();
+    returndata::success_str("succ")
+}
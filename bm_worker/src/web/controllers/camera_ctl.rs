@@ -14,6 +14,7 @@ use cffc_base::util::utils;
 use crate::dao::model::{BeUser, CfDfsource};
 use crate::error::{AppError, AppResult};
 use crate::web::AppState;
+use crate::web::upload_auth;
 use crate::web::proto::camera::CameraItem;
 use crate::web::svc::camera_svc;
 
@@ -49,6 +50,9 @@ pub struct AddFormData {
     pub c_type: Option<String>,
 
     pub min_face: Option<String>,
+
+    /// 进出口标志 0:未知 1:进口 2:出口 3:进口/出口
+    pub io_flag: Option<String>,
 }
 
 fn check_add_param(form: &web::Form<AddFormData>) -> std::result::Result<(), String> {
@@ -68,6 +72,10 @@ fn check_add_param(form: &web::Form<AddFormData>) -> std::result::Result<(), Str
         return Err("invalid type".to_string());
     }
 
+    if !utils::option_should_num_range(&form.io_flag, 0, 3) {
+        return Err("invalid io_flag".to_string());
+    }
+
     Ok(())
 }
 
@@ -140,18 +148,21 @@ pub async fn add(app_state: web::Data<AppState>, form: web::Form<AddFormData>) -
     }
     let config_json = config_json.unwrap();
 
+    let node_sid = app_state.ctx.cfg.web.client_node.sid.clone();
+    let push_url = upload_auth::load_push_url(&app_state, &node_sid, &src_sid).await;
+
     let po = CfDfsource {
         id: 0,
         src_sid: src_sid.clone(),
         name: name.clone(),
-        node_sid: app_state.ctx.cfg.web.client_node.sid.clone(),
+        node_sid,
         src_url: url.clone(),
-        push_url: app_state.ctx.cfg.web.notify_url.clone(),
+        push_url: push_url.clone(),
         ip: img_file::get_ip_from_rtsp(url, "localhost"),
         src_state: 1,
         src_config: config_json,
         grab_type: c_type as i32,
-        io_flag: 0,
+        io_flag: utils::get_option_num(&form.io_flag).unwrap_or(0) as i32,
         direction: 0,
         tp_id: None,
         upload_flag: 0,
@@ -163,6 +174,9 @@ pub async fn add(app_state: web::Data<AppState>, form: web::Form<AddFormData>) -
         rtcp_utc: 0,
         lane_desc: None,
         lane_count: 0,
+        speed_line1: 0,
+        speed_line2: 0,
+        speed_distance: 0_f64,
         memo: None,
         gmt_create: now,
         gmt_modified: now,
@@ -183,7 +197,7 @@ pub async fn add(app_state: web::Data<AppState>, form: web::Form<AddFormData>) -
     let mut created = false;
     let res = app_state.ctx.ana_api.create_source(
         Some(src_sid.clone()), url.clone(),
-        app_state.ctx.cfg.web.notify_url.clone(), src_req_cfg).await;
+        push_url, src_req_cfg).await;
     if let Err(e) = res {
         error!("error, camera_ctl, create_source: {:?}", e);
     } else {
@@ -228,6 +242,9 @@ pub struct ModifyFormData {
     pub c_type: Option<String>,
 
     pub min_face: Option<String>,
+
+    /// 进出口标志 0:未知 1:进口 2:出口 3:进口/出口
+    pub io_flag: Option<String>,
 }
 
 fn check_modify_param(form: &web::Form<ModifyFormData>) -> std::result::Result<(), String> {
@@ -250,6 +267,10 @@ fn check_modify_param(form: &web::Form<ModifyFormData>) -> std::result::Result<(
         return Err("invalid type".to_string());
     }
 
+    if !utils::option_should_num_range(&form.io_flag, 0, 3) {
+        return Err("invalid io_flag".to_string());
+    }
+
     Ok(())
 }
 
@@ -341,10 +362,12 @@ pub async fn modify(app_state: web::Data<AppState>, form: web::Form<ModifyFormDa
     }
     let config_json = config_json.unwrap();
 
+    let push_url = upload_auth::load_push_url(&app_state, &po.node_sid, sid).await;
+
     // 底层开启,则需要更新
     if po.src_state == 1 {
         let res = app_state.ctx.ana_api.update_source(sid.clone(), url.clone(),
-                                                      app_state.ctx.cfg.web.notify_url.clone(), src_req_cfg).await;
+                                                      push_url.clone(), src_req_cfg).await;
 
         if let Err(e) = res {
             error!("error, camera_ctl, api update_source, {:?}", e);
@@ -359,8 +382,12 @@ pub async fn modify(app_state: web::Data<AppState>, form: web::Form<ModifyFormDa
 
     po.name = name.clone();
     po.src_url = url.clone();
+    po.push_url = push_url;
     po.ip = img_file::get_ip_from_rtsp(url, "localhost");
     po.grab_type = c_type as i32;
+    if let Some(v) = utils::get_option_num(&form.io_flag) {
+        po.io_flag = v as i32;
+    }
     po.src_config = config_json;
     po.gmt_modified = now;
 
@@ -572,6 +599,91 @@ pub async fn set_on_screen(app_state: web::Data<AppState>,
     })
 }
 
+//----------------- set_speed_calib -------------------------------
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetSpeedCalibFormData {
+    pub sid: Option<String>,
+    /// 两条测速线，水平线 y 坐标(视频坐标)，都为 0 时取消标定
+    pub line1: Option<String>,
+    pub line2: Option<String>,
+    /// 两条线之间的实际距离, 米
+    pub distance: Option<String>,
+}
+
+fn check_speed_calib_param(form: &web::Form<SetSpeedCalibFormData>) -> std::result::Result<(), String> {
+    if !utils::option_must_length(&form.sid, 1, 50) {
+        return Err("invalid sid".to_string());
+    }
+    if !utils::option_must_num_range(&form.line1, 0, 10000) || !utils::option_must_num_range(&form.line2, 0, 10000) {
+        return Err("invalid line1 / line2".to_string());
+    }
+    if !utils::option_must_float(&form.distance) {
+        return Err("invalid distance".to_string());
+    }
+
+    let line1 = utils::get_option_must_num(&form.line1);
+    let line2 = utils::get_option_must_num(&form.line2);
+    let distance = utils::get_option_float(&form.distance).unwrap();
+    if line1 == 0 && line2 == 0 {
+        return Ok(());
+    }
+    if line1 == 0 || line2 == 0 || line1 == line2 {
+        return Err("invalid line1 / line2".to_string());
+    }
+    if !(distance > 0_f64 && distance <= 1000_f64) {
+        return Err("invalid distance".to_string());
+    }
+    Ok(())
+}
+
+/// 摄像头测速标定
+pub async fn set_speed_calib(app_state: web::Data<AppState>,
+                             form: web::Form<SetSpeedCalibFormData>) -> ReturnDataType<String> {
+    if let Err(e) = check_speed_calib_param(&form) {
+        return returndata::fail(e.as_str());
+    }
+    let sid = form.sid.as_ref().unwrap();
+
+    let ctx = app_state.ctx.clone();
+    let src_sid = sid.clone();
+    let po = web::block(move || {
+        ctx.web_dao.load_dfsource_by_sid(src_sid.as_str())
+    }).await;
+    if let Err(e) = po {
+        error!("error, camera_ctl, load_dfsource_by_sid, {:?}", e);
+        return returndata::fail(format!("{:?}", e).as_str());
+    }
+    let po = po.unwrap();
+    if po.is_none() {
+        debug!("camera_ctl, can't find source:{}", sid);
+        return returndata::fail_msg("摄像头不存在", "camera not exsit");
+    }
+    let mut po = po.unwrap();
+    po.speed_line1 = utils::get_option_must_num(&form.line1);
+    po.speed_line2 = utils::get_option_must_num(&form.line2);
+    po.speed_distance = match po.speed_line1 {
+        0 => 0_f64,
+        _ => utils::get_option_float(&form.distance).unwrap(),
+    };
+    po.gmt_modified = Local::now();
+
+    let ctx = app_state.ctx.clone();
+    let affect = web::block(move || {
+        ctx.web_dao.update_dfsource_for_speed_calib(&po)
+    }).await;
+    if let Err(e) = affect {
+        error!("error, camera_ctl, update_dfsource_for_speed_calib, {:?}", e);
+        return returndata::fail_msg("更新失败", "update fail");
+    }
+    let affect = affect.unwrap();
+    if affect != 1 {
+        error!("error, camera_ctl, update_dfsource_for_speed_calib, affect: {}", affect);
+        return returndata::fail_msg("更新失败", "update fail");
+    }
+
+    returndata::success_str("succ")
+}
+
 //----------------- set_state -------------------------------
 #[derive(Serialize, Deserialize, Debug)]
 struct SetStateResult {
@@ -15,6 +15,7 @@ use crate::app_ctx::AppCtx;
 use crate::error::{AppResult, AppError};
 use crate::web::svc::cartrack_svc;
 use crate::web::proto::cartrack::CartrackBo;
+use crate::queue_item::{CtQI, QI};
 
 /*
 pageSize, _ := web_util.GetInt64Value(s.GetString("pageSize"))
@@ -240,4 +241,146 @@ async fn get_match_coi(ctx: Arc<AppCtx>, track: &CfCartrack) -> AppResult<Option
 
     let po = po.unwrap();
     Ok(po)
-}
\ No newline at end of file
+}
+//----------------- review list -------------------------------
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReviewListFormData {
+    pub camera: Option<String>,
+    pub limit: Option<String>,
+}
+
+/// 车牌置信度低于分组阈值，待人工复核的记录
+pub async fn review_list(app_state: web::Data<AppState>, form: web::Query<ReviewListFormData>) -> ReturnDataType<Vec<CartrackBo>> {
+    if !utils::option_should_num_range(&form.limit, 1, 1000) {
+        return returndata::fail("invalid limit");
+    }
+    let camera = utils::clean_option_string(&form.camera);
+    let limit = utils::get_option_num(&form.limit).unwrap_or(100);
+
+    let ctx = app_state.ctx.clone();
+    let camera_list = web::block(move || {
+        ctx.web_dao.get_all_sourcelist()
+    }).await;
+    if let Err(e) = camera_list {
+        error!("error, cartrack_ctl, get_all_sourcelist, {:?}", e);
+        return returndata::fail(format!("{:?}", e).as_str());
+    }
+    let camera_list = camera_list.unwrap();
+
+    let ctx = app_state.ctx.clone();
+    let group_list = web::block(move || {
+        ctx.web_dao.get_coigroup_list()
+    }).await;
+    if let Err(e) = group_list {
+        error!("error, cartrack_ctl, get_coigroup_list, {:?}", e);
+        return returndata::fail(format!("{:?}", e).as_str());
+    }
+    let group_list = group_list.unwrap();
+
+    let ctx = app_state.ctx.clone();
+    let cartrack_list = web::block(move || {
+        ctx.web_dao.get_cartrack_review_list(camera, limit)
+    }).await;
+    if let Err(e) = cartrack_list {
+        error!("error, cartrack_ctl, get_cartrack_review_list, {:?}", e);
+        return returndata::fail(format!("{:?}", e).as_str());
+    }
+    let cartrack_list = cartrack_list.unwrap();
+
+    let mut bo_list = Vec::new();
+    for po in cartrack_list.iter() {
+        // 可能是模糊匹配，按 most_coi 查找
+        let coi_match = match po.most_coi.clone() {
+            Some(sid) => {
+                let ctx = app_state.ctx.clone();
+                let coi = web::block(move || {
+                    ctx.web_dao.load_cfcoi_by_sid(&sid)
+                }).await;
+                if let Err(e) = coi {
+                    error!("error, cartrack_ctl, load_cfcoi_by_sid, {:?}", e);
+                    return returndata::fail(format!("{:?}", e).as_str());
+                }
+                coi.unwrap()
+            }
+            None => None,
+        };
+        let bo = cartrack_svc::to_bo(po, &group_list, &camera_list,
+                                     &app_state.ctx.cfg.dfimg_url, coi_match);
+        bo_list.push(bo);
+    }
+
+    returndata::success(bo_list)
+}
+
+//----------------- review -------------------------------
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReviewFormData {
+    pub sid: Option<String>,
+    /// 1: 确认，0: 驳回
+    pub confirm: Option<String>,
+}
+
+/// 确认后报警，驳回后不报警且车牌视为未识别
+pub async fn review(app_state: web::Data<AppState>, form: web::Form<ReviewFormData>) -> ReturnDataType<String> {
+    if !utils::option_must_length(&form.sid, 1, 50) {
+        return returndata::fail("invalid sid");
+    }
+    if !utils::option_must_num_range(&form.confirm, 0, 1) {
+        return returndata::fail("invalid confirm");
+    }
+    let sid = utils::clean_option_string(&form.sid).unwrap();
+    let confirm = utils::get_option_must_num(&form.confirm) == 1;
+
+    let ctx = app_state.ctx.clone();
+    let sid_cl = sid.clone();
+    let affect = web::block(move || {
+        ctx.web_dao.update_cartrack_for_review(&sid_cl, confirm)
+    }).await;
+    if let Err(e) = affect {
+        error!("error, cartrack_ctl, update_cartrack_for_review, {:?}", e);
+        return returndata::fail(format!("{:?}", e).as_str());
+    }
+    let affect = affect.unwrap();
+    if affect != 1 {
+        debug!("cartrack_ctl, {} not pending review", sid);
+        return returndata::fail("not pending review");
+    }
+    debug!("cartrack_ctl, review:{}, confirm:{}", sid, confirm);
+
+    // 确认后推送报警，按重新判断处理，不重复统计和进出配对
+    if confirm {
+        let ctx = app_state.ctx.clone();
+        let qi = web::block(move || {
+            load_review_qi(&ctx, &sid)
+        }).await;
+        match qi {
+            Ok(Some(qi)) => {
+                if let Err(qi) = app_state.bus_queue.push(QI::CT(Box::new(qi))) {
+                    error!("error, cartrack_ctl, queue: {} is full, drop {}", app_state.bus_queue.name(), qi.get_sid());
+                }
+            }
+            Ok(None) => {}
+            Err(e) => error!("error, cartrack_ctl, load_review_qi, {:?}", e),
+        }
+    }
+
+    returndata::success_str("succ")
+}
+
+/// 复核确认的 cartrack
+fn load_review_qi(ctx: &AppCtx, sid: &str) -> AppResult<Option<CtQI>> {
+    let po = match ctx.web_dao.load_cartrack_by_sid(sid)? {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+    let camera = ctx.dao.load_source_by_sid(&po.src_sid)?;
+    let group_list = ctx.web_dao.get_coigroup_list()?;
+    let coi = match po.most_coi {
+        Some(ref v) => ctx.web_dao.load_cfcoi_by_sid(v)?,
+        None => None,
+    };
+
+    let mut qi = CtQI::from_po(&ctx.cfg.dfimg_url, &po, camera.as_ref(), &group_list, coi)?;
+    qi.car.rejudge = true;
+    Ok(Some(qi))
+}
@@ -26,6 +26,98 @@ pub async fn group_list(app_state: web::Data<AppState>) -> ReturnDataType<Vec<Cf
     let list = list.unwrap();
     returndata::success(list)
 }
+//----------------- group_modify -------------------------------
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GroupModifyFormData {
+    pub sid: Option<String>,
+    pub name: Option<String>,
+    pub bw_flag: Option<String>,
+    pub min_confidence: Option<String>,
+    pub memo: Option<String>,
+}
+
+fn check_group_modify_param(form: &web::Form<GroupModifyFormData>) -> std::result::Result<(), String> {
+    if !utils::option_must_length(&form.sid, 1, 50) {
+        return Err("invalid sid".to_string());
+    }
+
+    //选填
+    if !utils::option_should_length(&form.name, 1, 100) {
+        return Err("invalid name".to_string());
+    }
+    if !utils::option_should_num_range(&form.bw_flag, 0, 2) {
+        return Err("invalid bw_flag".to_string());
+    }
+    if let Some(v) = utils::clean_option_string(&form.min_confidence) {
+        if !matches!(v.parse::<f64>(), Ok(x) if (0_f64..=1_f64).contains(&x)) {
+            return Err("invalid min_confidence".to_string());
+        }
+    }
+
+    Ok(())
+}
+
+/// 修改车辆分组的名称、黑白名单标志、车牌最低置信度
+/// 判断服务在下一次处理时重新加载
+pub async fn group_modify(app_state: web::Data<AppState>, form: web::Form<GroupModifyFormData>) -> ReturnDataType<String> {
+    if let Err(e) = check_group_modify_param(&form) {
+        return returndata::fail(e.as_str());
+    }
+
+    let sid = utils::clean_option_string(&form.sid).unwrap();
+
+    let ctx = app_state.ctx.clone();
+    let group_list = web::block(move || {
+        ctx.web_dao.get_coigroup_list()
+    }).await;
+    if let Err(e) = group_list {
+        error!("error, coi_ctl, get_coigroup_list, {:?}", e);
+        return returndata::fail(format!("{:?}", e).as_str());
+    }
+    let group_list = group_list.unwrap();
+
+    let mut po = match group_list.into_iter().find(|x| x.sid == sid) {
+        Some(v) => v,
+        None => {
+            debug!("coi_ctl, can't find group:{}", sid);
+            return returndata::fail_msg("分组不存在", "group not exsit");
+        }
+    };
+
+    if let Some(v) = utils::clean_option_string(&form.name) {
+        po.name = v;
+    }
+    if let Some(v) = utils::get_option_num(&form.bw_flag) {
+        po.bw_flag = v as i32;
+    }
+    if let Some(v) = utils::get_option_float(&utils::clean_option_string(&form.min_confidence)) {
+        po.min_confidence = v;
+    }
+    if form.memo.is_some() {
+        po.memo = utils::clean_option_string(&form.memo);
+    }
+    po.gmt_modified = Local::now();
+
+    let ctx = app_state.ctx.clone();
+    let affect = web::block(move || {
+        ctx.web_dao.update_coigroup_for_modify(&po)
+    }).await;
+    if let Err(e) = affect {
+        error!("error, coi_ctl, update_coigroup_for_modify, {:?}", e);
+        return returndata::fail(format!("{:?}", e).as_str());
+    }
+    let affect = affect.unwrap();
+    if affect != 1 {
+        error!("error, coi_ctl, update coi_group, affect:{}", affect);
+        return returndata::fail("update fail");
+    }
+
+    app_state.ctx.registry.invalidate_groups();
+    // bw_flag 可能变化，模糊匹配的车牌不含白名单
+    app_state.ctx.registry.invalidate_plates();
+    returndata::success_str("succ")
+}
+
 
 //----------------- list -------------------------------
 #[derive(Serialize, Deserialize, Debug)]
@@ -295,6 +387,7 @@ pub async fn add(app_state: web::Data<AppState>, form: web::Form<AddFormData>) -
     let coi_id = coi_id.unwrap();
     debug!("coi_ctl, save db, cfcoi:{}, id:{}", coi_sid, coi_id);
 
+    app_state.ctx.registry.invalidate_plates();
     returndata::success_str("succ")
 }
 
@@ -349,6 +442,7 @@ pub async fn delete(app_state: web::Data<AppState>, form: web::Form<DeleteFormDa
         debug!("poi_ctl, delete_cfcoi_by_sid:{}, affect:{}", sid, affect);
     }
 
+    app_state.ctx.registry.invalidate_plates();
     returndata::success_str("succ")
 }
 
@@ -458,5 +552,6 @@ pub async fn modify(app_state: web::Data<AppState>, form: web::Form<ModifyFormDa
         return returndata::fail("update fail");
     }
 
+    app_state.ctx.registry.invalidate_plates();
     returndata::success_str("succ")
 }
\ No newline at end of file
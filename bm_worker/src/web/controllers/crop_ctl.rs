@@ -5,7 +5,7 @@ use chrono::prelude::*;
 use log::{debug, error};
 use serde::{Deserialize, Serialize};
 
-use cffc_base::api::bm_api::DetectRes;
+use cffc_base::api::bm_api::{ApiFeatureQuality, DetectRes};
 use cffc_base::model::img_file;
 use cffc_base::model::returndata::{self, ReturnDataType};
 use cffc_base::util::multipart_form;
@@ -135,4 +135,23 @@ async fn save_faces(res: DetectRes, root: &str, url_prefix: &str, relate_path: &
     }
 
     Ok(items)
-}
\ No newline at end of file
+}
+
+/// api 检测并提取特征值，多个人脸时取检测分值最高的
+pub async fn detect_feature(app_state: &web::Data<AppState>, img_base64: String) -> AppResult<ApiFeatureQuality> {
+    let res = app_state.ctx.recg_api.detect(img_base64, true, false).await?;
+    if res.code != 0 {
+        return Err(AppError::new(format!("detect return code:{}, msg:{}", res.code, res.msg).as_str()));
+    }
+
+    let face = res.faces.unwrap_or_default().into_iter()
+        .filter(|x| x.feature.is_some())
+        .max_by(|a, b| a.score.partial_cmp(&b.score).unwrap_or(std::cmp::Ordering::Equal));
+    match face {
+        Some(v) => Ok(ApiFeatureQuality {
+            feature: v.feature.unwrap(),
+            quality: v.score,
+        }),
+        None => Err(AppError::new("no face detected")),
+    }
+}
@@ -1,17 +1,26 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
+use actix_multipart::Multipart;
 use actix_web::web;
+use bytes::Buf;
+use chrono::Duration;
+use chrono::prelude::*;
 use log::{debug, error};
 use serde::{Deserialize, Serialize};
 
 use cffc_base::model::returndata::{self, ReturnDataType};
+use cffc_base::util::multipart_form;
 use cffc_base::util::utils;
 
 use crate::app_ctx::AppCtx;
 use crate::dao::model::{CfFacetrack, CfPoi};
+use crate::dao::web_dao::FtAttrFilter;
 use crate::error::{AppError, AppResult};
 use crate::web::{AppState, proto};
-use crate::web::proto::facetrack::FacetrackBo;
+use crate::services::face::retro_scan;
+use crate::web::controllers::crop_ctl;
+use crate::web::proto::facetrack::{FacetrackBo, FtSearchItem, FtSearchResult};
 
 use crate::web::svc::facetrack_svc;
 
@@ -39,6 +48,16 @@ pub struct ListFormData {
 
     pub gender: Option<String>,
 
+    #[serde(rename = "ageMin")]
+    pub age_min: Option<String>,
+
+    #[serde(rename = "ageMax")]
+    pub age_max: Option<String>,
+
+    pub glasses: Option<String>,
+    pub mask: Option<String>,
+    pub hat: Option<String>,
+
     #[serde(rename = "startTime")]
     pub start_time: Option<String>,
 
@@ -65,6 +84,22 @@ fn check_list_param(form: &web::Query<ListFormData>) -> std::result::Result<(),
         return Err("invalid alarm".to_string());
     }
 
+    if !utils::option_should_num_range(&form.age_min, 0, 150) || !utils::option_should_num_range(&form.age_max, 0, 150) {
+        return Err("invalid ageMin / ageMax".to_string());
+    }
+
+    if !utils::option_should_num_range(&form.glasses, 0, 2) {
+        return Err("invalid glasses".to_string());
+    }
+
+    if !utils::option_should_num_range(&form.mask, 0, 2) {
+        return Err("invalid mask".to_string());
+    }
+
+    if !utils::option_should_num_range(&form.hat, 0, 2) {
+        return Err("invalid hat".to_string());
+    }
+
     if utils::option_must_notempty(&form.start_time) || utils::option_must_notempty(&form.end_time) {
         // 验证时间字符串
         let valid = utils::option_must_datetime(&form.start_time, utils::DATETIME_FMT_SHORT)
@@ -107,7 +142,14 @@ pub async fn list(app_state: web::Data<AppState>, form: web::Query<ListFormData>
     let alarm = utils::get_option_num(&form.alarm);
     let name = utils::clean_option_string(&form.name);
     let identity_card = utils::clean_option_string(&form.identity_card);
-    let gender = utils::get_option_num(&form.gender);
+    let attr = FtAttrFilter {
+        gender: utils::get_option_num(&form.gender),
+        age_min: utils::get_option_num(&form.age_min),
+        age_max: utils::get_option_num(&form.age_max),
+        glasses: utils::get_option_num(&form.glasses),
+        mask: utils::get_option_num(&form.mask),
+        hat: utils::get_option_num(&form.hat),
+    };
     let date_range = utils::DateRange::from_option_str(&start_time, &end_time, utils::DATETIME_FMT_SHORT);
 
     // 查询摄像头列表
@@ -141,12 +183,12 @@ pub async fn list(app_state: web::Data<AppState>, form: web::Query<ListFormData>
     let alarm_cl = alarm.clone();
     let name_cl = name.clone();
     let identity_card_cl = identity_card.clone();
-    let gender_cl = gender.clone();
+    let attr_cl = attr.clone();
 
     let total = web::block(move || {
         ctx.web_dao.get_facetrack_total(camera_cl, date_range_cl,
                                         alarm_cl, name_cl, identity_card_cl,
-                                        gender_cl)
+                                        attr_cl)
     }).await;
     if let Err(e) = total {
         error!("error, facetrack_ctl, get_facetrack_total, {:?}", e);
@@ -169,13 +211,13 @@ pub async fn list(app_state: web::Data<AppState>, form: web::Query<ListFormData>
     let alarm_cl = alarm.clone();
     let name_cl = name.clone();
     let identity_card_cl = identity_card.clone();
-    let gender_cl = gender.clone();
+    let attr_cl = attr.clone();
     let start_index = dp.get_start_index();
 
     let facetrack_list = web::block(move || {
         ctx.web_dao.get_facetrack_datapage(camera_cl, date_range_cl,
                                            alarm_cl, name_cl, identity_card_cl,
-                                           gender_cl, page_size, start_index as i64)
+                                           attr_cl, page_size, start_index as i64)
     }).await;
     if let Err(e) = facetrack_list {
         error!("error, facetrack_ctl, get_facetrack_datapage, {:?}", e);
@@ -183,6 +225,17 @@ pub async fn list(app_state: web::Data<AppState>, form: web::Query<ListFormData>
     }
     let facetrack_list = facetrack_list.unwrap();
 
+    let ctx = app_state.ctx.clone();
+    let ft_sids: Vec<String> = facetrack_list.iter().map(|x| x.ft_sid.clone()).collect();
+    let candidate_map = web::block(move || {
+        ctx.web_dao.load_facetrack_candidates_by_sids(&ft_sids)
+    }).await;
+    if let Err(e) = candidate_map {
+        error!("error, facetrack_ctl, load_facetrack_candidates_by_sids, {:?}", e);
+        return returndata::fail(format!("{:?}", e).as_str());
+    }
+    let mut candidate_map = candidate_map.unwrap();
+
     let mut bo_list = Vec::new();
     for po in facetrack_list.iter() {
         let ctx = app_state.ctx.clone();
@@ -193,8 +246,11 @@ pub async fn list(app_state: web::Data<AppState>, form: web::Query<ListFormData>
             return returndata::fail(format!("{:?}", e).as_str());
         }
         let poi_match = poi_match.unwrap();
+
+        let candidates = candidate_map.remove(&po.ft_sid).unwrap_or_default();
+
         let bo = facetrack_svc::to_bo(po, &db_list, &camera_list,
-                                      &app_state.ctx.cfg.dfimg_url, poi_match);
+                                      &app_state.ctx.cfg.dfimg_url, poi_match, &candidates);
         bo_list.push(bo);
     }
 
@@ -227,4 +283,201 @@ async fn get_match_poi(ctx: Arc<AppCtx>, ft: &CfFacetrack) -> AppResult<Option<C
 
     let po = po.unwrap();
     Ok(po)
-}
\ No newline at end of file
+}
+
+//----------------- search by image -------------------------------
+fn get_search_range(values: &multipart_form::MultipartFormValues, max_days: i64) -> std::result::Result<utils::DateRange, String> {
+    let start_time = values.get_string_value("startTime").and_then(|x| utils::clean_option_string(&Some(x)));
+    let end_time = values.get_string_value("endTime").and_then(|x| utils::clean_option_string(&Some(x)));
+    if start_time.is_none() && end_time.is_none() {
+        let end = Local::now();
+        return Ok(utils::DateRange {
+            begin: end - Duration::days(max_days),
+            end,
+        });
+    }
+
+    let range = utils::DateRange::from_option_str(&start_time, &end_time, utils::DATETIME_FMT_SHORT);
+    match range {
+        Some(v) if v.begin < v.end && v.end - v.begin <= Duration::days(max_days) => Ok(v),
+        Some(_) => Err(format!("startTime / endTime out of range, max {} days", max_days)),
+        None => Err("invalid startTime / endTime".to_string()),
+    }
+}
+
+/// 上传人脸图片，与时间范围内保存的 facetrack 特征值比对
+/// 参数: img, camera, startTime, endTime, threshold, top, pageSize, pageNo
+/// 最多比对 max_rows 个特征值，top 个结果分页返回
+pub async fn search_by_image(app_state: web::Data<AppState>, payload: Multipart) -> ReturnDataType<FtSearchResult> {
+    let values = multipart_form::parse_multi_form(payload).await;
+    if let Err(e) = values {
+        error!("error, facetrack_ctl, parse_multi_form, {}", e);
+        return returndata::fail(e.as_str());
+    }
+    let values = values.unwrap();
+
+    let cfg = &app_state.ctx.cfg.image_search;
+    let camera = values.get_string_value("camera").and_then(|x| utils::clean_option_string(&Some(x)));
+    let threshold = values.get_string_value("threshold");
+    if !utils::option_should_num_range(&threshold, 1, 99) {
+        return returndata::fail("invalid threshold");
+    }
+    let threshold = utils::get_option_num(&threshold).unwrap_or(cfg.threshold);
+    let top = values.get_string_value("top");
+    if !utils::option_should_num_range(&top, 1, cfg.top) {
+        return returndata::fail("invalid top");
+    }
+    let top = utils::get_option_num(&top).unwrap_or(cfg.top) as usize;
+    let page_size = values.get_string_value("pageSize");
+    if !utils::option_should_num_range(&page_size, 1, 1000) {
+        return returndata::fail("invalid pageSize");
+    }
+    let page_size = utils::get_option_num(&page_size).unwrap_or(20);
+    let page_no = values.get_string_value("pageNo");
+    if !utils::option_should_num_range(&page_no, 1, 100_000_000) {
+        return returndata::fail("invalid pageNo");
+    }
+    let page_no = utils::get_option_num(&page_no).unwrap_or(1);
+    let range = get_search_range(&values, cfg.max_days);
+    if let Err(e) = range {
+        return returndata::fail(e.as_str());
+    }
+    let range = range.unwrap();
+
+    let img = values.get_file_value("img");
+    if img.is_none() {
+        error!("error, facetrack_ctl, img is none");
+        return returndata::fail("invalid img");
+    }
+    let (file_name, file_content) = img.unwrap();
+    debug!("facetrack_ctl, search_by_image, {}, {}", file_name, file_content.len());
+
+    let fea = crop_ctl::detect_feature(&app_state, base64::encode(file_content.bytes())).await;
+    if let Err(e) = fea {
+        error!("error, facetrack_ctl, detect_feature, {:?}", e);
+        return returndata::fail(e.msg.as_str());
+    }
+    let feas = vec![fea.unwrap()];
+
+    // 从最新的抓拍开始分页比对，超过 max_rows 时跳过的是较早的，每个 facetrack 取最高分
+    let mut best: HashMap<String, i64> = HashMap::new();
+    let mut before_id = i64::MAX;
+    let mut scanned = 0;
+    let mut truncated = false;
+    loop {
+        if scanned >= cfg.max_rows {
+            truncated = true;
+            break;
+        }
+
+        let ctx = app_state.ctx.clone();
+        let camera_cl = camera.clone();
+        let (begin, end) = (range.begin, range.end);
+        let limit = cfg.batch.max(1).min(cfg.max_rows - scanned);
+        let page = web::block(move || {
+            ctx.web_dao.load_facetrack_features_page(camera_cl.as_deref(), &begin, &end, before_id, limit)
+        }).await;
+        if let Err(e) = page {
+            error!("error, facetrack_ctl, load_facetrack_features_page, {:?}", e);
+            return returndata::fail(format!("{:?}", e).as_str());
+        }
+        let page = page.unwrap();
+        if page.is_empty() {
+            break;
+        }
+        before_id = page.last().unwrap().id;
+        scanned += page.len() as i64;
+
+        let tracks = retro_scan::group_by_track(&page);
+        let scores = retro_scan::score_tracks(&app_state.ctx, cfg.use_api, &feas, &tracks).await;
+        if let Err(e) = scores {
+            error!("error, facetrack_ctl, score_tracks, {:?}", e);
+            return returndata::fail(format!("{:?}", e).as_str());
+        }
+        let scores = scores.unwrap();
+        for (x, score) in tracks.iter().zip(scores.into_iter()) {
+            if score < threshold {
+                continue;
+            }
+            let v = best.entry(x[0].ft_sid.clone()).or_insert(score);
+            *v = (*v).max(score);
+        }
+    }
+
+    let mut hits: Vec<(String, i64)> = best.into_iter().collect();
+    hits.sort_by(|a, b| b.1.cmp(&a.1));
+    hits.truncate(top);
+    debug!("facetrack_ctl, search_by_image, scanned: {}, hits: {}", scanned, hits.len());
+
+    let dp = proto::DataPage::new(hits.len() as u64, page_size as u64, page_no as u64);
+    let hits: Vec<(String, i64)> = hits.into_iter().skip(dp.get_start_index() as usize).take(page_size as usize).collect();
+
+    let ctx = app_state.ctx.clone();
+    let camera_list = web::block(move || {
+        ctx.web_dao.get_all_sourcelist()
+    }).await;
+    if let Err(e) = camera_list {
+        error!("error, facetrack_ctl, get_all_sourcelist, {:?}", e);
+        return returndata::fail(format!("{:?}", e).as_str());
+    }
+    let camera_list = camera_list.unwrap();
+
+    let ctx = app_state.ctx.clone();
+    let db_list = web::block(move || {
+        ctx.web_dao.get_dfdb_list()
+    }).await;
+    if let Err(e) = db_list {
+        error!("error, facetrack_ctl, get_dfdb_list, {:?}", e);
+        return returndata::fail(format!("{:?}", e).as_str());
+    }
+    let db_list = db_list.unwrap();
+
+    let ctx = app_state.ctx.clone();
+    let ft_sids: Vec<String> = hits.iter().map(|x| x.0.clone()).collect();
+    let candidate_map = web::block(move || {
+        ctx.web_dao.load_facetrack_candidates_by_sids(&ft_sids)
+    }).await;
+    if let Err(e) = candidate_map {
+        error!("error, facetrack_ctl, load_facetrack_candidates_by_sids, {:?}", e);
+        return returndata::fail(format!("{:?}", e).as_str());
+    }
+    let mut candidate_map = candidate_map.unwrap();
+
+    let mut list = Vec::new();
+    for (ft_sid, score) in hits {
+        let ctx = app_state.ctx.clone();
+        let ft_sid_cl = ft_sid.clone();
+        let po = web::block(move || {
+            ctx.web_dao.load_facetrack_by_sid(&ft_sid_cl)
+        }).await;
+        if let Err(e) = po {
+            error!("error, facetrack_ctl, load_facetrack_by_sid:{}, {:?}", ft_sid, e);
+            return returndata::fail(format!("{:?}", e).as_str());
+        }
+        // 已被清理的跳过
+        let po = match po.unwrap() {
+            Some(v) => v,
+            None => continue,
+        };
+        let candidates = candidate_map.remove(&ft_sid).unwrap_or_default();
+
+        let poi_match = get_match_poi(app_state.ctx.clone(), &po).await;
+        if let Err(e) = poi_match {
+            error!("error, facetrack_ctl, get_match_poi, {:?}", e);
+            return returndata::fail(format!("{:?}", e).as_str());
+        }
+        let poi_match = poi_match.unwrap();
+
+        list.push(FtSearchItem {
+            score: score as f64,
+            facetrack: facetrack_svc::to_bo(&po, &db_list, &camera_list,
+                                            &app_state.ctx.cfg.dfimg_url, poi_match, &candidates),
+        });
+    }
+
+    returndata::success(FtSearchResult {
+        page: dp,
+        truncated,
+        list,
+    })
+}
@@ -102,6 +102,17 @@ pub async fn get_init_alarm_list(app_state: web::Data<AppState>)
     }
     let cartrack_list = cartrack_list.unwrap();
 
+    let ctx = app_state.ctx.clone();
+    let ft_sids: Vec<String> = facetrack_list.iter().map(|x| x.ft_sid.clone()).collect();
+    let candidate_map = web::block(move || {
+        ctx.web_dao.load_facetrack_candidates_by_sids(&ft_sids)
+    }).await;
+    if let Err(e) = candidate_map {
+        error!("error, home_ctl, load_facetrack_candidates_by_sids, {:?}", e);
+        return returndata::fail(format!("{:?}", e).as_str());
+    }
+    let mut candidate_map = candidate_map.unwrap();
+
 
     let mut qi_list = Vec::new();
 
@@ -131,7 +142,9 @@ pub async fn get_init_alarm_list(app_state: web::Data<AppState>)
             None => None,
         };
 
-        let qi_ft = FtQI::from_po(prefix, v, camera, &db_list, match_poi);
+        let candidates = candidate_map.remove(&v.ft_sid).unwrap_or_default();
+
+        let qi_ft = FtQI::from_po(prefix, v, camera, &db_list, match_poi, &candidates);
         if let Err(e) = qi_ft {
             error!("error, home_ctl, FtQI::from_po, {:?}", e);
             return returndata::fail(format!("{:?}", e).as_str());
@@ -181,6 +194,9 @@ pub async fn get_init_alarm_list(app_state: web::Data<AppState>)
         match x {
             QI::FT(v) => v.face.ts,
             QI::CT(v) => v.car.ts,
+            QI::TRIP(v) => v.ts,
+            QI::STRANGER(v) => v.ts,
+            QI::PARKING(v) => v.ts,
         }
     });
 
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+use actix_web::{HttpResponse, web};
+use log::{debug, error};
+use serde::{Deserialize, Serialize};
+
+use cffc_base::model::returndata::{self, ReturnDataError, ReturnDataType};
+use cffc_base::util::utils;
+
+use crate::dao::model::CfLaneStat;
+use crate::dao::web_dao::LaneStatFilter;
+use crate::services::car::lane_stat::LANE_STAT_PERIODS;
+use crate::web::AppState;
+
+//----------------- list -------------------------------
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ListFormData {
+    pub camera: Option<String>,
+
+    /// 统计周期, minute: 1/15/60/1440
+    pub period: Option<String>,
+
+    #[serde(rename = "startTime")]
+    pub start_time: Option<String>,
+
+    #[serde(rename = "endTime")]
+    pub end_time: Option<String>,
+
+    pub lane: Option<String>,
+
+    pub direct: Option<String>,
+
+    #[serde(rename = "carType")]
+    pub car_type: Option<String>,
+
+    /// 分组维度，逗号分隔: lane,direct,type，未指定的维度求和
+    pub group: Option<String>,
+
+    pub limit: Option<String>,
+}
+
+fn get_filter(form: &ListFormData) -> std::result::Result<(LaneStatFilter, i64), String> {
+    if !utils::option_must_num(&form.period)
+        || !LANE_STAT_PERIODS.contains(&utils::get_option_must_num(&form.period)) {
+        return Err("invalid period".to_string());
+    }
+
+    let valid = utils::option_must_datetime(&form.start_time, utils::DATETIME_FMT_SHORT)
+        && utils::option_must_datetime(&form.end_time, utils::DATETIME_FMT_SHORT);
+    if !valid {
+        return Err("invalid startTime / endTime".to_string());
+    }
+
+    if !utils::option_should_num_range(&form.lane, 0, 100) {
+        return Err("invalid lane".to_string());
+    }
+    if !utils::option_should_num_range(&form.direct, 0, 100) {
+        return Err("invalid direct".to_string());
+    }
+    if !utils::option_should_num_range(&form.limit, 1, 100000) {
+        return Err("invalid limit".to_string());
+    }
+
+    let group = utils::clean_option_string(&form.group).unwrap_or_default();
+    let groups: Vec<&str> = group.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()).collect();
+    if let Some(v) = groups.iter().find(|x| !["lane", "direct", "type"].contains(x)) {
+        return Err(format!("invalid group: {}", v));
+    }
+
+    let date_range = utils::DateRange::from_option_str(&form.start_time, &form.end_time, utils::DATETIME_FMT_SHORT);
+    let filter = LaneStatFilter {
+        camera: utils::clean_option_string(&form.camera),
+        period: utils::get_option_must_num(&form.period),
+        date_range: date_range.unwrap(),
+        lane: utils::get_option_num(&form.lane),
+        direct: utils::get_option_num(&form.direct),
+        car_type: utils::clean_option_string(&form.car_type),
+        group_lane: groups.contains(&"lane"),
+        group_direct: groups.contains(&"direct"),
+        group_type: groups.contains(&"type"),
+    };
+    let limit = utils::get_option_num(&form.limit).unwrap_or(10000);
+    Ok((filter, limit))
+}
+
+async fn query(app_state: &web::Data<AppState>, form: &ListFormData) -> std::result::Result<Vec<CfLaneStat>, String> {
+    debug!("lane_stat_ctl, form: {:?}", form);
+    let (filter, limit) = get_filter(form)?;
+
+    let ctx = app_state.ctx.clone();
+    let list = web::block(move || {
+        ctx.web_dao.get_lane_stat_list(&filter, limit)
+    }).await;
+    if let Err(e) = list {
+        error!("error, lane_stat_ctl, get_lane_stat_list, {:?}", e);
+        return Err("query db fail".to_string());
+    }
+    Ok(list.unwrap())
+}
+
+/// 按周期统计的车流量，按时间升序
+pub async fn list(app_state: web::Data<AppState>,
+                  form: web::Query<ListFormData>) -> ReturnDataType<Vec<CfLaneStat>> {
+    match query(&app_state, &form).await {
+        Ok(list) => returndata::success(list),
+        Err(e) => returndata::fail(e.as_str()),
+    }
+}
+
+//----------------- export -------------------------------
+fn csv_text(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+/// 与 list 参数相同，导出为 csv
+pub async fn export(app_state: web::Data<AppState>,
+                    form: web::Query<ListFormData>) -> Result<HttpResponse, ReturnDataError> {
+    let list = query(&app_state, &form).await.map_err(|e| ReturnDataError::new(&e))?;
+
+    // 摄像头名称
+    let ctx = app_state.ctx.clone();
+    let camera_list = web::block(move || {
+        ctx.web_dao.get_all_sourcelist()
+    }).await;
+    if let Err(e) = camera_list {
+        error!("error, lane_stat_ctl, get_all_sourcelist, {:?}", e);
+        return Err(ReturnDataError::new("query db fail"));
+    }
+    let names: HashMap<String, String> = camera_list.unwrap().into_iter().map(|x| (x.src_sid, x.name)).collect();
+
+    let mut body = String::from("bucket_time,period,src_sid,camera,lane_num,move_direct,car_type,count\n");
+    for v in list.iter() {
+        let name = names.get(&v.src_sid).map_or("", |x| x.as_str());
+        body += &format!("{},{},{},{},{},{},{},{}\n",
+                         v.bucket_time.format(utils::DATETIME_FMT_SHORT), v.period,
+                         csv_text(&v.src_sid), csv_text(name),
+                         v.lane_num, v.move_direct, csv_text(&v.car_type), v.count);
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .header("Content-Disposition", "attachment; filename=lane_stat.csv")
+        .body(body))
+}
@@ -9,4 +9,9 @@ pub mod poi_ctl;
 pub mod facetrack_ctl;
 pub mod cartrack_ctl;
 pub mod coi_ctl;
-
+pub mod sys_ctl;
+pub mod alarm_rule_ctl;
+pub mod retro_ctl;
+pub mod stranger_ctl;
+pub mod lane_stat_ctl;
+pub mod parking_ctl;
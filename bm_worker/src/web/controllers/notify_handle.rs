@@ -1,22 +1,61 @@
 use actix_multipart::Multipart;
-use actix_web::web;
+use actix_web::{HttpRequest, http::header, web};
+use actix_web::error::PayloadError;
 use chrono::prelude::*;
-use futures::StreamExt;
+use futures::{future, SinkExt, StreamExt};
+use futures::channel::mpsc;
 use log::{debug, error, info};
-use serde_json::{self, Result as JsonResult};
+use serde_json::{self, Result as JsonResult, Value};
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
 
-use cffc_base::api::bm_api::{CarNotifyParams, FaceNotifyParams};
-use cffc_base::model::returndata::{self, ReturnDataType};
-use cffc_base::util::multipart_form::{self, MultipartFormValues};
+use cffc_base::api::bm_api::{CarNotifyParams, FaceNotifyParams, NotifyBackground};
+use cffc_base::model::returndata::{self, ReturnDataError, ReturnDataType};
+use cffc_base::util::multipart_form::{self, BudgetGuard, FormFileData, MultipartError, MultipartFormValues, TempFile};
+use cffc_base::util::utils::{self, Sha256Digest};
 
+use crate::notify_dedup::{DEDUP_CAR, DEDUP_FACE};
+use crate::error::AppError;
+use crate::notify_recorder::{CaptureBody, CaptureMeta};
+use crate::notify_spool::{self, NotifySpool, SpoolBlob, SPOOL_CAR, SPOOL_FACE};
 use crate::queue_item::{NotifyCarQueueItem, NotifyFaceQueueItem};
 use crate::web::AppState;
+use crate::web::upload_auth::{self, UploadCred};
+
+const KIND_FACE: &str = "facetrack";
+const KIND_CAR: &str = "vehicletrack";
 
 fn print_time_use(ts_start: DateTime<Local>) {
     let ts_use = Local::now().signed_duration_since(ts_start).num_milliseconds();
     debug!("track_upload end, use: {} ms", ts_use);
 }
 
+/// 写入 spool, 返回 spool key
+async fn save_spool(data: &web::Data<AppState>, kind: &'static str, key: String,
+                    json: String, blobs: Vec<(String, SpoolBlob)>) -> Result<Option<String>, String> {
+    if !data.ctx.spool.is_enable() {
+        return Ok(None);
+    }
+
+    let ctx = data.ctx.clone();
+    let rst = web::block(move || {
+        ctx.spool.save(kind, &key, &json, &blobs).map(|_| key)
+    }).await;
+
+    match rst {
+        Ok(v) => Ok(Some(v)),
+        Err(e) => Err(format!("{:?}", e)),
+    }
+}
+
+fn remove_spool(data: &web::Data<AppState>, kind: &str, key: &Option<String>) {
+    if let Some(ref v) = key {
+        if let Err(e) = data.ctx.spool.remove(kind, v) {
+            error!("error, remove spool, {}, {:?}", v, e);
+        }
+    }
+}
+
 pub async fn test_upload(_data: web::Data<AppState>, mut payload: web::Payload) -> ReturnDataType<String> {
     info!("test_upload begin ...");
     // let values = multipart_form::parse_multi_form(multi_payload).await;
@@ -56,203 +95,578 @@ pub async fn test_upload(_data: web::Data<AppState>, mut payload: web::Payload)
     returndata::success_str("ok")
 }
 
-pub async fn track_upload(data: web::Data<AppState>, payload: Multipart) -> ReturnDataType<String> {
+/// 读取完整的 body, 计入全局额度, limit 为 0 时不限制
+async fn read_body(payload: &mut web::Payload, limit: usize, guard: &mut BudgetGuard) -> Result<web::Bytes, MultipartError> {
+    let mut body = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|e| MultipartError::Payload(format!("read payload, {:?}", e)))?;
+        if limit > 0 && body.len() + chunk.len() > limit {
+            return Err(MultipartError::TooLarge);
+        }
+        if !guard.grow(chunk.len()) {
+            return Err(MultipartError::Busy);
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body.freeze())
+}
+
+/// 录制的 body
+enum RawBody {
+    Memory(web::Bytes),
+    /// 接收时写入的临时文件
+    Temp(TempFile),
+}
+
+/// 录制模式下的原始请求
+struct RawUpload {
+    path: String,
+    content_type: String,
+    ts: DateTime<Local>,
+    body: RawBody,
+}
+
+impl RawUpload {
+    fn new(req: &HttpRequest, ts: DateTime<Local>, body: RawBody) -> Self {
+        let content_type = req.headers().get(header::CONTENT_TYPE)
+            .and_then(|x| x.to_str().ok())
+            .unwrap_or("")
+            .to_string();
+
+        RawUpload {
+            path: req.uri().path_and_query().map_or(String::new(), |x| x.to_string()),
+            content_type,
+            ts,
+            body,
+        }
+    }
+}
+
+/// 录制指定摄像头的请求
+async fn record_raw(data: &web::Data<AppState>, raw: Option<RawUpload>, source: &str, kind: &str) {
+    let raw = match raw {
+        Some(v) => v,
+        None => return,
+    };
+    if !data.ctx.recorder.is_selected(source) {
+        return;
+    }
+
+    let meta = CaptureMeta {
+        ts: raw.ts.timestamp_millis(),
+        path: raw.path,
+        content_type: raw.content_type,
+        source: source.to_string(),
+        kind: kind.to_string(),
+    };
+    let body = raw.body;
+    let ctx = data.ctx.clone();
+    let _ = web::block(move || -> Result<(), ()> {
+        let body = match body {
+            RawBody::Memory(ref v) => CaptureBody::Memory(v.as_ref()),
+            RawBody::Temp(ref v) => CaptureBody::File(v.path.as_path()),
+        };
+        ctx.recorder.record(meta, body);
+        Ok(())
+    }).await;
+}
+
+/// 接收 multipart body 时，边读边计算 sha256 (认证), 录制模式下同时写入临时文件
+#[derive(Default)]
+struct BodyTap {
+    digest: Option<Sha256Digest>,
+    file: Option<(File, TempFile)>,
+}
+
+impl BodyTap {
+    async fn new(data: &web::Data<AppState>) -> Self {
+        let mut tap = BodyTap::default();
+        if data.ctx.cfg.upload_auth.enable {
+            tap.digest = Some(Sha256Digest::new());
+        }
+        if data.ctx.recorder.is_enable() {
+            let file = match data.ctx.recorder.temp_path() {
+                Ok(path) => File::create(&path).await.map(|f| (f, TempFile { path, len: 0 })).map_err(AppError::from),
+                Err(e) => Err(e),
+            };
+            match file {
+                Ok(v) => tap.file = Some(v),
+                Err(e) => error!("error, BodyTap, create record file, {:?}", e),
+            }
+        }
+        tap
+    }
+
+    /// 录制失败不影响接收
+    async fn input(&mut self, chunk: &[u8]) {
+        if let Some(ref mut v) = self.digest {
+            v.input(chunk);
+        }
+
+        let failed = match self.file {
+            Some((ref mut f, ref mut t)) => match f.write_all(chunk).await {
+                Ok(_) => {
+                    t.len += chunk.len();
+                    false
+                }
+                Err(e) => {
+                    error!("error, BodyTap, write {:?}, {:?}", t.path, e);
+                    true
+                }
+            },
+            None => false,
+        };
+        if failed {
+            self.file = None;
+        }
+    }
+
+    async fn finish(self) -> (Option<String>, Option<TempFile>) {
+        let digest = self.digest.map(|mut x| x.result_hex());
+        let file = match self.file {
+            Some((mut f, t)) => match f.flush().await {
+                Ok(_) => Some(t),
+                Err(e) => {
+                    error!("error, BodyTap, flush {:?}, {:?}", t.path, e);
+                    None
+                }
+            },
+            None => None,
+        };
+        (digest, file)
+    }
+}
+
+fn upload_fail(e: MultipartError) -> ReturnDataType<String> {
+    match e {
+        MultipartError::Busy => returndata::busy("upload is busy"),
+        _ => returndata::fail(&e.to_string()),
+    }
+}
+
+pub async fn track_upload(req: HttpRequest, data: web::Data<AppState>, mut payload: web::Payload) -> ReturnDataType<String> {
     let ts_start = Local::now();
     debug!("track_upload begin ...");
 
-    let values = multipart_form::parse_multi_form(payload).await;
+    // 流式解析，大文件写入临时文件
+    // 开启认证时边读边计算 body 的 sha256, 录制模式下 body 同时写入临时文件
+    let mut tap = BodyTap::new(&data).await;
+    let max_size = data.multipart_opts.max_size;
+    let (mut tx, rx) = mpsc::channel::<Result<web::Bytes, PayloadError>>(4);
+    let pump = async move {
+        let mut total = 0_usize;
+        let mut parsing = true;
+        while let Some(chunk) = payload.next().await {
+            if let Ok(ref v) = chunk {
+                total += v.len();
+                tap.input(v).await;
+            }
+            // 解析结束后，读完剩余的 body (结束分隔符之后的内容), 超过上限时停止
+            if parsing && tx.send(chunk).await.is_err() {
+                parsing = false;
+            }
+            if !parsing && max_size > 0 && total > max_size {
+                break;
+            }
+        }
+        tap.finish().await
+    };
+    let multi_payload = Multipart::new(req.headers(), rx);
+    let ((digest, record_file), values) = future::join(pump, multipart_form::parse_multi_form_opts(multi_payload, &data.multipart_opts)).await;
+
+    let raw = record_file.map(|x| RawUpload::new(&req, ts_start, RawBody::Temp(x)));
+    let cred = digest.map(|x| UploadCred::from_request(&req, x));
+
     debug!("track_upload, after parse_multi_form");
     if let Err(e) = values {
         error!("error, parse_multi_form, {}", e);
 
         print_time_use(ts_start);
-        return returndata::fail(e.as_str());
+        return upload_fail(e);
     }
 
-    let values = values.unwrap();
+    let mut values = values.unwrap();
     // info!("track_upload, {:?}", values);
 
-    if let Some(notify_type) = values.get_string_value("type") {
-        match notify_type.as_str() {
-            "facetrack" => {
-                let rst = handle_face(data, values).await;
-                print_time_use(ts_start);
-                return rst;
-            }
-            "vehicletrack" => {
-                let rst = handle_car(data, values).await;
-                print_time_use(ts_start);
-                return rst;
-            }
-            _ => {
-                error!("error, unknown type, {}", notify_type);
-                print_time_use(ts_start);
-                return returndata::fail(&format!("unknown type, {}", notify_type));
-            }
+    let notify_type = match values.get_string_value("type") {
+        Some(v) => v,
+        None => {
+            error!("error, track_upload, param: type not found");
+            error!("error, no type, values: {:?}", values);
+            print_time_use(ts_start);
+            return returndata::fail("param: type not found");
         }
-    } else {
-        error!("error, track_upload, param: type not found");
-        error!("error, no type, values: {:?}", values);
-        print_time_use(ts_start);
-        return returndata::fail("param: type not found");
-    }
-}
+    };
 
-async fn handle_face(data: web::Data<AppState>, values: MultipartFormValues) -> ReturnDataType<String> {
-    let now = Local::now();
     let json_str = match values.get_string_value("json") {
         Some(v) => v,
         None => {
             error!("error, param: json not found");
             error!("error, no json, values: {:?}", values);
-
+            print_time_use(ts_start);
             return returndata::fail("param: json not found");
         }
     };
 
-    debug!("->face:{}", json_str);
+    let rst = match notify_type.as_str() {
+        KIND_FACE => {
+            debug!("->face:{}", json_str);
+            let notify: JsonResult<FaceNotifyParams> = serde_json::from_reader(json_str.as_bytes());
+            match notify {
+                Ok(item) => handle_face(data, item, cred, raw, NotifyImages::Multipart(&mut values)).await,
+                Err(e) => {
+                    error!("error, {:?}", e);
+                    returndata::fail("json parse fail")
+                }
+            }
+        }
+        KIND_CAR => {
+            debug!("->car:{}", json_str);
+            let notify: JsonResult<CarNotifyParams> = serde_json::from_reader(json_str.as_bytes());
+            match notify {
+                Ok(item) => handle_car(data, item, cred, raw, NotifyImages::Multipart(&mut values)).await,
+                Err(e) => {
+                    error!("error, {:?}", e);
+                    returndata::fail("json parse fail")
+                }
+            }
+        }
+        _ => {
+            error!("error, unknown type, {}", notify_type);
+            returndata::fail(&format!("unknown type, {}", notify_type))
+        }
+    };
+    print_time_use(ts_start);
+    rst
+}
 
-    let notify: JsonResult<FaceNotifyParams> = serde_json::from_reader(json_str.as_bytes());
-    if let Ok(mut item) = notify {
-        info!("recv track, {}, index:{}, ft", item.id, item.index);
+/// application/json 上传, 图片以 base64 内嵌在 image/aligned/display 字段中, 特征值在 feature 字段中
+/// body 为 FaceNotifyParams/CarNotifyParams, 另加 "type": facetrack|vehicletrack
+pub async fn track_upload_json(req: HttpRequest, data: web::Data<AppState>, mut payload: web::Payload) -> ReturnDataType<String> {
+    let ts_start = Local::now();
+    debug!("track_upload_json begin ...");
 
-        // 处理图片
-        item.background.image_buf =
-            match values.get_file_value(item.background.image_file.as_str()) {
-                Some((_, v)) => v,
-                None => {
-                    error!("error, can't find para: {}", item.background.image_file);
-                    return returndata::fail(format!("can't find para: {}", item.background.image_file).as_str());
-                }
-            };
+    // base64 编码后约大 1/3, max_size 为 0 时不限制
+    let limit = data.multipart_opts.max_size / 3 * 4;
+    let mut guard = data.upload_budget.guard();
+    let body = match read_body(&mut payload, limit, &mut guard).await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("error, track_upload_json, {}", e);
+            print_time_use(ts_start);
+            return upload_fail(e);
+        }
+    };
 
-        for x in item.faces.iter_mut() {
-            x.aligned_buf = match values.get_file_value(x.aligned_file.as_str()) {
-                Some((_, v)) => v,
-                None => {
-                    error!("error, can't find para: {}", x.aligned_file);
-                    return returndata::fail(format!("can't find para: {}", x.aligned_file).as_str());
-                }
-            };
+    let value: JsonResult<Value> = serde_json::from_slice(body.as_ref());
+    if let Err(e) = value {
+        error!("error, track_upload_json, {:?}", e);
+        print_time_use(ts_start);
+        return returndata::fail("json parse fail");
+    }
+    let value = value.unwrap();
 
-            x.display_buf = match values.get_file_value(x.display_file.as_str()) {
-                Some((_, v)) => v,
-                None => {
-                    error!("error, can't find para: {}", x.display_file);
-                    return returndata::fail(format!("can't find para: {}", x.display_file).as_str());
-                }
-            };
+    let notify_type = match value.get("type").and_then(|x| x.as_str()) {
+        Some(v) => v.to_string(),
+        None => {
+            error!("error, track_upload_json, param: type not found");
+            print_time_use(ts_start);
+            return returndata::fail("param: type not found");
+        }
+    };
 
-            if let Some(ref feature_file) = x.feature_file {
-                if !feature_file.is_empty() {
-                    x.feature_buf = match values.get_file_value(feature_file.as_str()) {
-                        Some((_, v)) => Some(v),
-                        None => {
-                            error!("error, can't find para: {}", feature_file);
-                            return returndata::fail(format!("can't find para: {}", feature_file).as_str());
-                        }
-                    }
-                } else {
-                    x.feature_file = None;
-                    debug!("{}, has no feature", item.id);
+    let cred = if data.ctx.cfg.upload_auth.enable {
+        Some(UploadCred::from_request(&req, utils::sha256_hex(body.as_ref())))
+    } else {
+        None
+    };
+    let raw = if data.ctx.recorder.is_enable() {
+        Some(RawUpload::new(&req, ts_start, RawBody::Memory(body)))
+    } else {
+        None
+    };
+
+    let rst = match notify_type.as_str() {
+        KIND_FACE => {
+            let notify: JsonResult<FaceNotifyParams> = serde_json::from_value(value);
+            match notify {
+                Ok(item) => handle_face(data, item, cred, raw, NotifyImages::Base64).await,
+                Err(e) => {
+                    error!("error, {:?}", e);
+                    returndata::fail("json parse fail")
                 }
-            } else {
+            }
+        }
+        KIND_CAR => {
+            let notify: JsonResult<CarNotifyParams> = serde_json::from_value(value);
+            match notify {
+                Ok(item) => handle_car(data, item, cred, raw, NotifyImages::Base64).await,
+                Err(e) => {
+                    error!("error, {:?}", e);
+                    returndata::fail("json parse fail")
+                }
+            }
+        }
+        _ => {
+            error!("error, unknown type, {}", notify_type);
+            returndata::fail(&format!("unknown type, {}", notify_type))
+        }
+    };
+    print_time_use(ts_start);
+    rst
+}
+
+/// 通知中图片的来源
+enum NotifyImages<'a> {
+    /// multipart 中的文件
+    Multipart(&'a mut MultipartFormValues),
+    /// json 中的 base64 字段
+    Base64,
+}
+
+/// 背景大图在临时文件中时，保留临时文件，不读入内存
+fn take_bg_file(values: &mut MultipartFormValues, bg: &mut NotifyBackground) -> Result<(), String> {
+    match values.take_file_data(bg.image_file.as_str()) {
+        Some((_, FormFileData::Memory(v))) => bg.image_buf = v,
+        Some((_, FormFileData::Temp(v))) => bg.image_spill = Some(v),
+        None => return Err(format!("can't find para: {}", bg.image_file)),
+    }
+    Ok(())
+}
+
+/// 小图超过 spill 大小时在临时文件中，读出
+async fn get_file(values: &MultipartFormValues, name: &str) -> Result<web::Bytes, String> {
+    match values.get_file_data(name) {
+        Some((_, v)) => v.to_bytes().await.map_err(|e| format!("read para: {}, {:?}", name, e)),
+        None => Err(format!("can't find para: {}", name)),
+    }
+}
+
+/// 解码 base64 图片，兼容 data:image/jpeg;base64, 前缀
+/// 解码后清除该字段, 避免写入 spool
+fn take_base64(field: &mut Option<String>, name: &str) -> Result<web::Bytes, String> {
+    let content = match field.take() {
+        Some(v) if !v.is_empty() => v,
+        _ => return Err(format!("can't find para: {}", name)),
+    };
+
+    let content = match content.find(";base64,") {
+        Some(pos) if content.starts_with("data:") => &content[pos + ";base64,".len()..],
+        _ => content.as_str(),
+    };
+
+    match base64::decode(content.trim()) {
+        Ok(v) => Ok(web::Bytes::from(v)),
+        Err(e) => Err(format!("{}, base64 decode fail, {:?}", name, e)),
+    }
+}
+
+async fn load_face_files(item: &mut FaceNotifyParams, values: &mut MultipartFormValues) -> Result<(), String> {
+    take_bg_file(values, &mut item.background)?;
+
+    for x in item.faces.iter_mut() {
+        x.aligned_buf = get_file(values, x.aligned_file.as_str()).await?;
+        x.display_buf = get_file(values, x.display_file.as_str()).await?;
+
+        match x.feature_file {
+            Some(ref feature_file) if !feature_file.is_empty() => {
+                x.feature_buf = Some(get_file(values, feature_file.as_str()).await?);
+            }
+            _ => {
                 x.feature_file = None;
                 debug!("{}, has no feature", item.id);
             }
         }
-        data.face_queue.push(NotifyFaceQueueItem {
-            uuid: item.id.clone(),
-            notify: item,
-            ts: now,
-        });
-        debug!("track_upload, end push face");
-        returndata::success_str("ok")
-    } else {
-        error!("error, {:?}", notify);
-        returndata::fail("json parse fail")
     }
+    Ok(())
 }
 
-async fn handle_car(data: web::Data<AppState>, values: MultipartFormValues) -> ReturnDataType<String> {
-    let now = Local::now();
+fn load_face_base64(item: &mut FaceNotifyParams) -> Result<(), String> {
+    item.background.image_buf = take_base64(&mut item.background.image, "background.image")?;
 
-    let json_str = match values.get_string_value("json") {
-        Some(v) => v,
-        None => {
-            error!("error, param: json not found");
-            error!("error, no json, values: {:?}", values);
-            return returndata::fail("param: json not found");
+    for (i, x) in item.faces.iter_mut().enumerate() {
+        x.aligned_buf = take_base64(&mut x.aligned, &format!("faces[{}].aligned", i))?;
+        x.display_buf = take_base64(&mut x.display, &format!("faces[{}].display", i))?;
+        // 特征值可选
+        x.feature_file = None;
+        if x.feature.as_ref().map_or(false, |v| !v.is_empty()) {
+            x.feature_buf = Some(take_base64(&mut x.feature, &format!("faces[{}].feature", i))?);
+        } else {
+            debug!("{}, has no feature", item.id);
         }
-    };
-    debug!("->car:{}", json_str);
-
-    let notify: JsonResult<CarNotifyParams> = serde_json::from_reader(json_str.as_bytes());
-    if let Ok(mut item) = notify {
-        info!("recv track, {}, index:{}, ct", item.id, item.index);
-
-        // 处理图片
-        item.background.image_buf =
-            match values.get_file_value(item.background.image_file.as_str()) {
-                Some((_, v)) => v,
-                None => {
-                    error!("error, can't find para: {}", item.background.image_file);
-                    return returndata::fail(format!("can't find para: {}", item.background.image_file).as_str());
-                }
-            };
+    }
+    Ok(())
+}
 
-        for x in item.vehicles.iter_mut() {
-            x.img_buf = match values.get_file_value(x.image_file.as_str()) {
-                Some((_, v)) => v,
-                None => {
-                    error!("error, can't find para: {}", x.image_file);
-                    return returndata::fail(format!("can't find para: {}", x.image_file).as_str());
-                }
-            };
+async fn load_car_files(item: &mut CarNotifyParams, values: &mut MultipartFormValues) -> Result<(), String> {
+    take_bg_file(values, &mut item.background)?;
+
+    for x in item.vehicles.iter_mut() {
+        x.img_buf = get_file(values, x.image_file.as_str()).await?;
+    }
+
+    // 有牌照号码
+    if item.has_plate_info() {
+        let x = item.plate_info.as_mut().unwrap();
+        if let Some(ref img) = x.image_file {
+            x.img_buf = get_file(values, img.as_str()).await?;
+        } else {
+            error!("error, has plate text, but hasn't plate img");
         }
+    }
 
-        // 有牌照号码
-        if item.has_plate_info() {
-            let x = item.plate_info.as_mut().unwrap();
-            if let Some(ref img) = x.image_file {
-                x.img_buf = match values.get_file_value(img.as_str()) {
-                    Some((_, v)) => v,
-                    None => {
-                        error!("error, can't find para: {}", img);
-                        return returndata::fail(format!("can't find para: {}", img).as_str());
-                    }
-                }
-            } else {
-                error!("error, has plate text, but hasn't plate img");
-            }
+    if item.has_plate_binary() {
+        let x = item.plate_info.as_mut().unwrap();
+        if let Some(ref img) = x.binary_file {
+            x.binary_buf = get_file(values, img.as_str()).await?;
+        } else {
+            error!("error, has plate binary, but hasn't plate binary img");
         }
+    }
+    Ok(())
+}
 
-        if item.has_plate_binary() {
-            let x = item.plate_info.as_mut().unwrap();
-            if let Some(ref img) = x.binary_file {
-                x.binary_buf = match values.get_file_value(img.as_str()) {
-                    Some((_, v)) => v,
-                    None => {
-                        error!("error, can't find para: {}", img);
-                        return returndata::fail(format!("can't find para: {}", img).as_str());
-                    }
-                }
-            } else {
-                error!("error, has plate binary, but hasn't plate binary img");
-            }
+fn load_car_base64(item: &mut CarNotifyParams) -> Result<(), String> {
+    item.background.image_buf = take_base64(&mut item.background.image, "background.image")?;
+
+    for (i, x) in item.vehicles.iter_mut().enumerate() {
+        x.img_buf = take_base64(&mut x.image, &format!("vehicles[{}].image", i))?;
+    }
+
+    // 有牌照号码
+    if item.has_plate_info() {
+        let x = item.plate_info.as_mut().unwrap();
+        if x.image.is_some() {
+            x.img_buf = take_base64(&mut x.image, "plate_info.image")?;
+        } else {
+            error!("error, has plate text, but hasn't plate img");
         }
+    }
 
+    // json 上传不带二值图
+    if let Some(x) = item.plate_info.as_mut() {
+        x.binary_file = None;
+    }
+    Ok(())
+}
 
-        debug!("track_upload, will push car");
-        data.car_queue.push(NotifyCarQueueItem {
-            uuid: item.id.clone(),
-            notify: item,
-            ts: now,
-        });
-        debug!("track_upload, end push car");
-        returndata::success_str("ok")
-    } else {
-        error!("error, {:?}", notify);
-        returndata::fail("json parse fail")
+async fn handle_face(data: web::Data<AppState>, mut item: FaceNotifyParams, cred: Option<UploadCred>,
+                     raw: Option<RawUpload>, images: NotifyImages<'_>) -> ReturnDataType<String> {
+    let now = Local::now();
+    info!("recv track, {}, index:{}, ft", item.id, item.index);
+
+    // 认证
+    if upload_auth::check(&data, &cred, &item.source).await.is_err() {
+        return Err(ReturnDataError::unauth("upload auth fail"));
+    }
+
+    // 录制
+    record_raw(&data, raw, &item.source, KIND_FACE).await;
+
+    // 处理图片
+    let rst = match images {
+        NotifyImages::Multipart(values) => load_face_files(&mut item, values).await,
+        NotifyImages::Base64 => load_face_base64(&mut item),
+    };
+    if let Err(e) = rst {
+        error!("error, {}, {}", item.id, e);
+        return returndata::fail(&e);
     }
+
+    // 重传的通知, 直接返回成功
+    if !data.ctx.dedup.check_insert(DEDUP_FACE, &item.id, item.index, now) {
+        info!("duplicate track, {}, index:{}, skip it", item.id, item.index);
+        return returndata::success_str("ok");
+    }
+
+    // 先写入 spool, 再回应
+    let spool_key = match serde_json::to_string(&item) {
+        Ok(json) => {
+            let key = NotifySpool::gen_key(now, &item.id, item.index);
+            save_spool(&data, SPOOL_FACE, key, json, notify_spool::face_blobs(&item)).await
+        }
+        Err(e) => Err(format!("{:?}", e)),
+    };
+    if let Err(e) = spool_key {
+        error!("error, save spool, {}, {}", item.id, e);
+        data.ctx.dedup.remove(DEDUP_FACE, &item.id, item.index);
+        return returndata::fail("save spool fail");
+    }
+    let spool_key = spool_key.unwrap();
+
+    let push_rst = data.face_queue.push(NotifyFaceQueueItem {
+        uuid: item.id.clone(),
+        notify: item,
+        ts: now,
+        spool_key: spool_key.clone(),
+    });
+    if let Err(v) = push_rst {
+        error!("error, face_queue is full ({}), drop {}", data.face_queue.len(), v.uuid);
+        data.ctx.dedup.remove(DEDUP_FACE, &v.notify.id, v.notify.index);
+        remove_spool(&data, SPOOL_FACE, &spool_key);
+        return returndata::busy("face queue is full");
+    }
+    debug!("track_upload, end push face");
+    returndata::success_str("ok")
+}
+
+async fn handle_car(data: web::Data<AppState>, mut item: CarNotifyParams, cred: Option<UploadCred>,
+                    raw: Option<RawUpload>, images: NotifyImages<'_>) -> ReturnDataType<String> {
+    let now = Local::now();
+    info!("recv track, {}, index:{}, ct", item.id, item.index);
+
+    // 认证
+    if upload_auth::check(&data, &cred, &item.source).await.is_err() {
+        return Err(ReturnDataError::unauth("upload auth fail"));
+    }
+
+    // 录制
+    record_raw(&data, raw, &item.source, KIND_CAR).await;
+
+    // 处理图片
+    let rst = match images {
+        NotifyImages::Multipart(values) => load_car_files(&mut item, values).await,
+        NotifyImages::Base64 => load_car_base64(&mut item),
+    };
+    if let Err(e) = rst {
+        error!("error, {}, {}", item.id, e);
+        return returndata::fail(&e);
+    }
+
+    // 重传的通知, 直接返回成功
+    if !data.ctx.dedup.check_insert(DEDUP_CAR, &item.id, item.index, now) {
+        info!("duplicate track, {}, index:{}, skip it", item.id, item.index);
+        return returndata::success_str("ok");
+    }
+
+    debug!("track_upload, will push car");
+    // 先写入 spool, 再回应
+    let spool_key = match serde_json::to_string(&item) {
+        Ok(json) => {
+            let key = NotifySpool::gen_key(now, &item.id, item.index);
+            save_spool(&data, SPOOL_CAR, key, json, notify_spool::car_blobs(&item)).await
+        }
+        Err(e) => Err(format!("{:?}", e)),
+    };
+    if let Err(e) = spool_key {
+        error!("error, save spool, {}, {}", item.id, e);
+        data.ctx.dedup.remove(DEDUP_CAR, &item.id, item.index);
+        return returndata::fail("save spool fail");
+    }
+    let spool_key = spool_key.unwrap();
+
+    let push_rst = data.car_queue.push(NotifyCarQueueItem {
+        uuid: item.id.clone(),
+        notify: item,
+        ts: now,
+        spool_key: spool_key.clone(),
+    });
+    if let Err(v) = push_rst {
+        error!("error, car_queue is full ({}), drop {}", data.car_queue.len(), v.uuid);
+        data.ctx.dedup.remove(DEDUP_CAR, &v.notify.id, v.notify.index);
+        remove_spool(&data, SPOOL_CAR, &spool_key);
+        return returndata::busy("car queue is full");
+    }
+    debug!("track_upload, end push car");
+    returndata::success_str("ok")
 }
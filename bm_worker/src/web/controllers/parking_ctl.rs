@@ -0,0 +1,116 @@
+use actix_web::web;
+use log::{debug, error};
+use serde::{Deserialize, Serialize};
+
+use cffc_base::model::returndata::{self, ReturnDataType};
+use cffc_base::util::utils;
+
+use crate::dao::model::CfParkingVisit;
+use crate::web::AppState;
+
+//----------------- visit list -------------------------------
+#[derive(Serialize, Deserialize, Debug)]
+pub struct VisitListFormData {
+    #[serde(rename = "plateContent")]
+    pub plate_content: Option<String>,
+
+    /// 0：在场，1：已出场，2：出场无入场记录，3：入场无出场记录
+    pub state: Option<String>,
+
+    #[serde(rename = "startTime")]
+    pub start_time: Option<String>,
+
+    #[serde(rename = "endTime")]
+    pub end_time: Option<String>,
+
+    pub limit: Option<String>,
+}
+
+/// 停车记录，入场或出场时间在范围内
+pub async fn visit_list(app_state: web::Data<AppState>,
+                        form: web::Query<VisitListFormData>) -> ReturnDataType<Vec<CfParkingVisit>> {
+    debug!("parking_ctl, form: {:?}", form);
+
+    if !utils::option_should_num_range(&form.state, 0, 3) {
+        return returndata::fail("invalid state");
+    }
+    if !utils::option_should_num_range(&form.limit, 1, 1000) {
+        return returndata::fail("invalid limit");
+    }
+    if utils::option_must_notempty(&form.start_time) || utils::option_must_notempty(&form.end_time) {
+        let valid = utils::option_must_datetime(&form.start_time, utils::DATETIME_FMT_SHORT)
+            && utils::option_must_datetime(&form.end_time, utils::DATETIME_FMT_SHORT);
+        if !valid {
+            return returndata::fail("invalid startTime / endTime");
+        }
+    }
+
+    let plate_content = utils::clean_space_option_string(&form.plate_content);
+    let state = utils::get_option_num(&form.state);
+    let start_time = utils::clean_option_string(&form.start_time);
+    let end_time = utils::clean_option_string(&form.end_time);
+    let date_range = utils::DateRange::from_option_str(&start_time, &end_time, utils::DATETIME_FMT_SHORT);
+    let limit = utils::get_option_num(&form.limit).unwrap_or(100);
+
+    let ctx = app_state.ctx.clone();
+    let list = web::block(move || {
+        ctx.web_dao.get_parking_visit_list(plate_content, state, date_range, limit)
+    }).await;
+    if let Err(e) = list {
+        error!("error, parking_ctl, get_parking_visit_list, {:?}", e);
+        return returndata::fail("query db fail");
+    }
+    let list = list.unwrap();
+    returndata::success(list)
+}
+
+//----------------- occupancy -------------------------------
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OccupancyResult {
+    /// 在场车辆数
+    pub parked: i64,
+    /// 其中超时停放的数量
+    pub overstay: i64,
+}
+
+pub async fn occupancy(app_state: web::Data<AppState>) -> ReturnDataType<OccupancyResult> {
+    let ctx = app_state.ctx.clone();
+    let v = web::block(move || {
+        ctx.web_dao.get_parking_occupancy()
+    }).await;
+    if let Err(e) = v {
+        error!("error, parking_ctl, get_parking_occupancy, {:?}", e);
+        return returndata::fail("query db fail");
+    }
+    let (parked, overstay) = v.unwrap();
+    returndata::success(OccupancyResult {
+        parked,
+        overstay,
+    })
+}
+
+//----------------- overstay list -------------------------------
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OverstayListFormData {
+    pub limit: Option<String>,
+}
+
+/// 超时停放报警，仍在场的记录
+pub async fn overstay_list(app_state: web::Data<AppState>,
+                           form: web::Query<OverstayListFormData>) -> ReturnDataType<Vec<CfParkingVisit>> {
+    if !utils::option_should_num_range(&form.limit, 1, 1000) {
+        return returndata::fail("invalid limit");
+    }
+    let limit = utils::get_option_num(&form.limit).unwrap_or(100);
+
+    let ctx = app_state.ctx.clone();
+    let list = web::block(move || {
+        ctx.web_dao.get_parking_overstay_list(limit)
+    }).await;
+    if let Err(e) = list {
+        error!("error, parking_ctl, get_parking_overstay_list, {:?}", e);
+        return returndata::fail("query db fail");
+    }
+    let list = list.unwrap();
+    returndata::success(list)
+}
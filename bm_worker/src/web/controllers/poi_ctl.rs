@@ -7,15 +7,17 @@ use log::{debug, warn, error};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use cffc_base::api::bm_api::{ApiFeatureQuality, RecognitionApi};
+use cffc_base::api::bm_api::ApiFeatureQuality;
+use cffc_base::api::recg_cluster::RecgCluster;
 use cffc_base::model::img_file;
 use cffc_base::model::returndata::{self, ReturnDataType};
 use cffc_base::util::utils;
 
-use crate::dao::model::{CfDfdb, CfPoi};
+use crate::dao::model::{CfDfdb, CfPoi, CfPoiFeature};
 use crate::error::{AppError, AppResult};
 use crate::web::AppState;
-use crate::web::proto::{self, poi::{ImgPathScore, PoiBo}};
+use crate::web::controllers::retro_ctl;
+use crate::web::proto::{self, poi::{ImgPathScore, PoiBo, TrajectoryBo}};
 use crate::web::proto::poi::ImgAppendItem;
 use crate::web::svc::poi_svc;
 
@@ -33,6 +35,90 @@ pub async fn group_list(app_state: web::Data<AppState>) -> ReturnDataType<Vec<Cf
     returndata::success(list)
 }
 
+//----------------- group_modify -------------------------------
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GroupModifyFormData {
+    pub sid: Option<String>,
+    pub name: Option<String>,
+    pub auto_match: Option<String>,
+    pub bw_flag: Option<String>,
+}
+
+fn check_group_modify_param(form: &web::Form<GroupModifyFormData>) -> std::result::Result<(), String> {
+    if !utils::option_must_length(&form.sid, 1, 50) {
+        return Err("invalid sid".to_string());
+    }
+
+    //选填
+    if !utils::option_should_length(&form.name, 1, 100) {
+        return Err("invalid name".to_string());
+    }
+    if !utils::option_should_num_range(&form.auto_match, 0, 1) {
+        return Err("invalid auto_match".to_string());
+    }
+    if !utils::option_should_num_range(&form.bw_flag, 0, 2) {
+        return Err("invalid bw_flag".to_string());
+    }
+
+    Ok(())
+}
+
+/// 修改人脸库的名称、自动比对、黑白名单标志
+/// 比对服务在下一批次时重新加载
+pub async fn group_modify(app_state: web::Data<AppState>, form: web::Form<GroupModifyFormData>) -> ReturnDataType<String> {
+    if let Err(e) = check_group_modify_param(&form) {
+        return returndata::fail(e.as_str());
+    }
+
+    let sid = utils::clean_option_string(&form.sid).unwrap();
+
+    let ctx = app_state.ctx.clone();
+    let db_list = web::block(move || {
+        ctx.web_dao.get_dfdb_list()
+    }).await;
+    if let Err(e) = db_list {
+        error!("error, poi_ctl, get_dfdb_list, {:?}", e);
+        return returndata::fail(format!("{:?}", e).as_str());
+    }
+    let db_list = db_list.unwrap();
+
+    let mut po = match db_list.into_iter().find(|x| x.db_sid == sid) {
+        Some(v) => v,
+        None => {
+            debug!("poi_ctl, can't find db:{}", sid);
+            return returndata::fail_msg("人脸库不存在", "db not exsit");
+        }
+    };
+
+    if let Some(v) = utils::clean_option_string(&form.name) {
+        po.name = v;
+    }
+    if let Some(v) = utils::get_option_num(&form.auto_match) {
+        po.auto_match = v as i32;
+    }
+    if let Some(v) = utils::get_option_num(&form.bw_flag) {
+        po.bw_flag = v as i32;
+    }
+    po.gmt_modified = Local::now();
+
+    let ctx = app_state.ctx.clone();
+    let affect = web::block(move || {
+        ctx.web_dao.update_dfdb_for_modify(&po)
+    }).await;
+    if let Err(e) = affect {
+        error!("error, poi_ctl, update_dfdb_for_modify, {:?}", e);
+        return returndata::fail(format!("{:?}", e).as_str());
+    }
+    let affect = affect.unwrap();
+    if affect != 1 {
+        error!("error, poi_ctl, update dfdb, affect:{}", affect);
+        return returndata::fail("update fail");
+    }
+
+    app_state.ctx.registry.invalidate_dbs();
+    returndata::success_str("succ")
+}
+
 
 //----------------- detail -------------------------------
 #[derive(Serialize, Deserialize, Debug)]
@@ -88,6 +174,105 @@ pub async fn detail(app_state: web::Data<AppState>,
 }
 
 
+//----------------- trajectory -------------------------------
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TrajectoryFormData {
+    pub sid: Option<String>,
+
+    #[serde(rename = "startTime")]
+    pub start_time: Option<String>,
+
+    #[serde(rename = "endTime")]
+    pub end_time: Option<String>,
+
+    /// 同一摄像头两次抓拍间隔不超过 gap 秒的，算作同一次到访
+    pub gap: Option<String>,
+    pub limit: Option<String>,
+}
+
+fn check_trajectory_param(form: &web::Query<TrajectoryFormData>) -> std::result::Result<(), String> {
+    if !utils::option_must_length(&form.sid, 1, 50) {
+        return Err("invalid sid".to_string());
+    }
+    if !utils::option_should_num_range(&form.gap, 1, 86400) {
+        return Err("invalid gap".to_string());
+    }
+    if !utils::option_should_num_range(&form.limit, 1, 10000) {
+        return Err("invalid limit".to_string());
+    }
+    if utils::option_must_notempty(&form.start_time) || utils::option_must_notempty(&form.end_time) {
+        let valid = utils::option_must_datetime(&form.start_time, utils::DATETIME_FMT_SHORT)
+            && utils::option_must_datetime(&form.end_time, utils::DATETIME_FMT_SHORT);
+        if !valid {
+            return Err("invalid startTime / endTime".to_string());
+        }
+    }
+
+    Ok(())
+}
+
+/// poi 在各摄像头的轨迹，按时间排序并合并为到访记录
+pub async fn trajectory(app_state: web::Data<AppState>,
+                        form: web::Query<TrajectoryFormData>) -> ReturnDataType<TrajectoryBo> {
+    if let Err(e) = check_trajectory_param(&form) {
+        return returndata::fail(e.as_str());
+    }
+
+    let sid = form.sid.clone().unwrap();
+    let gap = utils::get_option_num(&form.gap).unwrap_or(300);
+    let limit = utils::get_option_num(&form.limit).unwrap_or(1000);
+    let start_time = utils::clean_option_string(&form.start_time);
+    let end_time = utils::clean_option_string(&form.end_time);
+    let date_range = utils::DateRange::from_option_str(&start_time, &end_time, utils::DATETIME_FMT_SHORT);
+
+    // 查找poi
+    let ctx = app_state.ctx.clone();
+    let po_sid = sid.clone();
+    let po = web::block(move || {
+        ctx.web_dao.load_cfpoi_by_sid(po_sid.as_str())
+    }).await;
+    if let Err(e) = po {
+        error!("error, poi_ctl, load_cfpoi_by_sid:{}, {:?}", sid, e);
+        return returndata::fail(format!("{:?}", e).as_str());
+    }
+    let po = po.unwrap();
+    if po.is_none() {
+        error!("error, poi_ctl, can't find poi:{}", sid);
+        return returndata::fail(format!("can't find poi: {}", sid).as_str());
+    }
+    let po = po.unwrap();
+
+    let ctx = app_state.ctx.clone();
+    let lists = web::block(move || -> cffc_base::db::dbop::Result<_> {
+        let db_list = ctx.web_dao.get_dfdb_list()?;
+        let camera_list = ctx.web_dao.get_all_sourcelist()?;
+        Ok((db_list, camera_list))
+    }).await;
+    if let Err(e) = lists {
+        error!("error, poi_ctl, get_dfdb_list / get_all_sourcelist, {:?}", e);
+        return returndata::fail(format!("{:?}", e).as_str());
+    }
+    let (db_list, camera_list) = lists.unwrap();
+
+    let ctx = app_state.ctx.clone();
+    let po_sid = sid.clone();
+    let ft_list = web::block(move || {
+        ctx.web_dao.get_poi_facetracks(&po_sid, date_range, limit)
+    }).await;
+    if let Err(e) = ft_list {
+        error!("error, poi_ctl, get_poi_facetracks:{}, {:?}", sid, e);
+        return returndata::fail(format!("{:?}", e).as_str());
+    }
+    let ft_list = ft_list.unwrap();
+
+    let url_prefix = app_state.ctx.cfg.dfimg_url.as_str();
+    returndata::success(TrajectoryBo {
+        poi: poi_svc::to_bo(&po, &db_list, url_prefix),
+        visits: poi_svc::to_trajectory(&ft_list, &camera_list, url_prefix, gap),
+    })
+}
+
+
 //----------------- list -------------------------------
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ListResult {
@@ -353,7 +538,7 @@ pub async fn add(app_state: web::Data<AppState>, form: web::Form<HashMap<String,
         });
     }
 
-    let res = app_state.ctx.recg_api.create_persons(group.clone(), ids, vec![feaqua_list]).await;
+    let res = app_state.ctx.recg_api.create_persons(group.clone(), ids, vec![feaqua_list.clone()]).await;
     if let Err(e) = res {
         error!("error, poi_ctl, create_persons, {:?}", e);
         return returndata::fail(format!("{:?}", e).as_str());
@@ -432,6 +617,8 @@ pub async fn add(app_state: web::Data<AppState>, form: web::Form<HashMap<String,
         img_ids.truncate(img_ids.len() - 1);
     }
 
+    let retro_job = retro_ctl::new_job(&app_state, &poi_sid, &group, &name, threshold as i32, None);
+
     // 保存数据库
     let po = CfPoi {
         id: 0,
@@ -468,10 +655,46 @@ pub async fn add(app_state: web::Data<AppState>, form: web::Form<HashMap<String,
 
     let poi_id = poi_id.unwrap();
     debug!("poi_ctl, create poi ok, {}, {}", poi_sid, poi_id);
+
+    let added = res_person.faces.iter().copied().zip(feaqua_list.into_iter()).collect();
+    sync_feature_index(&app_state, &group, &poi_sid, added, vec![]).await;
+
+    // 回溯历史抓拍，失败只记录
+    if app_state.ctx.cfg.retro_scan.enable {
+        let _ = retro_ctl::save_job(&app_state, retro_job).await;
+    }
     returndata::success_str(poi_sid.as_str())
 }
 
-async fn delete_person_by_api(client: &RecognitionApi, db_sid: &str, poi_sid: &str) -> AppResult<()> {
+/// 同步本地特征索引，失败只记录
+async fn sync_feature_index(app_state: &web::Data<AppState>, db_sid: &str, poi_sid: &str,
+                            added: Vec<(i64, ApiFeatureQuality)>, removed: Vec<i64>) {
+    let now = Local::now();
+    let list: Vec<CfPoiFeature> = added.into_iter().map(|(face_id, x)| CfPoiFeature {
+        id: 0,
+        poi_sid: poi_sid.to_string(),
+        db_sid: db_sid.to_string(),
+        face_id,
+        feature: x.feature,
+        quality: x.quality,
+        gmt_create: now,
+        gmt_modified: now,
+    }).collect();
+
+    let ctx = app_state.ctx.clone();
+    let sid = poi_sid.to_string();
+    let synced = web::block(move || -> AppResult<usize> {
+        for face_id in removed.iter() {
+            ctx.feature_index.delete_face(&ctx.dao, &sid, *face_id)?;
+        }
+        ctx.feature_index.save(&ctx.dao, &list)
+    }).await;
+    if let Err(e) = synced {
+        error!("error, poi_ctl, sync_feature_index:{}, {:?}", poi_sid, e);
+    }
+}
+
+async fn delete_person_by_api(client: &RecgCluster, db_sid: &str, poi_sid: &str) -> AppResult<()> {
     let res = client.delete_person(db_sid.to_string(), poi_sid.to_string()).await?;
     if res.code != 0 {
         return Err(AppError::new(&format!("delete_person, return code:{}, msg:{}", res.code, res.msg)));
@@ -541,6 +764,16 @@ pub async fn delete(app_state: web::Data<AppState>, form: web::Form<DeleteFormDa
         debug!("poi_ctl, delete_cfpoi_by_sid:{}, affect:{}", sid, affect);
     }
 
+    // 本地特征索引删除
+    let ctx = app_state.ctx.clone();
+    let poi_sid = sid.clone();
+    let affect = web::block(move || {
+        ctx.feature_index.delete_person(&ctx.dao, &poi_sid)
+    }).await;
+    if let Err(e) = affect {
+        error!("error, poi_ctl, feature_index delete_person:{}, {:?}", sid, e);
+    }
+
     // 图片目录删除
     let df_imgs_path = app_state.ctx.cfg.df_imgs.as_str();
     let dir = img_file::get_person_imgdir(df_imgs_path, sid.as_str());
@@ -868,6 +1101,12 @@ pub async fn modify(app_state: web::Data<AppState>, form: web::Form<HashMap<Stri
         };
     }
 
+    let added = img_append_list.iter().map(|x| (x.face_id, ApiFeatureQuality {
+        feature: x.feature.clone(),
+        quality: x.score,
+    })).collect();
+    sync_feature_index(&app_state, &po.db_sid, &po.poi_sid, added, img_missing_list).await;
+
     //更新数据库
     let mut img_ids = String::new();
     for v in img_append_list.iter() {
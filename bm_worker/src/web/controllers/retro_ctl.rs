@@ -0,0 +1,158 @@
+use actix_web::web;
+use log::{debug, error};
+use serde::{Deserialize, Serialize};
+
+use cffc_base::model::img_file;
+use cffc_base::model::returndata::{self, ReturnDataType};
+use cffc_base::util::utils;
+
+use crate::dao::model::CfRetroJob;
+use crate::services::face::retro_scan;
+use crate::web::AppState;
+use crate::web::proto::retro::{RetroAlarmBo, RetroJobBo};
+
+/// 按配置生成回溯任务，配置的 threshold 为 0 时使用 poi 的阈值
+pub fn new_job(app_state: &web::Data<AppState>, poi_sid: &str, db_sid: &str, name: &str,
+               poi_threshold: i32, days: Option<i64>) -> CfRetroJob {
+    let cfg = &app_state.ctx.cfg.retro_scan;
+    let threshold = match cfg.threshold {
+        0 => poi_threshold,
+        v => v,
+    };
+    retro_scan::new_job(poi_sid, db_sid, name, threshold, days.unwrap_or(cfg.window_days))
+}
+
+/// 保存任务，由 RetroScanSvc 执行
+pub async fn save_job(app_state: &web::Data<AppState>, job: CfRetroJob) -> std::result::Result<i64, String> {
+    let ctx = app_state.ctx.clone();
+    let poi_sid = job.poi_sid.clone();
+    let job_id = web::block(move || {
+        ctx.web_dao.save_retro_job(&job)
+    }).await;
+    if let Err(e) = job_id {
+        error!("error, retro_ctl, save_retro_job:{}, {:?}", poi_sid, e);
+        return Err(format!("{:?}", e));
+    }
+    let job_id = job_id.unwrap();
+    debug!("retro_ctl, poi:{}, retro job:{}", poi_sid, job_id);
+    Ok(job_id)
+}
+
+//----------------- job list -------------------------------
+#[derive(Serialize, Deserialize, Debug)]
+pub struct JobListFormData {
+    pub poi_sid: Option<String>,
+    pub limit: Option<String>,
+}
+
+pub async fn job_list(app_state: web::Data<AppState>,
+                      form: web::Query<JobListFormData>) -> ReturnDataType<Vec<CfRetroJob>> {
+    if !utils::option_should_num_range(&form.limit, 1, 1000) {
+        return returndata::fail("invalid limit");
+    }
+
+    let poi_sid = utils::clean_option_string(&form.poi_sid);
+    let limit = utils::get_option_num(&form.limit).unwrap_or(100);
+
+    let ctx = app_state.ctx.clone();
+    let list = web::block(move || {
+        ctx.web_dao.get_retro_job_list(poi_sid, limit)
+    }).await;
+    if let Err(e) = list {
+        error!("error, retro_ctl, get_retro_job_list, {:?}", e);
+        return returndata::fail("query db fail");
+    }
+    let list = list.unwrap();
+    returndata::success(list)
+}
+
+//----------------- job detail -------------------------------
+#[derive(Serialize, Deserialize, Debug)]
+pub struct JobFormData {
+    pub id: Option<String>,
+}
+
+/// 任务进度及命中的抓拍，按分值降序
+pub async fn job_detail(app_state: web::Data<AppState>,
+                        form: web::Query<JobFormData>) -> ReturnDataType<RetroJobBo> {
+    if !utils::option_must_num(&form.id) {
+        return returndata::fail("invalid id");
+    }
+    let id = utils::get_option_num(&form.id).unwrap();
+
+    let ctx = app_state.ctx.clone();
+    let job = web::block(move || {
+        ctx.web_dao.load_retro_job(id)
+    }).await;
+    if let Err(e) = job {
+        error!("error, retro_ctl, load_retro_job:{}, {:?}", id, e);
+        return returndata::fail(format!("{:?}", e).as_str());
+    }
+    let job = job.unwrap();
+    if job.is_none() {
+        return returndata::fail(format!("can't find job: {}", id).as_str());
+    }
+    let job = job.unwrap();
+
+    let ctx = app_state.ctx.clone();
+    let list = web::block(move || {
+        ctx.web_dao.get_retro_alarm_list(id)
+    }).await;
+    if let Err(e) = list {
+        error!("error, retro_ctl, get_retro_alarm_list:{}, {:?}", id, e);
+        return returndata::fail(format!("{:?}", e).as_str());
+    }
+    let list = list.unwrap();
+
+    let url_prefix = app_state.ctx.cfg.dfimg_url.as_str();
+    let alarms = list.into_iter().map(|x| RetroAlarmBo {
+        img_url: img_file::get_facetrack_smallimg_url(url_prefix, &x.ft_sid, x.img_idx as i64),
+        bg_url: img_file::get_facetrack_bgimg_url(url_prefix, &x.ft_sid),
+        detail: x,
+    }).collect();
+
+    returndata::success(RetroJobBo {
+        job,
+        alarms,
+    })
+}
+
+//----------------- add -------------------------------
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AddFormData {
+    pub poi_sid: Option<String>,
+    pub days: Option<String>,
+}
+
+/// 对已有的 poi 手动发起回溯
+pub async fn add(app_state: web::Data<AppState>, form: web::Form<AddFormData>) -> ReturnDataType<String> {
+    if !utils::option_must_length(&form.poi_sid, 1, 50) {
+        return returndata::fail("invalid poi_sid");
+    }
+    if !utils::option_should_num_range(&form.days, 1, 90) {
+        return returndata::fail("invalid days");
+    }
+    let sid = form.poi_sid.clone().unwrap();
+    let days = utils::get_option_num(&form.days);
+
+    let ctx = app_state.ctx.clone();
+    let po_sid = sid.clone();
+    let po = web::block(move || {
+        ctx.web_dao.load_cfpoi_by_sid(po_sid.as_str())
+    }).await;
+    if let Err(e) = po {
+        error!("error, retro_ctl, load_cfpoi_by_sid:{}, {:?}", sid, e);
+        return returndata::fail(format!("{:?}", e).as_str());
+    }
+    let po = po.unwrap();
+    if po.is_none() {
+        return returndata::fail(format!("can't find poi: {}", sid).as_str());
+    }
+    let po = po.unwrap();
+
+    let job = new_job(&app_state, &po.poi_sid, &po.db_sid, &po.name, po.threshold, days);
+    match save_job(&app_state, job).await {
+        Ok(id) => returndata::success_str(id.to_string().as_str()),
+        Err(e) => returndata::fail(e.as_str()),
+    }
+}
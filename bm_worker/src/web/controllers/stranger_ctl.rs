@@ -0,0 +1,121 @@
+use actix_web::web;
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use cffc_base::model::img_file;
+use cffc_base::model::returndata::{self, ReturnDataType};
+use cffc_base::util::utils;
+
+use crate::web::AppState;
+use crate::web::proto::stranger::{StrangerAlarmBo, StrangerBo, StrangerTrackBo};
+
+//----------------- list -------------------------------
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ListFormData {
+    pub limit: Option<String>,
+}
+
+/// 匿名聚类，按最近出现时间降序
+pub async fn list(app_state: web::Data<AppState>,
+                  form: web::Query<ListFormData>) -> ReturnDataType<Vec<StrangerBo>> {
+    if !utils::option_should_num_range(&form.limit, 1, 1000) {
+        return returndata::fail("invalid limit");
+    }
+    let limit = utils::get_option_num(&form.limit).unwrap_or(100);
+
+    let ctx = app_state.ctx.clone();
+    let list = web::block(move || {
+        ctx.web_dao.get_stranger_list(limit)
+    }).await;
+    if let Err(e) = list {
+        error!("error, stranger_ctl, get_stranger_list, {:?}", e);
+        return returndata::fail("query db fail");
+    }
+    let list = list.unwrap();
+
+    let url_prefix = app_state.ctx.cfg.dfimg_url.as_str();
+    let list = list.into_iter().map(|x| StrangerBo {
+        img_url: img_file::get_facetrack_smallimg_url(url_prefix, &x.last_ft_sid, 1),
+        bg_url: img_file::get_facetrack_bgimg_url(url_prefix, &x.last_ft_sid),
+        cluster_sid: x.cluster_sid,
+        appear_count: x.appear_count,
+        first_time: x.first_time,
+        last_time: x.last_time,
+        last_src_sid: x.last_src_sid,
+        alarm_time: x.alarm_time,
+    }).collect();
+    returndata::success(list)
+}
+
+//----------------- tracks -------------------------------
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TracksFormData {
+    pub cluster_sid: Option<String>,
+    pub limit: Option<String>,
+}
+
+/// 聚类中的抓拍，按抓拍时间降序
+pub async fn tracks(app_state: web::Data<AppState>,
+                    form: web::Query<TracksFormData>) -> ReturnDataType<Vec<StrangerTrackBo>> {
+    if !utils::option_must_length(&form.cluster_sid, 1, 50) {
+        return returndata::fail("invalid cluster_sid");
+    }
+    if !utils::option_should_num_range(&form.limit, 1, 1000) {
+        return returndata::fail("invalid limit");
+    }
+    let cluster_sid = form.cluster_sid.clone().unwrap();
+    let limit = utils::get_option_num(&form.limit).unwrap_or(100);
+
+    let ctx = app_state.ctx.clone();
+    let list = web::block(move || {
+        ctx.web_dao.get_stranger_tracks(&cluster_sid, limit)
+    }).await;
+    if let Err(e) = list {
+        error!("error, stranger_ctl, get_stranger_tracks, {:?}", e);
+        return returndata::fail("query db fail");
+    }
+    let list = list.unwrap();
+
+    let url_prefix = app_state.ctx.cfg.dfimg_url.as_str();
+    let list = list.into_iter().map(|x| StrangerTrackBo {
+        img_url: img_file::get_facetrack_smallimg_url(url_prefix, &x.ft_sid, 1),
+        bg_url: img_file::get_facetrack_bgimg_url(url_prefix, &x.ft_sid),
+        detail: x,
+    }).collect();
+    returndata::success(list)
+}
+
+//----------------- alarm list -------------------------------
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AlarmListFormData {
+    pub cluster_sid: Option<String>,
+    pub limit: Option<String>,
+}
+
+/// 频繁出现的陌生人报警
+pub async fn alarm_list(app_state: web::Data<AppState>,
+                        form: web::Query<AlarmListFormData>) -> ReturnDataType<Vec<StrangerAlarmBo>> {
+    if !utils::option_should_num_range(&form.limit, 1, 1000) {
+        return returndata::fail("invalid limit");
+    }
+    let cluster_sid = utils::clean_option_string(&form.cluster_sid);
+    let limit = utils::get_option_num(&form.limit).unwrap_or(100);
+
+    let ctx = app_state.ctx.clone();
+    let list = web::block(move || {
+        ctx.web_dao.get_stranger_alarm_list(cluster_sid, limit)
+    }).await;
+    if let Err(e) = list {
+        error!("error, stranger_ctl, get_stranger_alarm_list, {:?}", e);
+        return returndata::fail("query db fail");
+    }
+    let list = list.unwrap();
+
+    let url_prefix = app_state.ctx.cfg.dfimg_url.as_str();
+    let list = list.into_iter().map(|x| StrangerAlarmBo {
+        img_url: img_file::get_facetrack_smallimg_url(url_prefix, &x.ft_sid, 1),
+        bg_url: img_file::get_facetrack_bgimg_url(url_prefix, &x.ft_sid),
+        detail: x,
+    }).collect();
+    returndata::success(list)
+}
@@ -0,0 +1,32 @@
+use actix_web::web;
+
+use cffc_base::api::recg_cluster::RecgNodeStat;
+use cffc_base::model::returndata::{self, ReturnDataType};
+use cffc_base::util::bounded_queue::QueueStat;
+
+use crate::web::AppState;
+use crate::web::upload_auth::UploadAuthStatBo;
+
+/// 各队列长度、容量及丢弃数
+pub async fn queue_stat(app_state: web::Data<AppState>) -> ReturnDataType<Vec<QueueStat>> {
+    returndata::success(app_state.ctx.queue_stat.stats())
+}
+
+/// /trackupload 认证统计
+pub async fn upload_auth_stat(app_state: web::Data<AppState>) -> ReturnDataType<UploadAuthStatBo> {
+    returndata::success(app_state.auth_stat.to_bo())
+}
+
+/// 人脸库/车辆分组/报警规则/车牌 在 worker 之外修改后，通知重新加载
+pub async fn reload_registry(app_state: web::Data<AppState>) -> ReturnDataType<String> {
+    app_state.ctx.registry.invalidate_dbs();
+    app_state.ctx.registry.invalidate_groups();
+    app_state.ctx.registry.invalidate_rules();
+    app_state.ctx.registry.invalidate_plates();
+    returndata::success_str("succ")
+}
+
+/// 识别节点状态
+pub async fn recg_nodes(app_state: web::Data<AppState>) -> ReturnDataType<Vec<RecgNodeStat>> {
+    returndata::success(app_state.ctx.recg_api.stats())
+}
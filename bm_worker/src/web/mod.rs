@@ -1,35 +1,67 @@
+use std::path::PathBuf;
 use std::sync::Arc;
 
-use deadqueue::unlimited::Queue;
 use tera::Tera;
 
+use cffc_base::util::bounded_queue::BoundedQueue;
+use cffc_base::util::multipart_form::{MultipartOptions, UploadBudget};
+
 use crate::app_ctx::AppCtx;
-use crate::queue_item::{NotifyCarQueueItem, NotifyFaceQueueItem};
+use crate::queue_item::{NotifyCarQueueItem, NotifyFaceQueueItem, QI};
+use crate::web::upload_auth::{UploadAuthStat, UploadNonces};
 
 pub mod server;
 pub mod router;
 pub mod controllers;
 pub mod api_auth;
+pub mod upload_auth;
 pub mod proto;
 pub mod svc;
 
 pub struct AppState {
     pub ctx: Arc<AppCtx>,
-    pub face_queue: Arc<Queue<NotifyFaceQueueItem>>,
-    pub car_queue: Arc<Queue<NotifyCarQueueItem>>,
+    pub face_queue: Arc<BoundedQueue<NotifyFaceQueueItem>>,
+    pub car_queue: Arc<BoundedQueue<NotifyCarQueueItem>>,
+    /// 发送到 EntBusSvc, 复核确认的报警
+    pub bus_queue: Arc<BoundedQueue<QI>>,
 
     pub tmpl: Tera,
+
+    /// /trackupload 认证统计
+    pub auth_stat: UploadAuthStat,
+    /// /trackupload 按摄像头凭证中已使用的 nonce
+    pub auth_nonces: UploadNonces,
+
+    /// 上传中的字节数限制
+    pub upload_budget: Arc<UploadBudget>,
+    /// multipart 解析参数
+    pub multipart_opts: MultipartOptions,
 }
 
 impl AppState {
-    pub fn new(ctx: Arc<AppCtx>, face_queue: Arc<Queue<NotifyFaceQueueItem>>, car_queue: Arc<Queue<NotifyCarQueueItem>>) -> Self {
+    pub fn new(ctx: Arc<AppCtx>, face_queue: Arc<BoundedQueue<NotifyFaceQueueItem>>, car_queue: Arc<BoundedQueue<NotifyCarQueueItem>>,
+               bus_queue: Arc<BoundedQueue<QI>>) -> Self {
         let tera = Tera::new("views/**/*.tpl").unwrap();
 
+        let upload_cfg = &ctx.cfg.upload;
+        let upload_budget = Arc::new(UploadBudget::new(upload_cfg.max_inflight_mb * 1024 * 1024));
+        let multipart_opts = MultipartOptions {
+            spill_size: upload_cfg.spill_kb * 1024,
+            tmp_dir: PathBuf::from(&upload_cfg.tmp_path),
+            max_size: upload_cfg.max_request_mb * 1024 * 1024,
+            budget: Some(upload_budget.clone()),
+        };
+
         AppState {
             ctx,
             face_queue,
             car_queue,
+            bus_queue,
             tmpl: tera,
+            auth_stat: UploadAuthStat::new(),
+            auth_nonces: UploadNonces::new(),
+            upload_budget,
+            multipart_opts,
         }
     }
 }
@@ -61,6 +61,9 @@ pub struct CameraItem {
     pub rtcp_utc: i32,
     pub lane_desc: Option<String>,
     pub lane_count: i32,
+    pub speed_line1: i64,
+    pub speed_line2: i64,
+    pub speed_distance: f64,
     pub memo: Option<String>,
     pub gmt_create: DateTime<Local>,
     pub gmt_modified: DateTime<Local>,
@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 
 use crate::dao::model::{CfDfsource, CfFacetrack};
+use crate::web::proto::DataPage;
 use crate::web::proto::poi::PoiBo;
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -15,6 +16,17 @@ pub struct FtBoFace {
     pub score: f64,
 }
 
+/// 比对的 top-N 候选
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FtBoCandidate {
+    pub rank: i32,
+    pub poi_sid: String,
+    pub name: String,
+    pub score: f64,
+    pub db_sid: String,
+    pub db_name: String,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct FacetrackBo {
     pub sid: String,
@@ -25,4 +37,21 @@ pub struct FacetrackBo {
 
     #[serde(rename = "match")]
     pub match_poi: Option<PoiBo>,
-}
\ No newline at end of file
+
+    pub candidates: Vec<FtBoCandidate>,
+}
+
+/// 以图搜图的结果，按分值降序
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FtSearchItem {
+    pub score: f64,
+    pub facetrack: FacetrackBo,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FtSearchResult {
+    pub page: DataPage,
+    /// 比对的特征值超过 max_rows，只返回已比对部分的结果
+    pub truncated: bool,
+    pub list: Vec<FtSearchItem>,
+}
@@ -6,6 +6,8 @@ pub mod poi;
 pub mod facetrack;
 pub mod cartrack;
 pub mod coi;
+pub mod retro;
+pub mod stranger;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DataPage {
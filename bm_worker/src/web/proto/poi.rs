@@ -1,3 +1,4 @@
+use chrono::prelude::*;
 use serde::{Serialize, Deserialize};
 use crate::dao::model::{CfDfdb, CfPoi};
 
@@ -37,3 +38,34 @@ pub struct ImgAppendItem {
     pub feature: String,
     pub face_id: i64,
 }
+
+/// 轨迹中的一次抓拍
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TrajectoryTrack {
+    pub ft_sid: String,
+    pub img_url: String,
+    pub score: Option<f64>,
+    pub capture_time: DateTime<Local>,
+    /// 画面中停留的时间, millisecond
+    pub duration: i64,
+}
+
+/// 同一摄像头下连续的抓拍，合并为一次到访
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TrajectoryVisit {
+    pub src_sid: String,
+    pub camera_name: Option<String>,
+    pub location_name: Option<String>,
+    pub coordinate: Option<String>,
+    pub begin: DateTime<Local>,
+    pub end: DateTime<Local>,
+    /// 估算的停留时间, second
+    pub dwell_sec: i64,
+    pub tracks: Vec<TrajectoryTrack>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TrajectoryBo {
+    pub poi: PoiBo,
+    pub visits: Vec<TrajectoryVisit>,
+}
@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+use crate::dao::model::{CfRetroAlarm, CfRetroJob};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RetroAlarmBo {
+    pub img_url: String,
+    pub bg_url: String,
+    pub detail: CfRetroAlarm,
+}
+
+/// 任务及命中的记录
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RetroJobBo {
+    pub job: CfRetroJob,
+    pub alarms: Vec<RetroAlarmBo>,
+}
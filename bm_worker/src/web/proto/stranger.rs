@@ -0,0 +1,31 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::dao::model::{CfStrangerAlarm, CfStrangerTrack};
+
+/// 匿名聚类，不返回特征值
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StrangerBo {
+    pub cluster_sid: String,
+    pub appear_count: i64,
+    pub first_time: DateTime<Local>,
+    pub last_time: DateTime<Local>,
+    pub last_src_sid: String,
+    pub alarm_time: Option<DateTime<Local>>,
+    pub img_url: String,
+    pub bg_url: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StrangerTrackBo {
+    pub img_url: String,
+    pub bg_url: String,
+    pub detail: CfStrangerTrack,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StrangerAlarmBo {
+    pub img_url: String,
+    pub bg_url: String,
+    pub detail: CfStrangerAlarm,
+}
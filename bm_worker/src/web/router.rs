@@ -10,7 +10,7 @@ use log::debug;
 
 use crate::services::ws::agent::WsAgent;
 use crate::services::ws::session::WsSession;
-use crate::web::controllers::{admin_ctl, coi_ctl};
+use crate::web::controllers::{admin_ctl, alarm_rule_ctl, coi_ctl};
 use crate::web::controllers::camera_ctl;
 use crate::web::controllers::cartrack_ctl;
 use crate::web::controllers::crop_ctl;
@@ -20,6 +20,11 @@ use crate::web::controllers::home_ctl;
 use crate::web::controllers::logon;
 use crate::web::controllers::notify_handle;
 use crate::web::controllers::poi_ctl;
+use crate::web::controllers::sys_ctl;
+use crate::web::controllers::retro_ctl;
+use crate::web::controllers::stranger_ctl;
+use crate::web::controllers::lane_stat_ctl;
+use crate::web::controllers::parking_ctl;
 
 async fn ws_route(web::Path((room)): web::Path<(String)>, req: HttpRequest,
                   stream: web::Payload, srv: web::Data<Addr<WsAgent>>) -> Result<HttpResponse, Error> {
@@ -47,6 +52,7 @@ pub fn config(cfg: &mut web::ServiceConfig) {
         .service(web::resource("/ws/{room}").to(ws_route))
         .route("/testupload", web::post().to(notify_handle::test_upload))
         .route("/trackupload", web::post().to(notify_handle::track_upload))
+        .route("/trackupload/json", web::post().to(notify_handle::track_upload_json))
         .route("/getsingleimg", web::get().to(getsingleimg::get))
         .route("/", web::get().to(logon::login))
         .route("/logon", web::post().to(logon::logon))
@@ -63,24 +69,52 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .route("/camera/modify", web::post().to(camera_ctl::modify))
             .route("/camera/setOnScreen", web::post().to(camera_ctl::set_on_screen))
             .route("/camera/setState", web::post().to(camera_ctl::set_state))
+            .route("/camera/setSpeedCalib", web::post().to(camera_ctl::set_speed_calib))
 
             .route("/group/list", web::get().to(poi_ctl::group_list))
+            .route("/group/modify", web::post().to(poi_ctl::group_modify))
             .route("/poi/detail", web::get().to(poi_ctl::detail))
             .route("/poi/list", web::get().to(poi_ctl::list))
             .route("/poi/add", web::post().to(poi_ctl::add))
             .route("/poi/delete", web::post().to(poi_ctl::delete))
             .route("/poi/modify", web::post().to(poi_ctl::modify))
+            .route("/poi/trajectory", web::get().to(poi_ctl::trajectory))
+            .route("/retro/jobList", web::get().to(retro_ctl::job_list))
+            .route("/retro/job", web::get().to(retro_ctl::job_detail))
+            .route("/retro/add", web::post().to(retro_ctl::add))
+            .route("/stranger/list", web::get().to(stranger_ctl::list))
+            .route("/stranger/tracks", web::get().to(stranger_ctl::tracks))
+            .route("/stranger/alarmList", web::get().to(stranger_ctl::alarm_list))
+            .route("/laneStat/list", web::get().to(lane_stat_ctl::list))
+            .route("/laneStat/export", web::get().to(lane_stat_ctl::export))
+            .route("/parking/visitList", web::get().to(parking_ctl::visit_list))
+            .route("/parking/occupancy", web::get().to(parking_ctl::occupancy))
+            .route("/parking/overstayList", web::get().to(parking_ctl::overstay_list))
 
             .route("/facetrack/list", web::get().to(facetrack_ctl::list))
+            .route("/facetrack/searchByImage", web::post().to(facetrack_ctl::search_by_image))
             .route("/cartrack/list", web::get().to(cartrack_ctl::list))
+            .route("/cartrack/reviewList", web::get().to(cartrack_ctl::review_list))
+            .route("/cartrack/review", web::post().to(cartrack_ctl::review))
 
             .route("/coi/group_list", web::get().to(coi_ctl::group_list))
+            .route("/coi/group_modify", web::post().to(coi_ctl::group_modify))
             .route("/coi/detail", web::get().to(coi_ctl::detail))
             .route("/coi/list", web::get().to(coi_ctl::list))
             .route("/coi/add", web::post().to(coi_ctl::add))
             .route("/coi/delete", web::post().to(coi_ctl::delete))
             .route("/coi/modify", web::post().to(coi_ctl::modify))
 
+            .route("/alarmRule/list", web::get().to(alarm_rule_ctl::list))
+            .route("/alarmRule/add", web::post().to(alarm_rule_ctl::add))
+            .route("/alarmRule/modify", web::post().to(alarm_rule_ctl::modify))
+            .route("/alarmRule/delete", web::post().to(alarm_rule_ctl::delete))
+
+            .route("/sys/queueStat", web::get().to(sys_ctl::queue_stat))
+            .route("/sys/uploadAuthStat", web::get().to(sys_ctl::upload_auth_stat))
+            .route("/sys/reloadRegistry", web::post().to(sys_ctl::reload_registry))
+            .route("/sys/recgNodes", web::get().to(sys_ctl::recg_nodes))
+
 
             .service(
                 web::resource("/crop")
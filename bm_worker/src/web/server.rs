@@ -7,12 +7,13 @@ use actix_web::dev::{Server, Service};
 use actix_web::http::{HeaderName, HeaderValue};
 use actix_web::middleware::Logger;
 use chrono::prelude::*;
-use deadqueue::unlimited::Queue;
 use log::{error, info};
 use tokio::stream::StreamExt;
 use tokio::sync::watch::Receiver;
 use tokio::task::JoinHandle as TkJoinHandle;
 
+use cffc_base::util::bounded_queue::BoundedQueue;
+
 use crate::app_ctx::AppCtx;
 use crate::queue_item::{NotifyCarQueueItem, NotifyFaceQueueItem};
 use crate::queue_item::QI;
@@ -26,19 +27,21 @@ use super::router;
 
 pub struct WebServer {
     ctx: Arc<AppCtx>,
-    face_queue: Arc<Queue<NotifyFaceQueueItem>>,
-    car_queue: Arc<Queue<NotifyCarQueueItem>>,
-    ws_queue: Arc<Queue<QI>>,
+    face_queue: Arc<BoundedQueue<NotifyFaceQueueItem>>,
+    car_queue: Arc<BoundedQueue<NotifyCarQueueItem>>,
+    ws_queue: Arc<BoundedQueue<QI>>,
+    bus_queue: Arc<BoundedQueue<QI>>,
 }
 
 impl WebServer {
-    pub fn new(ctx: Arc<AppCtx>, face_queue: Arc<Queue<NotifyFaceQueueItem>>, car_queue: Arc<Queue<NotifyCarQueueItem>>,
-               ws_queue: Arc<Queue<QI>>) -> Self {
+    pub fn new(ctx: Arc<AppCtx>, face_queue: Arc<BoundedQueue<NotifyFaceQueueItem>>, car_queue: Arc<BoundedQueue<NotifyCarQueueItem>>,
+               ws_queue: Arc<BoundedQueue<QI>>, bus_queue: Arc<BoundedQueue<QI>>) -> Self {
         WebServer {
             ctx,
             face_queue,
             car_queue,
             ws_queue,
+            bus_queue,
         }
     }
 }
@@ -49,6 +52,7 @@ impl CfService for WebServer {
         let face_queue = self.face_queue;
         let car_queue = self.car_queue;
         let ws_queue = self.ws_queue;
+        let bus_queue = self.bus_queue;
 
         let mut exit_rx = rx;
 
@@ -70,7 +74,7 @@ impl CfService for WebServer {
 
             let ws_agent = WsAgent::new(ws_worker).start();
 
-            let state = web::Data::new(AppState::new(ctx, face_queue, car_queue));
+            let state = web::Data::new(AppState::new(ctx, face_queue, car_queue, bus_queue));
 
             let server = HttpServer::new(move || {
                 App::new().app_data(state.clone())
@@ -72,6 +72,9 @@ pub fn to_cameraitem_list(src_list: &[CfDfsource], node_list: &[CfDfnode],
             rtcp_utc: v.rtcp_utc,
             lane_desc: v.lane_desc.clone(),
             lane_count: v.lane_count,
+            speed_line1: v.speed_line1,
+            speed_line2: v.speed_line2,
+            speed_distance: v.speed_distance,
             memo: v.memo.clone(),
             gmt_create: v.gmt_create,
             gmt_modified: v.gmt_modified,
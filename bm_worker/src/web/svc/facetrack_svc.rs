@@ -1,11 +1,12 @@
 use cffc_base::model::img_file;
 
-use crate::dao::model::{CfDfdb, CfDfsource, CfFacetrack, CfPoi};
-use crate::web::proto::facetrack::{FacetrackBo, FtBoFace};
+use crate::dao::model::{CfDfdb, CfDfsource, CfFacetrack, CfFacetrackCandidate, CfPoi};
+use crate::web::proto::facetrack::{FacetrackBo, FtBoCandidate, FtBoFace};
 use crate::web::svc::poi_svc;
 
 pub fn to_bo(po: &CfFacetrack, db_list: &Vec<CfDfdb>,
-             camera_list: &Vec<CfDfsource>, url_prefix: &str, poi: Option<CfPoi>) -> FacetrackBo {
+             camera_list: &Vec<CfDfsource>, url_prefix: &str, poi: Option<CfPoi>,
+             candidates: &[CfFacetrackCandidate]) -> FacetrackBo {
     let mut faces = Vec::new();
     let ids = img_file::get_item_from_idscores(po.img_ids.as_str());
 
@@ -35,6 +36,17 @@ pub fn to_bo(po: &CfFacetrack, db_list: &Vec<CfDfdb>,
         None => None,
     };
 
+    let candidates = candidates.iter().map(|x| {
+        FtBoCandidate {
+            rank: x.rank_num,
+            poi_sid: x.poi_sid.clone(),
+            name: x.poi_name.clone().unwrap_or_default(),
+            score: x.score,
+            db_sid: x.db_sid.clone(),
+            db_name: db_list.iter().find(|d| d.db_sid.eq(&x.db_sid)).map_or("".to_string(), |d| d.name.clone()),
+        }
+    }).collect();
+
     FacetrackBo {
         sid: po.ft_sid.clone(),
         bg_url,
@@ -42,6 +54,7 @@ pub fn to_bo(po: &CfFacetrack, db_list: &Vec<CfDfdb>,
         detail: po.clone(),
         camera,
         match_poi,
+        candidates,
     }
 }
 
@@ -2,9 +2,11 @@ use log::error;
 
 use cffc_base::model::img_file;
 
-use crate::dao::model::{CfDfdb, CfPoi};
+use chrono::Duration;
 
-use crate::web::proto::poi::{PoiBo, PoiBoFace};
+use crate::dao::model::{CfDfdb, CfDfsource, CfFacetrack, CfPoi};
+
+use crate::web::proto::poi::{PoiBo, PoiBoFace, TrajectoryTrack, TrajectoryVisit};
 
 fn find_group(sid: &str, db_list: &Vec<CfDfdb>) -> Option<CfDfdb> {
     db_list.iter().find_map(|x| {
@@ -57,5 +59,54 @@ pub fn to_bo_list(po_list: &Vec<CfPoi>, db_list: &Vec<CfDfdb>, url_prefix: &str)
     list
 }
 
+/// position 中的出现/消失时间，无效时为 0
+fn get_track_duration(po: &CfFacetrack) -> i64 {
+    if po.start_real_time > 0 && po.end_real_time > po.start_real_time {
+        po.end_real_time - po.start_real_time
+    } else {
+        0
+    }
+}
+
+/// list 按 capture_time 升序
+/// 同一摄像头，与上一次抓拍间隔不超过 gap_sec 的合并为一次到访
+/// 停留时间: 第一次抓拍到最后一次抓拍，加上最后一次在画面中的时间
+pub fn to_trajectory(list: &[CfFacetrack], camera_list: &[CfDfsource], url_prefix: &str, gap_sec: i64) -> Vec<TrajectoryVisit> {
+    let mut visits: Vec<TrajectoryVisit> = Vec::new();
 
+    for po in list.iter() {
+        let (face_id, _) = img_file::get_item_from_idscores(po.img_ids.as_str())
+            .ok().and_then(|x| x.first().copied()).unwrap_or((1, 0_f64));
+        let duration = get_track_duration(po);
+        let track = TrajectoryTrack {
+            ft_sid: po.ft_sid.clone(),
+            img_url: img_file::get_facetrack_smallimg_url(url_prefix, &po.ft_sid, face_id),
+            score: po.most_score,
+            capture_time: po.capture_time,
+            duration,
+        };
 
+        match visits.last_mut() {
+            Some(v) if v.src_sid == po.src_sid && po.capture_time - v.end <= Duration::seconds(gap_sec) => {
+                v.end = po.capture_time;
+                v.dwell_sec = (v.end - v.begin).num_seconds() + duration / 1000;
+                v.tracks.push(track);
+            }
+            _ => {
+                let camera = camera_list.iter().find(|x| x.src_sid.eq_ignore_ascii_case(&po.src_sid));
+                visits.push(TrajectoryVisit {
+                    src_sid: po.src_sid.clone(),
+                    camera_name: camera.map(|x| x.name.clone()),
+                    location_name: camera.and_then(|x| x.location_name.clone()),
+                    coordinate: camera.and_then(|x| x.coordinate.clone()),
+                    begin: po.capture_time,
+                    end: po.capture_time,
+                    dwell_sec: duration / 1000,
+                    tracks: vec![track],
+                });
+            }
+        }
+    }
+
+    visits
+}
@@ -0,0 +1,370 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use actix_web::{HttpRequest, web};
+use actix_web::web::Query;
+use chrono::prelude::*;
+use dashmap::DashMap;
+use log::{debug, error};
+use serde::{Deserialize, Serialize};
+
+use cffc_base::util::utils;
+
+use crate::web::AppState;
+
+/// /trackupload 认证，密钥为摄像头所属 cf_dfnode 的 auth_key
+/// 1) 按摄像头的凭证: push_url 中带 ?src=xxx, 每次上传追加 &ts=秒&nonce=随机串&token=hex(hmac_sha256(auth_key, src_sid + ts + nonce + hex(sha256(body))))
+///    同一摄像头的 nonce 在 max_skew_sec 内只能使用一次
+/// 2) hmac: header x-cf-ts(秒), x-cf-sign = hex(hmac_sha256(auth_key, ts + hex(sha256(body))))
+///    body 边接收边计算 sha256, 不需要缓存完整的 body
+/// ts 与服务器时间的偏差不超过 max_skew_sec, 签名按固定耗时比较
+pub const HEADER_TS: &str = "x-cf-ts";
+pub const HEADER_SIGN: &str = "x-cf-sign";
+
+#[derive(Deserialize)]
+struct UploadQuery {
+    src: Option<String>,
+    ts: Option<String>,
+    nonce: Option<String>,
+    token: Option<String>,
+}
+
+/// 请求中的认证信息
+pub struct UploadCred {
+    pub peer: String,
+    pub src: Option<String>,
+    pub src_ts: Option<String>,
+    pub nonce: Option<String>,
+    pub token: Option<String>,
+    pub ts: Option<String>,
+    pub sign: Option<String>,
+    /// hex(sha256(body))
+    pub body_sha256: String,
+}
+
+impl UploadCred {
+    pub fn from_request(req: &HttpRequest, body_sha256: String) -> Self {
+        let query = Query::<UploadQuery>::from_query(req.query_string()).ok();
+        let header_value = |name: &str| {
+            req.headers().get(name).and_then(|x| x.to_str().ok()).map(|x| x.to_string())
+        };
+
+        UploadCred {
+            peer: req.peer_addr().map_or(String::new(), |x| x.ip().to_string()),
+            src: query.as_ref().and_then(|x| x.src.clone()),
+            src_ts: query.as_ref().and_then(|x| x.ts.clone()),
+            nonce: query.as_ref().and_then(|x| x.nonce.clone()),
+            token: query.as_ref().and_then(|x| x.token.clone()),
+            ts: header_value(HEADER_TS),
+            sign: header_value(HEADER_SIGN),
+            body_sha256,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UploadAuthFail {
+    pub peer: String,
+    pub count: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UploadAuthStatBo {
+    pub passed: u64,
+    pub failed: u64,
+    pub fails: Vec<UploadAuthFail>,
+}
+
+/// 认证统计
+#[derive(Default)]
+pub struct UploadAuthStat {
+    passed: AtomicU64,
+    failed: AtomicU64,
+    /// 按来源ip统计失败数
+    fails: DashMap<String, u64>,
+}
+
+impl UploadAuthStat {
+    pub fn new() -> Self {
+        UploadAuthStat::default()
+    }
+
+    fn pass(&self) {
+        self.passed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn fail(&self, key: &str) {
+        self.failed.fetch_add(1, Ordering::Relaxed);
+        *self.fails.entry(key.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn to_bo(&self) -> UploadAuthStatBo {
+        let mut fails: Vec<UploadAuthFail> = self.fails.iter().map(|x| UploadAuthFail {
+            peer: x.key().clone(),
+            count: *x.value(),
+        }).collect();
+        fails.sort_by(|a, b| b.count.cmp(&a.count));
+
+        UploadAuthStatBo {
+            passed: self.passed.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            fails,
+        }
+    }
+}
+
+/// 已使用的 nonce, 防止重放
+pub struct UploadNonces {
+    /// src_sid:nonce -> ts
+    map: DashMap<String, i64>,
+    last_clean: Mutex<i64>,
+}
+
+impl UploadNonces {
+    pub fn new() -> Self {
+        UploadNonces {
+            map: DashMap::new(),
+            last_clean: Mutex::new(Local::now().timestamp()),
+        }
+    }
+
+    /// 未使用过返回 true, 并记录
+    fn check_insert(&self, src_sid: &str, nonce: &str, now: i64, max_skew: i64) -> bool {
+        // ts 超出偏差的凭证不会通过校验，过期的 nonce 不用保留
+        {
+            let mut last = self.last_clean.lock().unwrap();
+            if now - *last >= max_skew {
+                *last = now;
+                self.map.retain(|_, v| now - *v <= 2 * max_skew);
+            }
+        }
+
+        let key = format!("{}:{}", src_sid, nonce);
+        if self.map.contains_key(&key) {
+            return false;
+        }
+        self.map.insert(key, now);
+        true
+    }
+}
+
+impl Default for UploadNonces {
+    fn default() -> Self {
+        UploadNonces::new()
+    }
+}
+
+pub fn gen_src_token(src_sid: &str, ts: &str, nonce: &str, body_sha256: &str, auth_key: &str) -> String {
+    utils::hmac_sha256_hex(auth_key, &[src_sid.as_bytes(), ts.as_bytes(), nonce.as_bytes(), body_sha256.as_bytes()])
+}
+
+/// 摄像头的推送地址，开启认证时带上 src, 上传时再追加 ts 和 token
+pub fn gen_push_url(notify_url: &str, src_sid: &str, auth_key: &Option<String>) -> String {
+    match auth_key {
+        Some(ref key) if !key.is_empty() => {
+            let sep = if notify_url.contains('?') { '&' } else { '?' };
+            format!("{}{}src={}", notify_url, sep, src_sid)
+        }
+        _ => notify_url.to_string(),
+    }
+}
+
+fn check_ts(ts: &str, max_skew: i64) -> Result<(), String> {
+    let ts_val: i64 = ts.parse().map_err(|_| format!("invalid ts: {}", ts))?;
+    if (Local::now().timestamp() - ts_val).abs() > max_skew {
+        return Err(format!("ts expired: {}", ts));
+    }
+    Ok(())
+}
+
+fn check_sign(calc: &str, sign: &str) -> bool {
+    utils::const_time_eq(calc.as_bytes(), sign.to_ascii_lowercase().as_bytes())
+}
+
+/// 按节点生成摄像头的推送地址
+pub async fn load_push_url(data: &web::Data<AppState>, node_sid: &str, src_sid: &str) -> String {
+    let notify_url = data.ctx.cfg.web.notify_url.as_str();
+    if !data.ctx.cfg.upload_auth.enable {
+        return notify_url.to_string();
+    }
+
+    let ctx = data.ctx.clone();
+    let sid = node_sid.to_string();
+    let node = web::block(move || {
+        ctx.web_dao.load_dfnode_by_sid(&sid)
+    }).await;
+
+    match node {
+        Ok(Some(v)) => gen_push_url(notify_url, src_sid, &v.auth_key),
+        Ok(None) => notify_url.to_string(),
+        Err(e) => {
+            error!("error, load_push_url, load_dfnode_by_sid, {:?}", e);
+            notify_url.to_string()
+        }
+    }
+}
+
+fn verify(cred: &UploadCred, src_sid: &str, auth_key: &str, max_skew: i64) -> Result<(), String> {
+    // 按摄像头的凭证
+    if let Some(ref token) = cred.token {
+        if cred.src.as_deref() != Some(src_sid) {
+            return Err(format!("src mismatch, {:?}", cred.src));
+        }
+        let ts = cred.src_ts.as_deref().ok_or_else(|| "no ts".to_string())?;
+        check_ts(ts, max_skew)?;
+        let nonce = match cred.nonce.as_deref() {
+            Some(v) if !v.is_empty() => v,
+            _ => return Err("no nonce".to_string()),
+        };
+
+        if check_sign(&gen_src_token(src_sid, ts, nonce, &cred.body_sha256, auth_key), token) {
+            return Ok(());
+        }
+        return Err("invalid token".to_string());
+    }
+
+    // hmac
+    if let (Some(ref ts), Some(ref sign)) = (&cred.ts, &cred.sign) {
+        check_ts(ts, max_skew)?;
+
+        let sign_calc = utils::hmac_sha256_hex(auth_key, &[ts.as_bytes(), cred.body_sha256.as_bytes()]);
+        if check_sign(&sign_calc, sign) {
+            return Ok(());
+        }
+        return Err("invalid sign".to_string());
+    }
+
+    Err("no credential".to_string())
+}
+
+/// 按摄像头的凭证，校验通过后 nonce 不能再使用
+fn check_nonce(data: &web::Data<AppState>, cred: &UploadCred, src_sid: &str, max_skew: i64) -> Result<(), String> {
+    match (&cred.token, &cred.nonce) {
+        (Some(_), Some(nonce)) => {
+            if data.auth_nonces.check_insert(src_sid, nonce, Local::now().timestamp(), max_skew) {
+                Ok(())
+            } else {
+                Err(format!("nonce reused: {}", nonce))
+            }
+        }
+        _ => Ok(()),
+    }
+}
+
+/// 校验上传的通知, src_sid 为通知中的 source
+pub async fn check(data: &web::Data<AppState>, cred: &Option<UploadCred>, src_sid: &str) -> Result<(), String> {
+    let cred = match cred {
+        Some(v) => v,
+        None => return Ok(()),
+    };
+
+    let ctx = data.ctx.clone();
+    let sid = src_sid.to_string();
+    let node = web::block(move || {
+        ctx.web_dao.load_dfnode_by_src_sid(&sid)
+    }).await;
+
+    let max_skew = data.ctx.cfg.upload_auth.max_skew_sec;
+    let rst = match node {
+        Ok(Some(v)) => {
+            match v.auth_key {
+                Some(ref key) if !key.is_empty() => {
+                    verify(cred, src_sid, key, max_skew).and_then(|_| check_nonce(data, cred, src_sid, max_skew))
+                }
+                _ => Err(format!("node: {} has no auth_key", v.node_sid)),
+            }
+        }
+        Ok(None) => Err("unknown source".to_string()),
+        Err(e) => Err(format!("load_dfnode_by_src_sid, {:?}", e)),
+    };
+
+    match rst {
+        Ok(_) => {
+            debug!("upload auth ok, {}, {}", src_sid, cred.peer);
+            data.auth_stat.pass();
+            Ok(())
+        }
+        Err(e) => {
+            error!("error, upload auth fail, src:{}, peer:{}, {}", src_sid, cred.peer, e);
+            data.auth_stat.fail(&cred.peer);
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "key";
+    const SRC: &str = "src1";
+
+    fn empty_cred(body: &[u8]) -> UploadCred {
+        UploadCred {
+            peer: String::new(),
+            src: None,
+            src_ts: None,
+            nonce: None,
+            token: None,
+            ts: None,
+            sign: None,
+            body_sha256: utils::sha256_hex(body),
+        }
+    }
+
+    fn token_cred(body: &[u8], ts: i64, nonce: &str) -> UploadCred {
+        let ts = ts.to_string();
+        let mut cred = empty_cred(body);
+        cred.token = Some(gen_src_token(SRC, &ts, nonce, &cred.body_sha256, KEY));
+        cred.src = Some(SRC.to_string());
+        cred.src_ts = Some(ts);
+        cred.nonce = Some(nonce.to_string());
+        cred
+    }
+
+    #[test]
+    fn verify_token() {
+        let now = Local::now().timestamp();
+        assert_eq!(verify(&token_cred(b"body", now, "n1"), SRC, KEY, 60), Ok(()));
+
+        // token 与 body 绑定
+        let mut cred = token_cred(b"body", now, "n1");
+        cred.body_sha256 = utils::sha256_hex(b"other");
+        assert!(verify(&cred, SRC, KEY, 60).is_err());
+
+        let mut cred = token_cred(b"body", now, "n1");
+        cred.nonce = None;
+        assert_eq!(verify(&cred, SRC, KEY, 60), Err("no nonce".to_string()));
+
+        assert!(verify(&token_cred(b"body", now, "n1"), "src2", KEY, 60).is_err());
+        assert!(verify(&token_cred(b"body", now, "n1"), SRC, "key2", 60).is_err());
+        assert!(verify(&token_cred(b"body", now - 61, "n1"), SRC, KEY, 60).is_err());
+    }
+
+    #[test]
+    fn verify_hmac() {
+        let ts = Local::now().timestamp().to_string();
+        let mut cred = empty_cred(b"body");
+        cred.sign = Some(utils::hmac_sha256_hex(KEY, &[ts.as_bytes(), cred.body_sha256.as_bytes()]).to_uppercase());
+        cred.ts = Some(ts);
+        assert_eq!(verify(&cred, SRC, KEY, 60), Ok(()));
+
+        cred.body_sha256 = utils::sha256_hex(b"other");
+        assert_eq!(verify(&cred, SRC, KEY, 60), Err("invalid sign".to_string()));
+
+        assert_eq!(verify(&empty_cred(b"body"), SRC, KEY, 60), Err("no credential".to_string()));
+    }
+
+    #[test]
+    fn nonce_used_once() {
+        let nonces = UploadNonces::new();
+        let now = Local::now().timestamp();
+        assert!(nonces.check_insert(SRC, "n1", now, 60));
+        assert!(!nonces.check_insert(SRC, "n1", now + 1, 60));
+        assert!(nonces.check_insert("src2", "n1", now, 60));
+
+        // 超过 2 * max_skew 后清理
+        assert!(nonces.check_insert(SRC, "n2", now + 121, 60));
+        assert!(nonces.check_insert(SRC, "n1", now + 121, 60));
+    }
+}
@@ -12,6 +12,9 @@ use reqwest::header;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::{Error as Serde_Error, Value};
 
+use crate::util::multipart_form::TempFile;
+use crate::util::utils;
+
 #[derive(Debug)]
 pub enum ApiError {
     NetErr(String),
@@ -198,6 +201,18 @@ pub struct ApiFaceProps {
     pub gender: i64,
     pub glasses: i64,
     pub move_direction: i64,
+
+    // 扩展属性，新版本的 notify 才有
+    /// 0 未知; 1 有; 2 没有
+    #[serde(default)]
+    pub mask: Option<i64>,
+    #[serde(default)]
+    pub moustache: Option<i64>,
+    #[serde(default)]
+    pub hat: Option<i64>,
+    /// 人脸角度分
+    #[serde(default)]
+    pub plane_score: Option<f64>,
 }
 
 type ApiAngles = [f64; 3];
@@ -208,6 +223,7 @@ pub struct NotifyBackground {
     pub height: i64,
     pub width: i64,
     pub image: Option<String>,
+    #[serde(default)]
     pub image_file: String,
     pub rect: ApiRect,
     pub video_width: i64,
@@ -215,16 +231,24 @@ pub struct NotifyBackground {
 
     #[serde(skip)]
     pub image_buf: Bytes,
+    /// 上传时写入临时文件的大图, 此时 image_buf 为空, drop 时删除
+    #[serde(skip)]
+    pub image_spill: Option<TempFile>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct NotifyFace {
     pub aligned: Option<String>,
+    #[serde(default)]
     pub aligned_file: String,
     pub angles: ApiAngles,
     pub display: Option<String>,
+    #[serde(default)]
     pub display_file: String,
     pub feature_file: Option<String>,
+    /// json 上传时 base64 特征值
+    #[serde(default)]
+    pub feature: Option<String>,
     pub frame_num: i64,
     pub quality: f64,
     pub rect: ApiRect,
@@ -265,6 +289,7 @@ pub struct FaceNotifyParams {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct NotifyCar {
     pub image: Option<String>,
+    #[serde(default)]
     pub image_file: String,
     pub frame_num: i64,
     pub rect: ApiRect,
@@ -279,7 +304,7 @@ pub struct ApiCarPlateType {
     pub conf: f64,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ApiCarPlateBit {
     pub value: String,
     pub conf: f64,
@@ -535,6 +560,14 @@ pub struct ApiDetectFaceAttr {
     pub gender: i64,
     pub glasses: i64,
     pub direction: i64,
+
+    /// 0 未知; 1 有; 2 没有
+    #[serde(default)]
+    pub mask: Option<i64>,
+    #[serde(default)]
+    pub moustache: Option<i64>,
+    #[serde(default)]
+    pub hat: Option<i64>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -640,7 +673,7 @@ pub struct GetDbsRes {
     pub dbs: Option<Vec<String>>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ApiFeatureQuality {
     pub feature: String,
     pub quality: f64,
@@ -945,19 +978,30 @@ pub struct RecognitionApi {
 
 impl RecognitionApi {
     pub fn new(url: &str) -> Self {
+        Self::build(url, None)
+    }
+
+    /// 带请求超时
+    pub fn with_timeout(url: &str, timeout: Duration) -> Self {
+        Self::build(url, Some(timeout))
+    }
+
+    fn build(url: &str, timeout: Option<Duration>) -> Self {
         let mut headers = header::HeaderMap::new();
         headers.insert(header::USER_AGENT, header::HeaderValue::from_static("bm-api/1.0"));
         headers.insert(header::ACCEPT_ENCODING, header::HeaderValue::from_static("gzip, deflate"));
         headers.insert(header::ALLOW, header::HeaderValue::from_static("*/*"));
         headers.insert(header::CONNECTION, header::HeaderValue::from_static("keep-alive"));
 
-        let client = Client::builder()
+        let mut builder = Client::builder()
             .connect_timeout(Duration::from_secs(3))
             .pool_max_idle_per_host(0)
             .default_headers(headers)
-            .danger_accept_invalid_certs(true)
-            .build().unwrap();
-
+            .danger_accept_invalid_certs(true);
+        if let Some(v) = timeout {
+            builder = builder.timeout(v);
+        }
+        let client = builder.build().unwrap();
 
         Self {
             url: url.to_string(),
@@ -1118,6 +1162,23 @@ impl RecognitionApi {
 }
 
 //------------------------ impl notify ------------------------
+impl NotifyBackground {
+    /// 保存背景图，在临时文件中时直接复制
+    pub async fn write_image(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        match self.image_spill {
+            Some(ref v) => utils::copy_jpg_file(&v.path, path).await,
+            None => utils::write_jpg_file(path, self.image_buf.as_ref()).await,
+        }
+    }
+
+    pub fn clear_image(&mut self) {
+        if !self.image_buf.is_empty() {
+            self.image_buf = Bytes::new();
+        }
+        self.image_spill = None;
+    }
+}
+
 impl FaceNotifyParams {
     pub fn has_trip_info(&self) -> bool {
         if let Some(ref v) = self.trip {
@@ -1128,9 +1189,7 @@ impl FaceNotifyParams {
 
 
     pub fn clear_blob(&mut self, clean_fea: bool) {
-        if !self.background.image_buf.is_empty() {
-            self.background.image_buf = Bytes::new();
-        }
+        self.background.clear_image();
 
         let _: Vec<()> = self.faces.iter_mut().map(|x| {
             if !x.aligned_buf.is_empty() {
@@ -1243,9 +1302,7 @@ impl CarNotifyParams {
 
 
     pub fn clear_blob(&mut self) {
-        if !self.background.image_buf.is_empty() {
-            self.background.image_buf = Bytes::new();
-        }
+        self.background.clear_image();
 
         if let Some(x) = self.plate_info.as_mut() {
             if !x.img_buf.is_empty() {
@@ -1263,6 +1320,11 @@ impl CarNotifyParams {
         }).collect();
     }
 
+    /// 车牌每个字符的候选及置信度
+    pub fn get_plate_bits(&self) -> Vec<Vec<ApiCarPlateBit>> {
+        self.plate_info.as_ref().and_then(|x| x.bits.clone()).unwrap_or_default()
+    }
+
     /// 车牌号码置信度
     pub fn get_plate_confidence(&self) -> Option<f64> {
         if let Some(ref v) = self.plate_info {
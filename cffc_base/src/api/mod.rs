@@ -1,2 +1,4 @@
 pub mod bm_api;
-pub mod lane;
\ No newline at end of file
+pub mod lane;
+pub mod recg_cluster;
+pub mod speed;
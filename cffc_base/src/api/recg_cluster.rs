@@ -0,0 +1,505 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+
+use super::bm_api::*;
+
+/// 读请求的节点选择方式
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectPolicy {
+    RoundRobin,
+    LeastLatency,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RecgClusterOptions {
+    pub policy: SelectPolicy,
+    /// 连续失败次数达到后熔断
+    pub fail_threshold: u32,
+    /// 熔断时长，之后放行一次试探
+    pub open_sec: u64,
+    /// 单次请求超时, 0 不限制
+    pub timeout_ms: u64,
+}
+
+impl Default for RecgClusterOptions {
+    fn default() -> Self {
+        RecgClusterOptions {
+            policy: SelectPolicy::RoundRobin,
+            fail_threshold: 3,
+            open_sec: 30,
+            timeout_ms: 10000,
+        }
+    }
+}
+
+#[derive(Default)]
+struct NodeState {
+    consecutive_fail: u32,
+    open_until: Option<Instant>,
+    /// 平均耗时(ms), 指数平滑
+    latency_ms: f64,
+    total_ok: u64,
+    total_fail: u64,
+}
+
+struct RecgNode {
+    api: Arc<RecognitionApi>,
+    state: Mutex<NodeState>,
+    /// 熔断到期后的试探请求是否在进行中
+    probing: AtomicBool,
+}
+
+/// 节点是否放行请求
+enum Gate {
+    Closed,
+    /// 熔断到期，放行一个试探
+    Probe,
+    Reject,
+}
+
+/// 试探结束 (包括请求被取消) 时清除 probing
+struct ProbeGuard<'a>(Option<&'a AtomicBool>);
+
+impl Drop for ProbeGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(v) = self.0 {
+            v.store(false, Ordering::Release);
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct RecgNodeStat {
+    pub url: String,
+    pub primary: bool,
+    pub available: bool,
+    pub consecutive_fail: u32,
+    pub latency_ms: f64,
+    pub total_ok: u64,
+    pub total_fail: u64,
+}
+
+/// 网络错误和返回格式错误才算节点故障，业务错误不切换节点
+pub fn is_node_err(e: &ApiError) -> bool {
+    matches!(e, ApiError::NetErr(_) | ApiError::JsonErr(_))
+}
+
+/// 多个识别节点
+/// 读请求按 policy 选择可用节点，节点故障时切换到下一个
+/// 写请求 (建库、增删人员等) 只发往主节点 (第一个)，保证各次写入落在同一节点
+/// 每个节点独立熔断：连续失败 fail_threshold 次后熔断 open_sec 秒，到期后只放行一个试探
+pub struct RecgCluster {
+    nodes: Vec<RecgNode>,
+    opts: RecgClusterOptions,
+    next: AtomicUsize,
+}
+
+impl RecgCluster {
+    /// urls 不能为空，第一个为主节点
+    pub fn new(urls: &[String], opts: RecgClusterOptions) -> Self {
+        assert!(!urls.is_empty(), "RecgCluster, urls is empty");
+
+        let nodes = urls.iter().map(|url| {
+            let api = if opts.timeout_ms > 0 {
+                RecognitionApi::with_timeout(url, Duration::from_millis(opts.timeout_ms))
+            } else {
+                RecognitionApi::new(url)
+            };
+            RecgNode {
+                api: Arc::new(api),
+                state: Mutex::new(NodeState::default()),
+                probing: AtomicBool::new(false),
+            }
+        }).collect();
+
+        RecgCluster {
+            nodes,
+            opts,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn primary_url(&self) -> &str {
+        self.nodes[0].api.url.as_str()
+    }
+
+    /// 是否有未熔断的节点
+    pub fn has_available(&self) -> bool {
+        let now = Instant::now();
+        (0..self.nodes.len()).any(|x| self.is_available(x, now))
+    }
+
+    /// 未熔断，或者熔断到期且没有进行中的试探
+    fn is_available(&self, index: usize, now: Instant) -> bool {
+        let node = &self.nodes[index];
+        let state = node.state.lock().unwrap();
+        match state.open_until {
+            None => true,
+            Some(x) => now >= x && !node.probing.load(Ordering::Acquire),
+        }
+    }
+
+    /// 熔断到期后只放行一个试探，其他请求在试探结束前拒绝
+    fn enter(&self, index: usize, now: Instant) -> Gate {
+        let node = &self.nodes[index];
+        let state = node.state.lock().unwrap();
+        match state.open_until {
+            None => Gate::Closed,
+            Some(x) if now < x => Gate::Reject,
+            Some(_) => match node.probing.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => Gate::Probe,
+                Err(_) => Gate::Reject,
+            },
+        }
+    }
+
+    fn on_success(&self, index: usize, cost: Duration) {
+        let mut state = self.nodes[index].state.lock().unwrap();
+        if state.open_until.is_some() {
+            info!("RecgCluster, node: {} recovered", self.nodes[index].api.url);
+        }
+        let ms = cost.as_secs_f64() * 1000_f64;
+        state.latency_ms = if state.total_ok == 0 {
+            ms
+        } else {
+            state.latency_ms * 0.8 + ms * 0.2
+        };
+        state.consecutive_fail = 0;
+        state.open_until = None;
+        state.total_ok += 1;
+    }
+
+    fn on_failure(&self, index: usize) {
+        let mut state = self.nodes[index].state.lock().unwrap();
+        state.consecutive_fail += 1;
+        state.total_fail += 1;
+
+        // 熔断中的试探失败，或者连续失败达到阈值
+        if state.open_until.is_some() || state.consecutive_fail >= self.opts.fail_threshold.max(1) {
+            state.open_until = Some(Instant::now() + Duration::from_secs(self.opts.open_sec));
+            warn!("warn, RecgCluster, node: {} open circuit, fail: {}", self.nodes[index].api.url, state.consecutive_fail);
+        }
+    }
+
+    /// 可用节点按 policy 排序，没有可用节点时按顺序全部尝试
+    fn select_order(&self) -> Vec<usize> {
+        let now = Instant::now();
+        let count = self.nodes.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % count;
+
+        let all: Vec<usize> = (0..count).map(|i| (start + i) % count).collect();
+        let mut list: Vec<usize> = all.iter().copied().filter(|x| self.is_available(*x, now)).collect();
+        if list.is_empty() {
+            return all;
+        }
+
+        if self.opts.policy == SelectPolicy::LeastLatency {
+            let latency = |i: &usize| self.nodes[*i].state.lock().unwrap().latency_ms;
+            list.sort_by(|a, b| latency(a).partial_cmp(&latency(b)).unwrap_or(std::cmp::Ordering::Equal));
+        }
+        list
+    }
+
+    async fn call_node<T, F, Fut>(&self, index: usize, f: &F) -> ApiResult<T>
+        where F: Fn(Arc<RecognitionApi>) -> Fut,
+              Fut: Future<Output=ApiResult<T>> {
+        let start = Instant::now();
+        let _guard = match self.enter(index, start) {
+            Gate::Closed => ProbeGuard(None),
+            Gate::Probe => ProbeGuard(Some(&self.nodes[index].probing)),
+            Gate::Reject => return Err(ApiError::net_err("circuit open")),
+        };
+        let res = f(self.nodes[index].api.clone()).await;
+        match res {
+            Err(ref e) if is_node_err(e) => self.on_failure(index),
+            _ => self.on_success(index, start.elapsed()),
+        }
+        res
+    }
+
+    async fn read<T, F, Fut>(&self, method: &str, f: F) -> ApiResult<T>
+        where F: Fn(Arc<RecognitionApi>) -> Fut,
+              Fut: Future<Output=ApiResult<T>> {
+        let mut last_err = ApiError::net_err("no node");
+        for index in self.select_order() {
+            match self.call_node(index, &f).await {
+                Err(e) if is_node_err(&e) => {
+                    warn!("warn, RecgCluster, {}, node: {}, {:?}", method, self.nodes[index].api.url, e);
+                    last_err = e;
+                }
+                res => return res,
+            }
+        }
+        Err(last_err)
+    }
+
+    async fn write<T, F, Fut>(&self, method: &str, f: F) -> ApiResult<T>
+        where F: Fn(Arc<RecognitionApi>) -> Fut,
+              Fut: Future<Output=ApiResult<T>> {
+        if !self.is_available(0, Instant::now()) {
+            debug!("RecgCluster, {}, primary: {} is open", method, self.primary_url());
+            return Err(ApiError::net_err("primary node circuit open"));
+        }
+        self.call_node(0, &f).await
+    }
+
+    /// 探测节点状态，熔断到期的节点通过后恢复
+    pub async fn probe(&self) {
+        let now = Instant::now();
+        for index in 0..self.nodes.len() {
+            if !self.is_available(index, now) {
+                continue;
+            }
+            if let Err(e) = self.call_node(index, &|api: Arc<RecognitionApi>| async move { api.get_dbs().await }).await {
+                debug!("RecgCluster, probe node: {}, {:?}", self.nodes[index].api.url, e);
+            }
+        }
+    }
+
+    pub fn stats(&self) -> Vec<RecgNodeStat> {
+        let now = Instant::now();
+        self.nodes.iter().enumerate().map(|(i, node)| {
+            let state = node.state.lock().unwrap();
+            RecgNodeStat {
+                url: node.api.url.clone(),
+                primary: i == 0,
+                available: state.open_until.map_or(true, |x| now >= x),
+                consecutive_fail: state.consecutive_fail,
+                latency_ms: state.latency_ms,
+                total_ok: state.total_ok,
+                total_fail: state.total_fail,
+            }
+        }).collect()
+    }
+
+    //------------------------ 读 ------------------------
+    pub async fn detect(&self, data: String, retfeat: bool, retattr: bool) -> ApiResult<DetectRes> {
+        self.read("detect", |api| {
+            let data = data.clone();
+            async move { api.detect(data, retfeat, retattr).await }
+        }).await
+    }
+
+    pub async fn get_features(&self, images: Vec<String>, retattr: bool) -> ApiResult<GetFeaturesRes> {
+        self.read("get_features", |api| {
+            let images = images.clone();
+            async move { api.get_features(images, retattr).await }
+        }).await
+    }
+
+    pub async fn get_db_info(&self, id: String) -> ApiResult<GetDbInfoRes> {
+        self.read("get_db_info", |api| {
+            let id = id.clone();
+            async move { api.get_db_info(id).await }
+        }).await
+    }
+
+    pub async fn get_dbs(&self) -> ApiResult<GetDbsRes> {
+        self.read("get_dbs", |api| async move { api.get_dbs().await }).await
+    }
+
+    pub async fn get_db_persons(&self, id: String, offset: i64, count: i64) -> ApiResult<GetDbPersonsRes> {
+        self.read("get_db_persons", |api| {
+            let id = id.clone();
+            async move { api.get_db_persons(id, offset, count).await }
+        }).await
+    }
+
+    pub async fn get_person_info(&self, db: String, id: String) -> ApiResult<GetPersonInfoRes> {
+        self.read("get_person_info", |api| {
+            let (db, id) = (db.clone(), id.clone());
+            async move { api.get_person_info(db, id).await }
+        }).await
+    }
+
+    pub async fn search(&self, db: Vec<String>, top: Vec<i64>, threshold: Vec<i64>, features: Vec<Vec<ApiFeatureQuality>>) -> ApiResult<SearchRes> {
+        self.read("search", |api| {
+            let (db, top, threshold, features) = (db.clone(), top.clone(), threshold.clone(), features.clone());
+            async move { api.search(db, top, threshold, features).await }
+        }).await
+    }
+
+    pub async fn compare(&self, aligned_a: String, aligned_b: String) -> ApiResult<CompareRes> {
+        self.read("compare", |api| {
+            let (a, b) = (aligned_a.clone(), aligned_b.clone());
+            async move { api.compare(a, b).await }
+        }).await
+    }
+
+    pub async fn compare_n(&self, a: Vec<ApiFeatureQuality>, b: Vec<Vec<ApiFeatureQuality>>) -> ApiResult<CompareNRes> {
+        self.read("compare_n", |api| {
+            let (a, b) = (a.clone(), b.clone());
+            async move { api.compare_n(a, b).await }
+        }).await
+    }
+
+    //------------------------ 写 ------------------------
+    pub async fn create_db(&self, id: Option<String>, volume: i64) -> ApiResult<CreateDbRes> {
+        self.write("create_db", |api| {
+            let id = id.clone();
+            async move { api.create_db(id, volume).await }
+        }).await
+    }
+
+    pub async fn delete_db(&self, id: String) -> ApiResult<DeleteDbRes> {
+        self.write("delete_db", |api| {
+            let id = id.clone();
+            async move { api.delete_db(id).await }
+        }).await
+    }
+
+    pub async fn flush_db(&self, id: String) -> ApiResult<FlushDbRes> {
+        self.write("flush_db", |api| {
+            let id = id.clone();
+            async move { api.flush_db(id).await }
+        }).await
+    }
+
+    pub async fn create_persons(&self, db: String, ids: Vec<String>, features: Vec<Vec<ApiFeatureQuality>>) -> ApiResult<CreatePersonsRes> {
+        self.write("create_persons", |api| {
+            let (db, ids, features) = (db.clone(), ids.clone(), features.clone());
+            async move { api.create_persons(db, ids, features).await }
+        }).await
+    }
+
+    pub async fn delete_person(&self, db: String, id: String) -> ApiResult<DeletePersonRes> {
+        self.write("delete_person", |api| {
+            let (db, id) = (db.clone(), id.clone());
+            async move { api.delete_person(db, id).await }
+        }).await
+    }
+
+    pub async fn move_persons(&self, db_src: String, db_dst: String, ids: Vec<String>) -> ApiResult<MovePersonsRes> {
+        self.write("move_persons", |api| {
+            let (db_src, db_dst, ids) = (db_src.clone(), db_dst.clone(), ids.clone());
+            async move { api.move_persons(db_src, db_dst, ids).await }
+        }).await
+    }
+
+    pub async fn add_features_to_person(&self, db: String, id: String, features: Vec<ApiFeatureQuality>) -> ApiResult<AddFeaturesToPersonRes> {
+        self.write("add_features_to_person", |api| {
+            let (db, id, features) = (db.clone(), id.clone(), features.clone());
+            async move { api.add_features_to_person(db, id, features).await }
+        }).await
+    }
+
+    pub async fn add_aggregate_feature_to_person(&self, db: String, id: String, feature: String) -> ApiResult<AddAggregateFeatureToPersonRes> {
+        self.write("add_aggregate_feature_to_person", |api| {
+            let (db, id, feature) = (db.clone(), id.clone(), feature.clone());
+            async move { api.add_aggregate_feature_to_person(db, id, feature).await }
+        }).await
+    }
+
+    pub async fn delete_person_feature(&self, db: String, id: String, face_id: i64) -> ApiResult<DeletePersonFeatureRes> {
+        self.write("delete_person_feature", |api| {
+            let (db, id) = (db.clone(), id.clone());
+            async move { api.delete_person_feature(db, id, face_id).await }
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_cluster(count: usize, open_sec: u64) -> RecgCluster {
+        let urls: Vec<String> = (0..count).map(|i| format!("http://127.0.0.1:{}", 9000 + i)).collect();
+        RecgCluster::new(&urls, RecgClusterOptions {
+            open_sec,
+            ..Default::default()
+        })
+    }
+
+    fn fail_n(cluster: &RecgCluster, index: usize, n: u32) {
+        for _ in 0..n {
+            cluster.on_failure(index);
+        }
+    }
+
+    #[test]
+    fn open_after_threshold() {
+        let cluster = new_cluster(1, 30);
+        fail_n(&cluster, 0, 2);
+        assert!(cluster.is_available(0, Instant::now()));
+
+        // 成功后重新计数
+        cluster.on_success(0, Duration::from_millis(10));
+        fail_n(&cluster, 0, 2);
+        assert!(cluster.is_available(0, Instant::now()));
+
+        cluster.on_failure(0);
+        assert!(!cluster.has_available());
+        assert!(matches!(cluster.enter(0, Instant::now()), Gate::Reject));
+        assert_eq!(cluster.stats()[0].consecutive_fail, 3);
+    }
+
+    #[test]
+    fn half_open_probe() {
+        let cluster = new_cluster(1, 0);
+        fail_n(&cluster, 0, 3);
+
+        // 熔断到期，只放行一个试探
+        let now = Instant::now();
+        assert!(cluster.is_available(0, now));
+        assert!(matches!(cluster.enter(0, now), Gate::Probe));
+        assert!(!cluster.is_available(0, now));
+        assert!(matches!(cluster.enter(0, now), Gate::Reject));
+
+        // 试探结束后可再次试探
+        drop(ProbeGuard(Some(&cluster.nodes[0].probing)));
+        assert!(matches!(cluster.enter(0, now), Gate::Probe));
+        drop(ProbeGuard(Some(&cluster.nodes[0].probing)));
+
+        // 试探失败，一次就重新熔断
+        cluster.on_failure(0);
+        assert!(cluster.nodes[0].state.lock().unwrap().open_until.is_some());
+
+        // 试探成功，恢复
+        cluster.on_success(0, Duration::from_millis(10));
+        assert!(matches!(cluster.enter(0, Instant::now()), Gate::Closed));
+        assert_eq!(cluster.stats()[0].consecutive_fail, 0);
+    }
+
+    #[test]
+    fn select_skips_open_nodes() {
+        let cluster = new_cluster(3, 30);
+        fail_n(&cluster, 1, 3);
+        for _ in 0..3 {
+            let order = cluster.select_order();
+            assert_eq!(order.len(), 2);
+            assert!(!order.contains(&1));
+        }
+
+        // 全部熔断时按顺序全部尝试
+        fail_n(&cluster, 0, 3);
+        fail_n(&cluster, 2, 3);
+        assert_eq!(cluster.select_order().len(), 3);
+    }
+
+    #[test]
+    fn least_latency_order() {
+        let urls = vec!["http://127.0.0.1:9000".to_string(), "http://127.0.0.1:9001".to_string()];
+        let cluster = RecgCluster::new(&urls, RecgClusterOptions {
+            policy: SelectPolicy::LeastLatency,
+            ..Default::default()
+        });
+        cluster.on_success(0, Duration::from_millis(50));
+        cluster.on_success(1, Duration::from_millis(10));
+        assert_eq!(cluster.select_order(), vec![1, 0]);
+        assert_eq!(cluster.select_order(), vec![1, 0]);
+    }
+
+    #[test]
+    fn node_err() {
+        assert!(is_node_err(&ApiError::net_err("timeout")));
+        assert!(!is_node_err(&ApiError::BizErr("db not found".to_string())));
+        assert!(!is_node_err(&ApiError::JsonrpcErr(-1, "db not found".to_string())));
+    }
+}
@@ -0,0 +1,140 @@
+use crate::api::bm_api::ApiPosition;
+
+/// 超过该车速视为估算错误, km/h
+pub const MAX_SPEED: f64 = 300_f64;
+
+/// 摄像头测速标定
+/// 两条水平线(视频坐标)之间的实际距离
+#[derive(Debug, Clone, Copy)]
+pub struct SpeedCalib {
+    pub line1: i64,
+    pub line2: i64,
+    /// 米
+    pub distance: f64,
+}
+
+/// 车辆位置采样
+#[derive(Debug, Clone, Copy)]
+pub struct SpeedSample {
+    /// ms
+    pub time: i64,
+    /// 车辆框底边 y 坐标(视频坐标)
+    pub y: f64,
+}
+
+impl SpeedCalib {
+    /// 未标定时返回 None
+    pub fn new(line1: i64, line2: i64, distance: f64) -> Option<Self> {
+        if line1 <= 0 || line2 <= 0 || line1 == line2 || distance <= 0_f64 {
+            return None;
+        }
+        Some(SpeedCalib {
+            line1,
+            line2,
+            distance,
+        })
+    }
+
+    /// 车辆经过两条线的时间差计算车速, km/h
+    /// 采样需要覆盖两条线，否则返回 None
+    pub fn estimate(&self, samples: &[SpeedSample]) -> Option<f64> {
+        let mut samples = samples.to_vec();
+        samples.sort_by_key(|x| x.time);
+
+        let t1 = cross_time(&samples, self.line1 as f64)?;
+        let t2 = cross_time(&samples, self.line2 as f64)?;
+        let dt = (t2 - t1).abs();
+        if dt <= 0_f64 {
+            return None;
+        }
+
+        let speed = self.distance / (dt / 1000_f64) * 3.6;
+        if speed > MAX_SPEED {
+            return None;
+        }
+        Some((speed * 10_f64).round() / 10_f64)
+    }
+}
+
+/// 按时间线性插值，车辆经过 y 的时间
+fn cross_time(samples: &[SpeedSample], y: f64) -> Option<f64> {
+    samples.windows(2).find_map(|w| {
+        let (a, b) = (w[0], w[1]);
+        if (a.y - y) * (b.y - y) > 0_f64 || a.y == b.y {
+            return None;
+        }
+        Some(a.time as f64 + (y - a.y) / (b.y - a.y) * (b.time - a.time) as f64)
+    })
+}
+
+/// 帧号对应的时间, ms
+/// 按 position 中开始/结束帧的时间线性换算
+pub fn frame_to_time(position: &ApiPosition, frame: i64) -> Option<i64> {
+    let frames = position.end_frame - position.start_frame;
+    if frames <= 0 || position.end_real_time <= position.start_real_time {
+        return None;
+    }
+    let ms = (position.end_real_time - position.start_real_time) as f64 / frames as f64;
+    Some(position.start_real_time + ((frame - position.start_frame) as f64 * ms).round() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(time: i64, y: f64) -> SpeedSample {
+        SpeedSample {
+            time,
+            y,
+        }
+    }
+
+    #[test]
+    fn new_requires_calib() {
+        assert!(SpeedCalib::new(0, 600, 10_f64).is_none());
+        assert!(SpeedCalib::new(400, 400, 10_f64).is_none());
+        assert!(SpeedCalib::new(400, 600, 0_f64).is_none());
+        assert!(SpeedCalib::new(400, 600, 10_f64).is_some());
+    }
+
+    #[test]
+    fn cross_time_interpolates() {
+        let samples = [sample(0, 100_f64), sample(100, 200_f64), sample(200, 300_f64)];
+        assert_eq!(cross_time(&samples, 150_f64), Some(50_f64));
+        assert_eq!(cross_time(&samples, 250_f64), Some(150_f64));
+        assert_eq!(cross_time(&samples, 400_f64), None);
+
+        // 没有移动的采样跳过
+        let samples = [sample(0, 100_f64), sample(100, 100_f64), sample(200, 300_f64)];
+        assert_eq!(cross_time(&samples, 200_f64), Some(150_f64));
+    }
+
+    #[test]
+    fn estimate_speed() {
+        let calib = SpeedCalib::new(400, 600, 10_f64).unwrap();
+        // 乱序的采样
+        let samples = [sample(1000, 700_f64), sample(0, 300_f64), sample(500, 500_f64)];
+        assert_eq!(calib.estimate(&samples), Some(72_f64));
+
+        // 反方向行驶
+        let samples = [sample(0, 700_f64), sample(500, 500_f64), sample(1000, 300_f64)];
+        assert_eq!(calib.estimate(&samples), Some(72_f64));
+
+        // 保留一位小数
+        let samples = [sample(0, 400_f64), sample(700, 600_f64)];
+        assert_eq!(calib.estimate(&samples), Some(51.4));
+    }
+
+    #[test]
+    fn estimate_invalid() {
+        let calib = SpeedCalib::new(400, 600, 10_f64).unwrap();
+        // 没有经过 line2
+        let samples = [sample(0, 300_f64), sample(500, 500_f64)];
+        assert_eq!(calib.estimate(&samples), None);
+
+        // 超过 MAX_SPEED
+        let calib = SpeedCalib::new(400, 600, 100_f64).unwrap();
+        let samples = [sample(0, 300_f64), sample(500, 500_f64), sample(1000, 700_f64)];
+        assert_eq!(calib.estimate(&samples), None);
+    }
+}
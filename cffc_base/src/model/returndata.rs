@@ -9,6 +9,8 @@ pub const STATUS_OK: i32 = 0;
 pub const STATUS_ERR_COMMON_FAIL: i32 = 1;
 pub const STATUS_ERR_LOGINFAIL: i32 = 2;
 pub const STATUS_ERR_SYSTEMERROR: i32 = 500;
+// 系统繁忙(队列满等)，调用方可稍后重试
+pub const STATUS_ERR_BUSY: i32 = 503;
 
 pub const STATUS_ERR_UN_AUTHC: i32 = 101;
 pub const STATUS_ERR_UN_AUTHZ: i32 = 102;
@@ -20,6 +22,7 @@ pub const STATUS_ERR_INVALID: i32 = 202; // 业务参数无效(格式或内容)
 pub const MESSAGE_SUCCESS: &str = "操作成功";
 pub const MESSAGE_COMMON_FAIL: &str = "操作失败";
 pub const MESSAGE_ERR_UN_AUTHC: &str = "未登陆,请退出,重新登陆";
+pub const MESSAGE_ERR_BUSY: &str = "系统繁忙,请稍后重试";
 
 #[derive(Serialize)]
 pub struct ReturnData<T>
@@ -142,6 +145,14 @@ pub fn fail<T: Serialize>(result: &str) -> ReturnDataType<T> {
     })
 }
 
+pub fn busy<T: Serialize>(result: &str) -> ReturnDataType<T> {
+    Err(ReturnDataError {
+        status: STATUS_ERR_BUSY,
+        message: MESSAGE_ERR_BUSY.to_string(),
+        result: result.to_string(),
+    })
+}
+
 pub fn fail_msg<T: Serialize>(msg: &str, result: &str) -> ReturnDataType<T> {
     Err(ReturnDataError {
         status: STATUS_ERR_COMMON_FAIL,
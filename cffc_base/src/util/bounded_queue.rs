@@ -0,0 +1,204 @@
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use deadqueue::unlimited::Queue;
+use serde::{Deserialize, Serialize};
+
+/// 队列状态
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueueStat {
+    pub name: String,
+    /// 当前队列长度
+    pub depth: usize,
+    /// 容量，0 表示不限制
+    pub capacity: usize,
+    /// 入队成功数
+    pub pushed: u64,
+    /// 队列满，丢弃数
+    pub dropped: u64,
+}
+
+pub trait QueueStatus {
+    fn stat(&self) -> QueueStat;
+}
+
+/// 有容量限制的队列，基于 deadqueue::unlimited::Queue
+/// push 不阻塞，队列满时返回 Err(item)，并累计丢弃数 (QueueStat.dropped)
+pub struct BoundedQueue<T> {
+    name: String,
+    queue: Queue<T>,
+    capacity: usize,
+    /// 多个生产者时，检查容量和入队在同一个锁内，避免超过容量
+    push_lock: Mutex<()>,
+    pushed: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl<T> BoundedQueue<T> {
+    /// capacity 为 0 时不限制
+    pub fn new(name: &str, capacity: usize) -> Self {
+        BoundedQueue {
+            name: name.to_string(),
+            queue: Queue::new(),
+            capacity,
+            push_lock: Mutex::new(()),
+            pushed: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.len() == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.capacity > 0 && self.queue.len() >= self.capacity
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// 队列满时，返回 Err(item)
+    pub fn push(&self, item: T) -> Result<(), T> {
+        let _lock = self.push_lock.lock().unwrap();
+        if self.is_full() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return Err(item);
+        }
+        self.queue.push(item);
+        self.pushed.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// 忽略容量限制 (如启动时重放)
+    pub fn force_push(&self, item: T) {
+        self.queue.push(item);
+        self.pushed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub async fn pop(&self) -> T {
+        self.queue.pop().await
+    }
+
+    pub fn try_pop(&self) -> Option<T> {
+        self.queue.try_pop()
+    }
+
+    /// 取出一批，至少一个
+    pub async fn pop_batch(&self, max: usize) -> Vec<T> {
+        let mut list = Vec::new();
+
+        while let Some(v) = self.queue.try_pop() {
+            list.push(v);
+            if list.len() == max {
+                break;
+            }
+        }
+
+        if list.is_empty() {
+            let v = self.queue.pop().await;
+            list.push(v);
+        }
+        list
+    }
+}
+
+impl<T> QueueStatus for BoundedQueue<T> {
+    fn stat(&self) -> QueueStat {
+        QueueStat {
+            name: self.name.clone(),
+            depth: self.queue.len(),
+            capacity: self.capacity,
+            pushed: self.pushed.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 登记各个队列，用于查询状态
+#[derive(Default)]
+pub struct QueueStatRepo {
+    queues: RwLock<Vec<Arc<dyn QueueStatus + Send + Sync>>>,
+}
+
+impl QueueStatRepo {
+    pub fn new() -> Self {
+        QueueStatRepo::default()
+    }
+
+    pub fn register(&self, queue: Arc<dyn QueueStatus + Send + Sync>) {
+        let mut lock = self.queues.write().unwrap();
+        lock.push(queue);
+    }
+
+    pub fn stats(&self) -> Vec<QueueStat> {
+        let lock = self.queues.read().unwrap();
+        lock.iter().map(|x| x.stat()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_full() {
+        let queue = BoundedQueue::new("test", 2);
+        assert!(queue.push(1).is_ok());
+        assert!(queue.push(2).is_ok());
+        assert_eq!(queue.push(3), Err(3));
+        assert!(queue.is_full());
+
+        // 不受容量限制
+        queue.force_push(4);
+        assert_eq!(queue.try_pop(), Some(1));
+        assert_eq!(queue.push(5), Err(5));
+
+        let stat = queue.stat();
+        assert_eq!((stat.depth, stat.pushed, stat.dropped), (2, 3, 2));
+    }
+
+    #[test]
+    fn concurrent_push_within_capacity() {
+        let queue = Arc::new(BoundedQueue::new("test", 100));
+        let handles: Vec<_> = (0..8).map(|_| {
+            let queue = queue.clone();
+            std::thread::spawn(move || {
+                for i in 0..1000 {
+                    let _ = queue.push(i);
+                }
+            })
+        }).collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+
+        let stat = queue.stat();
+        assert_eq!(stat.depth, 100);
+        assert_eq!(stat.pushed, 100);
+        assert_eq!(stat.dropped, 8000 - 100);
+    }
+
+    #[test]
+    fn unlimited() {
+        let queue = BoundedQueue::new("test", 0);
+        for i in 0..1000 {
+            assert!(queue.push(i).is_ok());
+        }
+        assert!(!queue.is_full());
+        assert_eq!(queue.dropped(), 0);
+    }
+}
@@ -1,10 +1,12 @@
 pub mod intr_seg_queue;
 pub mod intr_queue;
+pub mod bounded_queue;
 pub mod multipart_form;
 pub mod logger;
 pub mod delay_queue;
 pub mod serial_process;
 pub mod utils;
+pub mod plate_match;
 
 pub fn get_local_ips() -> Vec<String> {
     use pnet::datalink;
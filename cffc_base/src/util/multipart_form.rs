@@ -4,9 +4,15 @@ use indexmap::IndexMap;
 use actix_multipart::Multipart;
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 use futures::{StreamExt, TryStreamExt};
-use log::{debug, warn};
-use std::fmt::Debug;
+use log::{debug, error, warn};
+use std::fmt::{self, Debug, Display};
 use std::fmt::Formatter;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
 
 #[derive(Debug)]
 pub enum MultipartFormItem {
@@ -40,7 +46,7 @@ pub struct MultipartFormFileValue {
     pub file_name: String,
 
     /// 文件内容
-    pub data: Bytes,
+    pub data: FormFileData,
 }
 
 impl Debug for MultipartFormFileValue {
@@ -48,13 +54,177 @@ impl Debug for MultipartFormFileValue {
         f.debug_struct("MultipartFormFileValue")
             .field("file_name", &self.file_name)
             .field("data_len", &self.data.len())
+            .field("spilled", &self.data.is_spilled())
             .finish()
     }
 }
 
+/// 临时文件，drop 时删除
+#[derive(Debug)]
+pub struct TempFile {
+    pub path: PathBuf,
+    pub len: usize,
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            error!("error, remove temp file: {:?}, {:?}", self.path, e);
+        }
+    }
+}
+
+/// 文件内容，小文件在内存中，大文件写入临时文件
+#[derive(Debug)]
+pub enum FormFileData {
+    Memory(Bytes),
+    Temp(TempFile),
+}
+
+impl FormFileData {
+    pub fn len(&self) -> usize {
+        match self {
+            FormFileData::Memory(v) => v.len(),
+            FormFileData::Temp(v) => v.len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_spilled(&self) -> bool {
+        matches!(self, FormFileData::Temp(_))
+    }
+
+    pub async fn to_bytes(&self) -> io::Result<Bytes> {
+        match self {
+            FormFileData::Memory(v) => Ok(v.clone()),
+            FormFileData::Temp(v) => tokio::fs::read(&v.path).await.map(Bytes::from),
+        }
+    }
+
+    /// 同步读取，临时文件会阻塞
+    pub fn to_bytes_sync(&self) -> io::Result<Bytes> {
+        match self {
+            FormFileData::Memory(v) => Ok(v.clone()),
+            FormFileData::Temp(v) => std::fs::read(&v.path).map(Bytes::from),
+        }
+    }
+}
+
+/// 全局的上传字节数限制，各请求共享
+#[derive(Debug)]
+pub struct UploadBudget {
+    /// 0 表示不限制
+    max: usize,
+    used: AtomicUsize,
+}
+
+impl UploadBudget {
+    pub fn new(max: usize) -> Self {
+        UploadBudget {
+            max,
+            used: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    fn try_take(&self, size: usize) -> bool {
+        let mut cur = self.used.load(Ordering::Relaxed);
+        loop {
+            if self.max > 0 && cur + size > self.max {
+                return false;
+            }
+            match self.used.compare_exchange_weak(cur, cur + size, Ordering::AcqRel, Ordering::Relaxed) {
+                Ok(_) => return true,
+                Err(v) => cur = v,
+            }
+        }
+    }
+
+    pub fn guard(self: &Arc<Self>) -> BudgetGuard {
+        BudgetGuard {
+            budget: self.clone(),
+            size: 0,
+        }
+    }
+}
 
+/// 占用的额度，drop 时归还
 #[derive(Debug)]
-pub struct MultipartFormValues(pub IndexMap<String, MultipartFormItem>);
+pub struct BudgetGuard {
+    budget: Arc<UploadBudget>,
+    size: usize,
+}
+
+impl BudgetGuard {
+    /// 额度不足时返回 false
+    pub fn grow(&mut self, size: usize) -> bool {
+        if !self.budget.try_take(size) {
+            return false;
+        }
+        self.size += size;
+        true
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl Drop for BudgetGuard {
+    fn drop(&mut self) {
+        self.budget.used.fetch_sub(self.size, Ordering::AcqRel);
+    }
+}
+
+/// 解析参数
+#[derive(Debug, Clone, Default)]
+pub struct MultipartOptions {
+    /// 文件超过该大小时写入临时文件, 0 表示不写
+    pub spill_size: usize,
+    pub tmp_dir: PathBuf,
+    /// 单个请求上限, 0 表示不限制
+    pub max_size: usize,
+    pub budget: Option<Arc<UploadBudget>>,
+}
+
+#[derive(Debug)]
+pub enum MultipartError {
+    /// 全局额度不足
+    Busy,
+    TooLarge,
+    Io(String),
+    Payload(String),
+}
+
+impl Display for MultipartError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MultipartError::Busy => write!(f, "upload budget exhausted"),
+            MultipartError::TooLarge => write!(f, "payload too large"),
+            MultipartError::Io(e) => write!(f, "io error, {}", e),
+            MultipartError::Payload(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<io::Error> for MultipartError {
+    fn from(e: io::Error) -> Self {
+        MultipartError::Io(format!("{:?}", e))
+    }
+}
+
+#[derive(Debug)]
+pub struct MultipartFormValues(pub IndexMap<String, MultipartFormItem>, Option<BudgetGuard>);
 
 //---------------------
 impl MultipartFormText {
@@ -79,7 +249,7 @@ impl MultipartFormText {
 }
 
 impl MultipartFormFile {
-    pub fn new(name: &str, file_name: &str, data: Bytes) -> Self {
+    pub fn new(name: &str, file_name: &str, data: FormFileData) -> Self {
         MultipartFormFile {
             name: name.to_string(),
             values: vec![MultipartFormFileValue {
@@ -89,7 +259,7 @@ impl MultipartFormFile {
         }
     }
 
-    pub fn add(&mut self, file_name: Option<&str>, data: bytes::Bytes) {
+    pub fn add(&mut self, file_name: Option<&str>, data: FormFileData) {
         if file_name.is_none() {
             // 不是文件类型的，忽略
             return;
@@ -107,7 +277,7 @@ impl MultipartFormFile {
 }
 
 impl MultipartFormItem {
-    pub fn new_file(field_name: &str, file_name: &str, data: bytes::Bytes) -> Self {
+    pub fn new_file(field_name: &str, file_name: &str, data: FormFileData) -> Self {
         MultipartFormItem::File(MultipartFormFile::new(field_name, file_name, data))
     }
 
@@ -115,7 +285,7 @@ impl MultipartFormItem {
         MultipartFormItem::Text(MultipartFormText::new(field_name, data))
     }
 
-    pub fn append_file(&mut self, file_name: &str, data: bytes::Bytes) {
+    pub fn append_file(&mut self, file_name: &str, data: FormFileData) {
         if let MultipartFormItem::File(item) = self {
             item.values.push(MultipartFormFileValue { file_name: file_name.to_string(), data });
         }
@@ -137,7 +307,7 @@ impl Default for MultipartFormValues {
 
 impl MultipartFormValues {
     pub fn new() -> Self {
-        MultipartFormValues(IndexMap::new())
+        MultipartFormValues(IndexMap::new(), None)
     }
 
     pub fn add(&mut self, name: &str, item: MultipartFormItem) {
@@ -149,42 +319,46 @@ impl MultipartFormValues {
      以最早的出现的field为准
     */
     pub fn add_form_value(&mut self, field_name: Option<&str>, file_name: Option<&str>, data: bytes::Bytes) {
-        if field_name.is_none() {
-            warn!("field_name is none!");
-            return;
+        match file_name {
+            Some(_) => self.add_form_data(field_name, file_name, FormFileData::Memory(data)),
+            None => self.add_form_text(field_name, data),
         }
+    }
 
-        let field_name = field_name.unwrap();
-
-        if let Some(f) = file_name {
-            // 文件
-            let value = self.0.get_mut(field_name);
-            if let Some(v) = value {
-                v.append_file(f, data);
-            } else {
-                self.add(field_name, MultipartFormItem::new_file(field_name, f, data));
+    fn add_form_data(&mut self, field_name: Option<&str>, file_name: Option<&str>, data: FormFileData) {
+        let field_name = match field_name {
+            Some(v) => v,
+            None => {
+                warn!("field_name is none!");
+                return;
             }
+        };
 
-            // if value.is_none() {
-            //     self.add(field_name, MultipartFormItem::new_file(field_name, f, data));
-            // } else {
-            //     value.unwrap().append_file(f, data);
-            // }
+        // 文件
+        let f = file_name.unwrap_or("");
+        let value = self.0.get_mut(field_name);
+        if let Some(v) = value {
+            v.append_file(f, data);
         } else {
-            // 文本
-            let value = self.0.get_mut(field_name);
+            self.add(field_name, MultipartFormItem::new_file(field_name, f, data));
+        }
+    }
 
-            if let Some(v) = value {
-                v.append_text(data);
-            } else {
-                self.add(field_name, MultipartFormItem::new_text(field_name, data));
+    fn add_form_text(&mut self, field_name: Option<&str>, data: bytes::Bytes) {
+        let field_name = match field_name {
+            Some(v) => v,
+            None => {
+                warn!("field_name is none!");
+                return;
             }
+        };
 
-            // if value.is_none() {
-            //     self.add(field_name, MultipartFormItem::new_text(field_name, data));
-            // } else {
-            //     value.unwrap().append_text(data);
-            // }
+        // 文本
+        let value = self.0.get_mut(field_name);
+        if let Some(v) = value {
+            v.append_text(data);
+        } else {
+            self.add(field_name, MultipartFormItem::new_text(field_name, data));
         }
     }
 
@@ -210,9 +384,39 @@ impl MultipartFormValues {
         }
     }
 
+    pub fn get_file_data(&self, name: &str) -> Option<(&str, &FormFileData)> {
+        if let Some(MultipartFormItem::File(v)) = self.0.get(name) {
+            v.first_value().map(|x| (x.file_name.as_str(), &x.data))
+        } else {
+            None
+        }
+    }
+
+    /// 取出文件内容，临时文件的所有权转给调用者
+    pub fn take_file_data(&mut self, name: &str) -> Option<(String, FormFileData)> {
+        if let Some(MultipartFormItem::File(v)) = self.0.get_mut(name) {
+            if v.values.is_empty() {
+                return None;
+            }
+            let x = v.values.remove(0);
+            Some((x.file_name, x.data))
+        } else {
+            None
+        }
+    }
+
+    /// 临时文件同步读取，读取失败的忽略
     pub fn get_file_values(&self, name: &str) -> Option<Vec<(String, Bytes)>> {
         if let Some(MultipartFormItem::File(v)) = self.0.get(name) {
-            let values = v.values.iter().map(|x| { (x.file_name.clone(), x.data.clone()) }).collect();
+            let values = v.values.iter().filter_map(|x| {
+                match x.data.to_bytes_sync() {
+                    Ok(data) => Some((x.file_name.clone(), data)),
+                    Err(e) => {
+                        error!("error, read form file: {}, {:?}", x.file_name, e);
+                        None
+                    }
+                }
+            }).collect();
             Some(values)
         } else {
             None
@@ -233,31 +437,86 @@ impl MultipartFormValues {
     }
 }
 
-pub async fn parse_multi_form(mut payload: Multipart) -> std::result::Result<MultipartFormValues, String> {
+pub async fn parse_multi_form(payload: Multipart) -> std::result::Result<MultipartFormValues, String> {
+    parse_multi_form_opts(payload, &MultipartOptions::default()).await
+        .map_err(|e| e.to_string())
+}
+
+static TEMP_SEQ: AtomicU64 = AtomicU64::new(0);
+
+fn temp_path(opts: &MultipartOptions) -> PathBuf {
+    let seq = TEMP_SEQ.fetch_add(1, Ordering::Relaxed);
+    opts.tmp_dir.join(format!("{}_{}.part", std::process::id(), seq))
+}
+
+/// 流式解析，文件超过 spill_size 时写入临时文件
+/// 读取的字节数计入全局额度，直到 MultipartFormValues 释放
+pub async fn parse_multi_form_opts(mut payload: Multipart, opts: &MultipartOptions) -> std::result::Result<MultipartFormValues, MultipartError> {
     let mut values = MultipartFormValues::new();
+    let mut guard = opts.budget.as_ref().map(|x| x.guard());
+    let mut total = 0_usize;
 
     while let Ok(Some(mut field)) = payload.try_next().await {
         if let Some(condis) = field.content_disposition() {
             let field_name = condis.get_name();
             let file_name = condis.get_filename();
+            let can_spill = file_name.is_some() && opts.spill_size > 0;
 
             let mut data = BytesMut::with_capacity(1024);
+            let mut temp: Option<(File, TempFile)> = None;
+
             while let Some(chunk) = field.next().await {
-                match chunk {
-                    Ok(v) => {
-                        data.put(v);
-                    }
+                let chunk = match chunk {
+                    Ok(v) => v,
                     Err(e) => {
-                        return Err(format!("{:?}", e));
+                        return Err(MultipartError::Payload(format!("{:?}", e)));
+                    }
+                };
+
+                total += chunk.len();
+                if opts.max_size > 0 && total > opts.max_size {
+                    return Err(MultipartError::TooLarge);
+                }
+                if let Some(ref mut g) = guard {
+                    if !g.grow(chunk.len()) {
+                        return Err(MultipartError::Busy);
                     }
                 }
+
+                if let Some((ref mut f, ref mut t)) = temp {
+                    f.write_all(&chunk).await?;
+                    t.len += chunk.len();
+                    continue;
+                }
+
+                data.put(chunk);
+                if can_spill && data.len() > opts.spill_size {
+                    // 超过大小，转写到临时文件
+                    let t = TempFile {
+                        path: temp_path(opts),
+                        len: data.len(),
+                    };
+                    let mut f = File::create(&t.path).await?;
+                    f.write_all(&data).await?;
+                    data.clear();
+                    temp = Some((f, t));
+                }
+            }
+
+            match temp {
+                Some((mut f, t)) => {
+                    f.flush().await?;
+                    debug!("parse_multi_form, spill {:?}, {:?}, {} bytes", file_name, t.path, t.len);
+                    values.add_form_data(field_name, file_name, FormFileData::Temp(t));
+                }
+                None => values.add_form_value(field_name, file_name, data.to_bytes()),
             }
-            values.add_form_value(field_name, file_name, data.to_bytes());
         } else {
             warn!("warn, field content_disposition is none");
         }
     }
+
+    values.1 = guard;
     debug!("parse_multi_form end");
     Ok(values)
 }
-
@@ -0,0 +1,367 @@
+use serde::{Deserialize, Serialize};
+
+use crate::api::bm_api::ApiCarPlateBit;
+
+/// 识别时容易混淆的字符，同组内互相替换的代价较低
+const CONFUSABLE: [&str; 8] = ["0DOQ", "8B", "1I", "2Z", "5S", "6G", "4A", "7T"];
+
+/// 替换为候选字符的代价
+const ALT_COST: f64 = 0.3;
+/// 替换为易混淆字符的代价
+const CONFUSABLE_COST: f64 = 0.5;
+
+/// 车牌匹配方式
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PlateMatchType {
+    /// 完全一致
+    #[default]
+    Exact,
+    /// 长度一致，不同的字符都是候选字符或易混淆字符
+    Confusable,
+    /// 编辑距离，只允许低置信度字符不一致
+    Fuzzy,
+    /// 识别出的是部分车牌，如缺少省份简称
+    Partial,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct PlateMatch {
+    pub match_type: PlateMatchType,
+    /// 0-100
+    pub score: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlateMatchOpts {
+    /// 最低分值, 0-100
+    pub min_score: f64,
+    /// 最大编辑代价，一个普通字符的替换/增删代价为 1
+    pub max_cost: f64,
+    /// 部分车牌的最少字符数
+    pub partial_min_len: usize,
+    /// 置信度低于此值的字符可以替换为任意字符或删除，其它字符只能替换为候选字符或易混淆字符
+    pub low_conf: f64,
+}
+
+impl Default for PlateMatchOpts {
+    fn default() -> Self {
+        PlateMatchOpts {
+            min_score: 80_f64,
+            max_cost: 1_f64,
+            partial_min_len: 5,
+            low_conf: 0.8,
+        }
+    }
+}
+
+struct PlateChar {
+    value: char,
+    /// 0-1
+    conf: f64,
+    /// 其它候选字符
+    alts: Vec<char>,
+}
+
+/// 识别出的车牌，每个字符带置信度和候选字符
+pub struct PlateText {
+    chars: Vec<PlateChar>,
+}
+
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '·' | '•' | '-' | '.')
+}
+
+/// 去掉分隔符，转为大写
+fn normalize(text: &str) -> Vec<char> {
+    text.chars().filter(|x| !is_separator(*x)).flat_map(|x| x.to_uppercase()).collect()
+}
+
+fn is_confusable(a: char, b: char) -> bool {
+    CONFUSABLE.iter().any(|x| x.contains(a) && x.contains(b))
+}
+
+impl PlateText {
+    /// bits 与 text 的字符一一对应时使用其置信度，否则按置信度 1 处理
+    pub fn new(text: &str, bits: &[Vec<ApiCarPlateBit>]) -> Self {
+        let raw: Vec<char> = text.chars().collect();
+        let use_bits = bits.len() == raw.len();
+
+        let chars = raw.iter().enumerate()
+            .filter(|(_, c)| !is_separator(**c))
+            .flat_map(|(i, c)| {
+                let (conf, alts) = match bits.get(i) {
+                    Some(v) if use_bits => {
+                        let conf = v.first().map_or(1_f64, |x| x.conf.max(0_f64).min(1_f64));
+                        let alts: Vec<char> = v.iter().skip(1).flat_map(|x| normalize(&x.value)).collect();
+                        (conf, alts)
+                    }
+                    _ => (1_f64, vec![]),
+                };
+                c.to_uppercase().map(move |value| PlateChar {
+                    value,
+                    conf,
+                    alts: alts.clone(),
+                })
+            })
+            .collect();
+        PlateText {
+            chars,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.chars.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+}
+
+/// 车牌模糊匹配
+/// 置信度低的字符不一致时，代价按比例降低，最低为一半
+/// 置信度高的字符不能删除或替换为普通字符，识别结果中不能插入字符(部分车牌的首尾除外)
+pub struct PlateMatcher {
+    opts: PlateMatchOpts,
+}
+
+impl PlateMatcher {
+    pub fn new(opts: PlateMatchOpts) -> Self {
+        PlateMatcher {
+            opts,
+        }
+    }
+
+    fn weight(c: &PlateChar) -> f64 {
+        0.5 + 0.5 * c.conf
+    }
+
+    /// 不计置信度的替换代价
+    fn base_sub_cost(c: &PlateChar, t: char) -> f64 {
+        if c.value == t {
+            0_f64
+        } else if c.alts.contains(&t) {
+            ALT_COST
+        } else if is_confusable(c.value, t) {
+            CONFUSABLE_COST
+        } else {
+            1_f64
+        }
+    }
+
+    fn is_low_conf(&self, c: &PlateChar) -> bool {
+        c.conf < self.opts.low_conf
+    }
+
+    /// 不允许的替换代价为 INFINITY
+    fn sub_cost(&self, c: &PlateChar, t: char) -> f64 {
+        let cost = Self::base_sub_cost(c, t);
+        if cost >= 1_f64 && !self.is_low_conf(c) {
+            return f64::INFINITY;
+        }
+        cost * Self::weight(c)
+    }
+
+    /// 删除识别出的字符的代价
+    fn del_cost(&self, c: &PlateChar) -> f64 {
+        match self.is_low_conf(c) {
+            true => Self::weight(c),
+            false => f64::INFINITY,
+        }
+    }
+
+    /// 加权编辑距离，不允许插入字符
+    /// free_ends 时 target 首尾多出的字符不计代价，用于部分车牌
+    fn edit_cost(&self, p: &[PlateChar], t: &[char], free_ends: bool) -> f64 {
+        let mut prev: Vec<f64> = (0..=t.len()).map(|j| if free_ends || j == 0 { 0_f64 } else { f64::INFINITY }).collect();
+        for c in p.iter() {
+            let mut cur = vec![prev[0] + self.del_cost(c); t.len() + 1];
+            for (j, tc) in t.iter().enumerate() {
+                cur[j + 1] = (prev[j] + self.sub_cost(c, *tc))
+                    .min(prev[j + 1] + self.del_cost(c));
+            }
+            prev = cur;
+        }
+
+        if free_ends {
+            prev.into_iter().fold(f64::MAX, f64::min)
+        } else {
+            prev[t.len()]
+        }
+    }
+
+    /// 识别的车牌与 target 是否匹配，返回匹配方式和分值
+    pub fn match_plate(&self, plate: &PlateText, target: &str) -> Option<PlateMatch> {
+        let p = &plate.chars;
+        let t = normalize(target);
+        if p.is_empty() || t.is_empty() {
+            return None;
+        }
+
+        if p.len() == t.len() && p.iter().zip(t.iter()).all(|(c, tc)| c.value == *tc) {
+            return Some(PlateMatch {
+                match_type: PlateMatchType::Exact,
+                score: 100_f64,
+            });
+        }
+
+        // (type, cost, score), 分值相同时取后面的
+        let mut found: Vec<(PlateMatchType, f64, f64)> = Vec::new();
+
+        let cost = self.edit_cost(p, &t, false);
+        found.push((PlateMatchType::Fuzzy, cost, 100_f64 * (1_f64 - cost / p.len().max(t.len()) as f64)));
+
+        if p.len() == t.len() && p.iter().zip(t.iter()).all(|(c, tc)| Self::base_sub_cost(c, *tc) < 1_f64) {
+            let cost: f64 = p.iter().zip(t.iter()).map(|(c, tc)| Self::base_sub_cost(c, *tc) * Self::weight(c)).sum();
+            found.push((PlateMatchType::Confusable, cost, 100_f64 * (1_f64 - cost / t.len() as f64)));
+        }
+
+        // 部分车牌，分值按识别出的比例折算
+        if p.len() < t.len() && p.len() >= self.opts.partial_min_len {
+            let cost = self.edit_cost(p, &t, true);
+            let ratio = p.len() as f64 / t.len() as f64;
+            found.push((PlateMatchType::Partial, cost, 100_f64 * (1_f64 - cost / p.len() as f64) * (0.5 + 0.5 * ratio)));
+        }
+
+        found.into_iter()
+            .filter(|x| x.1 <= self.opts.max_cost && x.2 >= self.opts.min_score)
+            .max_by(|a, b| a.2.partial_cmp(&b.2).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|x| PlateMatch {
+                match_type: x.0,
+                score: (x.2 * 100_f64).round() / 100_f64,
+            })
+    }
+
+    /// 在 targets 中查找分值最高的，返回下标
+    /// 最高分有多个不同的 target 时无法确定，返回 None
+    pub fn best_match<'a, I>(&self, plate: &PlateText, targets: I) -> Option<(usize, PlateMatch)>
+        where I: IntoIterator<Item = &'a str> {
+        let mut best: Option<(usize, PlateMatch)> = None;
+        let mut ambiguous = false;
+        for (i, t) in targets.into_iter().enumerate() {
+            let m = match self.match_plate(plate, t) {
+                Some(v) => v,
+                None => continue,
+            };
+            match best {
+                Some((_, ref b)) if m.score < b.score => {}
+                Some((_, ref b)) if (m.score - b.score).abs() < f64::EPSILON => ambiguous = true,
+                _ => {
+                    best = Some((i, m));
+                    ambiguous = false;
+                }
+            }
+        }
+
+        if ambiguous {
+            return None;
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bit(value: &str, conf: f64) -> ApiCarPlateBit {
+        ApiCarPlateBit {
+            value: value.to_string(),
+            conf,
+        }
+    }
+
+    fn match_text(plate: &str, target: &str) -> Option<PlateMatch> {
+        PlateMatcher::new(PlateMatchOpts::default()).match_plate(&PlateText::new(plate, &[]), target)
+    }
+
+    #[test]
+    fn exact_ignores_separator_and_case() {
+        let m = match_text("京a·12345", "京A12345").unwrap();
+        assert_eq!(m.match_type, PlateMatchType::Exact);
+        assert_eq!(m.score, 100_f64);
+    }
+
+    #[test]
+    fn confusable_char() {
+        let m = match_text("京A1234B", "京A12348").unwrap();
+        assert_eq!(m.match_type, PlateMatchType::Confusable);
+        assert_eq!(m.score, 92.86);
+    }
+
+    fn low_conf_last(plate: &str, conf: f64) -> PlateText {
+        let n = plate.chars().count();
+        let bits: Vec<Vec<ApiCarPlateBit>> = plate.chars().enumerate()
+            .map(|(i, x)| vec![bit(&x.to_string(), if i + 1 == n { conf } else { 1_f64 })])
+            .collect();
+        PlateText::new(plate, &bits)
+    }
+
+    #[test]
+    fn different_char() {
+        // 置信度高的字符不能替换为普通字符
+        assert!(match_text("京A12345", "京A12347").is_none());
+
+        let matcher = PlateMatcher::new(PlateMatchOpts::default());
+        let m = matcher.match_plate(&low_conf_last("京A12345", 0.2), "京A12347").unwrap();
+        assert_eq!(m.match_type, PlateMatchType::Fuzzy);
+        assert_eq!(m.score, 91.43);
+
+        // 超过最大编辑代价
+        assert!(match_text("京A12345", "京A12378").is_none());
+    }
+
+    #[test]
+    fn alt_char_with_low_confidence() {
+        let mut bits: Vec<Vec<ApiCarPlateBit>> = "京A1234".chars().map(|x| vec![bit(&x.to_string(), 1_f64)]).collect();
+        bits.push(vec![bit("5", 0.6), bit("7", 0.3)]);
+        let plate = PlateText::new("京A12345", &bits);
+
+        let m = PlateMatcher::new(PlateMatchOpts::default()).match_plate(&plate, "京A12347").unwrap();
+        assert_eq!(m.match_type, PlateMatchType::Confusable);
+        assert_eq!(m.score, 96.57);
+    }
+
+    #[test]
+    fn length_mismatch() {
+        // 多识别出一个置信度高的字符
+        assert!(match_text("京A123456", "京A12345").is_none());
+
+        let matcher = PlateMatcher::new(PlateMatchOpts::default());
+        let m = matcher.match_plate(&low_conf_last("京A123456", 0.2), "京A12345").unwrap();
+        assert_eq!(m.match_type, PlateMatchType::Fuzzy);
+        assert_eq!(m.score, 92.5);
+
+        // 少识别出的字符太多
+        assert!(match_text("京A12", "京A12345").is_none());
+        // 不能在识别结果中间插入字符
+        assert!(match_text("京A1245", "京A12345").is_none());
+    }
+
+    #[test]
+    fn missing_province_prefix() {
+        let m = match_text("A12345", "京A12345").unwrap();
+        assert_eq!(m.match_type, PlateMatchType::Partial);
+        assert_eq!(m.score, 92.86);
+
+        // 少于 partial_min_len
+        assert!(match_text("A123", "京A1234").is_none());
+    }
+
+    #[test]
+    fn best_match_prefers_exact() {
+        let matcher = PlateMatcher::new(PlateMatchOpts::default());
+        let plate = PlateText::new("京A1234B", &[]);
+        let (i, m) = matcher.best_match(&plate, vec!["京A12348", "京A1234B"]).unwrap();
+        assert_eq!(i, 1);
+        assert_eq!(m.match_type, PlateMatchType::Exact);
+    }
+
+    #[test]
+    fn best_match_ambiguous() {
+        let matcher = PlateMatcher::new(PlateMatchOpts::default());
+        let plate = low_conf_last("京A12345", 0.2);
+        assert!(matcher.best_match(&plate, vec!["京A12346", "京A12347"]).is_none());
+    }
+}
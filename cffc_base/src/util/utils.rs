@@ -4,7 +4,10 @@ use std::path::Path;
 use chrono::LocalResult;
 use chrono::prelude::*;
 use crypto::digest::Digest;
+use crypto::hmac::Hmac;
+use crypto::mac::Mac;
 use crypto::md5::Md5;
+use crypto::sha2::Sha256;
 use deadqueue::unlimited::Queue;
 use serde::{Deserialize, Serialize};
 use tokio::fs::{self, DirBuilder};
@@ -145,6 +148,53 @@ pub fn md5_it(s: &str) -> String {
     md5.result_str()
 }
 
+/// hex(hmac_sha256(key, parts...))
+pub fn hmac_sha256_hex(key: &str, parts: &[&[u8]]) -> String {
+    let mut hmac = Hmac::new(Sha256::new(), key.as_bytes());
+    for x in parts.iter() {
+        hmac.input(x);
+    }
+    hmac.result().code().iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+/// 比较耗时与内容无关，用于校验签名
+pub fn const_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0_u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// hex(sha256(buf))
+pub fn sha256_hex(buf: &[u8]) -> String {
+    let mut digest = Sha256Digest::new();
+    digest.input(buf);
+    digest.result_hex()
+}
+
+/// 分块计算 sha256, 用于边接收边计算
+pub struct Sha256Digest(Sha256);
+
+impl Default for Sha256Digest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sha256Digest {
+    pub fn new() -> Self {
+        Sha256Digest(Sha256::new())
+    }
+
+    pub fn input(&mut self, buf: &[u8]) {
+        self.0.input(buf);
+    }
+
+    pub fn result_hex(&mut self) -> String {
+        self.0.result_str()
+    }
+}
+
 pub fn md5_with_salt(s: &str, salt: &str) -> String {
     let mut md5 = Md5::new();
     md5.input_str(s);
@@ -184,6 +234,14 @@ pub fn option_must_length(str: &Option<String>, min: usize, max: usize) -> bool
     false
 }
 
+/// 如果有值，则长度在 min <= x <= max
+pub fn option_should_length(str: &Option<String>, min: usize, max: usize) -> bool {
+    if str.is_none() {
+        return true;
+    }
+    option_must_length(str, min, max)
+}
+
 pub fn option_must_notempty(str: &Option<String>) -> bool {
     if let Some(ref v) = str {
         let len = v.trim().len();
@@ -355,6 +413,21 @@ pub fn check_bmp_magic(buf: &[u8]) -> bool {
     buf.len() >= 2 && buf[0] == 0x42 && buf[1] == 0x4d
 }
 
+/// 从文件复制图片，bmp 需要读入内存转成jpg
+pub async fn copy_jpg_file(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> std::io::Result<()> {
+    use tokio::io::AsyncReadExt;
+
+    let mut head = [0_u8; 2];
+    let mut f = fs::File::open(src.as_ref()).await?;
+    let n = f.read(&mut head).await?;
+    if !check_bmp_magic(&head[..n]) {
+        return fs::copy(src, dst).await.map(|_| ());
+    }
+
+    let content = fs::read(src).await?;
+    write_jpg_file(dst, &content).await
+}
+
 /// 检查content是否是bmp，如果是，需要转成jpg存储
 pub async fn write_jpg_file(path: impl AsRef<Path>, content: &[u8]) -> std::io::Result<()> {
     if !check_bmp_magic(content) {
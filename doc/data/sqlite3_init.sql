@@ -28,6 +28,7 @@ create table cf_dfnode
     url          varchar(200) not null, /* 节点调用url */
     node_type    SMALLINT     not null, /* 节点类型 1:analysis(采集模块) 2:recognition(识别模块) */
     sort_num     SMALLINT default 0, /* 排序用 */
+    auth_key     varchar(100), /* 上传认证密钥 (analysis节点) */
     gmt_create   datetime     not null, /* 创建时间 */
     gmt_modified datetime     not null /* 修改时间 */
 );
@@ -73,6 +74,9 @@ create table cf_dfsource
     rtcp_utc         SMALLINT     not null default 0, /* rtcp中的ntp时间是否是utc时间， 1：是， 0：否 */
     lane_desc        varchar(500), /* 车道描述，lanes对象的json字符串 */
     lane_count       SMALLINT     not null default 4, /* 摄像头中，车道数量 */
+    speed_line1      integer      not null default 0, /* 测速线1, 水平线 y 坐标(视频坐标), 0 表示未标定 */
+    speed_line2      integer      not null default 0, /* 测速线2, 水平线 y 坐标(视频坐标), 0 表示未标定 */
+    speed_distance   double       not null default 0, /* 两条测速线之间的实际距离, 米 */
     memo             varchar(200), /* 备注 */
     gmt_create       datetime     not null, /* 创建时间 */
     gmt_modified     datetime     not null /* 修改时间 */
@@ -109,6 +113,21 @@ create index idx_poi_identity_card on cf_poi (identity_card);
 create index idx_poi_threshold on cf_poi (threshold);
 create index idx_poi_gmt_modified on cf_poi (gmt_modified);
 
+create table cf_poi_feature
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    poi_sid      varchar(50) not null, /* person uuid */
+    db_sid       varchar(50) not null, /* 所在db的sid */
+    face_id      INTEGER     not null default 0, /* 识别模块中的 face id */
+    feature      text        not null, /* base64 特征值 */
+    quality      double      not null default 0, /* 质量分 */
+    gmt_create   datetime    not null, /* 创建时间 */
+    gmt_modified datetime    not null /* 修改时间 */
+);
+create index idx_poi_feature_poi_sid on cf_poi_feature (poi_sid);
+create unique index idx_poi_feature_poi_face on cf_poi_feature (poi_sid, face_id);
+create index idx_poi_feature_db_sid on cf_poi_feature (db_sid);
+
 create table cf_facetrack
 (
     id           INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -118,6 +137,8 @@ create table cf_facetrack
     matched      SMALLINT              default 0, /* 是否匹配过 0：否，1：是 */
     judged       SMALLINT              default 0, /* 是否识别出某人 0：否，1：是 */
     alarmed      SMALLINT              default 0, /* 是否报警 0：否，1：是 */
+    alarm_rule   INTEGER      not null default 0, /* 触发的报警规则 id，0 表示无 */
+    alarm_level  SMALLINT     not null default 0, /* 报警级别，同 cf_alarm_rule.severity */
     most_person  varchar(50), /* 匹配的 top1的person */
     most_score   double, /* 匹配的分值 */
     gender       SMALLINT              default 0, /*  0 不确定; 1 男性; 2 ⼥性 */
@@ -136,6 +157,8 @@ create table cf_facetrack
     obj_id       varchar(50), /* GA1400基本对象统一标识, vcs datasourceid */
     submit_id    varchar(80), /* 提交上级平台返回的ID */
     submit_time  datetime, /* 提交上级平台时间 */
+    start_real_time INTEGER   not null default 0, /* notify position 中出现的时间, ms */
+    end_real_time   INTEGER   not null default 0, /* notify position 中消失的时间, ms */
     capture_time datetime     not null, /* 抓拍时间 */
     gmt_create   datetime     not null, /* 创建时间 */
     gmt_modified datetime     not null /* 修改时间 */
@@ -145,9 +168,122 @@ create index idx_facetrack_src_sid on cf_facetrack (src_sid);
 create index idx_facetrack_judged on cf_facetrack (judged);
 create index idx_facetrack_gender on cf_facetrack (gender);
 create index idx_facetrack_alarmed on cf_facetrack (alarmed);
-create index idx_facetrack_most_person on cf_facetrack (most_person);
+create index idx_facetrack_most_person on cf_facetrack (most_person, capture_time);
 create index idx_facetrack_capture_time on cf_facetrack (capture_time);
 
+create table cf_facetrack_feature
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    ft_sid       varchar(50) not null, /* facetrack uuid */
+    src_sid      varchar(50) not null, /* 摄像头 uuid */
+    img_idx      SMALLINT    not null default 0, /* 人脸图序号，从1开始，同 img_ids */
+    feature      text        not null, /* base64 特征值 */
+    quality      double      not null default 0, /* 质量分 */
+    capture_time datetime    not null, /* 抓拍时间 */
+    gmt_create   datetime    not null, /* 创建时间 */
+    gmt_modified datetime    not null /* 修改时间 */
+);
+create index idx_facetrack_feature_ft_sid on cf_facetrack_feature (ft_sid);
+create index idx_facetrack_feature_capture_time on cf_facetrack_feature (capture_time);
+
+create table cf_facetrack_candidate
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    ft_sid       varchar(50)  not null, /* facetrack uuid */
+    rank_num     SMALLINT     not null default 0, /* 排名，从1开始 */
+    poi_sid      varchar(50)  not null, /* 候选人 uuid */
+    poi_name     varchar(100), /* 候选人姓名 */
+    db_sid       varchar(50)  not null, /* 所在db的sid */
+    score        double       not null default 0, /* 匹配的分值 */
+    gmt_create   datetime     not null, /* 创建时间 */
+    gmt_modified datetime     not null /* 修改时间 */
+);
+create unique index idx_facetrack_candidate_rank on cf_facetrack_candidate (ft_sid, rank_num);
+create index idx_facetrack_candidate_poi_sid on cf_facetrack_candidate (poi_sid);
+
+create table cf_retro_job
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    poi_sid      varchar(50)  not null, /* person uuid */
+    db_sid       varchar(50)  not null, /* 所在db的sid */
+    poi_name     varchar(100), /* 姓名 */
+    threshold    SMALLINT     not null default 0, /* 命中阈值 */
+    time_start   datetime     not null, /* 回溯的抓拍时间范围 */
+    time_end     datetime     not null,
+    state        SMALLINT     not null default 0, /* 0 等待；1 运行中；2 完成；3 失败 */
+    total        INTEGER      not null default 0, /* 需比对的特征值数 */
+    scanned      INTEGER      not null default 0, /* 已比对的特征值数 */
+    hits         INTEGER      not null default 0, /* 命中的 facetrack 数 */
+    msg          varchar(200), /* 失败原因 */
+    gmt_create   datetime     not null, /* 创建时间 */
+    gmt_modified datetime     not null /* 修改时间 */
+);
+create index idx_retro_job_poi_sid on cf_retro_job (poi_sid);
+create index idx_retro_job_state on cf_retro_job (state);
+
+create table cf_retro_alarm
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    job_id       INTEGER      not null, /* cf_retro_job.id */
+    poi_sid      varchar(50)  not null, /* person uuid */
+    ft_sid       varchar(50)  not null, /* 命中的历史 facetrack uuid */
+    src_sid      varchar(50)  not null, /* 摄像头 uuid */
+    img_idx      SMALLINT     not null default 0, /* 命中的人脸图序号 */
+    score        double       not null default 0, /* 比对分值 */
+    capture_time datetime     not null, /* 抓拍时间 */
+    gmt_create   datetime     not null, /* 创建时间 */
+    gmt_modified datetime     not null /* 修改时间 */
+);
+create unique index idx_retro_alarm_job_ft on cf_retro_alarm (job_id, ft_sid);
+create index idx_retro_alarm_poi_sid on cf_retro_alarm (poi_sid);
+create index idx_retro_alarm_ft_sid on cf_retro_alarm (ft_sid);
+
+create table cf_stranger
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    cluster_sid  varchar(50)  not null, /* 陌生人聚类 uuid */
+    features     text         not null, /* 代表特征值, base64, 逗号分隔 */
+    appear_count INTEGER      not null default 0, /* 累计出现次数 */
+    first_time   datetime     not null, /* 第一次出现的抓拍时间 */
+    last_time    datetime     not null, /* 最后一次出现的抓拍时间 */
+    last_ft_sid  varchar(50)  not null, /* 最后一次出现的 facetrack uuid */
+    last_src_sid varchar(50)  not null, /* 最后一次出现的摄像头 uuid */
+    alarm_time   datetime, /* 最后一次报警时间 */
+    gmt_create   datetime     not null, /* 创建时间 */
+    gmt_modified datetime     not null /* 修改时间 */
+);
+create unique index idx_stranger_cluster_sid on cf_stranger (cluster_sid);
+create index idx_stranger_last_time on cf_stranger (last_time);
+
+create table cf_stranger_track
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    cluster_sid  varchar(50)  not null, /* cf_stranger.cluster_sid */
+    ft_sid       varchar(50)  not null, /* facetrack uuid */
+    src_sid      varchar(50)  not null, /* 摄像头 uuid */
+    score        double       not null default 0, /* 与聚类的相似度，新建聚类时为 100 */
+    capture_time datetime     not null, /* 抓拍时间 */
+    gmt_create   datetime     not null, /* 创建时间 */
+    gmt_modified datetime     not null /* 修改时间 */
+);
+create unique index idx_stranger_track_ft_sid on cf_stranger_track (ft_sid);
+create index idx_stranger_track_cluster on cf_stranger_track (cluster_sid, capture_time);
+
+create table cf_stranger_alarm
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    cluster_sid  varchar(50)  not null, /* cf_stranger.cluster_sid */
+    ft_sid       varchar(50)  not null, /* 触发报警的 facetrack uuid */
+    src_sid      varchar(50)  not null, /* 摄像头 uuid */
+    appear_count INTEGER      not null default 0, /* 时间窗口内出现的次数 */
+    window_start datetime     not null, /* 时间窗口的开始时间 */
+    capture_time datetime     not null, /* 抓拍时间 */
+    gmt_create   datetime     not null, /* 创建时间 */
+    gmt_modified datetime     not null /* 修改时间 */
+);
+create index idx_stranger_alarm_cluster on cf_stranger_alarm (cluster_sid);
+create index idx_stranger_alarm_capture_time on cf_stranger_alarm (capture_time);
+
 create table cf_dictory
 (
     id           INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -199,6 +335,8 @@ create table cf_cartrack
     src_sid          varchar(50)  not null, /* source 的uuid'*/
     img_ids          varchar(400) not null, /* index:quality,index:quality */
     alarmed          SMALLINT     not null default 0, /*  是否报警 0：否，1：是  */
+    alarm_rule       INTEGER      not null default 0, /* 触发的报警规则 id，0 表示无 */
+    alarm_level      SMALLINT     not null default 0, /* 报警级别，同 cf_alarm_rule.severity */
     most_coi         varchar(50), /* 根据拍照匹配的coi */
     plate_judged     SMALLINT     not null default 0, /*车牌是否识别出来 0：否，1：是 */
     vehicle_judged   SMALLINT     not null default 0, /* 车型是否识别出来 0：否，1：是 */
@@ -223,6 +361,8 @@ create table cf_cartrack
     capture_ts       INTEGER      not null default 0, /* 抓拍时间 trip.real_time */
     capture_pts      INTEGER      not null default 0, /* 抓拍时间  trip.pts*/
     lane_num         SMALLINT     not null default 0, /* 计算出来的车道 编号，从中间到旁边，从1开始 */
+    review_state     SMALLINT     not null default 0, /* 复核状态 0：无需复核，1：待复核，2：已确认，3：已驳回 */
+    speed            double       not null default 0, /* 估算车速 km/h, 0 表示未知 */
     gmt_create       datetime     not null, /* 创建时间  */
     gmt_modified     datetime     not null /* 修改时间  */
 );
@@ -233,6 +373,7 @@ create index idx_cf_cartrack_vehicle_judged on cf_cartrack (vehicle_judged);
 create index idx_cf_cartrack_alarmed on cf_cartrack (alarmed);
 create index idx_cf_cartrack_plate_content on cf_cartrack (plate_content);
 create index idx_cf_cartrack_capture_time on cf_cartrack (capture_time);
+create index idx_cf_cartrack_review_state on cf_cartrack (review_state);
 
 create table cf_coi
 (
@@ -268,6 +409,7 @@ create table cf_coi_group
     sid          varchar(50) not null, /*  uuid */
     name         varchar(50) not null, /* 分组名称 */
     bw_flag      SMALLINT    not null default 1, /* 1:黑名单  2:白名单 */
+    min_confidence double    not null default 0, /* 车牌置信度低于此值时，报警需人工复核，0 表示不限 */
     memo         varchar(100), /* 备注 */
     gmt_create   datetime(3) not null, /* 创建时间 */
     gmt_modified datetime(3) not null /* 修改时间 */
@@ -276,6 +418,109 @@ create table cf_coi_group
 create unique index idx_coigroup_sid on cf_coi_group (sid);
 create index idx_coi_group_sid on cf_coi (group_sid);
 
+create table cf_lane_stat
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    src_sid      varchar(50) not null, /* source 的uuid */
+    period       INTEGER     not null, /* 统计周期, 分钟: 1, 15, 60, 1440 */
+    bucket_time  datetime    not null, /* 周期开始时间 */
+    lane_num     SMALLINT    not null default 0, /* 车道编号，0 表示车道之外或未标注 */
+    move_direct  SMALLINT    not null default 0, /* 运动⽅向，0 未知；1 向上；2 向下 */
+    car_type     varchar(50) not null default '', /* 车粗分类别 car_top_type，空表示未知 */
+    count        INTEGER     not null default 0, /* 车辆数 */
+    gmt_create   datetime    not null, /* 创建时间 */
+    gmt_modified datetime    not null /* 修改时间 */
+);
+create unique index idx_lane_stat_key on cf_lane_stat (src_sid, period, bucket_time, lane_num, move_direct, car_type);
+create index idx_lane_stat_period_time on cf_lane_stat (period, bucket_time);
+
+/* --- parking table --- */
+create table cf_parking_visit
+(
+    id              INTEGER PRIMARY KEY AUTOINCREMENT,
+    plate_content   varchar(50) not null, /* 车牌 */
+    entry_ct_sid    varchar(50), /* 入场 cartrack 的uuid */
+    entry_src_sid   varchar(50), /* 入场摄像头 src_sid */
+    entry_time      datetime, /* 入场时间 */
+    exit_ct_sid     varchar(50), /* 出场 cartrack 的uuid */
+    exit_src_sid    varchar(50), /* 出场摄像头 src_sid */
+    exit_time       datetime, /* 出场时间 */
+    state           SMALLINT    not null default 0, /* 0：在场，1：已出场，2：出场无入场记录，3：入场无出场记录(再次入场时关闭) */
+    dwell_min       INTEGER     not null default 0, /* 停留时长，分钟 */
+    fee             double      not null default 0, /* 停车费 */
+    fee_free        SMALLINT    not null default 0, /* 是否免费 0：否，1：白名单分组 */
+    group_sid       varchar(50), /* 匹配的车辆分组 */
+    overstay        SMALLINT    not null default 0, /* 是否超时停放 0：否，1：是 */
+    gmt_create      datetime    not null, /* 创建时间 */
+    gmt_modified    datetime    not null /* 修改时间 */
+);
+create index idx_parking_visit_plate_state on cf_parking_visit (plate_content, state);
+create index idx_parking_visit_state_entry on cf_parking_visit (state, entry_time);
+create index idx_parking_visit_entry_time on cf_parking_visit (entry_time);
+
+/* --- alarm rule table --- */
+create table cf_alarm_rule
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    name         varchar(100) not null, /* 规则名称 */
+    track_type   SMALLINT     not null default 0, /* 0:人脸 1:车辆 */
+    enabled      SMALLINT     not null default 1, /* 是否启用 0：否，1：是 */
+    severity     SMALLINT     not null default 1, /* 报警级别 1：一般，2：重要，3：紧急 */
+    src_sids     varchar(1000), /* 摄像头 src_sid, 逗号分隔，空表示全部 */
+    group_sids   varchar(1000), /* 人脸库 db_sid 或车辆分组 sid, 逗号分隔，空表示全部 */
+    match_mode   SMALLINT     not null default 1, /* 0：不限，1：命中名单，2：未命中名单 */
+    bw_flag      SMALLINT     not null default 0, /* 名单类型 0：不限，1：黑名单，2：白名单 */
+    week_days    varchar(20), /* 1-7 表示周一到周日，逗号分隔，空表示每天 */
+    time_start   varchar(8), /* 每天开始时间 HH:MM, 空表示全天 */
+    time_end     varchar(8), /* 每天结束时间 HH:MM, 小于开始时间表示跨天 */
+    min_score    double       not null default 0, /* 人脸：比对分值；车辆：车牌置信度，0 表示不限 */
+    gender       SMALLINT     not null default 0, /* 人脸性别 0：不限，1：男，2：女 */
+    car_color    varchar(50), /* 车身颜色，空表示不限 */
+    speed_limits varchar(200), /* 车道限速 km/h, 车道号:限速 逗号分隔, 车道号 0 表示其它车道, 如 1:120,2:100,0:80; 空表示不判断车速 */
+    memo         varchar(200), /* 备注 */
+    gmt_create   datetime     not null, /* 创建时间 */
+    gmt_modified datetime     not null /* 修改时间 */
+);
+create index idx_alarm_rule_track_type on cf_alarm_rule (track_type);
+
+/* --- trip table --- */
+create table cf_trip_event
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    track_sid    varchar(50) not null, /* facetrack/cartrack uuid */
+    track_type   SMALLINT    not null default 0, /* 0:人脸 1:车辆 */
+    src_sid      varchar(50) not null, /* source 的uuid */
+    direction    SMALLINT    not null default 0, /* 越线方向，0 未知；1 向上；2 向下 */
+    trip_line    integer     not null default 0, /* 水平触发线 */
+    rect_x       integer     not null default 0, /* 越线时目标位置 trip.x */
+    rect_y       integer     not null default 0, /* trip.y */
+    rect_w       integer     not null default 0, /* trip.w */
+    rect_h       integer     not null default 0, /* trip.h */
+    trip_ts      INTEGER     not null default 0, /* 越线时间 trip.real_time */
+    trip_pts     INTEGER     not null default 0, /* 越线时间 trip.pts */
+    capture_time datetime    not null, /* 抓拍时间  */
+    gmt_create   datetime    not null, /* 创建时间  */
+    gmt_modified datetime    not null /* 修改时间  */
+);
+create unique index idx_cf_trip_event_track on cf_trip_event (track_sid, track_type);
+create index idx_cf_trip_event_src_sid on cf_trip_event (src_sid);
+create index idx_cf_trip_event_capture_time on cf_trip_event (capture_time);
+
+create table cf_trip_stat
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    src_sid      varchar(50) not null, /* source 的uuid */
+    track_type   SMALLINT    not null default 0, /* 0:人脸 1:车辆 */
+    bucket_time  datetime    not null, /* 统计时间段的开始时间 */
+    up_count     integer     not null default 0, /* 向上越线数 */
+    down_count   integer     not null default 0, /* 向下越线数 */
+    total_count  integer     not null default 0, /* 越线总数(含方向未知) */
+    gmt_create   datetime    not null, /* 创建时间  */
+    gmt_modified datetime    not null /* 修改时间  */
+);
+create unique index idx_cf_trip_stat_bucket on cf_trip_stat (src_sid, track_type, bucket_time);
+create index idx_cf_trip_stat_bucket_time on cf_trip_stat (bucket_time);
+
 /* --- options table --- */
 
 create table cf_gate
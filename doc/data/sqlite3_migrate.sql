@@ -0,0 +1,233 @@
+/* 旧版本数据库升级，启动时执行，新增的表和索引 */
+
+create table if not exists cf_poi_feature
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    poi_sid      varchar(50) not null, /* person uuid */
+    db_sid       varchar(50) not null, /* 所在db的sid */
+    face_id      INTEGER     not null default 0, /* 识别模块中的 face id */
+    feature      text        not null, /* base64 特征值 */
+    quality      double      not null default 0, /* 质量分 */
+    gmt_create   datetime    not null, /* 创建时间 */
+    gmt_modified datetime    not null /* 修改时间 */
+);
+create index if not exists idx_poi_feature_poi_sid on cf_poi_feature (poi_sid);
+create unique index if not exists idx_poi_feature_poi_face on cf_poi_feature (poi_sid, face_id);
+create index if not exists idx_poi_feature_db_sid on cf_poi_feature (db_sid);
+
+create table if not exists cf_facetrack_feature
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    ft_sid       varchar(50) not null, /* facetrack uuid */
+    src_sid      varchar(50) not null, /* 摄像头 uuid */
+    img_idx      SMALLINT    not null default 0, /* 人脸图序号，从1开始，同 img_ids */
+    feature      text        not null, /* base64 特征值 */
+    quality      double      not null default 0, /* 质量分 */
+    capture_time datetime    not null, /* 抓拍时间 */
+    gmt_create   datetime    not null, /* 创建时间 */
+    gmt_modified datetime    not null /* 修改时间 */
+);
+create index if not exists idx_facetrack_feature_ft_sid on cf_facetrack_feature (ft_sid);
+create index if not exists idx_facetrack_feature_capture_time on cf_facetrack_feature (capture_time);
+
+create table if not exists cf_facetrack_candidate
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    ft_sid       varchar(50)  not null, /* facetrack uuid */
+    rank_num     SMALLINT     not null default 0, /* 排名，从1开始 */
+    poi_sid      varchar(50)  not null, /* 候选人 uuid */
+    poi_name     varchar(100), /* 候选人姓名 */
+    db_sid       varchar(50)  not null, /* 所在db的sid */
+    score        double       not null default 0, /* 匹配的分值 */
+    gmt_create   datetime     not null, /* 创建时间 */
+    gmt_modified datetime     not null /* 修改时间 */
+);
+create unique index if not exists idx_facetrack_candidate_rank on cf_facetrack_candidate (ft_sid, rank_num);
+create index if not exists idx_facetrack_candidate_poi_sid on cf_facetrack_candidate (poi_sid);
+
+create table if not exists cf_retro_job
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    poi_sid      varchar(50)  not null, /* person uuid */
+    db_sid       varchar(50)  not null, /* 所在db的sid */
+    poi_name     varchar(100), /* 姓名 */
+    threshold    SMALLINT     not null default 0, /* 命中阈值 */
+    time_start   datetime     not null, /* 回溯的抓拍时间范围 */
+    time_end     datetime     not null,
+    state        SMALLINT     not null default 0, /* 0 等待；1 运行中；2 完成；3 失败 */
+    total        INTEGER      not null default 0, /* 需比对的特征值数 */
+    scanned      INTEGER      not null default 0, /* 已比对的特征值数 */
+    hits         INTEGER      not null default 0, /* 命中的 facetrack 数 */
+    msg          varchar(200), /* 失败原因 */
+    gmt_create   datetime     not null, /* 创建时间 */
+    gmt_modified datetime     not null /* 修改时间 */
+);
+create index if not exists idx_retro_job_poi_sid on cf_retro_job (poi_sid);
+create index if not exists idx_retro_job_state on cf_retro_job (state);
+
+create table if not exists cf_retro_alarm
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    job_id       INTEGER      not null, /* cf_retro_job.id */
+    poi_sid      varchar(50)  not null, /* person uuid */
+    ft_sid       varchar(50)  not null, /* 命中的历史 facetrack uuid */
+    src_sid      varchar(50)  not null, /* 摄像头 uuid */
+    img_idx      SMALLINT     not null default 0, /* 命中的人脸图序号 */
+    score        double       not null default 0, /* 比对分值 */
+    capture_time datetime     not null, /* 抓拍时间 */
+    gmt_create   datetime     not null, /* 创建时间 */
+    gmt_modified datetime     not null /* 修改时间 */
+);
+create unique index if not exists idx_retro_alarm_job_ft on cf_retro_alarm (job_id, ft_sid);
+create index if not exists idx_retro_alarm_poi_sid on cf_retro_alarm (poi_sid);
+create index if not exists idx_retro_alarm_ft_sid on cf_retro_alarm (ft_sid);
+
+create table if not exists cf_stranger
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    cluster_sid  varchar(50)  not null, /* 陌生人聚类 uuid */
+    features     text         not null, /* 代表特征值, base64, 逗号分隔 */
+    appear_count INTEGER      not null default 0, /* 累计出现次数 */
+    first_time   datetime     not null, /* 第一次出现的抓拍时间 */
+    last_time    datetime     not null, /* 最后一次出现的抓拍时间 */
+    last_ft_sid  varchar(50)  not null, /* 最后一次出现的 facetrack uuid */
+    last_src_sid varchar(50)  not null, /* 最后一次出现的摄像头 uuid */
+    alarm_time   datetime, /* 最后一次报警时间 */
+    gmt_create   datetime     not null, /* 创建时间 */
+    gmt_modified datetime     not null /* 修改时间 */
+);
+create unique index if not exists idx_stranger_cluster_sid on cf_stranger (cluster_sid);
+create index if not exists idx_stranger_last_time on cf_stranger (last_time);
+
+create table if not exists cf_stranger_track
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    cluster_sid  varchar(50)  not null, /* cf_stranger.cluster_sid */
+    ft_sid       varchar(50)  not null, /* facetrack uuid */
+    src_sid      varchar(50)  not null, /* 摄像头 uuid */
+    score        double       not null default 0, /* 与聚类的相似度，新建聚类时为 100 */
+    capture_time datetime     not null, /* 抓拍时间 */
+    gmt_create   datetime     not null, /* 创建时间 */
+    gmt_modified datetime     not null /* 修改时间 */
+);
+create unique index if not exists idx_stranger_track_ft_sid on cf_stranger_track (ft_sid);
+create index if not exists idx_stranger_track_cluster on cf_stranger_track (cluster_sid, capture_time);
+
+create table if not exists cf_stranger_alarm
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    cluster_sid  varchar(50)  not null, /* cf_stranger.cluster_sid */
+    ft_sid       varchar(50)  not null, /* 触发报警的 facetrack uuid */
+    src_sid      varchar(50)  not null, /* 摄像头 uuid */
+    appear_count INTEGER      not null default 0, /* 时间窗口内出现的次数 */
+    window_start datetime     not null, /* 时间窗口的开始时间 */
+    capture_time datetime     not null, /* 抓拍时间 */
+    gmt_create   datetime     not null, /* 创建时间 */
+    gmt_modified datetime     not null /* 修改时间 */
+);
+create index if not exists idx_stranger_alarm_cluster on cf_stranger_alarm (cluster_sid);
+create index if not exists idx_stranger_alarm_capture_time on cf_stranger_alarm (capture_time);
+
+create table if not exists cf_lane_stat
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    src_sid      varchar(50) not null, /* source 的uuid */
+    period       INTEGER     not null, /* 统计周期, 分钟: 1, 15, 60, 1440 */
+    bucket_time  datetime    not null, /* 周期开始时间 */
+    lane_num     SMALLINT    not null default 0, /* 车道编号，0 表示车道之外或未标注 */
+    move_direct  SMALLINT    not null default 0, /* 运动⽅向，0 未知；1 向上；2 向下 */
+    car_type     varchar(50) not null default '', /* 车粗分类别 car_top_type，空表示未知 */
+    count        INTEGER     not null default 0, /* 车辆数 */
+    gmt_create   datetime    not null, /* 创建时间 */
+    gmt_modified datetime    not null /* 修改时间 */
+);
+create unique index if not exists idx_lane_stat_key on cf_lane_stat (src_sid, period, bucket_time, lane_num, move_direct, car_type);
+create index if not exists idx_lane_stat_period_time on cf_lane_stat (period, bucket_time);
+
+create table if not exists cf_parking_visit
+(
+    id              INTEGER PRIMARY KEY AUTOINCREMENT,
+    plate_content   varchar(50) not null, /* 车牌 */
+    entry_ct_sid    varchar(50), /* 入场 cartrack 的uuid */
+    entry_src_sid   varchar(50), /* 入场摄像头 src_sid */
+    entry_time      datetime, /* 入场时间 */
+    exit_ct_sid     varchar(50), /* 出场 cartrack 的uuid */
+    exit_src_sid    varchar(50), /* 出场摄像头 src_sid */
+    exit_time       datetime, /* 出场时间 */
+    state           SMALLINT    not null default 0, /* 0：在场，1：已出场，2：出场无入场记录，3：入场无出场记录(再次入场时关闭) */
+    dwell_min       INTEGER     not null default 0, /* 停留时长，分钟 */
+    fee             double      not null default 0, /* 停车费 */
+    fee_free        SMALLINT    not null default 0, /* 是否免费 0：否，1：白名单分组 */
+    group_sid       varchar(50), /* 匹配的车辆分组 */
+    overstay        SMALLINT    not null default 0, /* 是否超时停放 0：否，1：是 */
+    gmt_create      datetime    not null, /* 创建时间 */
+    gmt_modified    datetime    not null /* 修改时间 */
+);
+create index if not exists idx_parking_visit_plate_state on cf_parking_visit (plate_content, state);
+create index if not exists idx_parking_visit_state_entry on cf_parking_visit (state, entry_time);
+create index if not exists idx_parking_visit_entry_time on cf_parking_visit (entry_time);
+
+create table if not exists cf_alarm_rule
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    name         varchar(100) not null, /* 规则名称 */
+    track_type   SMALLINT     not null default 0, /* 0:人脸 1:车辆 */
+    enabled      SMALLINT     not null default 1, /* 是否启用 0：否，1：是 */
+    severity     SMALLINT     not null default 1, /* 报警级别 1：一般，2：重要，3：紧急 */
+    src_sids     varchar(1000), /* 摄像头 src_sid, 逗号分隔，空表示全部 */
+    group_sids   varchar(1000), /* 人脸库 db_sid 或车辆分组 sid, 逗号分隔，空表示全部 */
+    match_mode   SMALLINT     not null default 1, /* 0：不限，1：命中名单，2：未命中名单 */
+    bw_flag      SMALLINT     not null default 0, /* 名单类型 0：不限，1：黑名单，2：白名单 */
+    week_days    varchar(20), /* 1-7 表示周一到周日，逗号分隔，空表示每天 */
+    time_start   varchar(8), /* 每天开始时间 HH:MM, 空表示全天 */
+    time_end     varchar(8), /* 每天结束时间 HH:MM, 小于开始时间表示跨天 */
+    min_score    double       not null default 0, /* 人脸：比对分值；车辆：车牌置信度，0 表示不限 */
+    gender       SMALLINT     not null default 0, /* 人脸性别 0：不限，1：男，2：女 */
+    car_color    varchar(50), /* 车身颜色，空表示不限 */
+    speed_limits varchar(200), /* 车道限速 km/h, 车道号:限速 逗号分隔, 车道号 0 表示其它车道, 如 1:120,2:100,0:80; 空表示不判断车速 */
+    memo         varchar(200), /* 备注 */
+    gmt_create   datetime     not null, /* 创建时间 */
+    gmt_modified datetime     not null /* 修改时间 */
+);
+create index if not exists idx_alarm_rule_track_type on cf_alarm_rule (track_type);
+
+create table if not exists cf_trip_event
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    track_sid    varchar(50) not null, /* facetrack/cartrack uuid */
+    track_type   SMALLINT    not null default 0, /* 0:人脸 1:车辆 */
+    src_sid      varchar(50) not null, /* source 的uuid */
+    direction    SMALLINT    not null default 0, /* 越线方向，0 未知；1 向上；2 向下 */
+    trip_line    integer     not null default 0, /* 水平触发线 */
+    rect_x       integer     not null default 0, /* 越线时目标位置 trip.x */
+    rect_y       integer     not null default 0, /* trip.y */
+    rect_w       integer     not null default 0, /* trip.w */
+    rect_h       integer     not null default 0, /* trip.h */
+    trip_ts      INTEGER     not null default 0, /* 越线时间 trip.real_time */
+    trip_pts     INTEGER     not null default 0, /* 越线时间 trip.pts */
+    capture_time datetime    not null, /* 抓拍时间  */
+    gmt_create   datetime    not null, /* 创建时间  */
+    gmt_modified datetime    not null /* 修改时间  */
+);
+create unique index if not exists idx_cf_trip_event_track on cf_trip_event (track_sid, track_type);
+create index if not exists idx_cf_trip_event_src_sid on cf_trip_event (src_sid);
+create index if not exists idx_cf_trip_event_capture_time on cf_trip_event (capture_time);
+
+create table if not exists cf_trip_stat
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    src_sid      varchar(50) not null, /* source 的uuid */
+    track_type   SMALLINT    not null default 0, /* 0:人脸 1:车辆 */
+    bucket_time  datetime    not null, /* 统计时间段的开始时间 */
+    up_count     integer     not null default 0, /* 向上越线数 */
+    down_count   integer     not null default 0, /* 向下越线数 */
+    total_count  integer     not null default 0, /* 越线总数(含方向未知) */
+    gmt_create   datetime    not null, /* 创建时间  */
+    gmt_modified datetime    not null /* 修改时间  */
+);
+create unique index if not exists idx_cf_trip_stat_bucket on cf_trip_stat (src_sid, track_type, bucket_time);
+create index if not exists idx_cf_trip_stat_bucket_time on cf_trip_stat (bucket_time);
+
+/* 已有表新增的索引 */
+create index if not exists idx_facetrack_most_person on cf_facetrack (most_person, capture_time);
+create index if not exists idx_cf_cartrack_review_state on cf_cartrack (review_state);